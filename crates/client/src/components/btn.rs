@@ -136,6 +136,7 @@ pub fn default_button(props: &DefaultBtnProps) -> Html {
                         OpenResultParams {
                             url: href.to_string(),
                             application: None,
+                            search: None,
                         },
                     )
                     .await;
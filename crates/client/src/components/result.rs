@@ -89,6 +89,17 @@ fn render_icon(result: &SearchResult) -> Html {
     icon
 }
 
+/// Formats a timestamp in an audio transcript as `m:ss` or `h:mm:ss`
+fn format_timestamp(timestamp_ms: u64) -> String {
+    let secs = timestamp_ms / 1000;
+    let (hours, mins, secs) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
 // TODO: Pull this special metadata from tags provided by the backend.
 fn render_metadata(result: &SearchResult) -> Html {
     let mut meta = Vec::new();
@@ -121,6 +132,16 @@ fn render_metadata(result: &SearchResult) -> Html {
     }
 
     meta.extend(priority_tags);
+    if let Some(timestamp_ms) = result.timestamp_ms {
+        meta.push(html! {
+            <Tag label={"at".to_string()} value={format_timestamp(timestamp_ms)} />
+        });
+    }
+    if let Some(page) = result.page {
+        meta.push(html! {
+            <Tag label={"page".to_string()} value={page.to_string()} />
+        });
+    }
     meta.extend(normal_tags);
 
     html! {
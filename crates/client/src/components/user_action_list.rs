@@ -170,6 +170,7 @@ pub async fn execute_action(selected: SearchResult, action: UserActionDefinition
                     OpenResultParams {
                         url,
                         application: Some(app_path.clone()),
+                        search: None,
                     },
                 )
                 .await
@@ -191,6 +192,7 @@ pub async fn execute_action(selected: SearchResult, action: UserActionDefinition
                     OpenResultParams {
                         url,
                         application: None,
+                        search: None,
                     },
                 )
                 .await
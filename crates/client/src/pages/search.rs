@@ -11,6 +11,7 @@ use yew::{html::Scope, prelude::*};
 
 use shared::{
     event::{ClientEvent, ClientInvoke, OpenResultParams},
+    request::ResultOpenedParam,
     response::{self, SearchMeta, SearchResult, SearchResults},
 };
 use ui_components::icons;
@@ -181,12 +182,17 @@ impl SearchPage {
 
     fn open_result(&mut self, selected: &SearchResult) {
         let url = selected.url.clone();
+        let search = ResultOpenedParam {
+            query: self.query.clone(),
+            doc_id: selected.doc_id.clone(),
+        };
         spawn_local(async move {
             if let Err(err) = tauri_invoke::<OpenResultParams, ()>(
                 ClientInvoke::OpenResult,
                 OpenResultParams {
                     url,
                     application: None,
+                    search: Some(search),
                 },
             )
             .await
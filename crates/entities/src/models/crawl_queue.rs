@@ -721,6 +721,32 @@ pub async fn mark_failed(db: &DatabaseConnection, id: i64, retry: bool) {
     }
 }
 
+/// Marks the task as a failed duplicate of the already indexed `canonical_url`.
+pub async fn mark_duplicate(
+    db: &DatabaseConnection,
+    id: i64,
+    canonical_url: &str,
+) -> Result<(), DbErr> {
+    if let Some(task) = Entity::find_by_id(id).one(db).await? {
+        let mut data_map = HashMap::new();
+        data_map.insert("canonical_url", canonical_url);
+
+        let mut update: ActiveModel = task.into();
+        update.status = Set(CrawlStatus::Failed);
+        if let Ok(data) = serde_json::to_string(&data_map) {
+            update.data = Set(Some(data));
+        }
+
+        update.error = Set(Some(TaskError {
+            error_type: TaskErrorType::Duplicate,
+            msg: "Content duplicates an indexed document".to_string(),
+        }));
+        update.update(db).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn insert_tags_by_id<C: ConnectionTrait>(
     db: &C,
     docs: &[Model],
@@ -950,6 +976,35 @@ pub async fn find_by_lens(
     .await
 }
 
+/// Completed web crawls for a lens that were last crawled before `crawled_before`,
+/// oldest first.
+pub async fn find_recrawl_candidates(
+    db: &DatabaseConnection,
+    lens: &str,
+    crawled_before: DateTimeUtc,
+    limit: u64,
+) -> Result<Vec<Model>, sea_orm::DbErr> {
+    Entity::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            r#"
+            SELECT
+                crawl_queue.*
+            FROM crawl_queue
+            JOIN crawl_tag on crawl_queue.id = crawl_tag.crawl_queue_id
+            JOIN tags on tags.id = crawl_tag.tag_id
+            WHERE tags.label = "lens" AND tags.value = $1
+                AND crawl_queue.status = "Completed"
+                AND crawl_queue.crawl_type = "Normal"
+                AND crawl_queue.updated_at < $2
+            ORDER BY crawl_queue.updated_at ASC
+            LIMIT $3"#,
+            vec![lens.into(), crawled_before.into(), limit.into()],
+        ))
+        .all(db)
+        .await
+}
+
 #[derive(Debug, FromQueryResult)]
 pub struct CrawlTaskIdsUrls {
     pub id: i64,
@@ -1016,6 +1071,7 @@ mod test {
     use shared::regex::{regex_for_robots, WildcardType};
 
     use crate::models::crawl_queue::{CrawlStatus, CrawlType};
+    use crate::models::tag::TagType;
     use crate::models::{crawl_queue, indexed_document};
     use crate::test::setup_test_db;
 
@@ -1389,4 +1445,43 @@ mod test {
         assert_eq!(res.id, first.id);
         assert_eq!(2, all_tasks.len());
     }
+
+    #[tokio::test]
+    async fn test_find_recrawl_candidates() {
+        let db = setup_test_db().await;
+        let week_ago = chrono::Utc::now() - chrono::Duration::days(7);
+
+        let mut tasks = Vec::new();
+        for (path, status, updated_at) in [
+            ("stale", CrawlStatus::Completed, week_ago),
+            ("fresh", CrawlStatus::Completed, chrono::Utc::now()),
+            ("queued", CrawlStatus::Queued, week_ago),
+        ] {
+            let model = crawl_queue::ActiveModel {
+                crawl_type: Set(CrawlType::Normal),
+                domain: Set("example.com".to_string()),
+                status: Set(status),
+                url: Set(format!("https://example.com/{path}")),
+                updated_at: Set(updated_at),
+                ..Default::default()
+            };
+            let task = model.insert(&db).await.expect("saved");
+            task.insert_tags(&db, &[(TagType::Lens, "example".to_string())])
+                .await
+                .expect("tagged");
+            tasks.push(task);
+        }
+
+        let one_day_ago = chrono::Utc::now() - chrono::Duration::days(1);
+        let due = super::find_recrawl_candidates(&db, "example", one_day_ago, 10)
+            .await
+            .expect("success");
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, tasks[0].id);
+
+        let due = super::find_recrawl_candidates(&db, "other", one_day_ago, 10)
+            .await
+            .expect("success");
+        assert!(due.is_empty());
+    }
 }
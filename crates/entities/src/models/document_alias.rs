@@ -0,0 +1,207 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ConnectionTrait, Set};
+use serde::Serialize;
+
+use crate::BATCH_SIZE;
+
+/// How an alias was matched to its canonical document.
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum, Serialize, Eq)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum MatchType {
+    /// Content hashes are identical.
+    #[sea_orm(string_value = "Exact")]
+    Exact,
+    /// SimHash fingerprints are within the near-duplicate threshold.
+    #[sea_orm(string_value = "Near")]
+    Near,
+}
+
+/// A URL whose content duplicates an already indexed document. Aliases are
+/// not added to the index, search results point at the canonical document.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Eq)]
+#[sea_orm(table_name = "document_alias")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// Canonical document this URL is an alias of.
+    pub indexed_document_id: i64,
+    /// Aliased URL.
+    #[sea_orm(unique)]
+    pub url: String,
+    pub match_type: MatchType,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    IndexedDocument,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::IndexedDocument => Entity::belongs_to(super::indexed_document::Entity)
+                .from(Column::IndexedDocumentId)
+                .to(super::indexed_document::Column::Id)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::indexed_document::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::IndexedDocument.def()
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    // Triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(chrono::Utc::now());
+        }
+        self.updated_at = Set(chrono::Utc::now());
+
+        Ok(self)
+    }
+}
+
+/// Records `url` as an alias of the document with database id
+/// `indexed_document_id`, repointing the alias if it already exists.
+pub async fn upsert<C: ConnectionTrait>(
+    db: &C,
+    indexed_document_id: i64,
+    url: &str,
+    match_type: MatchType,
+) -> Result<(), DbErr> {
+    let alias = ActiveModel {
+        indexed_document_id: Set(indexed_document_id),
+        url: Set(url.to_string()),
+        match_type: Set(match_type),
+        created_at: Set(chrono::Utc::now()),
+        updated_at: Set(chrono::Utc::now()),
+        ..Default::default()
+    };
+
+    Entity::insert(alias)
+        .on_conflict(
+            OnConflict::column(Column::Url)
+                .update_columns(vec![
+                    Column::IndexedDocumentId,
+                    Column::MatchType,
+                    Column::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Aliases of the document with database id `indexed_document_id`
+pub async fn find_by_document<C: ConnectionTrait>(
+    db: &C,
+    indexed_document_id: i64,
+) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::IndexedDocumentId.eq(indexed_document_id))
+        .all(db)
+        .await
+}
+
+/// Removes any alias entries for `urls`, e.g. once the URL is indexed itself.
+pub async fn delete_many_by_url<C: ConnectionTrait>(db: &C, urls: &[String]) -> Result<u64, DbErr> {
+    let mut num_deleted = 0;
+    for chunk in urls.chunks(BATCH_SIZE) {
+        let res = Entity::delete_many()
+            .filter(Column::Url.is_in(chunk.to_vec()))
+            .exec(db)
+            .await?;
+        num_deleted += res.rows_affected;
+    }
+
+    Ok(num_deleted)
+}
+
+// Helper method to copy the table from one database to another
+pub async fn copy_table(
+    from: &DatabaseConnection,
+    to: &DatabaseConnection,
+) -> anyhow::Result<(), sea_orm::DbErr> {
+    let mut pages = Entity::find().paginate(from, 1000);
+    Entity::delete_many().exec(to).await?;
+    while let Ok(Some(pages)) = pages.fetch_and_next().await {
+        let active_model = pages
+            .into_iter()
+            .map(|model| model.into())
+            .collect::<Vec<ActiveModel>>();
+        Entity::insert_many(active_model)
+            .on_conflict(
+                sea_orm::sea_query::OnConflict::columns(vec![Column::Id])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec(to)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::{ActiveModelTrait, Set};
+
+    use super::MatchType;
+    use crate::models::indexed_document;
+    use crate::test::setup_test_db;
+
+    #[tokio::test]
+    async fn test_upsert() {
+        let db = setup_test_db().await;
+
+        let mut ids = Vec::new();
+        for url in ["https://example.com/a", "https://example.com/b"] {
+            let doc = indexed_document::ActiveModel {
+                domain: Set("example.com".into()),
+                url: Set(url.into()),
+                doc_id: Set(url.into()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .expect("Unable to insert doc");
+            ids.push(doc.id);
+        }
+
+        let alias = "https://mirror.example.com/a";
+        super::upsert(&db, ids[0], alias, MatchType::Exact)
+            .await
+            .expect("Unable to add alias");
+        // Repoint the alias to the second document
+        super::upsert(&db, ids[1], alias, MatchType::Near)
+            .await
+            .expect("Unable to update alias");
+
+        let aliases = super::find_by_document(&db, ids[0]).await.unwrap();
+        assert!(aliases.is_empty());
+
+        let aliases = super::find_by_document(&db, ids[1]).await.unwrap();
+        assert_eq!(aliases.len(), 1);
+        assert_eq!(aliases[0].url, alias);
+        assert_eq!(aliases[0].match_type, MatchType::Near);
+
+        // Aliases are removed with their canonical document
+        indexed_document::delete_many_by_id(&db, &ids)
+            .await
+            .unwrap();
+        let aliases = super::find_by_document(&db, ids[1]).await.unwrap();
+        assert!(aliases.is_empty());
+    }
+}
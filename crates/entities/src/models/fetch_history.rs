@@ -25,6 +25,13 @@ pub struct Model {
     pub hash: Option<String>,
     /// HTTP status when last fetching this page.
     pub status: u16,
+    /// `ETag` response header, sent as `If-None-Match` when recrawling.
+    pub etag: Option<String>,
+    /// `Last-Modified` response header, sent as `If-Modified-Since` when recrawling.
+    pub last_modified: Option<String>,
+    /// Number of times the content hash changed between fetches.
+    #[sea_orm(default_value = 0)]
+    pub change_count: i64,
     /// Ignore this URL in the future.
     #[sea_orm(default_value = false)]
     pub no_index: bool,
@@ -67,6 +74,46 @@ impl ActiveModelBehavior for ActiveModel {
     }
 }
 
+impl Model {
+    pub fn validators(&self) -> CacheValidators {
+        CacheValidators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+/// Cache validators returned by a server, used to make conditional requests
+/// for pages we've already fetched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// Pulls the validators out of a list of response headers. Header names
+    /// are expected to be lowercase.
+    pub fn from_headers(headers: &[(String, String)]) -> Self {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        Self {
+            etag: find("etag"),
+            last_modified: find("last-modified"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
 pub async fn find_by_url(
     db: &DatabaseConnection,
     url: &Url,
@@ -84,6 +131,7 @@ pub async fn upsert(
     path: &str,
     hash: Option<String>,
     status: u16,
+    validators: &CacheValidators,
 ) -> anyhow::Result<Model, sea_orm::DbErr> {
     let history = Entity::find()
         .filter(Column::Domain.eq(domain))
@@ -94,9 +142,15 @@ pub async fn upsert(
     match history {
         // Already exists, update
         Some(res) => {
+            let changed = matches!((&res.hash, &hash), (Some(old), Some(new)) if old != new);
+            let change_count = res.change_count + i64::from(changed);
+
             let mut model: ActiveModel = res.into();
+            model.change_count = Set(change_count);
             model.hash = Set(hash.to_owned());
             model.status = Set(status);
+            model.etag = Set(validators.etag.clone());
+            model.last_modified = Set(validators.last_modified.clone());
             model.updated_at = Set(chrono::Utc::now());
             Ok(model.update(db).await?)
         }
@@ -107,6 +161,9 @@ pub async fn upsert(
                 path: Set(path.to_owned()),
                 hash: Set(hash.to_owned()),
                 status: Set(status),
+                etag: Set(validators.etag.clone()),
+                last_modified: Set(validators.last_modified.clone()),
+                change_count: Set(0),
                 ..Default::default()
             };
 
@@ -179,4 +236,55 @@ mod test {
         assert_eq!(res.path, path);
         assert_eq!(res.hash.unwrap(), hash);
     }
+
+    #[tokio::test]
+    async fn test_upsert_validators() {
+        let db = setup_test_db().await;
+
+        let headers = vec![
+            ("content-type".to_string(), "text/html".to_string()),
+            ("etag".to_string(), "\"abc123\"".to_string()),
+        ];
+        let validators = fetch_history::CacheValidators::from_headers(&headers);
+        assert_eq!(validators.etag, Some("\"abc123\"".to_string()));
+        assert!(validators.last_modified.is_none());
+
+        fetch_history::upsert(&db, "example.com", "/", None, 200, &validators)
+            .await
+            .unwrap();
+
+        let updated = fetch_history::CacheValidators {
+            etag: None,
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+        };
+        let res = fetch_history::upsert(&db, "example.com", "/", None, 200, &updated)
+            .await
+            .unwrap();
+        assert_eq!(res.validators(), updated);
+        assert_eq!(fetch_history::Entity::find().count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_change_count() {
+        let db = setup_test_db().await;
+        let validators = fetch_history::CacheValidators::default();
+
+        for hash in ["a", "a", "b", "c", "c"] {
+            fetch_history::upsert(
+                &db,
+                "example.com",
+                "/",
+                Some(hash.to_string()),
+                200,
+                &validators,
+            )
+            .await
+            .unwrap();
+        }
+
+        let res = fetch_history::upsert(&db, "example.com", "/", None, 200, &validators)
+            .await
+            .unwrap();
+        assert_eq!(res.change_count, 2);
+    }
 }
@@ -1,12 +1,13 @@
 use std::collections::HashSet;
 use std::ops::Sub;
 
-use crate::models::{document_tag, tag};
+use crate::models::{document_alias, document_tag, tag};
 use crate::BATCH_SIZE;
 use sea_orm::entity::prelude::*;
 use sea_orm::sea_query::OnConflict;
 use sea_orm::{
-    ConnectionTrait, FromQueryResult, InsertResult, QuerySelect, QueryTrait, Set, Statement,
+    Condition, ConnectionTrait, FromQueryResult, InsertResult, QueryOrder, QuerySelect, QueryTrait,
+    Set, Statement,
 };
 use serde::Serialize;
 
@@ -26,6 +27,17 @@ pub struct Model {
     pub open_url: Option<String>,
     /// Reference to the document in the index
     pub doc_id: String,
+    /// SHA-256 of the document content, used to find exact duplicates.
+    pub content_hash: Option<String>,
+    /// 64-bit SimHash of the document content, used to find near duplicates.
+    pub simhash: Option<i64>,
+    /// The 16-bit bands of the SimHash, from the highest bits to the lowest.
+    /// Indexed separately so near duplicate candidates can be looked up w/
+    /// equality checks, see `find_simhash_candidates`.
+    pub simhash_band0: Option<i32>,
+    pub simhash_band1: Option<i32>,
+    pub simhash_band2: Option<i32>,
+    pub simhash_band3: Option<i32>,
     /// When this was indexed
     pub created_at: DateTimeUtc,
     /// When this was last updated
@@ -79,6 +91,23 @@ impl ActiveModelBehavior for ActiveModel {
     }
 }
 
+impl ActiveModel {
+    /// Sets the SimHash along w/ the bands used to look it up.
+    pub fn set_simhash(&mut self, simhash: Option<i64>) {
+        let bands = simhash.map(simhash_bands);
+        self.simhash = Set(simhash);
+        self.simhash_band0 = Set(bands.map(|bands| bands[0]));
+        self.simhash_band1 = Set(bands.map(|bands| bands[1]));
+        self.simhash_band2 = Set(bands.map(|bands| bands[2]));
+        self.simhash_band3 = Set(bands.map(|bands| bands[3]));
+    }
+}
+
+/// Splits a SimHash into its four 16-bit bands, from the highest bits to the lowest.
+pub fn simhash_bands(simhash: i64) -> [i32; 4] {
+    [48, 32, 16, 0].map(|shift| ((simhash >> shift) & 0xffff) as i32)
+}
+
 impl Model {
     pub async fn insert_tags<C: ConnectionTrait>(
         &self,
@@ -145,7 +174,15 @@ pub async fn insert_many(db: &impl ConnectionTrait, docs: &[ActiveModel]) -> Res
         Entity::insert_many(insert_chunk.to_vec())
             .on_conflict(
                 OnConflict::columns(vec![Column::Url])
-                    .update_column(Column::UpdatedAt)
+                    .update_columns(vec![
+                        Column::ContentHash,
+                        Column::Simhash,
+                        Column::SimhashBand0,
+                        Column::SimhashBand1,
+                        Column::SimhashBand2,
+                        Column::SimhashBand3,
+                        Column::UpdatedAt,
+                    ])
                     .to_owned(),
             )
             .exec(db)
@@ -345,6 +382,12 @@ pub async fn delete_many_by_id(
         .exec(db)
         .await?;
 
+    // Delete all aliases pointing to these documents
+    document_alias::Entity::delete_many()
+        .filter(document_alias::Column::IndexedDocumentId.is_in(dbids.to_owned()))
+        .exec(db)
+        .await?;
+
     // Delete item
     let mut num_deleted = 0;
     for chunk in dbids.chunks(BATCH_SIZE) {
@@ -459,6 +502,56 @@ pub async fn get_tag_ids_by_doc_id(
     .await
 }
 
+/// Matches documents crawled from the web, as opposed to local files or
+/// documents synced from a connection.
+fn is_web_page() -> Condition {
+    Condition::any()
+        .add(Column::Url.starts_with("http://"))
+        .add(Column::Url.starts_with("https://"))
+}
+
+/// Finds an indexed web page, other than `url`, with the exact same content.
+pub async fn find_by_content_hash<C: ConnectionTrait>(
+    db: &C,
+    content_hash: &str,
+    url: &str,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::ContentHash.eq(content_hash))
+        .filter(Column::Url.ne(url))
+        .filter(is_web_page())
+        .order_by_asc(Column::Id)
+        .one(db)
+        .await
+}
+
+/// Finds indexed web pages, other than `url`, whose SimHash shares at least one
+/// 16-bit band with `simhash`. Any two fingerprints within a hamming distance
+/// of 3 are guaranteed to share a band, callers are expected to check the
+/// actual distance of the returned candidates. The oldest documents come first.
+pub async fn find_simhash_candidates<C: ConnectionTrait>(
+    db: &C,
+    simhash: i64,
+    url: &str,
+    limit: u64,
+) -> Result<Vec<Model>, DbErr> {
+    let bands = simhash_bands(simhash);
+    Entity::find()
+        .filter(
+            Condition::any()
+                .add(Column::SimhashBand0.eq(bands[0]))
+                .add(Column::SimhashBand1.eq(bands[1]))
+                .add(Column::SimhashBand2.eq(bands[2]))
+                .add(Column::SimhashBand3.eq(bands[3])),
+        )
+        .filter(Column::Url.ne(url))
+        .filter(is_web_page())
+        .order_by_asc(Column::Id)
+        .limit(limit)
+        .all(db)
+        .await
+}
+
 pub enum DocumentIdentifier<'a> {
     DocId(&'a str),
     Url(&'a str),
@@ -641,4 +734,59 @@ mod test {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_find_duplicates() {
+        let db = setup_test_db().await;
+
+        let fingerprint: i64 = 0x1234_5678_9abc_def0;
+        let mut doc = super::ActiveModel {
+            domain: Set("example.com".into()),
+            url: Set("https://example.com/page".into()),
+            doc_id: Set("1".into()),
+            content_hash: Set(Some("abc".into())),
+            ..Default::default()
+        };
+        doc.set_simhash(Some(fingerprint));
+        doc.save(&db).await.unwrap();
+
+        // Local files w/ the same content are never duplicates
+        let mut file = super::ActiveModel {
+            domain: Set("localhost".into()),
+            url: Set("file:///tmp/page.html".into()),
+            doc_id: Set("2".into()),
+            content_hash: Set(Some("abc".into())),
+            ..Default::default()
+        };
+        file.set_simhash(Some(fingerprint));
+        file.save(&db).await.unwrap();
+
+        let found = super::find_by_content_hash(&db, "abc", "https://example.com/page?ref=1")
+            .await
+            .unwrap();
+        assert_eq!(found.map(|doc| doc.doc_id), Some("1".to_string()));
+        // Documents never duplicate themselves
+        let found = super::find_by_content_hash(&db, "abc", "https://example.com/page")
+            .await
+            .unwrap();
+        assert!(found.is_none());
+
+        // Only the low band differs
+        let candidates =
+            super::find_simhash_candidates(&db, fingerprint ^ 0b111, "https://mirror.com", 10)
+                .await
+                .unwrap();
+        assert_eq!(candidates.len(), 1);
+
+        // Every band differs
+        let candidates = super::find_simhash_candidates(
+            &db,
+            fingerprint ^ 0x0001_0001_0001_0001,
+            "https://mirror.com",
+            10,
+        )
+        .await
+        .unwrap();
+        assert!(candidates.is_empty());
+    }
 }
@@ -1,5 +1,7 @@
+use std::collections::HashSet;
+
 use sea_orm::entity::prelude::*;
-use sea_orm::Set;
+use sea_orm::{QueryOrder, Set};
 use url::Url;
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -51,6 +53,75 @@ pub async fn save_link(
     Ok(())
 }
 
+/// Replaces the outbound links saved for `src` with `dsts`. Links without a
+/// host (e.g. file paths) and links back to the same page are skipped.
+pub async fn replace_links(
+    db: &DatabaseConnection,
+    src: &str,
+    dsts: &[String],
+) -> anyhow::Result<usize, sea_orm::DbErr> {
+    let src_domain = match Url::parse(src)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_owned()))
+    {
+        Some(domain) => domain,
+        None => return Ok(0),
+    };
+
+    Entity::delete_many()
+        .filter(Column::SrcUrl.eq(src))
+        .exec(db)
+        .await?;
+
+    let mut seen = HashSet::new();
+    let new_links = dsts
+        .iter()
+        .filter(|dst| dst.as_str() != src && seen.insert(dst.as_str()))
+        .filter_map(|dst| {
+            let dst_domain = Url::parse(dst).ok()?.host_str()?.to_owned();
+            Some(ActiveModel {
+                src_domain: Set(src_domain.clone()),
+                src_url: Set(src.to_owned()),
+                dst_domain: Set(dst_domain),
+                dst_url: Set(dst.to_owned()),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<ActiveModel>>();
+
+    let num_links = new_links.len();
+    // Insert in chunks to stay under the max number of SQL variables.
+    for chunk in new_links.chunks(100) {
+        Entity::insert_many(chunk.to_vec()).exec(db).await?;
+    }
+
+    Ok(num_links)
+}
+
+/// Pages that link to `url`
+pub async fn find_backlinks(
+    db: &DatabaseConnection,
+    url: &str,
+) -> anyhow::Result<Vec<Model>, sea_orm::DbErr> {
+    Entity::find()
+        .filter(Column::DstUrl.eq(url))
+        .filter(Column::SrcUrl.ne(url))
+        .order_by_asc(Column::SrcUrl)
+        .all(db)
+        .await
+}
+
+/// All (src, dst) url pairs in the link graph
+pub async fn all_edges(db: &DatabaseConnection) -> anyhow::Result<Vec<(String, String)>, DbErr> {
+    let mut edges = Vec::new();
+    let mut pages = Entity::find().order_by_asc(Column::Id).paginate(db, 1000);
+    while let Some(links) = pages.fetch_and_next().await? {
+        edges.extend(links.into_iter().map(|link| (link.src_url, link.dst_url)));
+    }
+
+    Ok(edges)
+}
+
 // Helper method to copy the table from one database to another
 pub async fn copy_table(
     from: &DatabaseConnection,
@@ -74,3 +145,56 @@ pub async fn copy_table(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use sea_orm::EntityTrait;
+
+    use crate::test::setup_test_db;
+
+    use super::{all_edges, find_backlinks, replace_links, Entity};
+
+    #[tokio::test]
+    async fn test_replace_links() {
+        let db = setup_test_db().await;
+        let src = "https://example.com/a";
+
+        let num_links = replace_links(
+            &db,
+            src,
+            &[
+                "https://example.com/b".into(),
+                "https://example.com/b".into(),
+                "https://other.com/c".into(),
+                src.into(),
+                "file:///tmp/test.txt".into(),
+            ],
+        )
+        .await
+        .expect("Unable to save links");
+        assert_eq!(num_links, 2);
+
+        // Recrawling replaces the old links
+        replace_links(&db, src, &["https://other.com/c".into()])
+            .await
+            .expect("Unable to save links");
+        let links = Entity::find()
+            .all(&db)
+            .await
+            .expect("Unable to query links");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].dst_domain, "other.com");
+
+        let backlinks = find_backlinks(&db, "https://other.com/c")
+            .await
+            .expect("Unable to query backlinks");
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].src_url, src);
+
+        let edges = all_edges(&db).await.expect("Unable to query edges");
+        assert_eq!(
+            edges,
+            vec![(src.to_string(), "https://other.com/c".to_string())]
+        );
+    }
+}
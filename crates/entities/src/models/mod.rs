@@ -4,6 +4,7 @@ pub mod bootstrap_queue;
 pub mod connection;
 pub mod crawl_queue;
 pub mod crawl_tag;
+pub mod document_alias;
 pub mod document_tag;
 pub mod fetch_history;
 pub mod indexed_document;
@@ -12,6 +13,7 @@ pub mod link;
 pub mod processed_files;
 pub mod resource_rule;
 pub mod schema;
+pub mod search_history;
 pub mod tag;
 
 use shared::config::Config;
@@ -67,6 +69,8 @@ pub async fn copy_all_tables(
     resource_rule::copy_table(from, to).await?;
     tag::copy_table(from, to).await?;
     document_tag::copy_table(from, to).await?;
+    document_alias::copy_table(from, to).await?;
+    search_history::copy_table(from, to).await?;
     Ok(())
 }
 
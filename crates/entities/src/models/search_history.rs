@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::entity::prelude::*;
+use sea_orm::{FromJsonQueryResult, QueryOrder, QuerySelect, Set};
+use serde::{Deserialize, Serialize};
+
+/// Searches made shortly after the previous one that extend or trim its query
+/// are treated as the same search still being typed.
+const KEYSTROKE_WINDOW_SECS: i64 = 60;
+/// Max number of past searches looked at when completing a query.
+const MAX_SEARCHES_SCANNED: u64 = 500;
+/// Max number of opened results looked at when ranking a query.
+const MAX_OPENS_SCANNED: u64 = 1000;
+/// Past queries sharing less than this fraction of their words w/ a query
+/// aren't considered similar to it.
+pub const MIN_QUERY_SIMILARITY: f32 = 0.5;
+/// Cap on the weight of a document opened many times, so it doesn't drown
+/// out how well other documents match.
+pub const MAX_OPENED_WEIGHT: f32 = 3.0;
+
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum, Serialize, Eq)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum HistoryAction {
+    /// A query was searched.
+    #[sea_orm(string_value = "Searched")]
+    Searched,
+    /// A result was opened from the results of a query.
+    #[sea_orm(string_value = "Opened")]
+    Opened,
+}
+
+/// Doc ids of the results shown for a search, in the order they were shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct ShownResults {
+    pub doc_ids: Vec<String>,
+}
+
+/// Searches & opened results, kept locally to rank the results the user has
+/// picked before higher & to complete queries.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Eq)]
+#[sea_orm(table_name = "search_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// Query as typed by the user.
+    pub query: String,
+    pub action: HistoryAction,
+    /// Results shown for a search.
+    pub shown: Option<ShownResults>,
+    /// Document opened from the results.
+    pub doc_id: Option<String>,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        Self {
+            created_at: Set(chrono::Utc::now()),
+            updated_at: Set(chrono::Utc::now()),
+            ..ActiveModelTrait::default()
+        }
+    }
+
+    // Triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert {
+            self.updated_at = Set(chrono::Utc::now());
+        }
+
+        Ok(self)
+    }
+}
+
+/// Lowercased words of a query, ignoring punctuation & field clause syntax.
+fn query_words(query: &str) -> HashSet<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Fraction of the words in either query that are in both.
+pub fn query_similarity(a: &str, b: &str) -> f32 {
+    let a = query_words(a);
+    let b = query_words(b);
+    let num_shared = a.intersection(&b).count();
+    let num_total = a.union(&b).count();
+    if num_total == 0 {
+        0.0
+    } else {
+        num_shared as f32 / num_total as f32
+    }
+}
+
+/// Records a search & the results shown for it. The search bar searches as
+/// the user types, so a search that extends or trims the previous one
+/// replaces it.
+pub async fn record_search(
+    db: &DatabaseConnection,
+    query: &str,
+    shown: Vec<String>,
+) -> Result<Model, DbErr> {
+    let query = query.trim();
+    let last = Entity::find()
+        .filter(Column::Action.eq(HistoryAction::Searched))
+        .order_by_desc(Column::Id)
+        .one(db)
+        .await?;
+
+    let shown = Some(ShownResults { doc_ids: shown });
+    if let Some(last) = last {
+        let is_keystroke = (chrono::Utc::now() - last.updated_at).num_seconds()
+            < KEYSTROKE_WINDOW_SECS
+            && (query.starts_with(last.query.as_str()) || last.query.starts_with(query));
+
+        if is_keystroke {
+            let mut update: ActiveModel = last.into();
+            update.query = Set(query.to_string());
+            update.shown = Set(shown);
+            return update.update(db).await;
+        }
+    }
+
+    ActiveModel {
+        query: Set(query.to_string()),
+        action: Set(HistoryAction::Searched),
+        shown: Set(shown),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+/// Records a result opened from the results of `query`.
+pub async fn record_opened(
+    db: &DatabaseConnection,
+    query: &str,
+    doc_id: &str,
+) -> Result<Model, DbErr> {
+    ActiveModel {
+        query: Set(query.trim().to_string()),
+        action: Set(HistoryAction::Opened),
+        doc_id: Set(Some(doc_id.to_string())),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+/// Past queries starting w/ `prefix`, ignoring case, most recent first.
+pub async fn past_queries(
+    db: &DatabaseConnection,
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<String>, DbErr> {
+    let prefix = prefix.to_lowercase();
+    let searches = Entity::find()
+        .filter(Column::Action.eq(HistoryAction::Searched))
+        .order_by_desc(Column::UpdatedAt)
+        .order_by_desc(Column::Id)
+        .limit(MAX_SEARCHES_SCANNED)
+        .all(db)
+        .await?;
+
+    let mut seen = HashSet::new();
+    let queries = searches
+        .into_iter()
+        .map(|search| search.query)
+        .filter(|query| {
+            let query = query.to_lowercase();
+            query.starts_with(&prefix) && query != prefix && seen.insert(query)
+        })
+        .take(limit)
+        .collect();
+
+    Ok(queries)
+}
+
+/// Documents opened from the results of queries similar to `query`, w/ a
+/// weight based on how often & for how similar a query they were opened.
+pub async fn opened_for_query(
+    db: &DatabaseConnection,
+    query: &str,
+) -> Result<Vec<(String, f32)>, DbErr> {
+    let opens = Entity::find()
+        .filter(Column::Action.eq(HistoryAction::Opened))
+        .order_by_desc(Column::Id)
+        .limit(MAX_OPENS_SCANNED)
+        .all(db)
+        .await?;
+
+    let mut weights: HashMap<String, f32> = HashMap::new();
+    for open in opens {
+        let similarity = query_similarity(query, &open.query);
+        if let Some(doc_id) = open.doc_id {
+            if similarity >= MIN_QUERY_SIMILARITY {
+                *weights.entry(doc_id).or_default() += similarity;
+            }
+        }
+    }
+
+    let mut opened = weights
+        .into_iter()
+        .map(|(doc_id, weight)| (doc_id, weight.min(MAX_OPENED_WEIGHT)))
+        .collect::<Vec<_>>();
+    opened.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    Ok(opened)
+}
+
+/// Removes all searches & opened results.
+pub async fn clear(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    let res = Entity::delete_many().exec(db).await?;
+    Ok(res.rows_affected)
+}
+
+// Helper method to copy the table from one database to another
+pub async fn copy_table(
+    from: &DatabaseConnection,
+    to: &DatabaseConnection,
+) -> anyhow::Result<(), sea_orm::DbErr> {
+    let mut pages = Entity::find().paginate(from, 1000);
+    Entity::delete_many().exec(to).await?;
+    while let Ok(Some(pages)) = pages.fetch_and_next().await {
+        let active_model = pages
+            .into_iter()
+            .map(|model| model.into())
+            .collect::<Vec<ActiveModel>>();
+        Entity::insert_many(active_model)
+            .on_conflict(
+                sea_orm::sea_query::OnConflict::columns(vec![Column::Id])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec(to)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sea_orm::prelude::*;
+
+    use super::{query_similarity, HistoryAction};
+    use crate::models::search_history;
+    use crate::test::setup_test_db;
+
+    #[tokio::test]
+    async fn test_record_search() {
+        let db = setup_test_db().await;
+
+        for query in ["ru", "rust", "rust async", "tokio", "rust"] {
+            search_history::record_search(&db, query, vec!["doc".into()])
+                .await
+                .expect("Unable to record search");
+        }
+
+        // Keystrokes are collapsed into the finished query
+        let searches = search_history::Entity::find()
+            .filter(search_history::Column::Action.eq(HistoryAction::Searched))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(searches, 3);
+
+        let queries = search_history::past_queries(&db, "", 5).await.unwrap();
+        assert_eq!(queries, vec!["rust", "tokio", "rust async"]);
+        let queries = search_history::past_queries(&db, "RUST", 5).await.unwrap();
+        assert_eq!(queries, vec!["rust async"]);
+
+        search_history::clear(&db).await.unwrap();
+        assert!(search_history::past_queries(&db, "", 5)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_opened_for_query() {
+        let db = setup_test_db().await;
+
+        assert_eq!(query_similarity("Rust async", "async rust"), 1.0);
+        assert_eq!(query_similarity("rust async", "rust"), 0.5);
+        assert_eq!(query_similarity("rust", "tokio"), 0.0);
+
+        for (query, doc_id) in [
+            ("rust async", "tokio-docs"),
+            ("async rust", "tokio-docs"),
+            ("rust", "rust-book"),
+            ("python", "python-docs"),
+        ] {
+            search_history::record_opened(&db, query, doc_id)
+                .await
+                .expect("Unable to record open");
+        }
+
+        let opened = search_history::opened_for_query(&db, "rust async")
+            .await
+            .unwrap();
+        assert_eq!(
+            opened,
+            vec![
+                ("tokio-docs".to_string(), 2.0),
+                ("rust-book".to_string(), 0.5)
+            ]
+        );
+    }
+}
@@ -52,6 +52,9 @@ pub enum TagType {
     /// Pull from the lens categorization
     #[strum(serialize = "category")]
     Category,
+    /// Programming language of a source file
+    #[strum(serialize = "language")]
+    Language,
     /// Other custom generated TagTypes.
     #[strum(serialize = "Other(String)")]
     Other(String),
@@ -78,6 +81,7 @@ fn string_to_tag_type(v: &str) -> TagType {
         "repository" => TagType::Repository,
         "fileext" => TagType::FileExt,
         "category" => TagType::Category,
+        "language" => TagType::Language,
         other => TagType::Other(String::from(other)),
     }
 }
@@ -97,6 +101,7 @@ impl ToString for TagType {
             Self::Repository => "repository",
             Self::FileExt => "fileext",
             Self::Category => "category",
+            Self::Language => "language",
             Self::Other(label) => label.as_str(),
         }
         .to_owned()
@@ -329,6 +334,28 @@ pub async fn get_tags_by_value(
     find.all(db).await
 }
 
+/// Helper method used to find tags by their label & value, ignoring case. Tag
+/// pairs that don't exist are skipped.
+pub async fn find_by_label_value_ignore_case(
+    db: &DatabaseConnection,
+    tags: &[(String, String)],
+) -> Result<Vec<Model>, DbErr> {
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut condition = Condition::any();
+    for (label, value) in tags {
+        condition = condition.add(
+            Condition::all()
+                .add(Expr::expr(Func::lower(Expr::col(Column::Label))).eq(label.to_lowercase()))
+                .add(Expr::expr(Func::lower(Expr::col(Column::Value))).eq(value.to_lowercase())),
+        );
+    }
+
+    Entity::find().filter(condition).all(db).await
+}
+
 // Helper method to copy the table from one database to another
 pub async fn copy_table(
     from: &DatabaseConnection,
@@ -396,6 +423,25 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_find_by_label_value_ignore_case() -> Result<(), DbErr> {
+        let db = setup_test_db().await;
+        let lens = super::get_or_create(&db, tag::TagType::Lens, "Rust").await?;
+        let _ = super::get_or_create(&db, tag::TagType::Type, "issue").await?;
+
+        let found = super::find_by_label_value_ignore_case(
+            &db,
+            &[
+                ("lens".into(), "rust".into()),
+                ("owner".into(), "nobody".into()),
+            ],
+        )
+        .await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, lens.id);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_conflict() -> Result<(), DbErr> {
         let db = setup_test_db().await;
@@ -2,8 +2,8 @@ use sea_orm::{sea_query::Index, ConnectionTrait, DatabaseConnection, Schema};
 use shared::config::Config;
 
 use crate::models::{
-    bootstrap_queue, connection, crawl_queue, crawl_tag, create_connection, document_tag,
-    fetch_history, indexed_document, lens, link, resource_rule, tag,
+    bootstrap_queue, connection, crawl_queue, crawl_tag, create_connection, document_alias,
+    document_tag, fetch_history, indexed_document, lens, link, resource_rule, search_history, tag,
 };
 
 #[allow(dead_code)]
@@ -92,6 +92,15 @@ async fn setup_schema(db: &DatabaseConnection) -> anyhow::Result<(), sea_orm::Db
     )
     .await?;
 
+    db.execute(
+        builder.build(
+            schema
+                .create_table_from_entity(document_alias::Entity)
+                .if_not_exists(),
+        ),
+    )
+    .await?;
+
     db.execute(
         builder.build(
             schema
@@ -110,6 +119,15 @@ async fn setup_schema(db: &DatabaseConnection) -> anyhow::Result<(), sea_orm::Db
     )
     .await?;
 
+    db.execute(
+        builder.build(
+            schema
+                .create_table_from_entity(search_history::Entity)
+                .if_not_exists(),
+        ),
+    )
+    .await?;
+
     db.execute(
         builder.build(
             &Index::create()
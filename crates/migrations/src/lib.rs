@@ -30,6 +30,12 @@ mod m20230201_000001_add_tag_index;
 mod m20230203_000001_add_indexed_document_index;
 mod m20230220_000001_remove_legacy_plugins;
 mod m20230315_000001_migrate_search_schema;
+mod m20230405_000002_add_link_index;
+mod m20230406_000001_add_fetch_validators;
+mod m20230407_000001_add_fetch_change_count;
+mod m20230409_000001_add_document_dedup;
+mod m20230410_000001_add_search_history;
+mod m20230411_000001_add_simhash_bands;
 mod utils;
 
 pub struct Migrator;
@@ -65,6 +71,12 @@ impl MigratorTrait for Migrator {
             Box::new(m20230203_000001_add_indexed_document_index::Migration),
             Box::new(m20230220_000001_remove_legacy_plugins::Migration),
             Box::new(m20230315_000001_migrate_search_schema::Migration),
+            Box::new(m20230405_000002_add_link_index::Migration),
+            Box::new(m20230406_000001_add_fetch_validators::Migration),
+            Box::new(m20230407_000001_add_fetch_change_count::Migration),
+            Box::new(m20230409_000001_add_document_dedup::Migration),
+            Box::new(m20230410_000001_add_search_history::Migration),
+            Box::new(m20230411_000001_add_simhash_bands::Migration),
         ]
     }
 }
@@ -0,0 +1,36 @@
+use entities::sea_orm::Statement;
+use sea_orm_migration::prelude::*;
+
+use sea_orm_migration::sea_orm::ConnectionTrait;
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230405_000002_add_link_index"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Used to replace a page's outbound links & to look up backlinks.
+        for statement in [
+            "CREATE INDEX IF NOT EXISTS \"idx-link-src-url\" ON \"link\" (\"src_url\");",
+            "CREATE INDEX IF NOT EXISTS \"idx-link-dst-url\" ON \"link\" (\"dst_url\");",
+        ] {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    statement.to_string(),
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
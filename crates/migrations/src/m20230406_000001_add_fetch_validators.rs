@@ -0,0 +1,33 @@
+use entities::models::fetch_history;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230406_000001_add_fetch_validators"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cache validators used for conditional requests when recrawling.
+        for column in ["etag", "last_modified"] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(fetch_history::Entity)
+                        .add_column(ColumnDef::new(Alias::new(column)).string())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
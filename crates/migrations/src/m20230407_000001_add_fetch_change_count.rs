@@ -0,0 +1,36 @@
+use entities::models::fetch_history;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230407_000001_add_fetch_change_count"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Used to determine how often a page is recrawled
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(fetch_history::Entity)
+                    .add_column(
+                        ColumnDef::new(Alias::new("change_count"))
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use entities::models::indexed_document;
+use entities::sea_orm::{ConnectionTrait, DbBackend, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230409_000001_add_document_dedup"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Content fingerprints used to detect exact & near duplicate documents
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(indexed_document::Entity)
+                    .add_column(ColumnDef::new(Alias::new("content_hash")).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(indexed_document::Entity)
+                    .add_column(ColumnDef::new(Alias::new("simhash")).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        let document_alias = if manager.get_database_backend() == DbBackend::Sqlite {
+            Some(
+                r#"CREATE TABLE IF NOT EXISTS "document_alias" (
+                    "id" integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+                    "indexed_document_id" integer NOT NULL,
+                    "url" text NOT NULL UNIQUE,
+                    "match_type" text NOT NULL,
+                    "created_at" text NOT NULL,
+                    "updated_at" text NOT NULL,
+                    FOREIGN KEY(indexed_document_id) REFERENCES indexed_document(id)
+                );"#,
+            )
+        } else if manager.get_database_backend() == DbBackend::Postgres {
+            Some(
+                r#"CREATE TABLE IF NOT EXISTS "document_alias" (
+                    "id" BIGSERIAL PRIMARY KEY,
+                    "indexed_document_id" integer NOT NULL,
+                    "url" text NOT NULL UNIQUE,
+                    "match_type" text NOT NULL,
+                    "created_at" TIMESTAMPTZ NOT NULL,
+                    "updated_at" TIMESTAMPTZ NOT NULL,
+                    CONSTRAINT fk_indexed_document_id
+                            FOREIGN KEY(indexed_document_id)
+                                REFERENCES indexed_document(id)
+                );"#,
+            )
+        } else {
+            None
+        };
+
+        if let Some(document_alias) = document_alias {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    document_alias.to_string(),
+                ))
+                .await?;
+        }
+
+        for statement in [
+            "CREATE INDEX IF NOT EXISTS \"idx-indexed_document-content_hash\" ON \"indexed_document\" (\"content_hash\");",
+            "CREATE INDEX IF NOT EXISTS \"idx-document_alias-indexed_document_id\" ON \"document_alias\" (\"indexed_document_id\");",
+        ] {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    statement.to_string(),
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
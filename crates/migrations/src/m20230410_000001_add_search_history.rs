@@ -0,0 +1,68 @@
+use entities::sea_orm::{ConnectionTrait, DbBackend, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230410_000001_add_search_history"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Searches & opened results, used to rank results & complete queries
+        let search_history = if manager.get_database_backend() == DbBackend::Sqlite {
+            Some(
+                r#"CREATE TABLE IF NOT EXISTS "search_history" (
+                    "id" integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+                    "query" text NOT NULL,
+                    "action" text NOT NULL,
+                    "shown" text,
+                    "doc_id" text,
+                    "created_at" text NOT NULL,
+                    "updated_at" text NOT NULL
+                );"#,
+            )
+        } else if manager.get_database_backend() == DbBackend::Postgres {
+            Some(
+                r#"CREATE TABLE IF NOT EXISTS "search_history" (
+                    "id" BIGSERIAL PRIMARY KEY,
+                    "query" text NOT NULL,
+                    "action" text NOT NULL,
+                    "shown" text,
+                    "doc_id" text,
+                    "created_at" TIMESTAMPTZ NOT NULL,
+                    "updated_at" TIMESTAMPTZ NOT NULL
+                );"#,
+            )
+        } else {
+            None
+        };
+
+        if let Some(search_history) = search_history {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    search_history.to_string(),
+                ))
+                .await?;
+
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    "CREATE INDEX IF NOT EXISTS \"idx-search_history-action\" ON \"search_history\" (\"action\");".to_string(),
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
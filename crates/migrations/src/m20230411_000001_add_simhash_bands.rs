@@ -0,0 +1,53 @@
+use entities::models::indexed_document;
+use entities::sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230411_000001_add_simhash_bands"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The 16-bit bands of the simhash, indexed to look up near duplicates
+        for band in 0..4 {
+            let column = format!("simhash_band{band}");
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(indexed_document::Entity)
+                        .add_column(ColumnDef::new(Alias::new(&column)).integer())
+                        .to_owned(),
+                )
+                .await?;
+
+            let shift = 48 - band * 16;
+            for statement in [
+                format!(
+                    "UPDATE \"indexed_document\" SET \"{column}\" = (\"simhash\" >> {shift}) & 65535 WHERE \"simhash\" IS NOT NULL;"
+                ),
+                format!(
+                    "CREATE INDEX IF NOT EXISTS \"idx-indexed_document-{column}\" ON \"indexed_document\" (\"{column}\");"
+                ),
+            ] {
+                manager
+                    .get_connection()
+                    .execute(Statement::from_string(
+                        manager.get_database_backend(),
+                        statement,
+                    ))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@ use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 pub use spyglass_lens::{
-    types::{LensFilters, LensRule, LensSource, UrlSanitizeConfig},
+    types::{LensFilters, LensRule, LensSource, RankingMode, RecrawlPolicy, UrlSanitizeConfig},
     LensConfig, PipelineConfiguration,
 };
 
@@ -125,6 +125,13 @@ pub struct UserSettings {
     pub user_action_settings: UserActionSettings,
     #[serde(default)]
     pub audio_settings: AudioSettings,
+    /// Stop recording searches & opened results, clearing the ones recorded
+    #[serde(default)]
+    pub disable_search_history: bool,
+    /// Elasticsearch compatible index to use instead of the local one, e.g.
+    /// `http://localhost:9200/spyglass`. Only set through the settings file.
+    #[serde(default)]
+    pub remote_index_url: Option<String>,
     // /// Hide the app icon from the dock/taskbar while running. Will still show up
     // /// in the menubar/systemtray.
     // #[serde(default)]
@@ -202,6 +209,13 @@ impl From<UserSettings> for Vec<(String, SettingOpts)> {
                 restart_required: false,
                 help_text: Some("Stop sending data to any 3rd-party service. See https://spyglass.fyi/telemetry for more info. This will require a restart.".into())
             }),
+            ("_.disable_search_history".into(), SettingOpts {
+                label: "Disable Search History".into(),
+                value: serde_json::to_string(&settings.disable_search_history).expect("Unable to ser search history value"),
+                form_type: FormType::Bool,
+                restart_required: false,
+                help_text: Some("Clears & stops recording your searches and the results you open. Search history is only stored on your computer and is used to rank results you've picked before higher.".into())
+            }),
             ("_.port".into(), SettingOpts {
                 label: "Spyglass Daemon Port".into(),
                 value: settings.port.to_string(),
@@ -273,6 +287,8 @@ impl Default for UserSettings {
             port: UserSettings::default_port(),
             user_action_settings: UserActionSettings::default(),
             audio_settings: AudioSettings::default(),
+            disable_search_history: false,
+            remote_index_url: None,
         }
     }
 }
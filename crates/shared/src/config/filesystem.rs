@@ -44,6 +44,10 @@ pub struct FileSystemSettings {
     pub watched_paths: Vec<PathBuf>,
     #[serde(default = "FileSystemSettings::default_extensions")]
     pub supported_extensions: Vec<String>,
+    /// External `pdftotext` binary used for PDFs the built-in extractor can't
+    /// read. Only set through the settings file.
+    #[serde(default)]
+    pub pdftotext_path: Option<PathBuf>,
 }
 
 impl FileSystemSettings {
@@ -76,6 +80,7 @@ impl Default for FileSystemSettings {
             enable_filesystem_scanning: false,
             watched_paths: FileSystemSettings::default_paths(),
             supported_extensions: FileSystemSettings::default_extensions(),
+            pdftotext_path: None,
         }
     }
 }
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use strum_macros::{AsRefStr, Display};
 
+use crate::request::ResultOpenedParam;
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ListenPayload<T> {
     pub payload: T,
@@ -103,6 +105,8 @@ pub struct InstallLensParams {
 pub struct OpenResultParams {
     pub url: String,
     pub application: Option<String>,
+    /// Search the result was opened from, if any.
+    pub search: Option<ResultOpenedParam>,
 }
 
 #[derive(Deserialize, Serialize)]
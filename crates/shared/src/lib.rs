@@ -41,3 +41,53 @@ pub fn url_to_file_path(path: &str, is_windows: bool) -> String {
 
     path
 }
+
+/// Separates the path of an archive from the path of a file inside of it in
+/// nested file URIs, e.g. `file:///a.zip!/docs/readme.md`
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+/// File name endings of the archives we index the contents of
+pub const ARCHIVE_SUFFIXES: &[&str] = &[".zip", ".tar.gz", ".tgz"];
+
+/// Whether the file name/path points to an archive we can look inside of.
+pub fn is_archive_path(path: &str) -> bool {
+    let path = path.to_lowercase();
+    ARCHIVE_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+}
+
+/// Splits a path to a file inside an archive into the path of the archive &
+/// the path of the file in the archive. Files in nested archives keep the
+/// rest of the nesting in the inner path, e.g. `a.zip!/b.zip!/c.md` is split
+/// into `a.zip` & `b.zip!/c.md`.
+pub fn split_archive_path(path: &str) -> (&str, Option<&str>) {
+    for (idx, _) in path.match_indices(ARCHIVE_SEPARATOR) {
+        let (archive, inner) = path.split_at(idx);
+        if is_archive_path(archive) {
+            return (archive, Some(&inner[ARCHIVE_SEPARATOR.len()..]));
+        }
+    }
+
+    (path, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::split_archive_path;
+
+    #[test]
+    fn test_split_archive_path() {
+        assert_eq!(
+            split_archive_path("/home/a.zip!/docs/readme.md"),
+            ("/home/a.zip", Some("docs/readme.md"))
+        );
+        assert_eq!(
+            split_archive_path("/home/wow!/a.TGZ!/b.zip!/c.md"),
+            ("/home/wow!/a.TGZ", Some("b.zip!/c.md"))
+        );
+        assert_eq!(split_archive_path("/home/a.zip"), ("/home/a.zip", None));
+        assert_eq!(
+            split_archive_path("/home/wow!/a.md"),
+            ("/home/wow!/a.md", None)
+        );
+    }
+}
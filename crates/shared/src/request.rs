@@ -1,11 +1,95 @@
+use crate::config::RankingMode;
 use crate::response::DocMetadata;
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct SearchParam {
     pub lenses: Vec<String>,
     pub query: String,
+    /// Number of results to skip, used to page through results.
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// Max number of results to return. Defaults to `SearchParam::DEFAULT_LIMIT`
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Only return documents published within this range.
+    #[serde(default)]
+    pub published: Option<DateRange>,
+    /// Only return documents last modified within this range.
+    #[serde(default)]
+    pub last_modified: Option<DateRange>,
+    /// Rank recently modified documents higher.
+    #[serde(default)]
+    pub boost_recent: bool,
+    /// Include tag, domain & lens counts for all matching documents.
+    #[serde(default)]
+    pub include_facets: bool,
+    /// Overrides how results are ranked. Defaults to the ranking configured by
+    /// the searched lenses.
+    #[serde(default)]
+    pub ranking: Option<RankingMode>,
+    /// Limits the number of results from the same domain, repository or folder.
+    #[serde(default)]
+    pub collapse: Option<CollapseParam>,
+}
+
+/// What search results are grouped by when collapsing.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CollapseField {
+    Domain,
+    /// Documents tagged with the same `repository` tag
+    Repository,
+    /// Documents in the same folder/path
+    ParentUrl,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CollapseParam {
+    pub field: CollapseField,
+    /// Max number of results to return per group. Defaults to
+    /// `CollapseParam::DEFAULT_MAX_PER_GROUP`
+    #[serde(default)]
+    pub max_per_group: Option<usize>,
+}
+
+impl CollapseParam {
+    pub const DEFAULT_MAX_PER_GROUP: usize = 2;
+
+    pub fn max_per_group(&self) -> usize {
+        self.max_per_group
+            .unwrap_or(Self::DEFAULT_MAX_PER_GROUP)
+            .max(1)
+    }
+}
+
+/// Range of dates as unix timestamps (in seconds). Either end can be left open.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DateRange {
+    /// Inclusive start of the range
+    pub start: Option<i64>,
+    /// Exclusive end of the range
+    pub end: Option<i64>,
+}
+
+impl SearchParam {
+    pub const DEFAULT_LIMIT: usize = 5;
+    pub const MAX_LIMIT: usize = 100;
+    /// Deepest result that can be paged to, the index collects every result
+    /// up to the offset.
+    pub const MAX_OFFSET: usize = 10_000;
+
+    /// Requested page size, clamped to a sane range.
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .clamp(1, Self::MAX_LIMIT)
+    }
+
+    /// Number of results to skip, clamped to `MAX_OFFSET`.
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or_default().min(Self::MAX_OFFSET)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -13,6 +97,35 @@ pub struct SearchLensesParam {
     pub query: String,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SuggestParam {
+    /// Query typed so far, the last word may be partial.
+    pub query: String,
+    /// Max number of completions to return. Defaults to
+    /// `SuggestParam::DEFAULT_LIMIT`
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl SuggestParam {
+    pub const DEFAULT_LIMIT: usize = 8;
+    pub const MAX_LIMIT: usize = 20;
+
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .clamp(1, Self::MAX_LIMIT)
+    }
+}
+
+/// A search result the user opened, recorded in the local search history.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ResultOpenedParam {
+    /// Query the result was found with.
+    pub query: String,
+    pub doc_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct QueueItemParam {
     pub url: String,
@@ -1,6 +1,8 @@
+use crate::config::RankingMode;
 use crate::url_to_file_path;
 use num_format::{Buffer, Locale};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use url::Url;
 
@@ -127,6 +129,16 @@ pub struct SearchMeta {
     pub query: String,
     pub num_docs: u32,
     pub wall_time_ms: u32,
+    /// Total number of documents matching the query.
+    #[serde(default)]
+    pub total_hits: u32,
+    /// Offset of the first result in this page.
+    #[serde(default)]
+    pub offset: u32,
+    /// Ranking used for these results, lexical when hybrid ranking was asked
+    /// for but the index has no embeddings.
+    #[serde(default)]
+    pub ranking: RankingMode,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -140,6 +152,17 @@ pub struct SearchResult {
     pub description: String,
     pub url: String,
     pub tags: Vec<(String, String)>,
+    /// Extracted metadata such as the author, language, page count, etc.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Where the best match starts in an audio transcript, in milliseconds.
+    /// The `url` opens the audio at this point.
+    #[serde(default)]
+    pub timestamp_ms: Option<u64>,
+    /// Page of a PDF w/ the best match, starting at 1. The `url` opens the
+    /// document at this page.
+    #[serde(default)]
+    pub page: Option<u32>,
     pub score: f32,
 }
 
@@ -230,6 +253,88 @@ impl From<SearchResult> for SearchResultTemplate {
 pub struct SearchResults {
     pub results: Vec<SearchResult>,
     pub meta: SearchMeta,
+    /// Breakdown of all matching documents, only included if requested.
+    #[serde(default)]
+    pub facets: Option<SearchFacets>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FacetCount {
+    pub label: String,
+    pub value: String,
+    /// Number of matching documents with this label/value
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SearchFacets {
+    /// Counts per tag, excluding lenses
+    pub tags: Vec<FacetCount>,
+    pub domains: Vec<FacetCount>,
+    pub lenses: Vec<FacetCount>,
+}
+
+/// Where a completion came from
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SuggestionKind {
+    /// A query that was searched before
+    PastQuery,
+    /// The last word completed w/ a word from the index
+    Term,
+    /// Title of a matching document
+    Title,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Full query to search for if the suggestion is picked
+    pub text: String,
+    pub kind: SuggestionKind,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SuggestResults {
+    pub completions: Vec<Suggestion>,
+    /// "Did you mean" query, set when words in the query have no exact match
+    /// in the index.
+    pub correction: Option<String>,
+}
+
+/// A page linking to another page
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Backlink {
+    pub url: String,
+    pub domain: String,
+    /// Document ID of the linking page, if it has been indexed
+    pub doc_id: Option<String>,
+    /// Title of the linking page, if it has been indexed
+    pub title: Option<String>,
+}
+
+/// Describes a snapshot of the index & database, stored alongside the snapshot
+/// as its manifest.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    /// Snapshot directory or exported archive
+    pub path: PathBuf,
+    /// RFC 3339 timestamp of when the snapshot was taken
+    pub created_at: String,
+    /// Version of the app that took the snapshot
+    pub app_version: String,
+    /// Search index schema version
+    pub schema_version: String,
+    pub num_documents: u64,
+}
+
+/// Outcome of merging a snapshot into the current library
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RestoreSnapshotResult {
+    /// Documents added from the snapshot
+    pub num_restored: u64,
+    /// Documents skipped since they're already in the library
+    pub num_skipped: u64,
+    /// Documents in the snapshot database missing from the snapshot index
+    pub num_missing: u64,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 pub mod pipeline;
 pub mod types;
 mod utils;
-use types::{LensFilters, LensRule, LensSource};
+use types::{LensFilters, LensRule, LensSource, RankingMode, RecrawlPolicy};
 
 pub use crate::pipeline::PipelineConfiguration;
 use utils::{regex_for_domain, regex_for_prefix};
@@ -45,6 +45,12 @@ pub struct LensConfig {
     /// Tags to automatically apply to any URLs indexed by this lens
     #[serde(default)]
     pub tags: Vec<(String, String)>,
+    /// How results are ranked when searching this lens
+    #[serde(default)]
+    pub ranking: RankingMode,
+    /// How often documents in this lens are recrawled
+    #[serde(default)]
+    pub recrawl: RecrawlPolicy,
     // Fields that are used internally & should not be serialized/deserialized
     #[serde(skip)]
     pub file_path: PathBuf,
@@ -135,6 +141,7 @@ impl LensConfig {
 #[cfg(test)]
 mod test {
     use super::LensConfig;
+    use crate::types::{RankingMode, RecrawlPolicy};
 
     #[test]
     fn test_into_regexes() {
@@ -177,4 +184,44 @@ mod test {
         let tags = config.all_tags();
         assert_eq!(tags.len(), 3);
     }
+
+    #[test]
+    fn test_ranking_mode() {
+        let lens_str = include_str!("../../../fixtures/lens/extra_fields.ron");
+        let config = LensConfig::from_string(lens_str).expect("is err");
+        assert_eq!(config.ranking, RankingMode::Lexical);
+
+        let config = LensConfig::from_string(
+            r#"(version: "1", name: "hybrid", domains: [], urls: [], ranking: Hybrid)"#,
+        )
+        .expect("is err");
+        assert_eq!(config.ranking, RankingMode::Hybrid);
+    }
+
+    #[test]
+    fn test_recrawl_policy() {
+        let lens_str = include_str!("../../../fixtures/lens/extra_fields.ron");
+        let config = LensConfig::from_string(lens_str).expect("is err");
+        assert_eq!(config.recrawl, RecrawlPolicy::Never);
+        assert_eq!(config.recrawl.min_interval_hours(), None);
+
+        let config = LensConfig::from_string(
+            r#"(
+                version: "1",
+                name: "news",
+                domains: [],
+                urls: [],
+                recrawl: Adaptive(min_interval_hours: 24, max_interval_hours: 720),
+            )"#,
+        )
+        .expect("is err");
+        assert_eq!(
+            config.recrawl,
+            RecrawlPolicy::Adaptive {
+                min_interval_hours: 24,
+                max_interval_hours: 720
+            }
+        );
+        assert_eq!(config.recrawl.min_interval_hours(), Some(24));
+    }
 }
@@ -85,6 +85,51 @@ pub enum LensSource {
     Remote(String),
 }
 
+/// How search results are ranked when searching within a lens.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Default, PartialEq)]
+pub enum RankingMode {
+    /// Keyword (BM25) ranking only
+    #[default]
+    Lexical,
+    /// Keyword & embedding results fused using reciprocal rank fusion
+    Hybrid,
+    /// Keyword & embedding scores normalized and combined, where `lexical_weight`
+    /// (0.0 - 1.0) is the weight given to the keyword score.
+    HybridWeighted { lexical_weight: f32 },
+}
+
+/// How often documents indexed by a lens are recrawled to keep them fresh. Web
+/// pages are never refetched more than once a day.
+#[derive(Clone, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+pub enum RecrawlPolicy {
+    /// Documents are crawled once and never refreshed
+    #[default]
+    Never,
+    /// Recrawl documents every `interval_hours`
+    Fixed { interval_hours: u32 },
+    /// Recrawl documents based on how often their content changed in the past,
+    /// somewhere between `min_interval_hours` and `max_interval_hours`.
+    Adaptive {
+        min_interval_hours: u32,
+        max_interval_hours: u32,
+    },
+}
+
+impl RecrawlPolicy {
+    /// Shortest amount of time a document will wait before being recrawled, or
+    /// `None` if recrawls are disabled.
+    pub fn min_interval_hours(&self) -> Option<u32> {
+        match self {
+            RecrawlPolicy::Never => None,
+            RecrawlPolicy::Fixed { interval_hours } => Some(*interval_hours),
+            RecrawlPolicy::Adaptive {
+                min_interval_hours,
+                max_interval_hours,
+            } => Some(*min_interval_hours.min(max_interval_hours)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::LensRule;
@@ -0,0 +1,382 @@
+use anyhow::anyhow;
+use flate2::read::GzDecoder;
+use shared::ARCHIVE_SEPARATOR;
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+use super::ParsedDocument;
+use crate::utils::extensions::SupportedExt;
+
+/// Limits on how much of an archive is read, so a small archive that unpacks
+/// into something huge (a zip bomb) can't fill up memory or the disk.
+#[derive(Clone, Debug)]
+pub struct ArchiveLimits {
+    /// How many archives deep to look, e.g. files in a zip inside a zip are 2
+    /// deep.
+    pub max_depth: usize,
+    /// Files larger than this once uncompressed are skipped
+    pub max_file_size: u64,
+    /// Stop once this many bytes have been uncompressed across all files
+    pub max_total_size: u64,
+    /// Stop after this many files
+    pub max_files: usize,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_file_size: 50 * 1024 * 1024,
+            max_total_size: 500 * 1024 * 1024,
+            max_files: 10_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Walks through the files in an archive & any archives inside of it, keeping
+/// track of how much has been read.
+struct ArchiveWalker<'a> {
+    limits: &'a ArchiveLimits,
+    pdftotext: Option<&'a Path>,
+    total_size: u64,
+    num_files: usize,
+    files: Vec<ParsedDocument>,
+}
+
+impl<'a> ArchiveWalker<'a> {
+    fn new(limits: &'a ArchiveLimits, pdftotext: Option<&'a Path>) -> Self {
+        Self {
+            limits,
+            pdftotext,
+            total_size: 0,
+            num_files: 0,
+            files: Vec::new(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.num_files >= self.limits.max_files || self.total_size >= self.limits.max_total_size
+    }
+
+    /// Reads the whole file, unless it goes over the size limits.
+    fn read_file(&mut self, reader: &mut dyn Read) -> anyhow::Result<Option<Vec<u8>>> {
+        let remaining = self.limits.max_total_size.saturating_sub(self.total_size);
+        let limit = self.limits.max_file_size.min(remaining);
+
+        // Read one byte past the limit to know if the file is over it, w/o
+        // trusting the size the archive claims the file is.
+        let mut buf = Vec::new();
+        reader.take(limit + 1).read_to_end(&mut buf)?;
+        self.total_size += buf.len() as u64;
+
+        if buf.len() as u64 > limit {
+            Ok(None)
+        } else {
+            Ok(Some(buf))
+        }
+    }
+
+    fn walk<R: Read + Seek>(
+        &mut self,
+        kind: ArchiveKind,
+        reader: R,
+        prefix: &str,
+        depth: usize,
+    ) -> anyhow::Result<()> {
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive = ZipArchive::new(reader)?;
+                for idx in 0..archive.len() {
+                    if self.is_done() {
+                        break;
+                    }
+
+                    // e.g. encrypted files
+                    let mut file = match archive.by_index(idx) {
+                        Ok(file) => file,
+                        Err(err) => {
+                            log::debug!("Skipping file {} in archive: {}", idx, err);
+                            continue;
+                        }
+                    };
+                    if file.is_dir() {
+                        continue;
+                    }
+
+                    let name = file.name().to_string();
+                    self.visit(&name, &mut file, prefix, depth);
+                }
+            }
+            ArchiveKind::TarGz => {
+                let mut archive = tar::Archive::new(GzDecoder::new(reader));
+                for entry in archive.entries()? {
+                    if self.is_done() {
+                        break;
+                    }
+
+                    let mut entry = entry?;
+                    // Entries are decompressed to get to the next one whether
+                    // they're read or not, so skipped ones count too.
+                    let size = entry.header().size()?;
+                    let total_before = self.total_size;
+                    if entry.header().entry_type().is_file() {
+                        let name = entry.path()?.to_string_lossy().to_string();
+                        self.visit(&name, &mut entry, prefix, depth);
+                    }
+
+                    let num_read = self.total_size - total_before;
+                    self.total_size += size.saturating_sub(num_read);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Processes a single file in an archive. Files that can't be read are
+    /// skipped, the rest of the archive is still worth indexing.
+    fn visit(&mut self, name: &str, reader: &mut dyn Read, prefix: &str, depth: usize) {
+        self.num_files += 1;
+
+        let name = name.trim_start_matches("./").trim_start_matches('/');
+        let path = format!("{prefix}{name}");
+        if let Err(err) = self.visit_file(name, reader, &path, depth) {
+            log::debug!("Skipping `{}` in archive: {}", path, err);
+        }
+    }
+
+    fn visit_file(
+        &mut self,
+        name: &str,
+        reader: &mut dyn Read,
+        path: &str,
+        depth: usize,
+    ) -> anyhow::Result<()> {
+        let ext = match Path::new(name).extension() {
+            Some(ext) => ext,
+            None => return Ok(()),
+        };
+
+        if let Some(kind) = ArchiveKind::from_name(name) {
+            if depth >= self.limits.max_depth {
+                return Err(anyhow!("Archive is nested too deep"));
+            }
+
+            let bytes = self.read_file(reader)?.ok_or_else(too_large)?;
+            let prefix = format!("{path}{ARCHIVE_SEPARATOR}");
+            return self.walk(kind, Cursor::new(bytes), &prefix, depth + 1);
+        }
+
+        let content = match SupportedExt::from_ext(&ext.to_string_lossy()) {
+            SupportedExt::Code(_) | SupportedExt::Text(_) => {
+                let bytes = self.read_file(reader)?.ok_or_else(too_large)?;
+                ParsedDocument {
+                    content: String::from_utf8(bytes)?,
+                    ..Default::default()
+                }
+            }
+            SupportedExt::Document(_) => {
+                let bytes = self.read_file(reader)?.ok_or_else(too_large)?;
+                parse_document(ext, &bytes, self.pdftotext)?
+            }
+            // Transcribing audio is too slow to do for every file in an archive
+            SupportedExt::Archive(_) | SupportedExt::Audio(_) | SupportedExt::NotSupported => {
+                return Ok(())
+            }
+        };
+
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        self.files.push(ParsedDocument {
+            title: content.title.or_else(|| Some(file_name.to_string())),
+            part_id: Some(path.to_string()),
+            // Parts of a part, e.g. messages in a mailbox, aren't indexed on
+            // their own.
+            parts: Vec::new(),
+            ..content
+        });
+
+        Ok(())
+    }
+}
+
+fn too_large() -> anyhow::Error {
+    anyhow!("File is over the archive size limits")
+}
+
+/// Documents are parsed from a temporary copy, since the parsers work w/ files
+/// on disk.
+fn parse_document(
+    ext: &OsStr,
+    bytes: &[u8],
+    pdftotext: Option<&Path>,
+) -> anyhow::Result<ParsedDocument> {
+    let tmp_path = std::env::temp_dir().join(format!(
+        "spyglass-{}.{}",
+        uuid::Uuid::new_v4(),
+        ext.to_string_lossy()
+    ));
+
+    let parsed = std::fs::write(&tmp_path, bytes)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| super::parse_file(ext, &tmp_path, pdftotext));
+    if let Err(err) = std::fs::remove_file(&tmp_path) {
+        log::warn!("Unable to remove `{}`: {}", tmp_path.display(), err);
+    }
+
+    parsed
+}
+
+/*
+ * Reads the files in the provided zip or tar.gz archive, including the ones in
+ * any archives inside of it, as parts. Each part is identified by its path in
+ * the archive, w/ the paths of nested archives separated by `!/`. The archive
+ * itself lists the paths of the files in it.
+ */
+pub fn parse(
+    file_path: &Path,
+    pdftotext: Option<&Path>,
+    limits: &ArchiveLimits,
+) -> anyhow::Result<ParsedDocument> {
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let kind = ArchiveKind::from_name(&file_name)
+        .ok_or_else(|| anyhow!("`{file_name}` is not a supported archive"))?;
+
+    let mut walker = ArchiveWalker::new(limits, pdftotext);
+    walker.walk(kind, File::open(file_path)?, "", 1)?;
+    Ok(into_document(walker.files))
+}
+
+fn into_document(files: Vec<ParsedDocument>) -> ParsedDocument {
+    let content = files
+        .iter()
+        .filter_map(|file| file.part_id.clone())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ParsedDocument {
+        content,
+        parts: files,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::{into_document, ArchiveKind, ArchiveLimits, ArchiveWalker};
+
+    fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, FileOptions::default())
+                .expect("Unable to add file");
+            writer.write_all(content).expect("Unable to write file");
+        }
+        writer.finish().expect("Unable to build zip").into_inner()
+    }
+
+    fn walk(kind: ArchiveKind, bytes: Vec<u8>, limits: &ArchiveLimits) -> Vec<(String, String)> {
+        let mut walker = ArchiveWalker::new(limits, None);
+        walker
+            .walk(kind, Cursor::new(bytes), "", 1)
+            .expect("Unable to walk archive");
+        into_document(walker.files)
+            .parts
+            .into_iter()
+            .map(|part| (part.part_id.unwrap_or_default(), part.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_walk_zip() {
+        let innermost = build_zip(&[("deep.md", b"too deep")]);
+        let inner = build_zip(&[("b.md", b"bee"), ("c.zip", &innermost)]);
+        let archive = build_zip(&[
+            ("docs/readme.md", b"hello"),
+            ("logo.png", b"not text"),
+            ("big.txt", &[b'a'; 4096]),
+            ("inner.zip", &inner),
+        ]);
+
+        let limits = ArchiveLimits {
+            max_depth: 2,
+            max_file_size: 1024,
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(ArchiveKind::Zip, archive, &limits),
+            vec![
+                ("docs/readme.md".to_string(), "hello".to_string()),
+                ("inner.zip!/b.md".to_string(), "bee".to_string()),
+            ]
+        );
+    }
+
+    fn build_tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, *content)
+                .expect("Unable to add file");
+        }
+        builder
+            .into_inner()
+            .and_then(|gz| gz.finish())
+            .expect("Unable to build archive")
+    }
+
+    #[test]
+    fn test_walk_tar_gz() {
+        let archive = build_tar_gz(&[("./notes/a.txt", b"first"), ("b.txt", b"second")]);
+        let limits = ArchiveLimits {
+            max_files: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(ArchiveKind::TarGz, archive, &limits),
+            vec![("notes/a.txt".to_string(), "first".to_string())]
+        );
+
+        // Files that are skipped still count toward the total size
+        let archive = build_tar_gz(&[("logo.png", &[0; 4096]), ("b.txt", b"second")]);
+        let limits = ArchiveLimits {
+            max_total_size: 1024,
+            ..Default::default()
+        };
+        assert!(walk(ArchiveKind::TarGz, archive, &limits).is_empty());
+    }
+}
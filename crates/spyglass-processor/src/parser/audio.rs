@@ -199,6 +199,7 @@ fn parse_audio_file(path: &PathBuf) -> anyhow::Result<AudioFile> {
 
 #[derive(Clone, Debug)]
 pub struct Segment {
+    /// Timestamps are in centiseconds, as returned by whisper
     pub start_timestamp: i64,
     pub end_timestamp: i64,
     pub segment: String,
@@ -212,6 +213,16 @@ impl Segment {
             segment: segment.to_string(),
         }
     }
+
+    /// Start of the segment in milliseconds
+    pub fn start_ms(&self) -> u64 {
+        self.start_timestamp.max(0) as u64 * 10
+    }
+
+    /// End of the segment in milliseconds
+    pub fn end_ms(&self) -> u64 {
+        self.end_timestamp.max(0) as u64 * 10
+    }
 }
 
 pub struct TranscriptionResult {
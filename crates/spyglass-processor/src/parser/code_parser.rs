@@ -0,0 +1,706 @@
+use crate::utils::extensions::CodeExt;
+
+/// Language a source file is written in, based on its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodeLanguage {
+    C,
+    Cpp,
+    Go,
+    JavaScript,
+    Python,
+    Rust,
+    TypeScript,
+}
+
+impl CodeLanguage {
+    pub fn from_ext(ext: &CodeExt) -> Self {
+        match ext {
+            CodeExt::C => Self::C,
+            CodeExt::Cpp => Self::Cpp,
+            CodeExt::Go => Self::Go,
+            CodeExt::Js => Self::JavaScript,
+            CodeExt::Py => Self::Python,
+            CodeExt::Rs => Self::Rust,
+            CodeExt::Ts => Self::TypeScript,
+        }
+    }
+
+    /// Name used to tag documents written in this language.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Cpp => "cpp",
+            Self::Go => "go",
+            Self::JavaScript => "javascript",
+            Self::Python => "python",
+            Self::Rust => "rust",
+            Self::TypeScript => "typescript",
+        }
+    }
+
+    fn has_block_comments(&self) -> bool {
+        !matches!(self, Self::Python)
+    }
+
+    /// Lines such as attributes & decorators that sit between a doc comment
+    /// and the item it documents.
+    fn is_annotation(&self, line: &str) -> bool {
+        match self {
+            Self::Rust => line.starts_with("#[") || line.starts_with("#!["),
+            Self::Python | Self::JavaScript | Self::TypeScript => line.starts_with('@'),
+            Self::Cpp => line.starts_with("template"),
+            Self::C | Self::Go => false,
+        }
+    }
+
+    /// Symbol defined on a line, if any.
+    fn symbol(&self, line: &str, trimmed: &str) -> Option<(SymbolKind, String)> {
+        match self {
+            Self::C | Self::Cpp => c_symbol(line, trimmed),
+            Self::Go => go_symbol(trimmed),
+            Self::JavaScript | Self::TypeScript => js_symbol(trimmed),
+            Self::Python => python_symbol(trimmed),
+            Self::Rust => rust_symbol(trimmed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Enum,
+    Function,
+    /// Traits & interfaces
+    Interface,
+    Macro,
+    /// Modules, namespaces & packages
+    Module,
+    Struct,
+    /// Type aliases
+    Type,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Doc comment/string for the symbol
+    pub doc: Option<String>,
+    /// Line the symbol is defined on, starting at 1
+    pub line: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParsedCode {
+    pub language: CodeLanguage,
+    pub symbols: Vec<Symbol>,
+    /// Doc comment/string for the whole file, e.g. `//!` comments in Rust
+    pub module_doc: Option<String>,
+}
+
+impl ParsedCode {
+    /// Names of the symbols defined in the file, w/o duplicates.
+    pub fn symbol_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for symbol in &self.symbols {
+            if !names.contains(&symbol.name) {
+                names.push(symbol.name.clone());
+            }
+        }
+        names
+    }
+}
+
+/// Kind of comment a line starts w/.
+enum Comment {
+    /// Documents the item below it
+    Doc(String),
+    /// Documents the whole file
+    ModuleDoc(String),
+    /// Not documentation, ignored
+    Other,
+}
+
+fn line_comment(language: CodeLanguage, line: &str) -> Option<Comment> {
+    let comment = match language {
+        CodeLanguage::Python => {
+            return line.strip_prefix('#').map(|_| Comment::Other);
+        }
+        CodeLanguage::Rust => {
+            if let Some(text) = line.strip_prefix("//!") {
+                Comment::ModuleDoc(text.trim().to_string())
+            } else if line.starts_with("////") {
+                Comment::Other
+            } else if let Some(text) = line.strip_prefix("///") {
+                Comment::Doc(text.trim().to_string())
+            } else if line.starts_with("//") {
+                Comment::Other
+            } else {
+                return None;
+            }
+        }
+        _ => match line.strip_prefix("//") {
+            Some(text) => Comment::Doc(text.trim_start_matches('/').trim().to_string()),
+            None => return None,
+        },
+    };
+
+    Some(comment)
+}
+
+/// Text of a `/* */` comment starting on `start`, & the line it ends on.
+fn block_comment(lines: &[&str], start: usize) -> (Vec<String>, usize) {
+    let mut text = Vec::new();
+    let mut end = start;
+    for (idx, line) in lines.iter().enumerate().skip(start) {
+        end = idx;
+        let line = line.trim();
+        let (line, is_end) = match line.find("*/") {
+            Some(pos) => (&line[..pos], true),
+            None => (line, false),
+        };
+
+        let line = line
+            .trim_start_matches("/*")
+            .trim_start_matches(['*', '!'])
+            .trim();
+        if !line.is_empty() {
+            text.push(line.to_string());
+        }
+
+        if is_end {
+            break;
+        }
+    }
+
+    (text, end)
+}
+
+/// Text of a Python docstring starting on `start`, & the line it ends on.
+fn python_docstring(lines: &[&str], start: usize) -> Option<(String, usize)> {
+    let first = lines.get(start)?.trim();
+    let quote = ["\"\"\"", "'''"]
+        .into_iter()
+        .find(|quote| first.starts_with(quote))?;
+
+    let mut text = Vec::new();
+    let mut rest = &first[quote.len()..];
+    for (idx, line) in lines.iter().enumerate().skip(start) {
+        if idx > start {
+            rest = line.trim();
+        }
+
+        match rest.find(quote) {
+            Some(pos) => {
+                text.push(rest[..pos].trim());
+                return Some((join_doc(&text)?, idx));
+            }
+            None => text.push(rest.trim()),
+        }
+    }
+
+    None
+}
+
+fn join_doc<S: AsRef<str>>(lines: &[S]) -> Option<String> {
+    let doc = lines
+        .iter()
+        .map(|line| line.as_ref().trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if doc.is_empty() {
+        None
+    } else {
+        Some(doc)
+    }
+}
+
+/// Splits off the first word of a line.
+fn split_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (line, ""),
+    }
+}
+
+/// Identifier at the start of `text`.
+fn take_ident(text: &str) -> Option<String> {
+    let ident: String = text
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        .collect();
+
+    match ident.chars().next() {
+        Some(first) if !first.is_numeric() => Some(ident),
+        _ => None,
+    }
+}
+
+/// Skips over leading words that modify an item, e.g. `pub` or `export`.
+fn skip_modifiers(mut line: &str, is_modifier: impl Fn(&str) -> bool) -> &str {
+    loop {
+        let (word, rest) = split_word(line);
+        if word.is_empty() || rest.is_empty() || !is_modifier(word) {
+            return line;
+        }
+        line = rest;
+    }
+}
+
+fn rust_symbol(line: &str) -> Option<(SymbolKind, String)> {
+    let line = skip_modifiers(line, |word| {
+        matches!(word, "pub" | "async" | "const" | "unsafe" | "default" | "extern")
+            || word.starts_with("pub(")
+            // ABI, e.g. `extern "C" fn`
+            || word.starts_with('"')
+    });
+
+    let (keyword, rest) = split_word(line);
+    let kind = match keyword {
+        "fn" => SymbolKind::Function,
+        "struct" | "union" => SymbolKind::Struct,
+        "enum" => SymbolKind::Enum,
+        "trait" => SymbolKind::Interface,
+        "mod" => SymbolKind::Module,
+        "type" => SymbolKind::Type,
+        "macro_rules!" => SymbolKind::Macro,
+        _ => return None,
+    };
+
+    take_ident(rest).map(|name| (kind, name))
+}
+
+fn go_symbol(line: &str) -> Option<(SymbolKind, String)> {
+    let (keyword, rest) = split_word(line);
+    match keyword {
+        "func" => {
+            // Methods, e.g. `func (s *Server) Serve()`
+            let rest = match rest.strip_prefix('(') {
+                Some(receiver) => receiver.split_once(')')?.1.trim_start(),
+                None => rest,
+            };
+            take_ident(rest).map(|name| (SymbolKind::Function, name))
+        }
+        "type" => {
+            let name = take_ident(rest)?;
+            let kind = match split_word(rest[name.len()..].trim_start()).0 {
+                "struct" => SymbolKind::Struct,
+                "interface" => SymbolKind::Interface,
+                _ => SymbolKind::Type,
+            };
+            Some((kind, name))
+        }
+        "package" => take_ident(rest).map(|name| (SymbolKind::Module, name)),
+        _ => None,
+    }
+}
+
+fn js_symbol(line: &str) -> Option<(SymbolKind, String)> {
+    let line = skip_modifiers(line, |word| {
+        matches!(
+            word,
+            "export" | "default" | "async" | "declare" | "abstract"
+        )
+    });
+
+    let (keyword, rest) = split_word(line);
+    let kind = match keyword {
+        "function" | "function*" => SymbolKind::Function,
+        "class" => SymbolKind::Class,
+        "interface" => SymbolKind::Interface,
+        "type" => SymbolKind::Type,
+        "enum" => SymbolKind::Enum,
+        "namespace" | "module" => SymbolKind::Module,
+        // Functions assigned to a variable, e.g. `const handleFetch = async () => {}`
+        "const" | "let" | "var" => {
+            let name = take_ident(rest)?;
+            let (_, value) = rest.split_once('=')?;
+            let value = value.trim_start();
+            if value.starts_with("function") || value.starts_with("async") || value.contains("=>") {
+                return Some((SymbolKind::Function, name));
+            }
+            return None;
+        }
+        _ => return None,
+    };
+
+    take_ident(rest.trim_start_matches('*').trim_start()).map(|name| (kind, name))
+}
+
+fn python_symbol(line: &str) -> Option<(SymbolKind, String)> {
+    let line = skip_modifiers(line, |word| word == "async");
+    let (keyword, rest) = split_word(line);
+    let kind = match keyword {
+        "def" => SymbolKind::Function,
+        "class" => SymbolKind::Class,
+        _ => return None,
+    };
+
+    take_ident(rest).map(|name| (kind, name))
+}
+
+fn c_symbol(line: &str, trimmed: &str) -> Option<(SymbolKind, String)> {
+    let (keyword, rest) = split_word(skip_modifiers(trimmed, |word| {
+        matches!(word, "typedef" | "export")
+    }));
+    let kind = match keyword {
+        "struct" | "union" => Some(SymbolKind::Struct),
+        "class" => Some(SymbolKind::Class),
+        "enum" => Some(SymbolKind::Enum),
+        "namespace" => Some(SymbolKind::Module),
+        _ => None,
+    };
+
+    if let Some(kind) = kind {
+        // Skip forward declarations & variables of the type
+        if (trimmed.ends_with(';') && !trimmed.contains('{')) || trimmed.contains('(') {
+            return None;
+        }
+
+        let rest = match (kind, split_word(rest)) {
+            (SymbolKind::Enum, ("class" | "struct", rest)) => rest,
+            _ => rest,
+        };
+        return take_ident(rest).map(|name| (kind, name));
+    }
+
+    // Function definitions start at the beginning of the line, w/ their return
+    // type before the name & no trailing `;`.
+    if line.starts_with(char::is_whitespace)
+        || trimmed.starts_with(['#', '}', '{', '/', '*'])
+        || trimmed.ends_with(';')
+    {
+        return None;
+    }
+
+    let (before, _) = trimmed.split_once('(')?;
+    if before.contains('=') {
+        return None;
+    }
+
+    let before = before.trim_end();
+    let name_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '~'))
+        .map(|pos| pos + 1)
+        .unwrap_or_default();
+    let name = &before[name_start..];
+    // Constructors & methods defined outside of their class have no return type
+    if name.is_empty() || (name_start == 0 && !name.contains("::")) {
+        return None;
+    }
+
+    let short_name = name.rsplit("::").next().unwrap_or(name);
+    if matches!(
+        short_name,
+        "if" | "for" | "while" | "switch" | "return" | "sizeof" | "else"
+    ) {
+        return None;
+    }
+
+    take_ident(short_name.trim_start_matches('~'))?;
+    Some((SymbolKind::Function, name.to_string()))
+}
+
+/// Line the header of a Python definition ends on, i.e. once all the brackets
+/// opened on `start` are closed.
+fn python_header_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0;
+    for (idx, line) in lines.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        if depth <= 0 {
+            return idx;
+        }
+    }
+
+    start
+}
+
+/// Extracts the names & doc comments of the functions, types, & modules
+/// defined in a source file. Definitions are found w/ a line by line scan
+/// rather than a full parse, which is good enough to find where something is
+/// defined.
+pub fn parse(ext: &CodeExt, content: &str) -> ParsedCode {
+    let language = CodeLanguage::from_ext(ext);
+    let lines = content.lines().collect::<Vec<_>>();
+
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut module_doc: Vec<String> = Vec::new();
+    // Doc comment lines right above the current line
+    let mut doc: Vec<String> = Vec::new();
+
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            doc.clear();
+            idx += 1;
+            continue;
+        }
+
+        // Python modules are documented w/ a docstring at the top of the file
+        if language == CodeLanguage::Python && symbols.is_empty() && module_doc.is_empty() {
+            if let Some((text, end)) = python_docstring(&lines, idx) {
+                module_doc.push(text);
+                idx = end + 1;
+                continue;
+            }
+        }
+
+        if language.has_block_comments() && trimmed.starts_with("/*") {
+            let (text, end) = block_comment(&lines, idx);
+            match language {
+                CodeLanguage::Rust if trimmed.starts_with("/*!") => module_doc.extend(text),
+                CodeLanguage::Rust if !trimmed.starts_with("/**") => {}
+                _ => doc.extend(text),
+            }
+            idx = end + 1;
+            continue;
+        }
+
+        match line_comment(language, trimmed) {
+            Some(Comment::Doc(text)) => doc.push(text),
+            Some(Comment::ModuleDoc(text)) => module_doc.push(text),
+            Some(Comment::Other) => {}
+            None if language.is_annotation(trimmed) => {}
+            None => {
+                if let Some((kind, name)) = language.symbol(line, trimmed) {
+                    let mut symbol = Symbol {
+                        name,
+                        kind,
+                        doc: join_doc(&doc),
+                        line: idx + 1,
+                    };
+
+                    // Go packages are documented by the comment above them
+                    if kind == SymbolKind::Module
+                        && language == CodeLanguage::Go
+                        && module_doc.is_empty()
+                    {
+                        module_doc.extend(doc.iter().cloned());
+                    }
+
+                    if language == CodeLanguage::Python {
+                        let header_end = python_header_end(&lines, idx);
+                        let next = (header_end + 1..lines.len())
+                            .find(|next| !lines[*next].trim().is_empty());
+                        if let Some((text, end)) =
+                            next.and_then(|next| python_docstring(&lines, next))
+                        {
+                            symbol.doc = Some(text);
+                            idx = end;
+                        } else {
+                            idx = header_end;
+                        }
+                    }
+
+                    symbols.push(symbol);
+                }
+                doc.clear();
+            }
+        }
+
+        idx += 1;
+    }
+
+    ParsedCode {
+        language,
+        symbols,
+        module_doc: join_doc(&module_doc),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, SymbolKind};
+    use crate::utils::extensions::CodeExt;
+
+    fn symbols(ext: CodeExt, content: &str) -> Vec<(SymbolKind, String)> {
+        parse(&ext, content)
+            .symbols
+            .into_iter()
+            .map(|symbol| (symbol.kind, symbol.name))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rust() {
+        let content = r#"
+//! Fetches pages from the web.
+use std::path::Path;
+
+/// Fetches a URL
+/// & handles redirects.
+#[tracing::instrument]
+pub(crate) async fn handle_fetch(url: &str) -> Result<(), FetchError> {
+    // not a doc comment
+    let handle_other = 1;
+}
+
+pub enum FetchError {}
+struct Fetcher;
+impl Fetcher {
+    pub const fn new() -> Self {}
+}
+const MAX_REDIRECTS: usize = 10;
+"#;
+        let parsed = parse(&CodeExt::Rs, content);
+        assert_eq!(
+            parsed.module_doc,
+            Some("Fetches pages from the web.".to_string())
+        );
+        assert_eq!(parsed.language.name(), "rust");
+        assert_eq!(
+            parsed.symbol_names(),
+            vec!["handle_fetch", "FetchError", "Fetcher", "new"]
+        );
+
+        let handle_fetch = &parsed.symbols[0];
+        assert_eq!(handle_fetch.kind, SymbolKind::Function);
+        assert_eq!(
+            handle_fetch.doc,
+            Some("Fetches a URL & handles redirects.".to_string())
+        );
+        assert_eq!(handle_fetch.line, 8);
+        assert_eq!(parsed.symbols[1].kind, SymbolKind::Enum);
+        assert_eq!(parsed.symbols[2].doc, None);
+    }
+
+    #[test]
+    fn test_parse_python() {
+        let content = r#""""Crawls the web."""
+import os
+
+class Crawler(object):
+    '''Crawls
+    pages.'''
+
+    async def handle_fetch(
+        self,
+        url,
+    ):
+        """Fetches a URL."""
+        def_name = 1
+"#;
+        let parsed = parse(&CodeExt::Py, content);
+        assert_eq!(parsed.module_doc, Some("Crawls the web.".to_string()));
+        assert_eq!(
+            symbols(CodeExt::Py, content),
+            vec![
+                (SymbolKind::Class, "Crawler".to_string()),
+                (SymbolKind::Function, "handle_fetch".to_string()),
+            ]
+        );
+        assert_eq!(parsed.symbols[0].doc, Some("Crawls pages.".to_string()));
+        assert_eq!(parsed.symbols[1].doc, Some("Fetches a URL.".to_string()));
+    }
+
+    #[test]
+    fn test_parse_js_ts() {
+        let content = r#"
+/**
+ * Fetches a URL.
+ */
+export async function handleFetch(url) {}
+export default class Crawler {}
+const fetchAll = async (urls) => {};
+const MAX_PAGES = 10;
+export interface Page {}
+type PageId = string;
+"#;
+        assert_eq!(
+            symbols(CodeExt::Ts, content),
+            vec![
+                (SymbolKind::Function, "handleFetch".to_string()),
+                (SymbolKind::Class, "Crawler".to_string()),
+                (SymbolKind::Function, "fetchAll".to_string()),
+                (SymbolKind::Interface, "Page".to_string()),
+                (SymbolKind::Type, "PageId".to_string()),
+            ]
+        );
+        let parsed = parse(&CodeExt::Js, content);
+        assert_eq!(parsed.symbols[0].doc, Some("Fetches a URL.".to_string()));
+    }
+
+    #[test]
+    fn test_parse_c_cpp() {
+        let content = r#"
+#include <stdio.h>
+
+// Fetches a URL.
+static int handle_fetch(const char *url)
+{
+    if (url == NULL) {
+        return -1;
+    }
+}
+
+struct fetcher {
+    int fd;
+};
+struct fetcher *create(void);
+
+namespace crawler {
+class Crawler {};
+}
+
+Crawler::~Crawler() {}
+"#;
+        assert_eq!(
+            symbols(CodeExt::Cpp, content),
+            vec![
+                (SymbolKind::Function, "handle_fetch".to_string()),
+                (SymbolKind::Struct, "fetcher".to_string()),
+                (SymbolKind::Module, "crawler".to_string()),
+                (SymbolKind::Class, "Crawler".to_string()),
+                (SymbolKind::Function, "Crawler::~Crawler".to_string()),
+            ]
+        );
+        let parsed = parse(&CodeExt::C, content);
+        assert_eq!(parsed.symbols[0].doc, Some("Fetches a URL.".to_string()));
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let content = r#"
+// Package crawler fetches pages.
+package crawler
+
+// Server serves pages.
+type Server struct {}
+type Handler interface {}
+
+func (s *Server) HandleFetch(url string) error {}
+func main() {}
+"#;
+        let parsed = parse(&CodeExt::Go, content);
+        assert_eq!(
+            parsed.module_doc,
+            Some("Package crawler fetches pages.".to_string())
+        );
+        assert_eq!(
+            symbols(CodeExt::Go, content),
+            vec![
+                (SymbolKind::Module, "crawler".to_string()),
+                (SymbolKind::Struct, "Server".to_string()),
+                (SymbolKind::Interface, "Handler".to_string()),
+                (SymbolKind::Function, "HandleFetch".to_string()),
+                (SymbolKind::Function, "main".to_string()),
+            ]
+        );
+        assert_eq!(
+            parsed.symbols[1].doc,
+            Some("Server serves pages.".to_string())
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::anyhow;
+use bytes::Bytes;
+use mailparse::{DispositionType, MailAddr, MailHeaderMap, ParsedMail};
+use std::{collections::HashSet, path::Path};
+
+use super::ParsedDocument;
+
+/// Email addresses in an address header, e.g. `To` or `Cc`.
+fn addresses(mail: &ParsedMail, header: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for header in mail.headers.get_all_headers(header) {
+        let list = match mailparse::addrparse_header(header) {
+            Ok(list) => list,
+            Err(err) => {
+                log::debug!("Unable to parse {} addresses: {}", header.get_key(), err);
+                continue;
+            }
+        };
+
+        for addr in list.iter() {
+            match addr {
+                MailAddr::Single(info) => found.push(info.addr.clone()),
+                MailAddr::Group(group) => {
+                    found.extend(group.addrs.iter().map(|info| info.addr.clone()))
+                }
+            }
+        }
+    }
+
+    found
+}
+
+/// Strips the tags from an HTML body, for messages without a plain text part.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut tag = None;
+    for c in html.chars() {
+        match (&mut tag, c) {
+            (None, '<') => tag = Some(String::new()),
+            (None, c) => text.push(c),
+            (Some(inner), '>') => {
+                // Block level tags break the line
+                let name = inner
+                    .trim_start_matches('/')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .trim_end_matches('/')
+                    .to_lowercase();
+                if ["p", "div", "br", "li", "tr"].contains(&name.as_str()) {
+                    text.push('\n');
+                } else {
+                    text.push(' ');
+                }
+                tag = None;
+            }
+            (Some(inner), c) => inner.push(c),
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Text of the message body, preferring the plain text version of the message
+/// over the HTML one. Attachments are skipped.
+fn message_body(mail: &ParsedMail) -> Option<String> {
+    let is_attachment = mail.get_content_disposition().disposition == DispositionType::Attachment;
+    if is_attachment {
+        return None;
+    }
+
+    if mail.subparts.is_empty() {
+        return match mail.ctype.mimetype.as_str() {
+            "text/plain" => mail.get_body().ok(),
+            "text/html" => mail.get_body().ok().map(|html| html_to_text(&html)),
+            _ => None,
+        };
+    }
+
+    let plain = mail
+        .subparts
+        .iter()
+        .find(|part| part.ctype.mimetype == "text/plain")
+        .and_then(message_body);
+    plain.or_else(|| mail.subparts.iter().find_map(message_body))
+}
+
+fn normalize_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Parses a single RFC 822 message. The subject is used as the title, the
+/// sender as the author & the `To`/`Cc` addresses as the recipients.
+fn parse_message(raw: &[u8]) -> anyhow::Result<ParsedDocument> {
+    let mail = mailparse::parse_mail(raw).map_err(|err| anyhow!("Invalid email: {err}"))?;
+
+    let author = addresses(&mail, "From").into_iter().next();
+    let mut recipients = addresses(&mail, "To");
+    recipients.extend(addresses(&mail, "Cc"));
+
+    let published_at = mail
+        .headers
+        .get_first_value("Date")
+        .and_then(|date| mailparse::dateparse(&date).ok());
+    let part_id = mail
+        .headers
+        .get_first_value("Message-ID")
+        .map(|id| {
+            id.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+        .filter(|id| !id.is_empty());
+
+    Ok(ParsedDocument {
+        title: mail.headers.get_first_value("Subject"),
+        author,
+        recipients,
+        published_at,
+        part_id,
+        content: normalize_lines(&message_body(&mail).unwrap_or_default()),
+        ..Default::default()
+    })
+}
+
+/// Splits a mailbox into its messages. Each message starts w/ a `From ` line
+/// at the start of the file or after an empty line, lines in the body that
+/// would look like one are escaped as `>From `.
+fn split_mbox(mbox: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut prev_blank = true;
+    for line in mbox.split_inclusive(|b| *b == b'\n') {
+        if prev_blank && line.starts_with(b"From ") {
+            messages.extend(current.take());
+            current = Some(Vec::new());
+            prev_blank = false;
+            continue;
+        }
+
+        prev_blank = line.iter().all(|b| b.is_ascii_whitespace());
+        if let Some(message) = current.as_mut() {
+            let is_escaped = line.starts_with(b">")
+                && line.iter().skip_while(|b| **b == b'>').take(5).eq(b"From ");
+            if is_escaped {
+                message.extend_from_slice(&line[1..]);
+            } else {
+                message.extend_from_slice(line);
+            }
+        }
+    }
+    messages.extend(current);
+
+    messages
+}
+
+pub fn parse_eml_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    parse_message(&b)
+}
+
+/// Parses each message in a mailbox as its own document. The mailbox itself
+/// lists the subjects of its messages.
+pub fn parse_mbox_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    let mut seen = HashSet::new();
+    let mut parts = Vec::new();
+    for (idx, raw) in split_mbox(&b).iter().enumerate() {
+        match parse_message(raw) {
+            Ok(mut message) => {
+                // Message ids are used to point to the message, fall back to
+                // its position if it's missing or repeated.
+                let is_unique = match &message.part_id {
+                    Some(id) => seen.insert(id.clone()),
+                    None => false,
+                };
+                if !is_unique {
+                    message.part_id = Some(format!("message-{}", idx + 1));
+                }
+                parts.push(message);
+            }
+            Err(err) => log::debug!("Skipping message {}: {}", idx + 1, err),
+        }
+    }
+
+    if parts.is_empty() && !b.is_empty() {
+        return Err(anyhow!("No messages found in mailbox"));
+    }
+
+    let content = parts
+        .iter()
+        .filter_map(|message| message.title.clone())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(ParsedDocument {
+        content,
+        parts,
+        ..Default::default()
+    })
+}
+
+/*
+ * Reads the provided file as a single email message (EML)
+ */
+pub fn parse_eml(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    parse_eml_bytes(Bytes::from(std::fs::read(file_path)?))
+}
+
+/*
+ * Reads the provided file as a mailbox (MBOX), w/ each message as a part
+ */
+pub fn parse_mbox(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    parse_mbox_bytes(Bytes::from(std::fs::read(file_path)?))
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{html_to_text, parse_eml_bytes, parse_mbox_bytes, split_mbox};
+
+    const MESSAGE: &str = "From: Ada Lovelace <ada@example.com>\r
+To: Charles <charles@example.com>, team: bob@example.com, eve@example.com;\r
+Cc: grace@example.com\r
+Subject: =?utf-8?q?Engine_notes_=E2=9C=94?=\r
+Date: Tue, 1 Aug 2023 10:00:00 +0000\r
+Message-ID: <notes-1@example.com>\r
+Content-Type: multipart/alternative; boundary=\"b\"\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+The engine works.\r
+--b\r
+Content-Type: text/html\r
+\r
+<p>The engine <b>works</b>.</p>\r
+--b--\r
+";
+
+    #[test]
+    fn test_parse_eml() {
+        let parsed = parse_eml_bytes(Bytes::from(MESSAGE)).expect("Unable to parse email");
+        assert_eq!(parsed.title, Some("Engine notes ✔".to_string()));
+        assert_eq!(parsed.author, Some("ada@example.com".to_string()));
+        assert_eq!(
+            parsed.recipients,
+            vec![
+                "charles@example.com",
+                "bob@example.com",
+                "eve@example.com",
+                "grace@example.com"
+            ]
+        );
+        assert_eq!(parsed.published_at, Some(1_690_884_000));
+        assert_eq!(parsed.part_id, Some("notes-1@example.com".to_string()));
+        assert_eq!(parsed.content, "The engine works.");
+    }
+
+    #[test]
+    fn test_parse_mbox() {
+        let mbox = "From ada@example.com Tue Aug  1 10:00:00 2023\n\
+            From: ada@example.com\n\
+            Subject: First\n\
+            \n\
+            >From the start.\n\
+            \n\
+            From bob@example.com Tue Aug  1 11:00:00 2023\n\
+            From: bob@example.com\n\
+            Subject: Second\n\
+            \n\
+            Hi\n";
+
+        let messages = split_mbox(mbox.as_bytes());
+        assert_eq!(messages.len(), 2);
+        assert!(String::from_utf8_lossy(&messages[0]).contains("\nFrom the start."));
+
+        let parsed = parse_mbox_bytes(Bytes::from(mbox)).expect("Unable to parse mbox");
+        assert_eq!(parsed.content, "First\nSecond");
+        assert_eq!(parsed.parts.len(), 2);
+        assert_eq!(parsed.parts[1].author, Some("bob@example.com".to_string()));
+        assert_eq!(parsed.parts[1].content, "Hi");
+        assert_eq!(parsed.parts[1].part_id, Some("message-2".to_string()));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<div class=\"a\">Fish &amp; chips</div><br/>ok"),
+            "\nFish & chips\n\nok"
+        );
+    }
+}
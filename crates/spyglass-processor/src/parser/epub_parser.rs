@@ -0,0 +1,198 @@
+use anyhow::anyhow;
+use bytes::Bytes;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+use super::xml_text::{self, TextRules};
+use super::ParsedDocument;
+
+/// Lists where the package document (the `.opf` file) is
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// Chapters are XHTML documents.
+const CHAPTER_RULES: TextRules = TextRules {
+    blocks: &[
+        "p",
+        "div",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "li",
+        "tr",
+        "br",
+        "blockquote",
+        "pre",
+    ],
+    spaces: &["td", "th"],
+    skip: &["head", "script", "style"],
+};
+
+/// The parts of the package document we care about.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Package {
+    title: Option<String>,
+    author: Option<String>,
+    /// Paths of the chapters in the archive, in reading order
+    chapters: Vec<String>,
+}
+
+/// Path of the package document in the archive.
+fn package_path(container: &str) -> Option<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"rootfile" => {
+                return xml_text::attribute(&e, "full-path");
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Reads the metadata & reading order (the spine) from the package document.
+/// Chapter paths are relative to the package document at `opf_path`.
+fn parse_package(opf: &str, opf_path: &str) -> Package {
+    let base = match opf_path.rfind('/') {
+        Some(idx) => &opf_path[..=idx],
+        None => "",
+    };
+
+    let mut manifest = HashMap::new();
+    let mut spine = Vec::new();
+    let mut reader = Reader::from_str(opf);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"item" => {
+                    if let (Some(id), Some(href)) = (
+                        xml_text::attribute(&e, "id"),
+                        xml_text::attribute(&e, "href"),
+                    ) {
+                        manifest.insert(id, href);
+                    }
+                }
+                b"itemref" => {
+                    if let Some(idref) = xml_text::attribute(&e, "idref") {
+                        spine.push(idref);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    let chapters = spine
+        .iter()
+        .filter_map(|idref| manifest.get(idref))
+        .map(|href| {
+            let href = href.split('#').next().unwrap_or_default();
+            format!("{base}{href}")
+        })
+        .collect();
+
+    Package {
+        title: xml_text::element_text(opf, "title"),
+        author: xml_text::element_text(opf, "creator"),
+        chapters,
+    }
+}
+
+fn process_file<R: Read + Seek>(mut archive: ZipArchive<R>) -> anyhow::Result<ParsedDocument> {
+    let container = xml_text::read_entry(&mut archive, CONTAINER_PATH)?;
+    let opf_path = package_path(&container)
+        .ok_or_else(|| anyhow!("No package document listed in {CONTAINER_PATH}"))?;
+    let opf = xml_text::read_entry(&mut archive, &opf_path)?;
+    let package = parse_package(&opf, &opf_path);
+
+    let mut text = Vec::new();
+    for chapter in &package.chapters {
+        // Skip chapters that are missing or can't be read, the rest of the
+        // book is still worth indexing.
+        match xml_text::read_entry(&mut archive, chapter)
+            .and_then(|xhtml| xml_text::xml_to_text(&xhtml, &CHAPTER_RULES))
+        {
+            Ok(chapter_text) => text.push(chapter_text),
+            Err(err) => log::debug!("Unable to read chapter {}: {}", chapter, err),
+        }
+    }
+
+    Ok(ParsedDocument {
+        title: package.title,
+        author: package.author,
+        content: text.join("\n\n"),
+        ..Default::default()
+    })
+}
+
+pub fn parse_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(Cursor::new(b))?)
+}
+
+/*
+ * Reads the provided file as an EPUB, pulls out the text of each chapter in
+ * reading order & the title/author from the package metadata.
+ */
+pub fn parse(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(File::open(file_path)?)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{package_path, parse_package, Package};
+
+    #[test]
+    fn test_parse_package() {
+        let container = r#"<?xml version="1.0"?>
+            <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+                <rootfiles>
+                    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+                </rootfiles>
+            </container>"#;
+        assert_eq!(
+            package_path(container),
+            Some("OEBPS/content.opf".to_string())
+        );
+
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <metadata>
+                <dc:title>The Book</dc:title>
+                <dc:creator>A. Writer</dc:creator>
+            </metadata>
+            <manifest>
+                <item id="ch2" href="text/ch2.xhtml" media-type="application/xhtml+xml"/>
+                <item id="ch1" href="text/ch1.xhtml#start" media-type="application/xhtml+xml"/>
+                <item id="css" href="style.css" media-type="text/css"/>
+            </manifest>
+            <spine>
+                <itemref idref="ch1"/>
+                <itemref idref="ch2"/>
+                <itemref idref="missing"/>
+            </spine>
+        </package>"#;
+
+        assert_eq!(
+            parse_package(opf, "OEBPS/content.opf"),
+            Package {
+                title: Some("The Book".to_string()),
+                author: Some("A. Writer".to_string()),
+                chapters: vec![
+                    "OEBPS/text/ch1.xhtml".to_string(),
+                    "OEBPS/text/ch2.xhtml".to_string()
+                ],
+            }
+        );
+    }
+}
@@ -4,15 +4,28 @@ use std::{ffi::OsStr, path::Path};
 
 use crate::utils;
 
+pub mod archive_parser;
 pub mod audio;
+pub mod code_parser;
 pub mod docx_parser;
+pub mod email_parser;
+pub mod epub_parser;
+pub mod odf_parser;
 pub mod pdf_parser;
+pub mod pptx_parser;
+pub mod rtf_parser;
 pub mod xlsx_parser;
+mod xml_text;
 
 /*
- * Parses the specified file
+ * Parses the specified file. `pdftotext` is an optional external binary used
+ * for PDFs the built-in extractor can't read.
  */
-pub fn parse_file(extension: &OsStr, file_path: &Path) -> anyhow::Result<ParsedDocument> {
+pub fn parse_file(
+    extension: &OsStr,
+    file_path: &Path,
+    pdftotext: Option<&Path>,
+) -> anyhow::Result<ParsedDocument> {
     if extension.eq_ignore_ascii_case("docx") {
         Ok(ParsedDocument {
             content: docx_parser::parse(file_path)?,
@@ -27,7 +40,19 @@ pub fn parse_file(extension: &OsStr, file_path: &Path) -> anyhow::Result<ParsedD
             ..Default::default()
         })
     } else if extension.eq_ignore_ascii_case("pdf") {
-        Ok(pdf_parser::parse(file_path)?.into())
+        Ok(pdf_parser::parse(file_path, pdftotext)?.into())
+    } else if extension.eq_ignore_ascii_case("pptx") {
+        pptx_parser::parse(file_path)
+    } else if extension.eq_ignore_ascii_case("odt") || extension.eq_ignore_ascii_case("odp") {
+        odf_parser::parse(file_path)
+    } else if extension.eq_ignore_ascii_case("epub") {
+        epub_parser::parse(file_path)
+    } else if extension.eq_ignore_ascii_case("rtf") {
+        rtf_parser::parse(file_path)
+    } else if extension.eq_ignore_ascii_case("eml") {
+        email_parser::parse_eml(file_path)
+    } else if extension.eq_ignore_ascii_case("mbox") {
+        email_parser::parse_mbox(file_path)
     } else {
         Err(anyhow!(format!("Extension {extension:?} not supported")))
     }
@@ -57,14 +82,15 @@ pub fn parse_content(mime_type_str: &str, content: &Bytes) -> anyhow::Result<Par
                         ..Default::default()
                     })
                 }
-                utils::mime::PDF => {
-                    let pdf_content = pdf_parser::parse_bytes(content.clone())?;
-                    Ok(ParsedDocument {
-                        content: pdf_content.content,
-                        title: pdf_content.metadata.title,
-                        author: pdf_content.metadata.author,
-                    })
+                utils::mime::PDF => Ok(pdf_parser::parse_bytes(content.clone(), None)?.into()),
+                utils::mime::PPTX => pptx_parser::parse_bytes(content.clone()),
+                utils::mime::ODT | utils::mime::ODP => odf_parser::parse_bytes(content.clone()),
+                utils::mime::EPUB => epub_parser::parse_bytes(content.clone()),
+                utils::mime::RTF | utils::mime::TEXT_RTF => {
+                    rtf_parser::parse_bytes(content.clone())
                 }
+                utils::mime::EML => email_parser::parse_eml_bytes(content.clone()),
+                utils::mime::MBOX => email_parser::parse_mbox_bytes(content.clone()),
                 _ => Err(anyhow!(format!(
                     "Document Mimetype {mime_type_str:?} not supported"
                 ))),
@@ -84,7 +110,21 @@ pub fn parse_content(mime_type_str: &str, content: &Bytes) -> anyhow::Result<Par
 pub struct ParsedDocument {
     pub title: Option<String>,
     pub author: Option<String>,
+    /// Number of pages, for paged formats such as PDFs
+    pub page_count: Option<u32>,
     pub content: String,
+    /// Byte offset in the content where each page starts, for paged formats
+    pub page_offsets: Vec<u64>,
+    /// Addresses a message was sent to, for emails
+    pub recipients: Vec<String>,
+    /// When the document was written/sent, in seconds since the epoch
+    pub published_at: Option<i64>,
+    /// Identifies a part within the document it's in, e.g. the message id of
+    /// an email in a mailbox
+    pub part_id: Option<String>,
+    /// Documents contained in this one that are indexed on their own, e.g.
+    /// the messages in a mailbox
+    pub parts: Vec<ParsedDocument>,
 }
 
 impl From<pdf_parser::Pdf> for ParsedDocument {
@@ -92,7 +132,10 @@ impl From<pdf_parser::Pdf> for ParsedDocument {
         Self {
             title: value.metadata.title,
             author: value.metadata.author,
+            page_count: value.metadata.page_count,
             content: value.content,
+            page_offsets: value.page_offsets,
+            ..Default::default()
         }
     }
 }
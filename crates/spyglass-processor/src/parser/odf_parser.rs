@@ -0,0 +1,48 @@
+use bytes::Bytes;
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+use super::xml_text::{self, TextRules};
+use super::ParsedDocument;
+
+/// Text documents & presentations both keep their text in `<text:p>` &
+/// `<text:h>` elements of `content.xml`, repeated spaces & tabs are elements
+/// of their own.
+const CONTENT_RULES: TextRules = TextRules {
+    blocks: &["p", "h", "line-break"],
+    spaces: &["s", "tab", "table-cell"],
+    // Tracked changes that were removed & comments
+    skip: &["tracked-changes", "annotation"],
+};
+
+fn process_file<R: Read + Seek>(mut archive: ZipArchive<R>) -> anyhow::Result<ParsedDocument> {
+    let content = xml_text::read_entry(&mut archive, "content.xml")?;
+    let meta = xml_text::read_entry(&mut archive, "meta.xml").ok();
+    let meta = meta.as_deref();
+
+    Ok(ParsedDocument {
+        title: meta.and_then(|xml| xml_text::element_text(xml, "title")),
+        author: meta.and_then(|xml| {
+            xml_text::element_text(xml, "initial-creator")
+                .or_else(|| xml_text::element_text(xml, "creator"))
+        }),
+        content: xml_text::xml_to_text(&content, &CONTENT_RULES)?,
+        ..Default::default()
+    })
+}
+
+pub fn parse_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(Cursor::new(b))?)
+}
+
+/*
+ * Reads the provided file as an OpenDocument text document or presentation
+ * (ODT/ODP) & pulls out its text & the title/author from its metadata.
+ */
+pub fn parse(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(File::open(file_path)?)?)
+}
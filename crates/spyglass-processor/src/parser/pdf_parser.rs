@@ -1,6 +1,12 @@
+use anyhow::anyhow;
 use bytes::Bytes;
+use pdf::content::{Op, TextDrawAdjusted};
 use pdf::file::FileOptions;
+use pdf::font::{FontType, ToUnicodeMap};
+use pdf::object::{Page, Resolve};
+use pdf::primitive::PdfString;
 use std::{
+    collections::HashMap,
     env,
     fs::{self, File},
     io::Write,
@@ -8,20 +14,163 @@ use std::{
     process,
 };
 
-#[cfg(all(target_os = "windows", not(debug_assertions)))]
-const EXE_NAME: &str = "./pdftotext.exe";
+/// Adjustments in a `TJ` array are in thousandths of a unit of text space,
+/// moving right by more than this is treated as a space between words.
+const WORD_SPACING: f32 = 200.0;
+/// Min fraction of printable characters for extracted text to be kept. Text
+/// drawn w/ a font we can't map to unicode comes out as raw glyph ids.
+const MIN_PRINTABLE_RATIO: f32 = 0.9;
 
-#[cfg(all(not(target_os = "windows"), not(debug_assertions)))]
-const EXE_NAME: &str = "./pdftotext";
+/// How the strings drawn w/ a font map to text.
+struct FontDecoder {
+    to_unicode: Option<ToUnicodeMap>,
+    /// Composite fonts use 2 byte character codes
+    is_composite: bool,
+}
+
+impl FontDecoder {
+    fn decode(&self, text: &PdfString) -> String {
+        let bytes = text.as_bytes();
+        match &self.to_unicode {
+            Some(map) if self.is_composite => bytes
+                .chunks_exact(2)
+                .filter_map(|code| map.get(u16::from_be_bytes([code[0], code[1]])))
+                .collect(),
+            Some(map) => bytes
+                .iter()
+                .map(|code| match map.get(*code as u16) {
+                    Some(mapped) => mapped.to_string(),
+                    None => (*code as char).to_string(),
+                })
+                .collect(),
+            None => text.to_string_lossy(),
+        }
+    }
+}
+
+/// Decoders for the fonts used on a page, by resource name.
+fn page_fonts(page: &Page, resolve: &impl Resolve) -> HashMap<String, FontDecoder> {
+    let mut fonts = HashMap::new();
+    let resources = match page.resources() {
+        Ok(resources) => resources,
+        Err(_) => return fonts,
+    };
+
+    for (name, font) in resources.fonts.iter() {
+        let to_unicode = match font.to_unicode(resolve) {
+            Some(Ok(map)) => Some(map),
+            Some(Err(err)) => {
+                log::debug!("Unable to read unicode map for font {}: {:?}", name, err);
+                None
+            }
+            None => None,
+        };
+
+        fonts.insert(
+            name.to_string(),
+            FontDecoder {
+                to_unicode,
+                is_composite: matches!(font.subtype, FontType::Type0),
+            },
+        );
+    }
+
+    fonts
+}
+
+fn push_break(text: &mut String, brk: char) {
+    if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+        text.push(brk);
+    }
+}
+
+/// Text drawn by the operations in a page's content stream, in the order it's
+/// drawn.
+fn page_text(ops: &[Op], fonts: &HashMap<String, FontDecoder>) -> String {
+    let mut text = String::new();
+    let mut font = None;
+    let decode = |font: Option<&FontDecoder>, drawn: &PdfString| match font {
+        Some(font) => font.decode(drawn),
+        None => drawn.to_string_lossy(),
+    };
 
-#[cfg(all(target_os = "windows", debug_assertions))]
-const EXE_NAME: &str = "../../utils/win/pdftotext.exe";
+    for op in ops {
+        match op {
+            Op::TextFont { name, .. } => font = fonts.get(&name.to_string()),
+            Op::TextDraw { text: drawn } => text.push_str(&decode(font, drawn)),
+            Op::TextDrawAdjusted { array } => {
+                for adjusted in array {
+                    match adjusted {
+                        TextDrawAdjusted::Text(drawn) => text.push_str(&decode(font, drawn)),
+                        TextDrawAdjusted::Spacing(spacing) if -spacing > WORD_SPACING => {
+                            push_break(&mut text, ' ')
+                        }
+                        TextDrawAdjusted::Spacing(_) => {}
+                    }
+                }
+            }
+            // Moving down starts a new line. Moves along the line are also
+            // used to place each character, so they don't break words.
+            Op::MoveTextPosition { translation } if translation.y != 0.0 => {
+                push_break(&mut text, '\n')
+            }
+            Op::TextNewline | Op::SetTextMatrix { .. } | Op::EndText => push_break(&mut text, '\n'),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Whether extracted text is readable rather than raw glyph ids.
+fn is_printable(text: &str) -> bool {
+    let chars = text.chars().filter(|c| !c.is_whitespace());
+    let total = chars.clone().count();
+    let printable = chars
+        .filter(|c| !c.is_control() && *c != char::REPLACEMENT_CHARACTER)
+        .count();
 
-#[cfg(all(target_os = "macos", debug_assertions))]
-const EXE_NAME: &str = "../../utils/mac/pdftotext";
+    total == 0 || printable as f32 / total as f32 >= MIN_PRINTABLE_RATIO
+}
 
-#[cfg(all(target_os = "linux", debug_assertions))]
-const EXE_NAME: &str = "../../utils/linux/pdftotext";
+/// Text of a single page.
+fn read_page(page: &Page, resolve: &impl Resolve) -> anyhow::Result<String> {
+    match &page.contents {
+        Some(contents) => {
+            let ops = contents
+                .operations(resolve)
+                .map_err(|err| anyhow!("Unable to read page contents: {err:?}"))?;
+            Ok(page_text(&ops, &page_fonts(page, resolve)))
+        }
+        None => Ok(String::new()),
+    }
+}
+
+/// Extracts the text of each page w/ the `pdf` crate. Pages that can't be read
+/// are `None` rather than failing the whole document.
+fn extract_pages(path: &Path) -> anyhow::Result<Vec<Option<String>>> {
+    let file = FileOptions::cached()
+        .open(path)
+        .map_err(|err| anyhow!("Unable to open PDF: {err:?}"))?;
+    let resolver = file.resolver();
+
+    let mut pages = Vec::new();
+    for (idx, page) in file.pages().enumerate() {
+        let text = page
+            .map_err(|err| anyhow!("Unable to read page: {err:?}"))
+            .and_then(|page| read_page(&page, &resolver));
+
+        match text {
+            Ok(text) => pages.push(Some(text)),
+            Err(err) => {
+                log::debug!("Skipping page {} of {:?}: {}", idx + 1, path, err);
+                pages.push(None);
+            }
+        }
+    }
+
+    Ok(pages)
+}
 
 // Uses utility pdftotxt. The utility documentation is as follows
 //
@@ -59,73 +208,78 @@ const EXE_NAME: &str = "../../utils/linux/pdftotext";
 //   -help                  : print usage information
 //   --help                 : print usage information
 //   -?                     : print usage information
-pub fn parse(path: &Path) -> anyhow::Result<Pdf> {
-    let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
-
-    let current_dir = match env::current_exe() {
-        Ok(current_exe) => current_exe.parent().map(|path| path.to_owned()),
-        Err(err) => {
-            log::error!("Unable to access current exe {:?}", err);
-            None
-        }
-    };
-
-    let temp_dir = env::temp_dir();
-    let txt_path = temp_dir.join(format!("{uuid}-spyglass-processing.txt"));
-
-    let exe_path = if let Some(path) = &current_dir {
-        path.join(EXE_NAME)
-    } else {
-        Path::new(EXE_NAME).to_path_buf()
-    };
-
-    log::debug!("Executable Path {:?}", exe_path);
-
-    let mut cmd = process::Command::new(exe_path.to_str().unwrap());
-    cmd.arg("-layout")
+fn pdftotext_pages(exe_path: &Path, path: &Path) -> anyhow::Result<Vec<String>> {
+    log::debug!("Extracting text from {:?} w/ {:?}", path, exe_path);
+    // `-` writes the text to stdout, w/ a page break after each page
+    let output = process::Command::new(exe_path)
+        .arg("-layout")
         .arg("-q")
-        .arg("-nopgbrk")
-        .arg(path.to_str().unwrap())
-        .arg(txt_path.to_str().unwrap());
+        .arg(path)
+        .arg("-")
+        .output()?;
 
-    if let Some(path) = current_dir {
-        cmd.current_dir(path);
+    if !output.status.success() {
+        return Err(anyhow!("pdftotext exited w/ {}", output.status));
     }
 
-    log::debug!("Full Command {:?}", cmd);
-    let cmd = cmd.spawn();
-
-    match cmd {
-        Ok(mut child) => {
-            if let Err(err) = child.wait() {
-                let _ = fs::remove_file(txt_path);
-                return Err(anyhow::format_err!(err));
-            }
-        }
-        Err(err) => {
-            let _ = fs::remove_file(txt_path);
-            return Err(anyhow::format_err!(err));
-        }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut pages = text
+        .split('\x0c')
+        .map(|page| page.to_string())
+        .collect::<Vec<_>>();
+    // Drop what comes after the last page break
+    if pages.len() > 1 && pages.last().map(|page| page.trim().is_empty()) == Some(true) {
+        pages.pop();
     }
 
-    let content = match std::fs::read(txt_path.as_path()) {
-        Ok(content) => {
-            let pdf_contents = String::from_utf8_lossy(&content);
-            let _ = fs::remove_file(txt_path);
-            String::from(pdf_contents.as_ref())
-        }
-        Err(err) => {
-            let _ = fs::remove_file(txt_path);
-            return Err(anyhow::format_err!(err));
+    Ok(pages)
+}
+
+/// Extracts the text of each page in process, falling back to an external
+/// `pdftotext` binary if one is configured for pages whose text couldn't be
+/// read. Pages that can't be read either way are left empty.
+pub fn parse(path: &Path, pdftotext: Option<&Path>) -> anyhow::Result<Pdf> {
+    let mut pages = match (extract_pages(path), pdftotext) {
+        (Ok(pages), _) => pages
+            .into_iter()
+            .map(|page| page.filter(|text| is_printable(text)))
+            .collect::<Vec<_>>(),
+        (Err(err), Some(exe_path)) => {
+            log::debug!("Falling back to pdftotext for {:?}: {}", path, err);
+            pdftotext_pages(exe_path, path)?
+                .into_iter()
+                .map(Some)
+                .collect()
         }
+        (Err(err), None) => return Err(err),
     };
 
-    let metadata = PdfMetadata::parse(path);
+    if let Some(exe_path) = pdftotext.filter(|_| pages.iter().any(Option::is_none)) {
+        log::debug!(
+            "Falling back to pdftotext for unreadable pages in {:?}",
+            path
+        );
+        match pdftotext_pages(exe_path, path) {
+            Ok(fallback) => {
+                for (page, text) in pages.iter_mut().zip(fallback) {
+                    if page.is_none() {
+                        *page = Some(text);
+                    }
+                }
+            }
+            Err(err) => log::warn!("Unable to run pdftotext on {:?}: {}", path, err),
+        }
+    }
 
-    Ok(Pdf { content, metadata })
+    let pages = pages
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect::<Vec<_>>();
+    let metadata = PdfMetadata::parse(path);
+    Ok(Pdf::from_pages(&pages, metadata))
 }
 
-pub fn parse_bytes(b: Bytes) -> anyhow::Result<Pdf> {
+pub fn parse_bytes(b: Bytes, pdftotext: Option<&Path>) -> anyhow::Result<Pdf> {
     let uuid = uuid::Uuid::new_v4().as_hyphenated().to_string();
     let temp_dir = env::temp_dir();
     let temp_doc = temp_dir.join(format!("{uuid}.pdf"));
@@ -135,20 +289,43 @@ pub fn parse_bytes(b: Bytes) -> anyhow::Result<Pdf> {
         file.write_all(&b)?;
     }
 
-    let result = parse(temp_doc.as_path());
+    let result = parse(temp_doc.as_path(), pdftotext);
     let _ = fs::remove_file(temp_doc);
     result
 }
 
 pub struct Pdf {
     pub content: String,
+    /// Byte offset in the content where each page starts
+    pub page_offsets: Vec<u64>,
     pub metadata: PdfMetadata,
 }
 
+impl Pdf {
+    fn from_pages(pages: &[String], metadata: PdfMetadata) -> Self {
+        let mut content = String::new();
+        let mut page_offsets = Vec::new();
+        for page in pages {
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            page_offsets.push(content.len() as u64);
+            content.push_str(page.trim());
+        }
+
+        Self {
+            content,
+            page_offsets,
+            metadata,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PdfMetadata {
     pub title: Option<String>,
     pub author: Option<String>,
+    pub page_count: Option<u32>,
 }
 
 impl PdfMetadata {
@@ -157,13 +334,20 @@ impl PdfMetadata {
             Ok(pdf) => pdf,
             Err(_) => return Default::default(),
         };
+        let page_count = Some(pdf.num_pages());
         let pdf_info = match &pdf.trailer.info_dict {
             Some(dict) => dict,
-            None => return Default::default(),
+            None => {
+                return Self {
+                    page_count,
+                    ..Default::default()
+                }
+            }
         };
         Self {
             title: pdf_info.get("Title").and_then(|v| v.to_string().ok()),
             author: pdf_info.get("Author").and_then(|v| v.to_string().ok()),
+            page_count,
         }
     }
 }
@@ -178,6 +362,7 @@ mod tests {
         let metadata = super::PdfMetadata::parse(&path_with_metadata);
         assert_eq!(metadata.title, Some("PDF title".to_string()));
         assert_eq!(metadata.author, Some("PDF author".to_string()));
+        assert_eq!(metadata.page_count, Some(1));
     }
 
     #[test]
@@ -187,4 +372,28 @@ mod tests {
         assert_eq!(metadata.title, None);
         assert_eq!(metadata.author, None);
     }
+
+    #[test]
+    fn test_pdf_text_extraction() {
+        let path = Path::new("../../fixtures/pdf/pdf_with_metadata.pdf");
+        let pdf = super::parse(&path, None).expect("Unable to parse PDF");
+        assert_eq!(
+            pdf.content.split_whitespace().collect::<Vec<_>>(),
+            vec!["PDF", "content"]
+        );
+        assert_eq!(pdf.page_offsets, vec![0]);
+        assert_eq!(pdf.metadata.page_count, Some(1));
+    }
+
+    #[test]
+    fn test_pdf_from_pages() {
+        let pages = vec![
+            " First page ".to_string(),
+            String::new(),
+            "Third".to_string(),
+        ];
+        let pdf = super::Pdf::from_pages(&pages, Default::default());
+        assert_eq!(pdf.content, "First page\n\n\n\nThird");
+        assert_eq!(pdf.page_offsets, vec![0, 12, 14]);
+    }
 }
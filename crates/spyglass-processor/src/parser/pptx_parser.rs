@@ -0,0 +1,77 @@
+use bytes::Bytes;
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+use super::xml_text::{self, TextRules};
+use super::ParsedDocument;
+
+/// Text in a slide is in `<a:t>` runs, grouped into `<a:p>` paragraphs.
+const SLIDE_RULES: TextRules = TextRules {
+    blocks: &["p", "br"],
+    spaces: &["tc"],
+    skip: &[],
+};
+
+/// Number of the slide stored at `path`, e.g. `ppt/slides/slide12.xml` is 12.
+fn slide_number(path: &str) -> Option<u32> {
+    path.strip_prefix("ppt/slides/slide")?
+        .strip_suffix(".xml")?
+        .parse()
+        .ok()
+}
+
+fn process_file<R: Read + Seek>(mut archive: ZipArchive<R>) -> anyhow::Result<ParsedDocument> {
+    // Slides are numbered in the order they're shown
+    let mut slides = archive
+        .file_names()
+        .filter_map(|name| slide_number(name).map(|num| (num, name.to_string())))
+        .collect::<Vec<_>>();
+    slides.sort();
+
+    let mut text = Vec::new();
+    for (_, name) in &slides {
+        let xml = xml_text::read_entry(&mut archive, name)?;
+        text.push(xml_text::xml_to_text(&xml, &SLIDE_RULES)?);
+    }
+
+    let properties = xml_text::read_entry(&mut archive, "docProps/core.xml").ok();
+    Ok(ParsedDocument {
+        title: properties
+            .as_deref()
+            .and_then(|xml| xml_text::element_text(xml, "title")),
+        author: properties
+            .as_deref()
+            .and_then(|xml| xml_text::element_text(xml, "creator")),
+        page_count: Some(slides.len() as u32),
+        content: text.join("\n\n"),
+        ..Default::default()
+    })
+}
+
+pub fn parse_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(Cursor::new(b))?)
+}
+
+/*
+ * Reads the provided file as a PPTX, pulls out the text of each slide in order
+ * & the title/author from the document properties.
+ */
+pub fn parse(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    process_file(ZipArchive::new(File::open(file_path)?)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::slide_number;
+
+    #[test]
+    fn test_slide_number() {
+        assert_eq!(slide_number("ppt/slides/slide12.xml"), Some(12));
+        assert_eq!(slide_number("ppt/slides/_rels/slide1.xml.rels"), None);
+        assert_eq!(slide_number("ppt/slideLayouts/slideLayout1.xml"), None);
+    }
+}
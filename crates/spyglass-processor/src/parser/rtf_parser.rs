@@ -0,0 +1,276 @@
+use anyhow::anyhow;
+use bytes::Bytes;
+use std::path::Path;
+
+use super::ParsedDocument;
+
+/// Groups whose text isn't part of the document, e.g. font & style tables or
+/// embedded pictures.
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "colortbl",
+    "datastore",
+    "filetbl",
+    "fonttbl",
+    "footer",
+    "footerf",
+    "footerl",
+    "footerr",
+    "generator",
+    "header",
+    "headerf",
+    "headerl",
+    "headerr",
+    "latentstyles",
+    "listoverridetable",
+    "listtable",
+    "object",
+    "pict",
+    "revtbl",
+    "rsidtbl",
+    "stylesheet",
+    "themedata",
+    "xmlnsdecl",
+];
+
+/// Where the text in the current group goes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Destination {
+    Body,
+    /// Document properties, only the title & author are kept
+    Info,
+    Title,
+    Author,
+    Skip,
+}
+
+#[derive(Clone, Copy)]
+struct GroupState {
+    destination: Destination,
+    /// Number of fallback characters that follow a `\uN` character
+    unicode_skip: usize,
+}
+
+#[derive(Default)]
+struct RtfText {
+    body: String,
+    title: String,
+    author: String,
+}
+
+impl RtfText {
+    fn push(&mut self, destination: Destination, c: char) {
+        match destination {
+            Destination::Body => self.body.push(c),
+            Destination::Title => self.title.push(c),
+            Destination::Author => self.author.push(c),
+            Destination::Info | Destination::Skip => {}
+        }
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+/// Text of an RTF document. Characters in the document's code page (`\'hh`)
+/// are read as Latin-1, which covers most western documents.
+fn rtf_to_text(rtf: &[u8]) -> anyhow::Result<ParsedDocument> {
+    if !rtf.starts_with(b"{\\rtf") {
+        return Err(anyhow!("Not an RTF document"));
+    }
+
+    let mut text = RtfText::default();
+    let mut state = GroupState {
+        destination: Destination::Body,
+        unicode_skip: 1,
+    };
+    let mut stack = Vec::new();
+    // Fallback characters left to skip after a `\uN` character
+    let mut to_skip = 0;
+    let mut high_surrogate: Option<u32> = None;
+
+    let mut idx = 0;
+    while idx < rtf.len() {
+        let byte = rtf[idx];
+        idx += 1;
+        match byte {
+            b'{' => {
+                stack.push(state);
+                to_skip = 0;
+            }
+            b'}' => {
+                state = stack.pop().unwrap_or(state);
+                to_skip = 0;
+            }
+            b'\r' | b'\n' => {}
+            b'\\' => {
+                let next = match rtf.get(idx) {
+                    Some(next) => *next,
+                    None => break,
+                };
+                idx += 1;
+
+                match next {
+                    b'\\' | b'{' | b'}' => {
+                        if to_skip > 0 {
+                            to_skip -= 1;
+                        } else {
+                            text.push(state.destination, next as char);
+                        }
+                    }
+                    b'\'' => {
+                        let hex = rtf.get(idx..idx + 2).unwrap_or_default();
+                        idx += hex.len();
+                        let code = std::str::from_utf8(hex)
+                            .ok()
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                        if to_skip > 0 {
+                            to_skip -= 1;
+                        } else if let Some(code) = code {
+                            text.push(state.destination, code as char);
+                        }
+                    }
+                    // Ignorable destination, e.g. `{\*\generator ...}`
+                    b'*' => state.destination = Destination::Skip,
+                    b'~' => text.push(state.destination, '\u{a0}'),
+                    b'_' => text.push(state.destination, '-'),
+                    // Escaped new line is a paragraph break
+                    b'\r' | b'\n' => text.push(state.destination, '\n'),
+                    c if c.is_ascii_alphabetic() => {
+                        let start = idx - 1;
+                        while idx < rtf.len() && rtf[idx].is_ascii_alphabetic() {
+                            idx += 1;
+                        }
+                        let word = std::str::from_utf8(&rtf[start..idx]).unwrap_or_default();
+
+                        let param_start = idx;
+                        if rtf.get(idx) == Some(&b'-') {
+                            idx += 1;
+                        }
+                        while idx < rtf.len() && rtf[idx].is_ascii_digit() {
+                            idx += 1;
+                        }
+                        let param = std::str::from_utf8(&rtf[param_start..idx])
+                            .ok()
+                            .and_then(|param| param.parse::<i32>().ok());
+                        // A space ends the control word & isn't part of the text
+                        if rtf.get(idx) == Some(&b' ') {
+                            idx += 1;
+                        }
+
+                        let push = |text: &mut RtfText, c: char| text.push(state.destination, c);
+                        match word {
+                            "par" | "line" | "sect" | "page" | "row" => push(&mut text, '\n'),
+                            "tab" | "cell" => push(&mut text, ' '),
+                            "emdash" => push(&mut text, '—'),
+                            "endash" => push(&mut text, '–'),
+                            "bullet" => push(&mut text, '•'),
+                            "lquote" => push(&mut text, '‘'),
+                            "rquote" => push(&mut text, '’'),
+                            "ldblquote" => push(&mut text, '“'),
+                            "rdblquote" => push(&mut text, '”'),
+                            "uc" => state.unicode_skip = param.unwrap_or(1).max(0) as usize,
+                            "u" => {
+                                // Code points are written as signed 16 bit numbers
+                                let code = param.unwrap_or_default().rem_euclid(0x10000) as u32;
+                                let decoded = match (high_surrogate.take(), code) {
+                                    (_, 0xD800..=0xDBFF) => {
+                                        high_surrogate = Some(code);
+                                        None
+                                    }
+                                    (Some(high), 0xDC00..=0xDFFF) => char::from_u32(
+                                        0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00),
+                                    ),
+                                    (_, code) => char::from_u32(code),
+                                };
+                                if let Some(decoded) = decoded {
+                                    push(&mut text, decoded);
+                                }
+                                to_skip = state.unicode_skip;
+                            }
+                            "info" => state.destination = Destination::Info,
+                            "title" if state.destination == Destination::Info => {
+                                state.destination = Destination::Title
+                            }
+                            "author" if state.destination == Destination::Info => {
+                                state.destination = Destination::Author
+                            }
+                            word if SKIPPED_DESTINATIONS.contains(&word) => {
+                                state.destination = Destination::Skip
+                            }
+                            _ => {}
+                        }
+                    }
+                    // Other control symbols, e.g. optional hyphens
+                    _ => {}
+                }
+            }
+            _ => {
+                if to_skip > 0 {
+                    to_skip -= 1;
+                } else {
+                    text.push(state.destination, byte as char);
+                }
+            }
+        }
+    }
+
+    let content = text
+        .body
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(ParsedDocument {
+        title: non_empty(&text.title),
+        author: non_empty(&text.author),
+        content,
+        ..Default::default()
+    })
+}
+
+pub fn parse_bytes(b: Bytes) -> anyhow::Result<ParsedDocument> {
+    rtf_to_text(&b)
+}
+
+/*
+ * Reads the provided file as an RTF document, pulls out the text of the body
+ * & the title/author from the document info.
+ */
+pub fn parse(file_path: &Path) -> anyhow::Result<ParsedDocument> {
+    rtf_to_text(&std::fs::read(file_path)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::rtf_to_text;
+
+    #[test]
+    fn test_rtf_to_text() {
+        let rtf = br#"{\rtf1\ansi{\fonttbl{\f0 Times New Roman;}}{\colortbl;\red255;}
+{\info{\title Trip notes}{\author Ada Lovelace}}
+{\*\generator Riched20 10.0;}\pard\f0\fs24 Caf\'e9 on the \b corner\b0 .\par
+Price: 5\'80 \u8364? \{approx\}\par
+\u-10179?\u-8704?\tab done}"#;
+
+        let parsed = rtf_to_text(rtf).expect("Unable to parse RTF");
+        assert_eq!(parsed.title, Some("Trip notes".to_string()));
+        assert_eq!(parsed.author, Some("Ada Lovelace".to_string()));
+        assert_eq!(
+            parsed.content,
+            "Café on the corner.\nPrice: 5\u{80} € {approx}\n😀 done"
+        );
+    }
+
+    #[test]
+    fn test_not_rtf() {
+        assert!(rtf_to_text(b"plain text").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Helpers to pull the text out of the zipped XML formats, e.g. presentations,
+//! OpenDocument files & ebooks.
+use anyhow::anyhow;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// How the elements of a format map to text. Elements are matched by their
+/// local name, i.e. w/o the namespace prefix.
+pub struct TextRules<'a> {
+    /// Elements that hold a block of text, e.g. a paragraph. Each ends a line.
+    pub blocks: &'a [&'a str],
+    /// Elements that separate words, e.g. a table cell or a tab.
+    pub spaces: &'a [&'a str],
+    /// Elements whose text is left out, e.g. `<script>` in XHTML.
+    pub skip: &'a [&'a str],
+}
+
+fn is_one_of(names: &[&str], name: &[u8]) -> bool {
+    names.iter().any(|candidate| candidate.as_bytes() == name)
+}
+
+fn push_break(text: &mut String, brk: char) {
+    if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+        text.push(brk);
+    }
+}
+
+/// Collapses the whitespace in each line & drops empty lines.
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of an XML document, one line per block.
+pub fn xml_to_text(xml: &str, rules: &TextRules) -> anyhow::Result<String> {
+    let mut reader = Reader::from_str(xml);
+    // XHTML in the wild isn't always well formed
+    reader.check_end_names(false);
+
+    let mut text = String::new();
+    let mut skip_depth = 0;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                if skip_depth > 0 || is_one_of(rules.skip, e.local_name().as_ref()) {
+                    skip_depth += 1;
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = e.local_name();
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                } else if is_one_of(rules.blocks, name.as_ref()) {
+                    push_break(&mut text, '\n');
+                } else if is_one_of(rules.spaces, name.as_ref()) {
+                    push_break(&mut text, ' ');
+                }
+            }
+            Ok(Event::Empty(e)) if skip_depth == 0 => {
+                let name = e.local_name();
+                if is_one_of(rules.blocks, name.as_ref()) {
+                    push_break(&mut text, '\n');
+                } else if is_one_of(rules.spaces, name.as_ref()) {
+                    push_break(&mut text, ' ');
+                }
+            }
+            Ok(Event::Text(e)) if skip_depth == 0 => match e.unescape() {
+                Ok(unescaped) => text.push_str(&unescaped),
+                // e.g. HTML entities that aren't defined in XML
+                Err(_) => text.push_str(&String::from_utf8_lossy(&e)),
+            },
+            Ok(Event::CData(e)) if skip_depth == 0 => text.push_str(&String::from_utf8_lossy(&e)),
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => {
+                return Err(anyhow!(
+                    "Unable to read XML at {}: {err}",
+                    reader.buffer_position()
+                ))
+            }
+        }
+    }
+
+    Ok(normalize_whitespace(&text))
+}
+
+/// Text of the first element w/ the local name `name`, e.g. the `dc:title` of
+/// a document's properties.
+pub fn element_text(xml: &str, name: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut inside = false;
+    let mut text = String::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) if e.local_name().as_ref() == name.as_bytes() => inside = true,
+            Ok(Event::Text(e)) if inside => {
+                if let Ok(unescaped) = e.unescape() {
+                    text.push_str(&unescaped);
+                }
+            }
+            Ok(Event::End(e)) if inside && e.local_name().as_ref() == name.as_bytes() => break,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+/// Value of the attribute w/ the local name `name`.
+pub fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|value| value.to_string())
+}
+
+/// Reads a file in a zip archive as text.
+pub fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> anyhow::Result<String> {
+    let mut entry = archive.by_name(name)?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{element_text, xml_to_text, TextRules};
+
+    #[test]
+    fn test_xml_to_text() {
+        let rules = TextRules {
+            blocks: &["p", "h"],
+            spaces: &["s", "tab"],
+            skip: &["annotation"],
+        };
+        let xml = r#"<office:text xmlns:office="o" xmlns:text="t">
+            <text:h>Release <text:span>notes</text:span></text:h>
+            <text:p>Fixed<text:s/>the &amp; bug<text:tab/>today</text:p>
+            <text:p><text:annotation>Skip me</text:annotation>Done.</text:p>
+        </office:text>"#;
+
+        assert_eq!(
+            xml_to_text(xml, &rules).unwrap(),
+            "Release notes\nFixed the & bug today\nDone."
+        );
+    }
+
+    #[test]
+    fn test_element_text() {
+        let xml = r#"<cp:coreProperties xmlns:dc="dc" xmlns:cp="cp">
+            <dc:title>Quarterly review</dc:title><dc:creator>Ada</dc:creator><dc:subject/>
+        </cp:coreProperties>"#;
+
+        assert_eq!(
+            element_text(xml, "title"),
+            Some("Quarterly review".to_string())
+        );
+        assert_eq!(element_text(xml, "creator"), Some("Ada".to_string()));
+        assert_eq!(element_text(xml, "subject"), None);
+    }
+}
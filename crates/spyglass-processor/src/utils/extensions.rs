@@ -1,3 +1,5 @@
+use shared::ARCHIVE_SUFFIXES;
+use std::path::Path;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter, EnumString};
@@ -6,6 +8,8 @@ use strum_macros::{Display, EnumIter, EnumString};
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
 #[strum(serialize_all = "snake_case")]
 pub enum SupportedExt {
+    /// Handled by looking inside the archive & processing the files in it
+    Archive(ArchiveExt),
     /// Handled by our audio transcription pipeline
     Audio(AudioExt),
     /// Handled by our code symbol extraction pipeline
@@ -20,6 +24,7 @@ pub enum SupportedExt {
 impl SupportedExt {
     pub fn list_all() -> Vec<String> {
         let mut list = Vec::new();
+        list.extend(ArchiveExt::iter().map(|x| x.to_string()));
         list.extend(AudioExt::iter().map(|x| x.to_string()));
         list.extend(CodeExt::iter().map(|x| x.to_string()));
         list.extend(DocumentExt::iter().map(|x| x.to_string()));
@@ -30,7 +35,9 @@ impl SupportedExt {
 
     pub fn from_ext(ext: &str) -> Self {
         let ext = ext.to_lowercase();
-        if let Ok(ext) = AudioExt::from_str(&ext) {
+        if let Ok(ext) = ArchiveExt::from_str(&ext) {
+            Self::Archive(ext)
+        } else if let Ok(ext) = AudioExt::from_str(&ext) {
             Self::Audio(ext)
         } else if let Ok(ext) = CodeExt::from_str(&ext) {
             Self::Code(ext)
@@ -42,6 +49,34 @@ impl SupportedExt {
             Self::NotSupported
         }
     }
+
+    /// Like `from_ext`, but goes by the whole file name so archives w/ more
+    /// than one extension, e.g. `.tar.gz`, are recognized.
+    pub fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let archive_ext = ARCHIVE_SUFFIXES
+            .iter()
+            .find(|suffix| name.ends_with(*suffix))
+            .and_then(|suffix| ArchiveExt::from_str(suffix.trim_start_matches('.')).ok());
+        match (archive_ext, path.extension()) {
+            (Some(ext), _) => Self::Archive(ext),
+            (None, Some(ext)) => Self::from_ext(&ext.to_string_lossy()),
+            (None, None) => Self::NotSupported,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Display, EnumString, PartialEq, Eq, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum ArchiveExt {
+    #[strum(serialize = "tar.gz")]
+    TarGz,
+    Tgz,
+    Zip,
 }
 
 #[derive(Clone, Debug, Display, EnumString, PartialEq, Eq, EnumIter)]
@@ -63,7 +98,9 @@ pub enum AudioExt {
 pub enum CodeExt {
     C,
     Cpp,
+    Go,
     Js,
+    Py,
     Rs,
     Ts,
 }
@@ -77,6 +114,13 @@ pub enum DocumentExt {
     Xlsx,
     Gsheet,
     Pdf,
+    Eml,
+    Epub,
+    Mbox,
+    Odp,
+    Odt,
+    Pptx,
+    Rtf,
 }
 
 #[derive(Clone, Debug, Display, EnumString, PartialEq, Eq, EnumIter)]
@@ -95,7 +139,7 @@ pub enum TextExt {
 #[cfg(test)]
 mod test {
     use super::SupportedExt;
-    use crate::utils::extensions::AudioExt;
+    use crate::utils::extensions::{ArchiveExt, AudioExt};
 
     #[test]
     fn test_extension_to_enum() {
@@ -120,4 +164,20 @@ mod test {
         let ext = SupportedExt::from_ext(&ext);
         assert_eq!(ext, SupportedExt::Audio(AudioExt::Wav));
     }
+
+    #[test]
+    fn test_path_to_enum() {
+        let path = std::path::Path::new("/tmp/backup.TAR.GZ");
+        assert_eq!(
+            SupportedExt::from_path(path),
+            SupportedExt::Archive(ArchiveExt::TarGz)
+        );
+
+        // Other gzipped files aren't archives we can look inside of
+        let path = std::path::Path::new("/tmp/access.log.gz");
+        assert_eq!(SupportedExt::from_path(path), SupportedExt::NotSupported);
+
+        let path = std::path::Path::new("/tmp/notes.md");
+        assert_eq!(SupportedExt::from_path(path), SupportedExt::from_ext("md"));
+    }
 }
@@ -8,6 +8,13 @@ pub const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadshee
 pub const XLTX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.template";
 pub const XLS: &str = "application/vnd.ms-excel";
 pub const ODS: &str = "application/vnd.oasis.opendocument.spreadsheet";
+pub const ODT: &str = "application/vnd.oasis.opendocument.text";
+pub const ODP: &str = "application/vnd.oasis.opendocument.presentation";
+pub const EPUB: &str = "application/epub+zip";
+pub const RTF: &str = "application/rtf";
+pub const TEXT_RTF: &str = "text/rtf";
+pub const EML: &str = "message/rfc822";
+pub const MBOX: &str = "application/mbox";
 pub const GDOC: &str = "application/vnd.google-apps.document";
 pub const GSHEET: &str = "application/vnd.google-apps.spreadsheet";
 pub const GSLIDES: &str = "application/vnd.google-apps.presentation";
@@ -102,7 +109,9 @@ impl SupportedMime {
 }
 #[cfg(test)]
 mod test {
-    use crate::utils::mime::{SupportedMime, DOCX, GSLIDES, ODS, XLS, XLSX};
+    use crate::utils::mime::{
+        SupportedMime, DOCX, EML, EPUB, GSLIDES, MBOX, ODP, ODS, ODT, PPTX, RTF, XLS, XLSX,
+    };
 
     #[test]
     pub fn test_document_mime_types() {
@@ -130,6 +139,13 @@ mod test {
             SupportedMime::from_mime("application/pdf"),
             SupportedMime::Document("application/pdf".parse().unwrap())
         );
+
+        for mime in [PPTX, ODP, ODT, EPUB, RTF, EML, MBOX] {
+            assert_eq!(
+                SupportedMime::from_mime(mime),
+                SupportedMime::Document(mime.parse().unwrap())
+            );
+        }
     }
 
     #[test]
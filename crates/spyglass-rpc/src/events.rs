@@ -6,6 +6,7 @@ pub enum RpcEventType {
     LensUninstalled,
     LensInstalled,
     ModelDownloadStatus,
+    SchemaMigrationStatus,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -22,3 +23,18 @@ pub enum ModelDownloadStatusPayload {
     Error { model_name: String, msg: String },
     InProgress { model_name: String, percent: u8 },
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum SchemaMigrationStatusPayload {
+    Finished {
+        num_docs: usize,
+    },
+    Error {
+        msg: String,
+    },
+    InProgress {
+        num_migrated: usize,
+        total: usize,
+        percent: u8,
+    },
+}
@@ -1,12 +1,17 @@
 use jsonrpsee::core::{Error, JsonValue};
 use jsonrpsee::proc_macros::rpc;
 use shared::config::UserSettings;
-use shared::request::{BatchDocumentRequest, RawDocumentRequest, SearchLensesParam, SearchParam};
+use shared::request::{
+    BatchDocumentRequest, RawDocumentRequest, ResultOpenedParam, SearchLensesParam, SearchParam,
+    SuggestParam,
+};
 use shared::response::{
-    AppStatus, DefaultIndices, LensResult, LibraryStats, ListConnectionResult, PluginResult,
-    SearchLensesResp, SearchResults,
+    AppStatus, Backlink, DefaultIndices, LensResult, LibraryStats, ListConnectionResult,
+    PluginResult, RestoreSnapshotResult, SearchLensesResp, SearchResults, SnapshotInfo,
+    SuggestResults,
 };
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 mod events;
 pub use events::*;
@@ -41,6 +46,24 @@ pub trait Rpc {
     #[method(name = "index.delete_document_by_url")]
     async fn delete_document_by_url(&self, url: String) -> Result<(), Error>;
 
+    /// Pages that link to `url`, i.e. "what links here"
+    #[method(name = "index.backlinks")]
+    async fn backlinks(&self, url: String) -> Result<Vec<Backlink>, Error>;
+
+    /// Writes a consistent snapshot of the index & database into the `path`
+    /// directory.
+    #[method(name = "index.create_snapshot")]
+    async fn create_snapshot(&self, path: PathBuf) -> Result<SnapshotInfo, Error>;
+
+    /// Snapshots the index & database and exports them as a single `.tar.gz`
+    /// archive at `path`.
+    #[method(name = "index.export_snapshot")]
+    async fn export_snapshot(&self, path: PathBuf) -> Result<SnapshotInfo, Error>;
+
+    /// Merges a snapshot directory or exported archive into the library.
+    #[method(name = "index.restore_snapshot")]
+    async fn restore_snapshot(&self, path: PathBuf) -> Result<RestoreSnapshotResult, Error>;
+
     #[method(name = "authorize_connection")]
     async fn authorize_connection(&self, id: String) -> Result<(), Error>;
 
@@ -77,6 +100,16 @@ pub trait Rpc {
     #[method(name = "search_docs")]
     async fn search_docs(&self, query: SearchParam) -> Result<SearchResults, Error>;
 
+    /// Completions for a partially typed query & spelling corrections. Cheap
+    /// enough to call on every keystroke.
+    #[method(name = "search.suggest")]
+    async fn suggest(&self, query: SuggestParam) -> Result<SuggestResults, Error>;
+
+    /// Records a result opened from a search, used to rank it higher for
+    /// similar queries.
+    #[method(name = "search.record_opened")]
+    async fn record_opened(&self, opened: ResultOpenedParam) -> Result<(), Error>;
+
     #[method(name = "search_lenses")]
     async fn search_lenses(&self, query: SearchLensesParam) -> Result<SearchLensesResp, Error>;
 
@@ -0,0 +1,306 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Journal of uncommitted index writes, kept alongside the index.
+pub const JOURNAL_FILE: &str = "write_journal.jsonl";
+
+/// Controls how often buffered index writes are committed.
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    /// Commit once this many adds/deletes are pending.
+    pub max_pending: usize,
+    /// Commit pending writes that are older than this.
+    pub max_delay: Duration,
+    /// Sync the journal to disk at most this often. Writes recorded in between
+    /// are synced together rather than each waiting on the disk.
+    pub max_sync_delay: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_pending: 1000,
+            max_delay: Duration::from_secs(10),
+            max_sync_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A single buffered index write.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalEntry {
+    /// Document serialized w/ `Schema::to_json`
+    Upsert {
+        doc: String,
+    },
+    Delete {
+        doc_id: String,
+    },
+}
+
+/// Append-only log of the writes made since the last commit. Replayed when the
+/// index is opened after a crash, then cleared on every commit.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Opens the journal in `index_dir`, returning any entries left over from
+    /// writes that were never committed.
+    pub fn open(index_dir: &Path) -> std::io::Result<(Self, Vec<JournalEntry>)> {
+        let path = index_dir.join(JOURNAL_FILE);
+
+        let mut entries = Vec::new();
+        // Length of the journal up to the last valid entry
+        let mut valid_len = 0;
+        if path.exists() {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 0 {
+                // The last write may have been cut short by the crash.
+                if !line.ends_with('\n') {
+                    log::warn!("Skipping incomplete journal entry");
+                    break;
+                }
+
+                if !line.trim().is_empty() {
+                    match serde_json::from_str::<JournalEntry>(&line) {
+                        Ok(entry) => entries.push(entry),
+                        Err(err) => {
+                            log::warn!("Skipping invalid journal entry: {err}");
+                            break;
+                        }
+                    }
+                }
+
+                valid_len += line.len() as u64;
+                line.clear();
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Drop anything after the last valid entry so new entries aren't
+        // appended to a partial one.
+        if file.metadata()?.len() > valid_len {
+            file.set_len(valid_len)?;
+        }
+
+        Ok((Journal { path, file }, entries))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the entries, they survive the app crashing right away but are
+    /// only guaranteed to survive the OS crashing once the journal is synced.
+    pub fn append(&mut self, entries: &[JournalEntry]) -> std::io::Result<()> {
+        let mut buf = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut buf, entry)?;
+            buf.push(b'\n');
+        }
+
+        self.file.write_all(&buf)
+    }
+
+    /// Syncs the appended entries to disk.
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Removes all entries, called once they've been committed to the index.
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_data()
+    }
+}
+
+/// Tracks writes that have been made to the index writer but not committed.
+pub struct WriteBatch {
+    config: BatchConfig,
+    /// Only on-disk indices keep a journal
+    journal: Option<Journal>,
+    num_pending: usize,
+    oldest_pending: Option<Instant>,
+    /// When the journal was last synced to disk
+    last_sync: Instant,
+    /// Copy of the writes recorded while a schema migration is running
+    tap: Option<Vec<JournalEntry>>,
+}
+
+impl WriteBatch {
+    pub fn new(config: BatchConfig, journal: Option<Journal>) -> Self {
+        Self {
+            config,
+            journal,
+            num_pending: 0,
+            oldest_pending: None,
+            last_sync: Instant::now(),
+            tap: None,
+        }
+    }
+
+    pub fn set_config(&mut self, config: BatchConfig) {
+        self.config = config;
+    }
+
+    pub fn num_pending(&self) -> usize {
+        self.num_pending
+    }
+
+    /// Records writes that are about to be made to the index writer.
+    pub fn record(&mut self, entries: Vec<JournalEntry>) -> std::io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(journal) = &mut self.journal {
+            journal.append(&entries)?;
+            // Group commit, writes made soon after a sync are synced w/ the next
+            // ones or made durable by the commit on the next commit tick.
+            if self.last_sync.elapsed() >= self.config.max_sync_delay {
+                journal.sync()?;
+                self.last_sync = Instant::now();
+            }
+        }
+
+        if let Some(tap) = &mut self.tap {
+            tap.extend(entries.iter().cloned());
+        }
+
+        self.num_pending += entries.len();
+        self.oldest_pending.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    /// Whether the pending writes have hit the size or time threshold.
+    pub fn should_commit(&self) -> bool {
+        self.num_pending >= self.config.max_pending
+            || self
+                .oldest_pending
+                .map(|since| since.elapsed() >= self.config.max_delay)
+                .unwrap_or_default()
+    }
+
+    /// Starts keeping a copy of every write recorded from now on.
+    pub fn start_tap(&mut self) {
+        self.tap = Some(Vec::new());
+    }
+
+    /// Takes the writes recorded since the tap was started or last drained.
+    pub fn drain_tap(&mut self) -> Vec<JournalEntry> {
+        self.tap.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn stop_tap(&mut self) {
+        self.tap = None;
+    }
+
+    /// Replaces the journal, e.g. once the index has moved.
+    pub fn set_journal(&mut self, journal: Option<Journal>) {
+        self.journal = journal;
+    }
+
+    /// Resets the batch after the pending writes have been committed.
+    pub fn committed(&mut self) -> std::io::Result<()> {
+        self.num_pending = 0;
+        self.oldest_pending = None;
+        if let Some(journal) = &mut self.journal {
+            journal.clear()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{BatchConfig, Journal, JournalEntry, WriteBatch};
+
+    #[test]
+    fn test_journal_replay() {
+        let dir = std::env::temp_dir().join(format!("spyglass-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Unable to create dir");
+
+        let entries = vec![
+            JournalEntry::Upsert {
+                doc: "{\"id\":[\"a\"]}".into(),
+            },
+            JournalEntry::Delete { doc_id: "b".into() },
+        ];
+
+        let (mut journal, replay) = Journal::open(&dir).expect("Unable to open journal");
+        assert!(replay.is_empty());
+        journal.append(&entries).expect("Unable to append");
+        drop(journal);
+
+        // Simulate a write that was cut short
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(super::JOURNAL_FILE))
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b"{\"op\":\"del"))
+            .expect("Unable to write");
+
+        let (mut journal, replay) = Journal::open(&dir).expect("Unable to open journal");
+        assert_eq!(replay, entries);
+        // New entries are not appended to the partial one
+        journal.append(&entries[1..]).expect("Unable to append");
+        let (mut journal, replay) = Journal::open(&dir).expect("Unable to open journal");
+        assert_eq!(replay.len(), 3);
+
+        journal.clear().expect("Unable to clear");
+        let (_, replay) = Journal::open(&dir).expect("Unable to open journal");
+        assert!(replay.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_commit_thresholds() {
+        let config = BatchConfig {
+            max_pending: 2,
+            max_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        let mut batch = WriteBatch::new(config, None);
+        assert!(!batch.should_commit());
+
+        let delete = JournalEntry::Delete { doc_id: "a".into() };
+        batch.record(vec![delete.clone()]).unwrap();
+        assert!(!batch.should_commit());
+        batch.record(vec![delete.clone()]).unwrap();
+        assert!(batch.should_commit());
+
+        batch.committed().unwrap();
+        assert_eq!(batch.num_pending(), 0);
+        assert!(!batch.should_commit());
+
+        let config = BatchConfig {
+            max_pending: 100,
+            max_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut batch = WriteBatch::new(config, None);
+        batch.record(vec![delete.clone()]).unwrap();
+        assert!(batch.should_commit());
+
+        // Only writes made while the tap is running are copied
+        batch.start_tap();
+        batch.record(vec![delete.clone()]).unwrap();
+        assert_eq!(batch.drain_tap(), vec![delete.clone()]);
+        assert!(batch.drain_tap().is_empty());
+        batch.stop_tap();
+        batch.record(vec![delete]).unwrap();
+        assert!(batch.drain_tap().is_empty());
+    }
+}
@@ -0,0 +1,1132 @@
+use std::fmt::{Debug, Error, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{TimeZone, Utc};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Map, Value as JsonValue};
+use tantivy::schema::*;
+use url::Url;
+
+use crate::facets::FacetCounts;
+use crate::query_parser::{ParsedQuery, QueryTerm};
+use crate::schema::{self, DocFields, SearchDocument};
+use crate::{
+    field_to_string, Boost, DateField, IndexBackend, IndexClient, QueryBoost, RetrievedDocument,
+    Score, SearchError, SearchQueryResult, SearchTrait, SearcherResult, WriteTrait,
+};
+
+/// Client for an Elasticsearch compatible REST API (Elasticsearch, OpenSearch,
+/// Quickwit, etc.). The endpoint is expected to point at the index itself, e.g.
+/// `http://localhost:9200/spyglass`.
+#[derive(Clone)]
+pub struct HttpSearcher {
+    pub client: Client,
+    pub endpoint: Url,
+    /// Number of documents in the index as of the last refresh, so searches
+    /// don't need an extra round trip to count them.
+    doc_count: Arc<AtomicU64>,
+}
+
+impl Debug for HttpSearcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_struct("HttpSearcher")
+            .field("endpoint", &self.endpoint.as_str())
+            .finish()
+    }
+}
+
+/// Document as it is stored in the remote index.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RemoteDocument {
+    id: String,
+    domain: String,
+    title: String,
+    description: String,
+    content: String,
+    url: String,
+    tags: Vec<u64>,
+    /// Dates are stored as milliseconds since the epoch
+    published: Option<i64>,
+    lastmodified: Option<i64>,
+    /// `key: value` metadata entries
+    metadata: Vec<String>,
+    /// Symbols defined in source code
+    symbols: Vec<String>,
+    /// `start_ms end_ms text` transcript segments
+    segments: Vec<String>,
+    /// Byte offsets of the pages of paged documents
+    page_offsets: Vec<u64>,
+    /// ISO 639-1 code of the detected language
+    lang: Option<String>,
+}
+
+impl From<RemoteDocument> for RetrievedDocument {
+    fn from(doc: RemoteDocument) -> Self {
+        let to_date = |millis: i64| Utc.timestamp_millis_opt(millis).single();
+        RetrievedDocument {
+            doc_id: doc.id,
+            domain: doc.domain,
+            title: doc.title,
+            description: doc.description,
+            content: doc.content,
+            url: doc.url,
+            tags: doc.tags,
+            published: doc.published.and_then(to_date),
+            last_modified: doc.lastmodified.and_then(to_date),
+            metadata: doc
+                .metadata
+                .iter()
+                .filter_map(|entry| schema::parse_metadata_entry(entry))
+                .collect(),
+            symbols: doc.symbols,
+            segments: doc
+                .segments
+                .iter()
+                .filter_map(|entry| schema::parse_segment_entry(entry))
+                .collect(),
+            page_offsets: doc.page_offsets,
+            lang: doc.lang,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetResponse {
+    #[serde(default)]
+    found: bool,
+    #[serde(rename = "_source")]
+    source: Option<RemoteDocument>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    #[serde(rename = "_score")]
+    score: Option<Score>,
+    #[serde(rename = "_source")]
+    source: RemoteDocument,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TotalHits {
+    value: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHits {
+    #[serde(default)]
+    total: TotalHits,
+    hits: Vec<SearchHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    hits: SearchHits,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bucket<K> {
+    key: K,
+    doc_count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Buckets<K> {
+    buckets: Vec<Bucket<K>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FacetAggregations {
+    tags: Buckets<u64>,
+    domains: Buckets<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FacetResponse {
+    aggregations: FacetAggregations,
+}
+
+impl From<FacetResponse> for FacetCounts {
+    fn from(resp: FacetResponse) -> Self {
+        FacetCounts {
+            tags: resp
+                .aggregations
+                .tags
+                .buckets
+                .into_iter()
+                .map(|bucket| (bucket.key, bucket.doc_count))
+                .collect(),
+            domains: resp
+                .aggregations
+                .domains
+                .buckets
+                .into_iter()
+                .map(|bucket| (bucket.key, bucket.doc_count))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CountResponse {
+    count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkResponse {
+    #[serde(default)]
+    errors: bool,
+}
+
+#[async_trait::async_trait]
+impl WriteTrait for HttpSearcher {
+    async fn delete_many_by_id(&self, doc_ids: &[String]) -> SearcherResult<usize> {
+        if doc_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut body = String::new();
+        for doc_id in doc_ids {
+            body.push_str(&json!({ "delete": { "_id": doc_id } }).to_string());
+            body.push('\n');
+        }
+
+        // Deletes are immediately visible, mirroring the local index which
+        // commits after each delete.
+        self.bulk(body, true).await?;
+        self.refresh_doc_count().await;
+        Ok(doc_ids.len())
+    }
+
+    async fn upsert_many(&self, updates: &[Document]) -> SearcherResult<Vec<String>> {
+        if updates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let fields = DocFields::as_fields();
+        let mut upserted = Vec::new();
+        let mut body = String::new();
+        for doc in updates {
+            let doc_id = field_to_string(doc, fields.id);
+            body.push_str(&json!({ "index": { "_id": doc_id } }).to_string());
+            body.push('\n');
+            body.push_str(&document_to_json(doc).to_string());
+            body.push('\n');
+            upserted.push(doc_id);
+        }
+
+        self.bulk(body, false).await?;
+        Ok(upserted)
+    }
+}
+
+#[async_trait::async_trait]
+impl SearchTrait for HttpSearcher {
+    /// Get a single document by id
+    async fn get(&self, doc_id: &str) -> Option<RetrievedDocument> {
+        let url = self.api_url(&format!("_doc/{doc_id}"));
+        let resp = match self.client.get(url).send().await {
+            Ok(resp) => resp,
+            Err(err) => {
+                log::warn!("Unable to get doc {doc_id} from remote index: {err}");
+                return None;
+            }
+        };
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return None;
+        }
+
+        match resp.json::<GetResponse>().await {
+            Ok(GetResponse {
+                found: true,
+                source: Some(doc),
+            }) => Some(doc.into()),
+            Ok(_) => None,
+            Err(err) => {
+                log::warn!("Unable to parse remote doc {doc_id}: {err}");
+                None
+            }
+        }
+    }
+
+    /// Runs a search against the remote index
+    async fn search_with_query(
+        &self,
+        query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+        offset: usize,
+        num_results: usize,
+    ) -> SearchQueryResult {
+        let start_timer = Instant::now();
+        let term_counts = query.text.split_whitespace().count();
+        let body = build_remote_query(query, filters, boosts, offset, num_results);
+
+        let (total_hits, documents) = match self.post_json("_search", &body).await {
+            Ok(resp) => match serde_json::from_value::<SearchResponse>(resp) {
+                Ok(resp) => (
+                    resp.hits.total.value,
+                    resp.hits
+                        .hits
+                        .into_iter()
+                        .map(|hit| (hit.score.unwrap_or_default(), hit.source.into()))
+                        // Filter out negative scores
+                        .filter(|(score, _)| *score > 0.0)
+                        .collect(),
+                ),
+                Err(err) => {
+                    log::warn!("Unable to parse remote search response: {err}");
+                    (0, Vec::new())
+                }
+            },
+            Err(err) => {
+                log::warn!("Unable to search remote index: {err}");
+                (0, Vec::new())
+            }
+        };
+
+        let num_docs = self.doc_count.load(Ordering::Relaxed);
+
+        log::debug!(
+            "query `{:?}` returned {} results from {} docs in {} ms",
+            query,
+            documents.len(),
+            num_docs,
+            Instant::now().duration_since(start_timer).as_millis()
+        );
+
+        SearchQueryResult {
+            wall_time_ms: Instant::now().duration_since(start_timer).as_millis(),
+            num_docs,
+            total_hits,
+            term_counts,
+            documents,
+        }
+    }
+
+    async fn facet_counts(
+        &self,
+        query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+    ) -> SearcherResult<FacetCounts> {
+        let body = build_facet_query(query, filters, boosts);
+        let resp = self.post_json("_search", &body).await?;
+        let resp = serde_json::from_value::<FacetResponse>(resp)
+            .map_err(|err| SearchError::Other(err.into()))?;
+        Ok(resp.into())
+    }
+}
+
+#[async_trait::async_trait]
+impl IndexClient for HttpSearcher {
+    async fn save(&self) -> SearcherResult<()> {
+        HttpSearcher::save(self).await
+    }
+
+    async fn num_docs(&self) -> SearcherResult<u64> {
+        HttpSearcher::num_docs(self).await
+    }
+
+    async fn search_by_query(
+        &self,
+        urls: Option<Vec<String>>,
+        ids: Option<Vec<String>>,
+        has_tags: &[u64],
+        exclude_tags: &[u64],
+    ) -> Vec<(Score, RetrievedDocument)> {
+        let body = build_document_query(
+            &urls.unwrap_or_default(),
+            &ids.unwrap_or_default(),
+            has_tags,
+            exclude_tags,
+        );
+
+        match self.post_json("_search", &body).await {
+            Ok(resp) => match serde_json::from_value::<SearchResponse>(resp) {
+                Ok(resp) => resp
+                    .hits
+                    .hits
+                    .into_iter()
+                    .map(|hit| (1.0, hit.source.into()))
+                    .collect(),
+                Err(err) => {
+                    log::warn!("Unable to parse remote search response: {err}");
+                    Vec::new()
+                }
+            },
+            Err(err) => {
+                log::warn!("Unable to search remote index: {err}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl HttpSearcher {
+    /// Constructs a new HttpSearcher pointed at the remote index @ `endpoint`
+    pub fn with_endpoint(endpoint: &Url) -> Self {
+        let client = Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(3))
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .expect("Unable to create reqwest client");
+
+        // Make sure joins are relative to the index path rather than replacing
+        // the last segment of it.
+        let mut endpoint = endpoint.clone();
+        if !endpoint.path().ends_with('/') {
+            endpoint.set_path(&format!("{}/", endpoint.path()));
+        }
+
+        HttpSearcher {
+            client,
+            endpoint,
+            doc_count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Constructs a new HttpSearcher from an `IndexBackend::Http` backend.
+    pub fn with_index(backend: &IndexBackend) -> SearcherResult<Self> {
+        match backend {
+            IndexBackend::Http(endpoint) => Ok(Self::with_endpoint(endpoint)),
+            other => Err(SearchError::UnsupportedBackend(format!("{other:?}"))),
+        }
+    }
+
+    /// Makes any pending changes visible to searches.
+    pub async fn save(&self) -> SearcherResult<()> {
+        let resp = self.client.post(self.api_url("_refresh")).send().await?;
+        resp.error_for_status()?;
+        self.refresh_doc_count().await;
+        Ok(())
+    }
+
+    /// Total number of documents in the remote index.
+    pub async fn num_docs(&self) -> SearcherResult<u64> {
+        let resp = self.client.get(self.api_url("_count")).send().await?;
+        let count = resp.error_for_status()?.json::<CountResponse>().await?;
+        Ok(count.count)
+    }
+
+    /// Updates the document count reported w/ search results.
+    async fn refresh_doc_count(&self) {
+        match self.num_docs().await {
+            Ok(count) => self.doc_count.store(count, Ordering::Relaxed),
+            Err(err) => log::warn!("Unable to count remote docs: {err}"),
+        }
+    }
+
+    fn api_url(&self, path: &str) -> Url {
+        self.endpoint
+            .join(path)
+            .expect("Unable to build remote index URL")
+    }
+
+    async fn post_json(&self, path: &str, body: &JsonValue) -> SearcherResult<JsonValue> {
+        let resp = self
+            .client
+            .post(self.api_url(path))
+            .json(body)
+            .send()
+            .await?;
+        Ok(resp.error_for_status()?.json::<JsonValue>().await?)
+    }
+
+    async fn bulk(&self, body: String, refresh: bool) -> SearcherResult<()> {
+        let mut url = self.api_url("_bulk");
+        if refresh {
+            url.set_query(Some("refresh=true"));
+        }
+
+        let resp = self
+            .client
+            .post(url)
+            .header("content-type", "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?;
+
+        let resp = resp.error_for_status()?.json::<BulkResponse>().await?;
+        if resp.errors {
+            return Err(SearchError::Other(anyhow::anyhow!(
+                "Remote index reported errors during bulk request"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a tantivy document into the JSON representation used by the remote
+/// index. Fields are keyed by their name in the schema.
+pub fn document_to_json(doc: &Document) -> JsonValue {
+    let schema = DocFields::as_schema();
+    let fields = DocFields::as_fields();
+
+    let mut obj = Map::new();
+    // Always send multi-valued fields as an array so the remote mapping stays
+    // consistent.
+    obj.insert("tags".into(), json!([]));
+    obj.insert("metadata".into(), json!([]));
+    obj.insert("symbols".into(), json!([]));
+    obj.insert("segments".into(), json!([]));
+    obj.insert("page_offsets".into(), json!([]));
+    for field_value in doc.field_values() {
+        let field = field_value.field();
+        // Index-only fields (e.g. content analyzed for its language) are
+        // derived from stored ones, the remote index does its own analysis.
+        if !schema.get_field_entry(field).is_stored() {
+            continue;
+        }
+
+        let name = schema.get_field_name(field).to_string();
+        let value = field_value.value();
+
+        let json_value = if let Some(text) = value.as_text() {
+            json!(text)
+        } else if let Some(num) = value.as_u64() {
+            json!(num)
+        } else if let Some(date) = value.as_date() {
+            json!(date.into_timestamp_millis())
+        } else {
+            continue;
+        };
+
+        let is_multi_valued = [
+            fields.tags,
+            fields.metadata,
+            fields.symbols,
+            fields.segments,
+            fields.page_offsets,
+        ]
+        .contains(&field);
+        if is_multi_valued {
+            if let Some(JsonValue::Array(values)) = obj.get_mut(&name) {
+                values.push(json_value);
+            }
+        } else {
+            obj.insert(name, json_value);
+        }
+    }
+
+    JsonValue::Object(obj)
+}
+
+fn boost_to_term(boost: &Boost) -> Option<JsonValue> {
+    match boost {
+        Boost::DocId(doc_id) | Boost::Opened(doc_id) => Some(json!({ "term": { "id": doc_id } })),
+        Boost::Favorite { id, .. } => Some(json!({ "term": { "tags": id } })),
+        Boost::Tag(tag_id) => Some(json!({ "term": { "tags": tag_id } })),
+        Boost::Url(url) => Some(json!({ "term": { "url": url } })),
+        Boost::CustomField { field_name, value } => {
+            let mut term = Map::new();
+            term.insert(field_name.clone(), json!(value));
+            Some(json!({ "term": term }))
+        }
+        Boost::DateRange { field, start, end } => {
+            let mut bounds = Map::new();
+            if let Some(start) = start {
+                bounds.insert("gte".into(), json!(start.timestamp_millis()));
+            }
+            if let Some(end) = end {
+                bounds.insert("lt".into(), json!(end.timestamp_millis()));
+            }
+
+            let mut range = Map::new();
+            range.insert(date_field_name(field).into(), JsonValue::Object(bounds));
+            Some(json!({ "range": range }))
+        }
+        // Scores decay the further a date is from now, halving at the pivot.
+        Boost::Recency(field) => Some(json!({
+            "distance_feature": { "field": date_field_name(field), "pivot": "30d", "origin": "now" }
+        })),
+    }
+}
+
+fn date_field_name(field: &DateField) -> &'static str {
+    match field {
+        DateField::Published => "published",
+        DateField::LastModified => "lastmodified",
+    }
+}
+
+fn with_boost(mut query: JsonValue, boost: f32) -> JsonValue {
+    if let Some((kind, clause)) = query.as_object_mut().and_then(|obj| obj.iter_mut().next()) {
+        let inner = clause
+            .as_object_mut()
+            .and_then(|clause| clause.values_mut().next());
+
+        match (kind.as_str(), inner) {
+            ("term", Some(inner)) => {
+                *inner = json!({ "value": inner.clone(), "boost": boost });
+            }
+            ("range", Some(JsonValue::Object(bounds))) => {
+                bounds.insert("boost".into(), json!(boost));
+            }
+            _ => {
+                if let Some(clause) = clause.as_object_mut() {
+                    clause.insert("boost".into(), json!(boost));
+                }
+            }
+        }
+    }
+
+    query
+}
+
+/// Builds a query DSL request that mirrors the ranking done by `build_query`
+/// for the local index.
+pub fn build_remote_query(
+    query: &ParsedQuery,
+    filters: &[QueryBoost],
+    boosts: &[QueryBoost],
+    offset: usize,
+    num_results: usize,
+) -> JsonValue {
+    let query_string = query.text.as_str();
+    let num_terms = query_string.split_whitespace().count();
+
+    let mut should = Vec::new();
+    if num_terms > 0 {
+        // single term matches, weight symbol & title matches a little more
+        should.push(json!({
+            "multi_match": {
+                "query": query_string,
+                "fields": [
+                    "symbols^3.0",
+                    "title^2.0",
+                    "metadata^1.5",
+                    "description^1.25",
+                    "content^1.0"
+                ],
+                "type": "most_fields"
+            }
+        }));
+    } else if query.is_structured() {
+        // Only field clauses, match everything that passes them
+        should.push(json!({ "match_all": {} }));
+    }
+
+    // Boost exact matches to the full query string, relative to the number of
+    // segments in a continuous phrase.
+    if num_terms > 1 {
+        should.push(json!({
+            "match_phrase": {
+                "content": { "query": query_string, "slop": 3, "boost": 1.5 * num_terms as f32 }
+            }
+        }));
+        should.push(json!({
+            "match_phrase": {
+                "title": { "query": query_string, "slop": 3, "boost": 2.5 * num_terms as f32 }
+            }
+        }));
+    }
+
+    // Typos & the last word still being typed, scored well below exact matches
+    if num_terms > 0 {
+        should.push(json!({
+            "multi_match": {
+                "query": query_string,
+                "fields": ["title^2.0", "content^1.0"],
+                "type": "bool_prefix",
+                "fuzziness": "AUTO",
+                "boost": 0.25
+            }
+        }));
+    }
+
+    let mut filter = Vec::new();
+    let mut optional = Vec::new();
+    for boost in boosts {
+        if let Some(term) = boost_to_term(&boost.field) {
+            match boost.field {
+                // Only considered in filters
+                Boost::Favorite { .. } => {}
+                // Only rank docs that already match, so they're kept out of the
+                // required terms
+                Boost::Recency(_) | Boost::Opened(_) => {
+                    optional.push(with_boost(term, boost.value))
+                }
+                _ => should.push(with_boost(term, boost.value)),
+            }
+        }
+    }
+
+    for qf in filters {
+        if let Some(term) = boost_to_term(&qf.field) {
+            match qf.field {
+                Boost::Favorite { required: true, .. } => filter.push(term),
+                Boost::Favorite { .. } => optional.push(with_boost(term, 3.0)),
+                // Only considered in boosts
+                Boost::Recency(_) | Boost::Opened(_) => {}
+                _ => filter.push(term),
+            }
+        }
+    }
+
+    // Must hit at least one of the terms
+    let mut must = vec![json!({ "bool": { "should": should, "minimum_should_match": 1 } })];
+    for group in &query.required {
+        let clauses = group.iter().filter_map(term_to_json).collect::<Vec<_>>();
+        if !clauses.is_empty() {
+            must.push(json!({ "bool": { "should": clauses, "minimum_should_match": 1 } }));
+        }
+    }
+
+    let must_not = query
+        .excluded
+        .iter()
+        .filter_map(term_to_json)
+        .collect::<Vec<_>>();
+
+    json!({
+        "from": offset,
+        "size": num_results,
+        // Count every match rather than stopping at the default of 10k.
+        "track_total_hits": true,
+        "query": {
+            "bool": {
+                "must": must,
+                "must_not": must_not,
+                "filter": filter,
+                "should": optional
+            }
+        }
+    })
+}
+
+/// Max number of documents returned when looking up documents by url, id or tag,
+/// the most the remote API returns in a single page by default.
+const DOCUMENT_QUERY_LIMIT: usize = 10_000;
+
+/// Builds a query DSL request that mirrors `build_document_query` for the
+/// local index.
+pub fn build_document_query(
+    urls: &[String],
+    ids: &[String],
+    tags: &[u64],
+    exclude_tags: &[u64],
+) -> JsonValue {
+    let mut filter = Vec::new();
+    if !urls.is_empty() {
+        filter.push(json!({ "terms": { "url": urls } }));
+    }
+    if !ids.is_empty() {
+        filter.push(json!({ "terms": { "id": ids } }));
+    }
+    for tag_id in tags {
+        filter.push(json!({ "term": { "tags": tag_id } }));
+    }
+
+    let must_not = exclude_tags
+        .iter()
+        .map(|tag_id| json!({ "term": { "tags": tag_id } }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "size": DOCUMENT_QUERY_LIMIT,
+        "query": {
+            "bool": {
+                "filter": filter,
+                "must_not": must_not
+            }
+        }
+    })
+}
+
+/// Max number of buckets returned for each facet
+const FACET_BUCKET_LIMIT: usize = 100;
+
+/// Builds a query DSL request that only returns the tag & domain counts for
+/// documents matching the query.
+pub fn build_facet_query(
+    query: &ParsedQuery,
+    filters: &[QueryBoost],
+    boosts: &[QueryBoost],
+) -> JsonValue {
+    let mut body = build_remote_query(query, filters, boosts, 0, 0);
+    if let Some(body) = body.as_object_mut() {
+        body.insert(
+            "aggs".into(),
+            json!({
+                "tags": { "terms": { "field": "tags", "size": FACET_BUCKET_LIMIT } },
+                "domains": { "terms": { "field": "domain", "size": FACET_BUCKET_LIMIT } }
+            }),
+        );
+    }
+
+    body
+}
+
+/// Converts a clause from the query language into the query DSL.
+fn term_to_json(term: &QueryTerm) -> Option<JsonValue> {
+    match term {
+        QueryTerm::Text(text) => Some(json!({
+            "multi_match": {
+                "query": text,
+                "fields": ["title", "description", "metadata", "symbols", "content"]
+            }
+        })),
+        QueryTerm::Phrase(phrase) => Some(json!({
+            "multi_match": {
+                "query": phrase,
+                "fields": ["title", "description", "content"],
+                "type": "phrase"
+            }
+        })),
+        QueryTerm::Title(title) => Some(json!({ "match_phrase": { "title": title } })),
+        QueryTerm::Domain(domain) => Some(json!({ "term": { "domain": domain } })),
+        QueryTerm::Url(prefix) => Some(json!({ "prefix": { "url": prefix } })),
+        QueryTerm::Lang(code) => Some(json!({ "term": { "lang": code } })),
+        QueryTerm::TagId(tag_id) => Some(json!({ "term": { "tags": tag_id } })),
+        // Tag doesn't exist, so nothing will match it.
+        QueryTerm::Tag { .. } => Some(json!({ "match_none": {} })),
+        QueryTerm::Date { field, start, end } => boost_to_term(&Boost::DateRange {
+            field: *field,
+            start: *start,
+            end: *end,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value as JsonValue};
+    use url::Url;
+    use warp::Filter;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{
+        build_document_query, build_facet_query, build_remote_query, FacetResponse, HttpSearcher,
+    };
+    use crate::facets::FacetCounts;
+    use crate::query_parser::parse_query;
+    use crate::schema::{DocumentUpdate, ToDocument};
+    use crate::{Boost, DateField, QueryBoost, SearchTrait, WriteTrait};
+
+    type MockStore = Arc<Mutex<HashMap<String, JsonValue>>>;
+
+    fn _matches_filter(doc: &JsonValue, filter: &JsonValue) -> bool {
+        let (field, expected) = match filter["term"].as_object().and_then(|x| x.iter().next()) {
+            Some(pair) => pair,
+            None => return true,
+        };
+
+        match &doc[field] {
+            JsonValue::Array(vals) => vals.contains(expected),
+            val => val == expected,
+        }
+    }
+
+    /// Spins up a bare-bones Elasticsearch look-alike to test against.
+    async fn _mock_server() -> SocketAddr {
+        let store: MockStore = Arc::new(Mutex::new(HashMap::new()));
+        let with_store = warp::any().map(move || store.clone());
+
+        let bulk = warp::post()
+            .and(warp::path!("spyglass" / "_bulk"))
+            .and(warp::body::bytes())
+            .and(with_store.clone())
+            .map(|body: bytes::Bytes, store: MockStore| {
+                let body = String::from_utf8_lossy(&body).to_string();
+                let mut lines = body.lines();
+                let mut store = store.lock().unwrap();
+                while let Some(action) = lines.next() {
+                    let action: JsonValue = serde_json::from_str(action).unwrap();
+                    if let Some(id) = action["index"]["_id"].as_str() {
+                        let doc = serde_json::from_str(lines.next().unwrap()).unwrap();
+                        store.insert(id.to_string(), doc);
+                    } else if let Some(id) = action["delete"]["_id"].as_str() {
+                        store.remove(id);
+                    }
+                }
+                warp::reply::json(&json!({ "errors": false, "items": [] }))
+            });
+
+        let get = warp::get()
+            .and(warp::path!("spyglass" / "_doc" / String))
+            .and(with_store.clone())
+            .map(|id: String, store: MockStore| {
+                let store = store.lock().unwrap();
+                match store.get(&id) {
+                    Some(doc) => warp::reply::with_status(
+                        warp::reply::json(&json!({ "_id": id, "found": true, "_source": doc })),
+                        warp::http::StatusCode::OK,
+                    ),
+                    None => warp::reply::with_status(
+                        warp::reply::json(&json!({ "_id": id, "found": false })),
+                        warp::http::StatusCode::NOT_FOUND,
+                    ),
+                }
+            });
+
+        let search = warp::post()
+            .and(warp::path!("spyglass" / "_search"))
+            .and(warp::body::json())
+            .and(with_store.clone())
+            .map(|body: JsonValue, store: MockStore| {
+                let bool_query = &body["query"]["bool"];
+                let query = bool_query["must"][0]["bool"]["should"][0]["multi_match"]["query"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_lowercase();
+                let filters = bool_query["filter"].as_array().cloned().unwrap_or_default();
+
+                let store = store.lock().unwrap();
+                let hits = store
+                    .values()
+                    .filter(|doc| filters.iter().all(|f| _matches_filter(doc, f)))
+                    .filter_map(|doc| {
+                        let text = format!("{} {}", doc["title"], doc["content"]).to_lowercase();
+                        let score = query
+                            .split_whitespace()
+                            .filter(|term| text.contains(term))
+                            .count();
+                        if score > 0 {
+                            Some(json!({ "_id": doc["id"], "_score": score, "_source": doc }))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                let total = hits.len();
+                let from = body["from"].as_u64().unwrap_or_default() as usize;
+                let size = body["size"].as_u64().unwrap_or(10) as usize;
+                let hits = hits.into_iter().skip(from).take(size).collect::<Vec<_>>();
+                warp::reply::json(&json!({ "hits": { "total": { "value": total }, "hits": hits } }))
+            });
+
+        let count = warp::get()
+            .and(warp::path!("spyglass" / "_count"))
+            .and(with_store)
+            .map(|store: MockStore| {
+                warp::reply::json(&json!({ "count": store.lock().unwrap().len() }))
+            });
+
+        let refresh = warp::post()
+            .and(warp::path!("spyglass" / "_refresh"))
+            .map(|| warp::reply::json(&json!({})));
+
+        let (addr, server) = warp::serve(bulk.or(get).or(search).or(count).or(refresh))
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn _build_test_index(searcher: &HttpSearcher) {
+        let docs = vec![
+            DocumentUpdate {
+                doc_id: None,
+                title: "Of Mice and Men",
+                domain: "example.com",
+                url: "https://example.com/mice_and_men",
+                content: "A few miles south of Soledad, the Salinas River drops in close to the hillside",
+                description: None,
+                metadata: &Default::default(),
+                symbols: &[],
+                segments: &[],
+                page_offsets: &[],
+                tags: &[1_i64],
+                published_at: None,
+                last_modified: None,
+            }
+            .to_document(),
+            DocumentUpdate {
+                doc_id: None,
+                title: "Of Mice and Men",
+                domain: "en.wikipedia.org",
+                url: "https://en.wikipedia.org/mice_and_men",
+                content: "A few miles south of Soledad, the Salinas River drops in close to the hillside",
+                description: None,
+                metadata: &Default::default(),
+                symbols: &[],
+                segments: &[],
+                page_offsets: &[],
+                tags: &[2_i64],
+                published_at: None,
+                last_modified: None,
+            }
+            .to_document(),
+        ];
+
+        searcher
+            .upsert_many(&docs)
+            .await
+            .expect("Unable to add docs");
+        searcher.save().await.expect("Unable to refresh index");
+    }
+
+    #[test]
+    fn test_build_remote_query() {
+        let filters = vec![
+            QueryBoost::new(Boost::Tag(2_u64)),
+            QueryBoost::new(Boost::Favorite {
+                id: 3,
+                required: false,
+            }),
+        ];
+        let boosts = vec![QueryBoost::new(Boost::Url("https://example.com".into()))];
+        let query = build_remote_query(&parse_query("salinas river"), &filters, &boosts, 10, 5);
+
+        assert_eq!(query["from"], 10);
+        assert_eq!(query["size"], 5);
+        let bool_query = &query["query"]["bool"];
+        assert_eq!(bool_query["filter"], json!([{ "term": { "tags": 2 } }]));
+        assert_eq!(
+            bool_query["should"],
+            json!([{ "term": { "tags": { "value": 3, "boost": 3.0 } } }])
+        );
+
+        // term match + 2 phrase matches + fuzzy/prefix match + url boost
+        let should = bool_query["must"][0]["bool"]["should"].as_array().unwrap();
+        assert_eq!(should.len(), 5);
+        assert_eq!(should[3]["multi_match"]["type"], "bool_prefix");
+        assert_eq!(
+            should[4],
+            json!({ "term": { "url": { "value": "https://example.com", "boost": 3.0 } } })
+        );
+    }
+
+    #[test]
+    fn test_build_remote_date_query() {
+        let start = Utc.timestamp_millis_opt(1_000).unwrap();
+        let filters = vec![QueryBoost::new(Boost::DateRange {
+            field: DateField::LastModified,
+            start: Some(start),
+            end: None,
+        })];
+        let boosts = vec![QueryBoost::new(Boost::Recency(DateField::Published))];
+        let query = build_remote_query(&parse_query("salinas"), &filters, &boosts, 0, 5);
+
+        let bool_query = &query["query"]["bool"];
+        assert_eq!(
+            bool_query["filter"],
+            json!([{ "range": { "lastmodified": { "gte": 1_000 } } }])
+        );
+        assert_eq!(
+            bool_query["should"],
+            json!([{
+                "distance_feature": {
+                    "field": "published",
+                    "pivot": "30d",
+                    "origin": "now",
+                    "boost": 1.0
+                }
+            }])
+        );
+    }
+
+    #[test]
+    fn test_build_remote_structured_query() {
+        let mut query = parse_query("lens:rust OR domain:docs.rs -tag:type=issue tokio");
+        let mut tag_ids = HashMap::new();
+        tag_ids.insert(("lens".to_string(), "rust".to_string()), 1);
+        query.resolve_tags(&tag_ids);
+
+        let query = build_remote_query(&query, &[], &[], 0, 5);
+        let bool_query = &query["query"]["bool"];
+        assert_eq!(
+            bool_query["must"][1],
+            json!({
+                "bool": {
+                    "should": [
+                        { "term": { "tags": 1 } },
+                        { "term": { "domain": "docs.rs" } }
+                    ],
+                    "minimum_should_match": 1
+                }
+            })
+        );
+        // Unknown tags won't match anything
+        assert_eq!(bool_query["must_not"], json!([{ "match_none": {} }]));
+    }
+
+    #[test]
+    fn test_build_document_query() {
+        let query = build_document_query(&["https://example.com".into()], &[], &[1], &[2]);
+        let bool_query = &query["query"]["bool"];
+        assert_eq!(
+            bool_query["filter"],
+            json!([
+                { "terms": { "url": ["https://example.com"] } },
+                { "term": { "tags": 1 } }
+            ])
+        );
+        assert_eq!(bool_query["must_not"], json!([{ "term": { "tags": 2 } }]));
+    }
+
+    #[test]
+    fn test_facet_query() {
+        let query = build_facet_query(&parse_query("salinas"), &[], &[]);
+        assert_eq!(query["size"], 0);
+        assert_eq!(query["aggs"]["tags"]["terms"]["field"], "tags");
+        assert_eq!(query["aggs"]["domains"]["terms"]["field"], "domain");
+
+        let resp: FacetResponse = serde_json::from_value(json!({
+            "hits": { "total": { "value": 3 }, "hits": [] },
+            "aggregations": {
+                "tags": { "buckets": [{ "key": 1, "doc_count": 3 }, { "key": 2, "doc_count": 1 }] },
+                "domains": { "buckets": [{ "key": "example.com", "doc_count": 3 }] }
+            }
+        }))
+        .unwrap();
+
+        let counts: FacetCounts = resp.into();
+        assert_eq!(counts.tags.get(&1), Some(&3));
+        assert_eq!(counts.tags.get(&2), Some(&1));
+        assert_eq!(counts.domains.get("example.com"), Some(&3));
+    }
+
+    #[tokio::test]
+    async fn test_remote_search() {
+        let addr = _mock_server().await;
+        let endpoint = Url::parse(&format!("http://{addr}/spyglass")).unwrap();
+        let searcher = HttpSearcher::with_endpoint(&endpoint);
+        _build_test_index(&searcher).await;
+
+        let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
+        let results = searcher.search("salinas", &filters, &[], 0, 5).await;
+        assert_eq!(results.num_docs, 2);
+        assert_eq!(results.total_hits, 1);
+        assert_eq!(results.documents.len(), 1);
+
+        let (_, doc) = results.documents.first().unwrap();
+        assert_eq!(doc.domain, "en.wikipedia.org");
+        assert_eq!(doc.tags, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_remote_get_and_delete() {
+        let addr = _mock_server().await;
+        let endpoint = Url::parse(&format!("http://{addr}/spyglass")).unwrap();
+        let searcher = HttpSearcher::with_endpoint(&endpoint);
+        _build_test_index(&searcher).await;
+
+        let results = searcher.search("salinas", &[], &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+
+        let page = searcher.search("salinas", &[], &[], 1, 5).await;
+        assert_eq!(page.total_hits, 2);
+        assert_eq!(page.documents.len(), 1);
+
+        let (_, doc) = results.documents.first().unwrap();
+        let doc_id = doc.doc_id.clone();
+        let fetched = searcher.get(&doc_id).await.expect("doc should exist");
+        assert_eq!(fetched.url, doc.url);
+
+        searcher.delete(&doc_id).await.expect("Unable to delete");
+        assert!(searcher.get(&doc_id).await.is_none());
+        assert_eq!(searcher.num_docs().await.unwrap(), 1);
+    }
+}
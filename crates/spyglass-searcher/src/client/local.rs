@@ -1,35 +1,69 @@
+use std::collections::HashMap;
 use std::fmt::{Debug, Error, Formatter};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::time::Instant;
 
-use tantivy::collector::TopDocs;
-use tantivy::directory::error::LockError;
-use tantivy::query::TermQuery;
+use tantivy::collector::{Count, DocSetCollector, TopDocs};
+use tantivy::directory::error::{LockError, OpenReadError};
+use tantivy::query::{AllQuery, TermQuery};
 use tantivy::{schema::*, TantivyError};
-use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy};
+use tantivy::{Directory, Index, IndexReader, IndexWriter, ReloadPolicy};
 use uuid::Uuid;
 
-use crate::query::{build_document_query, build_query, terms_for_field, QueryOptions};
+use crate::batch::{BatchConfig, Journal, JournalEntry, WriteBatch};
+use crate::facets::{FacetCollector, FacetCounts};
+use crate::query::{
+    authority_bucket, build_document_query, build_query, terms_for_field, QueryOptions,
+};
+use crate::query_parser::ParsedQuery;
+use crate::ranking::{fuse_results, FusionStrategy};
 use crate::schema::{self, DocFields, SearchDocument};
+use crate::schema_migration::convert_document;
+use crate::similarity::{Embedder, VectorStore};
 use crate::{
-    document_to_struct, field_to_string, Boost, IndexBackend, QueryBoost, RetrievedDocument, Score,
-    SearchError, SearchQueryResult, SearchTrait, SearcherResult, WriteTrait,
+    document_to_struct, field_to_string, Boost, IndexBackend, IndexClient, QueryBoost,
+    RetrievedDocument, Score, SearchError, SearchQueryResult, SearchTrait, SearcherResult,
+    WriteTrait,
 };
 
+/// Max number of nearest neighbours checked against the filters in a semantic search
+const SEMANTIC_CANDIDATE_LIMIT: usize = 1000;
+/// Min number of results pulled from each retriever before fusing them in a hybrid search
+const HYBRID_CANDIDATE_LIMIT: usize = 50;
+/// Number of documents rewritten at a time when updating pageranks
+const PAGERANK_BATCH_SIZE: usize = 500;
+
 pub const SPYGLASS_NS: Uuid = uuid::uuid!("5fdfe40a-de2c-11ed-bfa7-00155deae876");
 
 /// Tantivy searcher client
 #[derive(Clone)]
 pub struct Searcher {
+    /// Index & reader being served, shared by all clones so a migrated index
+    /// can be swapped in.
+    pub(crate) live: Arc<RwLock<LiveIndex>>,
+    pub writer: Option<Arc<Mutex<IndexWriter>>>,
+    /// Embeddings of the document content, used for semantic search.
+    pub vectors: Option<Arc<VectorStore>>,
+    /// Writes made since the last commit
+    pub(crate) batch: Arc<Mutex<WriteBatch>>,
+    /// Schema documents are built with. Newer than the schema of the index being
+    /// served while a schema migration is running.
+    pub(crate) schema: Schema,
+    /// Location of the index on disk, `None` for in memory indices
+    pub(crate) path: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub(crate) struct LiveIndex {
     pub index: Index,
     pub reader: IndexReader,
-    pub writer: Option<Arc<Mutex<IndexWriter>>>,
 }
 
 impl Debug for Searcher {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.debug_struct("Searcher")
-            .field("index", &self.index)
+            .field("index", &self.index())
             .finish()
     }
 }
@@ -39,28 +73,62 @@ impl WriteTrait for Searcher {
     async fn delete_many_by_id(&self, doc_ids: &[String]) -> SearcherResult<usize> {
         {
             let writer = self.lock_writer()?;
+            self.record_writes(
+                doc_ids
+                    .iter()
+                    .map(|doc_id| JournalEntry::Delete {
+                        doc_id: doc_id.clone(),
+                    })
+                    .collect(),
+            )?;
+
             let fields = DocFields::as_fields();
             for doc_id in doc_ids {
                 writer.delete_term(Term::from_field_text(fields.id, doc_id));
             }
         }
 
-        self.save().await?;
+        if let Some(vectors) = &self.vectors {
+            vectors.delete(doc_ids);
+        }
+
+        self.commit_if_needed().await?;
         Ok(doc_ids.len())
     }
 
     async fn upsert_many(&self, updates: &[Document]) -> SearcherResult<Vec<String>> {
         let mut upserted = Vec::new();
         let fields = DocFields::as_fields();
+        let updates = self.with_indexed_pagerank(updates);
 
-        for doc_update in updates {
+        {
             let writer = self.lock_writer()?;
-            writer.add_document(doc_update.clone())?;
+            self.record_writes(
+                updates
+                    .iter()
+                    .map(|doc| JournalEntry::Upsert {
+                        doc: self.schema.to_json(doc),
+                    })
+                    .collect(),
+            )?;
+
+            let index_schema = self.index().schema();
+            for doc_update in &updates {
+                writer.add_document(conform_document(&index_schema, doc_update))?;
+
+                let doc_id = field_to_string(doc_update, fields.id);
+                if let Some(vectors) = &self.vectors {
+                    let content = field_to_string(doc_update, fields.content);
+                    if let Err(err) = vectors.upsert(&doc_id, &content) {
+                        log::warn!("Unable to embed doc {doc_id}: {err}");
+                    }
+                }
 
-            let doc_id = field_to_string(doc_update, fields.id);
-            upserted.push(doc_id.clone());
+                upserted.push(doc_id.clone());
+            }
         }
 
+        self.commit_if_needed().await?;
         Ok(upserted)
     }
 }
@@ -70,7 +138,7 @@ impl SearchTrait for Searcher {
     /// Get a single document by id
     async fn get(&self, doc_id: &str) -> Option<RetrievedDocument> {
         let fields = DocFields::as_fields();
-        let searcher = self.reader.searcher();
+        let searcher = self.reader().searcher();
 
         let query = TermQuery::new(
             Term::from_field_text(fields.id, doc_id),
@@ -95,42 +163,45 @@ impl SearchTrait for Searcher {
     }
 
     /// Runs a search against the index
-    async fn search(
+    async fn search_with_query(
         &self,
-        query_string: &str,
+        parsed_query: &ParsedQuery,
         filters: &[QueryBoost],
         boosts: &[QueryBoost],
+        offset: usize,
         num_results: usize,
     ) -> SearchQueryResult {
         let start_timer = Instant::now();
 
-        let index = &self.index;
-        let reader = &self.reader;
-        let searcher = reader.searcher();
+        let index = &self.index();
+        let searcher = self.reader().searcher();
 
         let (term_counts, query) = build_query(
             index,
-            query_string,
+            parsed_query,
             filters,
             boosts,
             QueryOptions::default(),
         );
 
-        let collector = TopDocs::with_limit(num_results);
+        let collector = (
+            TopDocs::with_limit(num_results.max(1)).and_offset(offset),
+            Count,
+        );
 
-        let top_docs = searcher
+        let (top_docs, total_hits) = searcher
             .search(&query, &collector)
             .expect("Unable to execute query");
 
         log::debug!(
-            "query `{}` returned {} results from {} docs in {} ms",
-            query_string,
+            "query `{:?}` returned {} results from {} docs in {} ms",
+            parsed_query,
             top_docs.len(),
             searcher.num_docs(),
             Instant::now().duration_since(start_timer).as_millis()
         );
 
-        let doc_reader = self.reader.searcher();
+        let doc_reader = self.reader().searcher();
         let docs = top_docs
             .into_iter()
             // Filter out negative scores
@@ -147,10 +218,59 @@ impl SearchTrait for Searcher {
         SearchQueryResult {
             wall_time_ms: Instant::now().duration_since(start_timer).as_millis(),
             num_docs: searcher.num_docs(),
+            total_hits,
             term_counts,
             documents: docs,
         }
     }
+
+    async fn facet_counts(
+        &self,
+        parsed_query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+    ) -> SearcherResult<FacetCounts> {
+        let (_, query) = build_query(
+            &self.index(),
+            parsed_query,
+            filters,
+            boosts,
+            QueryOptions::default(),
+        );
+
+        let searcher = self.reader().searcher();
+        let collector = FacetCollector::new(DocFields::as_fields());
+        Ok(searcher.search(&query, &collector)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl IndexClient for Searcher {
+    async fn save(&self) -> SearcherResult<()> {
+        Searcher::save(self).await
+    }
+
+    async fn num_docs(&self) -> SearcherResult<u64> {
+        Ok(self.reader().searcher().num_docs())
+    }
+
+    async fn search_by_query(
+        &self,
+        urls: Option<Vec<String>>,
+        ids: Option<Vec<String>>,
+        has_tags: &[u64],
+        exclude_tags: &[u64],
+    ) -> Vec<(Score, RetrievedDocument)> {
+        Searcher::search_by_query(self, urls, ids, has_tags, exclude_tags).await
+    }
+
+    fn num_pending_writes(&self) -> usize {
+        Searcher::num_pending_writes(self)
+    }
+
+    fn as_local(&self) -> Option<&Searcher> {
+        Some(self)
+    }
 }
 
 impl Searcher {
@@ -169,22 +289,198 @@ impl Searcher {
         }
     }
 
+    /// Index currently being served
+    pub fn index(&self) -> Index {
+        self.live().index
+    }
+
+    pub fn reader(&self) -> IndexReader {
+        self.live().reader
+    }
+
+    pub(crate) fn live(&self) -> LiveIndex {
+        match self.live.read() {
+            Ok(live) => live.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Commits all pending writes to the index.
     pub async fn save(&self) -> SearcherResult<()> {
         let mut writer = self.lock_writer()?;
+        self.commit_writes(&mut writer)
+    }
+
+    /// Commits the writer & resets the write batch, called w/ the writer locked.
+    pub(crate) fn commit_writes(&self, writer: &mut IndexWriter) -> SearcherResult<()> {
+        writer.commit()?;
+        if let Some(vectors) = &self.vectors {
+            vectors.save()?;
+        }
+        self.lock_batch()?
+            .committed()
+            .map_err(|err| SearchError::Other(err.into()))?;
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the index into `dest`. Pending writes are
+    /// committed first & new writes are blocked until the copy is done.
+    pub async fn snapshot(&self, dest: &Path) -> SearcherResult<()> {
+        let mut writer = self.lock_writer()?;
+        self.commit_writes(&mut writer)?;
+
+        std::fs::create_dir_all(dest).map_err(|err| SearchError::Other(err.into()))?;
+        // Background merges may still swap out segments while copying, so only
+        // the segments listed in these metas are copied & the metas are written
+        // out as they were read.
+        let index = self.index();
+        let metas = index.load_metas()?;
+        let directory = index.directory();
+        for segment in &metas.segments {
+            for file in segment.list_files() {
+                let bytes = match directory.open_read(&file) {
+                    Ok(slice) => slice
+                        .read_bytes()
+                        .map_err(|err| SearchError::Other(err.into()))?,
+                    // Not every segment component is written for every segment
+                    Err(OpenReadError::FileDoesNotExist(_)) => continue,
+                    Err(err) => return Err(TantivyError::from(err).into()),
+                };
+
+                std::fs::write(dest.join(&file), bytes.as_slice())
+                    .map_err(|err| SearchError::Other(err.into()))?;
+            }
+        }
+
+        let meta_json =
+            serde_json::to_vec_pretty(&metas).map_err(|err| SearchError::Other(err.into()))?;
+        std::fs::write(dest.join("meta.json"), meta_json)
+            .map_err(|err| SearchError::Other(err.into()))?;
+
+        if let Some(vectors) = &self.vectors {
+            vectors.save_to(dest)?;
+        }
+
+        Ok(())
+    }
+
+    /// Commits pending writes once the batch size or time threshold is hit,
+    /// returning whether a commit was made.
+    pub async fn commit_if_needed(&self) -> SearcherResult<bool> {
+        if self.lock_batch()?.should_commit() {
+            self.save().await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Number of adds/deletes made since the last commit.
+    pub fn num_pending_writes(&self) -> usize {
+        self.lock_batch()
+            .map(|batch| batch.num_pending())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn lock_batch(&self) -> SearcherResult<MutexGuard<WriteBatch>> {
+        self.batch.lock().map_err(|_| SearchError::WriterLocked)
+    }
+
+    fn record_writes(&self, entries: Vec<JournalEntry>) -> SearcherResult<()> {
+        self.lock_batch()?
+            .record(entries)
+            .map_err(|err| SearchError::Other(err.into()))
+    }
+
+    /// Re-applies writes from a previous run that were never committed.
+    fn replay_journal(
+        schema: &Schema,
+        index: &Index,
+        writer: &mut IndexWriter,
+        vectors: Option<&VectorStore>,
+        entries: Vec<JournalEntry>,
+    ) -> SearcherResult<()> {
+        log::info!("Replaying {} uncommitted index writes", entries.len());
+        let index_schema = index.schema();
+        for entry in entries {
+            // The write may have been committed before the crash, which is fine
+            // since applying a write is idempotent.
+            apply_write(schema, &index_schema, writer, vectors, entry)?;
+        }
+
         writer.commit()?;
+        if let Some(vectors) = vectors {
+            vectors.save()?;
+        }
+        Ok(())
+    }
+
+    /// Embeds every document in the index, for when the stored vectors were
+    /// missing or couldn't be used.
+    fn rebuild_vectors(reader: &IndexReader, vectors: &VectorStore) -> SearcherResult<()> {
+        let searcher = reader.searcher();
+        let schema = searcher.schema();
+        let (id_field, content_field) = match (schema.get_field("id"), schema.get_field("content"))
+        {
+            (Some(id_field), Some(content_field)) => (id_field, content_field),
+            _ => return Ok(()),
+        };
+
+        log::info!("Embedding {} docs for semantic search", searcher.num_docs());
+        for addr in searcher.search(&AllQuery, &DocSetCollector)? {
+            let doc = searcher.doc(addr)?;
+            let doc_id = field_to_string(&doc, id_field);
+            if let Err(err) = vectors.upsert(&doc_id, &field_to_string(&doc, content_field)) {
+                log::warn!("Unable to embed doc {doc_id}: {err}");
+            }
+        }
+
+        vectors.save()?;
         Ok(())
     }
 
-    /// Constructs a new Searcher object w/ the index @ `index_path`
+    /// Overrides when buffered writes are committed.
+    pub fn with_batch_config(self, config: BatchConfig) -> Self {
+        if let Ok(mut batch) = self.batch.lock() {
+            batch.set_config(config);
+        }
+        self
+    }
+
+    /// Swaps out the vector store, e.g. to use a different embedding provider.
+    pub fn with_vector_store(mut self, vectors: VectorStore) -> Self {
+        self.vectors = Some(Arc::new(vectors));
+        self
+    }
+
+    /// Constructs a new Searcher object w/ the index @ `index_path`. Content is
+    /// only embedded for semantic search when an `embedder` is provided.
     pub fn with_index(
         index_path: &IndexBackend,
         schema: Schema,
+        embedder: Option<Arc<dyn Embedder>>,
         readonly: bool,
     ) -> SearcherResult<Self> {
-        let index = match index_path {
-            IndexBackend::LocalPath(path) => schema::initialize_index(schema, path)?,
-            IndexBackend::Memory => schema::initialize_in_memory_index(schema),
-            IndexBackend::Http(_) => unimplemented!(""),
+        let (index, vectors, journal) = match index_path {
+            IndexBackend::LocalPath(path) => (
+                schema::initialize_index(schema.clone(), path)?,
+                embedder.map(|embedder| VectorStore::open(path, embedder)),
+                // Read only searchers never write, leave the journal for the writer.
+                if readonly {
+                    None
+                } else {
+                    Some(Journal::open(path).map_err(|err| SearchError::Other(err.into()))?)
+                },
+            ),
+            IndexBackend::Memory => (
+                schema::initialize_in_memory_index(schema.clone()),
+                embedder.map(VectorStore::in_memory),
+                None,
+            ),
+            // Remote indices are handled by the HttpSearcher client
+            IndexBackend::Http(_) => {
+                return Err(SearchError::UnsupportedBackend(format!("{index_path:?}")))
+            }
         };
 
         // Should only be one writer at a time. This single IndexWriter is already
@@ -201,6 +497,22 @@ impl Searcher {
             })))
         };
 
+        let journal = match (journal, &writer) {
+            (Some((journal, entries)), Some(writer)) => {
+                if !entries.is_empty() {
+                    let mut writer = writer.lock().map_err(|_| SearchError::WriterLocked)?;
+                    Self::replay_journal(&schema, &index, &mut writer, vectors.as_ref(), entries)?;
+                }
+
+                let mut journal = journal;
+                journal
+                    .clear()
+                    .map_err(|err| SearchError::Other(err.into()))?;
+                Some(journal)
+            }
+            _ => None,
+        };
+
         // For a search server you will typically create on reader for the entire
         // lifetime of your program.
         let reader = index
@@ -209,13 +521,248 @@ impl Searcher {
             .try_into()
             .expect("Unable to create reader");
 
+        if index.schema() != schema {
+            log::warn!("Index schema is out of date, the index needs to be migrated");
+        }
+
+        if let Some(vectors) = &vectors {
+            // Read only searchers leave the rebuild to the writer
+            if vectors.needs_rebuild() && !readonly {
+                Self::rebuild_vectors(&reader, vectors)?;
+            }
+        }
+
         Ok(Searcher {
-            index,
-            reader,
+            live: Arc::new(RwLock::new(LiveIndex { index, reader })),
             writer,
+            vectors: vectors.map(Arc::new),
+            batch: Arc::new(Mutex::new(WriteBatch::new(BatchConfig::default(), journal))),
+            schema,
+            path: match index_path {
+                IndexBackend::LocalPath(path) => Some(path.clone()),
+                _ => None,
+            },
+        })
+    }
+
+    /// Runs a nearest neighbour search over the embedded document content. Results
+    /// are filtered after the fact, so `total_hits` only covers the candidates checked.
+    pub async fn semantic_search(
+        &self,
+        query: &str,
+        filters: &[QueryBoost],
+        offset: usize,
+        num_results: usize,
+    ) -> SearcherResult<SearchQueryResult> {
+        let start_timer = Instant::now();
+        let vectors = match &self.vectors {
+            Some(vectors) => vectors,
+            None => {
+                return Err(SearchError::Other(anyhow::anyhow!(
+                    "No vector store configured"
+                )))
+            }
+        };
+
+        let mut documents = Vec::new();
+        let mut total_hits = 0;
+        for (score, doc_id) in vectors.nearest(query, SEMANTIC_CANDIDATE_LIMIT)? {
+            let doc = match self.get(&doc_id).await {
+                Some(doc) => doc,
+                None => continue,
+            };
+
+            if !filters
+                .iter()
+                .all(|filter| doc.matches_filter(&filter.field))
+            {
+                continue;
+            }
+
+            total_hits += 1;
+            if total_hits > offset && documents.len() < num_results {
+                documents.push((score, doc));
+            }
+        }
+
+        Ok(SearchQueryResult {
+            wall_time_ms: Instant::now().duration_since(start_timer).as_millis(),
+            num_docs: self.reader().searcher().num_docs(),
+            total_hits,
+            term_counts: query.split_whitespace().count(),
+            documents,
         })
     }
 
+    /// Runs both a lexical & semantic search and fuses the results into a
+    /// single ranking. For structured queries the vector results are only used
+    /// to re-rank documents that matched the query clauses.
+    pub async fn hybrid_search(
+        &self,
+        parsed_query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+        offset: usize,
+        num_results: usize,
+        strategy: FusionStrategy,
+    ) -> SearcherResult<SearchQueryResult> {
+        let start_timer = Instant::now();
+        let candidates = (offset + num_results).max(HYBRID_CANDIDATE_LIMIT);
+
+        let lexical = self
+            .search_with_query(parsed_query, filters, boosts, 0, candidates)
+            .await;
+        let mut vector = self
+            .semantic_search(&parsed_query.text, filters, 0, candidates)
+            .await?;
+
+        if parsed_query.is_structured() {
+            vector.documents.retain(|(_, doc)| {
+                lexical
+                    .documents
+                    .iter()
+                    .any(|(_, matched)| matched.doc_id == doc.doc_id)
+            });
+        }
+
+        let fused = fuse_results(strategy, lexical.documents, vector.documents);
+        let total_hits = lexical.total_hits.max(fused.len());
+        let documents = fused.into_iter().skip(offset).take(num_results).collect();
+
+        Ok(SearchQueryResult {
+            wall_time_ms: Instant::now().duration_since(start_timer).as_millis(),
+            num_docs: lexical.num_docs,
+            total_hits,
+            term_counts: lexical.term_counts,
+            documents,
+        })
+    }
+
+    /// Updates the pagerank of every document in the index, keyed by URL. Documents
+    /// without a score are reset to 0. Ranking only looks at the authority bucket
+    /// a score falls in, so only documents moving to another bucket are rewritten.
+    /// Returns the number of documents changed.
+    pub async fn update_pagerank(&self, scores: HashMap<String, f64>) -> SearcherResult<usize> {
+        let searcher = self.clone();
+        let updated = tokio::task::spawn_blocking(move || searcher.apply_pagerank(&scores))
+            .await
+            .map_err(|err| SearchError::Other(err.into()))??;
+
+        self.commit_if_needed().await?;
+        Ok(updated)
+    }
+
+    /// Rewrites the documents whose authority bucket changed, blocks until done.
+    fn apply_pagerank(&self, scores: &HashMap<String, f64>) -> SearcherResult<usize> {
+        let fields = DocFields::as_fields();
+        let live = self.live();
+        let index_schema = live.index.schema();
+        let searcher = live.reader.searcher();
+
+        let mut updated = 0;
+        let mut docs = Vec::new();
+        for addr in searcher.search(&AllQuery, &DocSetCollector)? {
+            let stored = convert_document(&index_schema, &self.schema, &searcher.doc(addr)?);
+            let score = scores
+                .get(&field_to_string(&stored, fields.url))
+                .copied()
+                .unwrap_or_default();
+            let current = stored
+                .get_first(fields.pagerank)
+                .and_then(|value| value.as_f64())
+                .unwrap_or_default();
+            if authority_bucket(score) == authority_bucket(current) {
+                continue;
+            }
+
+            // Documents can't be updated in place, so replace it with a copy
+            // that has the new score.
+            let mut doc = Document::default();
+            for field_value in stored.field_values() {
+                if field_value.field() != fields.pagerank {
+                    doc.add_field_value(field_value.field(), field_value.value().clone());
+                }
+            }
+            doc.add_f64(fields.pagerank, score);
+            docs.push(schema::regenerate_document(&self.schema, &doc));
+
+            if docs.len() >= PAGERANK_BATCH_SIZE {
+                updated += docs.len();
+                self.replace_documents(&docs)?;
+                docs.clear();
+            }
+        }
+
+        updated += docs.len();
+        self.replace_documents(&docs)?;
+        Ok(updated)
+    }
+
+    /// Replaces documents w/o embedding their content again, for updates that
+    /// only change ranking signals. Goes through the write batch like any other
+    /// write, so the changes are journaled & picked up by a schema migration.
+    fn replace_documents(&self, docs: &[Document]) -> SearcherResult<()> {
+        if docs.is_empty() {
+            return Ok(());
+        }
+
+        let fields = DocFields::as_fields();
+        let writer = self.lock_writer()?;
+        self.record_writes(
+            docs.iter()
+                .map(|doc| JournalEntry::Upsert {
+                    doc: self.schema.to_json(doc),
+                })
+                .collect(),
+        )?;
+
+        let index_schema = self.index().schema();
+        for doc in docs {
+            writer.delete_term(Term::from_field_text(
+                fields.id,
+                &field_to_string(doc, fields.id),
+            ));
+            writer.add_document(conform_document(&index_schema, doc))?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the pagerank of the indexed version of each document that doesn't
+    /// have one. Scores come from the link graph rather than the content, so
+    /// they're kept until the next time they're computed.
+    fn with_indexed_pagerank(&self, docs: &[Document]) -> Vec<Document> {
+        let fields = DocFields::as_fields();
+        let searcher = self.reader().searcher();
+        let pagerank = searcher.schema().get_field("pagerank");
+
+        docs.iter()
+            .map(|doc| {
+                let mut doc = doc.clone();
+                if doc.get_first(fields.pagerank).is_some() {
+                    return doc;
+                }
+
+                let query = TermQuery::new(
+                    Term::from_field_text(fields.id, &field_to_string(&doc, fields.id)),
+                    IndexRecordOption::Basic,
+                );
+                let indexed = searcher
+                    .search(&query, &TopDocs::with_limit(1))
+                    .ok()
+                    .and_then(|res| res.first().and_then(|(_, addr)| searcher.doc(*addr).ok()));
+                if let Some(score) = indexed
+                    .zip(pagerank)
+                    .and_then(|(indexed, field)| indexed.get_first(field).and_then(|v| v.as_f64()))
+                {
+                    doc.add_f64(fields.pagerank, score);
+                }
+
+                doc
+            })
+            .collect()
+    }
+
     /// Helper method to execute a search based on the provided document query
     pub async fn search_by_query(
         &self,
@@ -232,8 +779,7 @@ impl Searcher {
 
         let collector = tantivy::collector::DocSetCollector;
 
-        let reader = &self.reader;
-        let index_search = reader.searcher();
+        let index_search = self.reader().searcher();
 
         let docs = index_search
             .search(&query, &collector)
@@ -257,15 +803,15 @@ impl Searcher {
         query_string: &str,
         boosts: &[QueryBoost],
     ) -> Option<f32> {
-        let index = &self.index;
-        let reader = &self.reader;
+        let index = &self.index();
+        let reader = &self.reader();
         let fields = DocFields::as_fields();
 
         let tantivy_searcher = reader.searcher();
         let filters = vec![QueryBoost::new(Boost::DocId(doc_id.clone()))];
         let (_, final_query) = build_query(
-            &self.index,
-            query_string,
+            index,
+            &ParsedQuery::parse(query_string),
             &filters,
             boosts,
             QueryOptions::default(),
@@ -300,3 +846,56 @@ impl Searcher {
         None
     }
 }
+
+/// Drops values for fields the index doesn't have, i.e. fields added to the
+/// schema while the index is still being migrated. New fields are always
+/// appended to the schema, so the ids of existing fields match.
+pub(crate) fn conform_document(index_schema: &Schema, doc: &Document) -> Document {
+    let num_fields = index_schema.fields().count();
+    doc.field_values()
+        .iter()
+        .filter(|value| (value.field().field_id() as usize) < num_fields)
+        .cloned()
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Applies a journaled write. Any existing copy of an upserted doc is deleted
+/// first, so applying the same write more than once is safe.
+pub(crate) fn apply_write(
+    schema: &Schema,
+    index_schema: &Schema,
+    writer: &IndexWriter,
+    vectors: Option<&VectorStore>,
+    entry: JournalEntry,
+) -> SearcherResult<()> {
+    let fields = DocFields::as_fields();
+    match entry {
+        JournalEntry::Upsert { doc } => {
+            let doc = match schema.parse_document(&doc) {
+                Ok(doc) => doc,
+                Err(err) => {
+                    log::warn!("Unable to parse journaled doc: {err}");
+                    return Ok(());
+                }
+            };
+
+            let doc_id = field_to_string(&doc, fields.id);
+            writer.delete_term(Term::from_field_text(fields.id, &doc_id));
+            if let Some(vectors) = vectors {
+                if let Err(err) = vectors.upsert(&doc_id, &field_to_string(&doc, fields.content)) {
+                    log::warn!("Unable to embed doc {doc_id}: {err}");
+                }
+            }
+            writer.add_document(conform_document(index_schema, &doc))?;
+        }
+        JournalEntry::Delete { doc_id } => {
+            writer.delete_term(Term::from_field_text(fields.id, &doc_id));
+            if let Some(vectors) = vectors {
+                vectors.delete(&[doc_id]);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,2 +1,28 @@
+use std::sync::Arc;
+
+use tantivy::schema::Schema;
+
+use crate::similarity::Embedder;
+use crate::{IndexBackend, IndexClient, SearcherResult};
+
+mod http;
 mod local;
+pub use self::http::*;
 pub use self::local::*;
+
+/// Opens the index w/ the client for its backend. Remote indices do their own
+/// analysis, so the embedder is only used by local ones. Remote indices are
+/// also never read only, writes are up to the permissions of the remote API.
+pub fn open_index(
+    backend: &IndexBackend,
+    schema: Schema,
+    embedder: Option<Arc<dyn Embedder>>,
+    readonly: bool,
+) -> SearcherResult<Arc<dyn IndexClient>> {
+    match backend {
+        IndexBackend::Http(_) => Ok(Arc::new(HttpSearcher::with_index(backend)?)),
+        IndexBackend::LocalPath(_) | IndexBackend::Memory => Ok(Arc::new(Searcher::with_index(
+            backend, schema, embedder, readonly,
+        )?)),
+    }
+}
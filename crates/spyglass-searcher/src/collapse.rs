@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+
+use url::Url;
+
+use crate::{RetrievedDocument, Score};
+
+/// How many candidates are fetched per requested result when collapsing, so
+/// that pages are still filled after results are dropped.
+pub const OVERFETCH_FACTOR: usize = 4;
+/// Upper bound on the number of candidates fetched when collapsing.
+pub const MAX_COLLAPSE_CANDIDATES: usize = 500;
+
+/// What search results are grouped by when collapsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollapseKey {
+    /// Group by the document domain
+    Domain,
+    /// Group by the first tag in this set of tag ids found on the document,
+    /// e.g. all `repository` tags.
+    Tag(HashSet<u64>),
+    /// Group by the URL with the last path segment removed, e.g. pages in the
+    /// same folder.
+    ParentUrl,
+}
+
+/// Limits the number of results from the same group, keeping the best ranked
+/// results of each group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collapse {
+    pub key: CollapseKey,
+    pub max_per_group: usize,
+}
+
+impl Collapse {
+    pub fn new(key: CollapseKey, max_per_group: usize) -> Self {
+        Self {
+            key,
+            max_per_group: max_per_group.max(1),
+        }
+    }
+
+    /// Number of ranked candidates to fetch to fill the requested page.
+    pub fn candidate_limit(&self, offset: usize, num_results: usize) -> usize {
+        ((offset + num_results) * OVERFETCH_FACTOR)
+            .min(MAX_COLLAPSE_CANDIDATES)
+            .max(offset + num_results)
+    }
+
+    /// Group a document belongs to. Documents without a group are never
+    /// collapsed.
+    pub fn group(&self, doc: &RetrievedDocument) -> Option<String> {
+        match &self.key {
+            CollapseKey::Domain => {
+                if doc.domain.is_empty() {
+                    None
+                } else {
+                    Some(doc.domain.clone())
+                }
+            }
+            CollapseKey::Tag(tag_ids) => doc
+                .tags
+                .iter()
+                .find(|tag| tag_ids.contains(tag))
+                .map(|tag| tag.to_string()),
+            CollapseKey::ParentUrl => parent_url(&doc.url),
+        }
+    }
+
+    /// Drops results once their group has `max_per_group` better ranked
+    /// results. `results` are expected to be sorted by score, best first, and
+    /// stay in that order.
+    pub fn apply(
+        &self,
+        results: Vec<(Score, RetrievedDocument)>,
+    ) -> Vec<(Score, RetrievedDocument)> {
+        let mut group_counts: HashMap<String, usize> = HashMap::new();
+        results
+            .into_iter()
+            .filter(|(_, doc)| match self.group(doc) {
+                Some(group) => {
+                    let count = group_counts.entry(group).or_default();
+                    *count += 1;
+                    *count <= self.max_per_group
+                }
+                None => true,
+            })
+            .collect()
+    }
+}
+
+/// Strips the query, fragment & last path segment from the URL, e.g.
+/// `https://example.com/docs/page.html?q=1` becomes `https://example.com/docs/`
+pub fn parent_url(url: &str) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    url.set_query(None);
+    url.set_fragment(None);
+
+    let path = url.path().trim_end_matches('/');
+    let parent = match path.rfind('/') {
+        Some(idx) => path[..=idx].to_string(),
+        None => "/".to_string(),
+    };
+    url.set_path(&parent);
+
+    Some(url.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{parent_url, Collapse, CollapseKey};
+    use crate::RetrievedDocument;
+
+    fn doc(id: &str, domain: &str, url: &str, tags: Vec<u64>) -> RetrievedDocument {
+        RetrievedDocument {
+            doc_id: id.into(),
+            domain: domain.into(),
+            title: id.into(),
+            description: String::new(),
+            content: String::new(),
+            url: url.into(),
+            tags,
+            published: None,
+            last_modified: None,
+            metadata: Default::default(),
+            symbols: Vec::new(),
+            segments: Vec::new(),
+            page_offsets: Vec::new(),
+            lang: None,
+        }
+    }
+
+    fn ids(results: &[(f32, RetrievedDocument)]) -> Vec<&str> {
+        results.iter().map(|(_, doc)| doc.doc_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parent_url() {
+        assert_eq!(
+            parent_url("https://example.com/docs/page.html?q=1#top"),
+            Some("https://example.com/docs/".into())
+        );
+        assert_eq!(
+            parent_url("https://example.com/docs/"),
+            Some("https://example.com/".into())
+        );
+        assert_eq!(
+            parent_url("https://example.com"),
+            Some("https://example.com/".into())
+        );
+        assert_eq!(
+            parent_url("file:///Users/alice/notes/todo.md"),
+            Some("file:///Users/alice/notes/".into())
+        );
+        assert_eq!(parent_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_collapse_by_domain() {
+        let results = vec![
+            (5.0, doc("a1", "a.com", "https://a.com/1", vec![])),
+            (4.0, doc("a2", "a.com", "https://a.com/2", vec![])),
+            (3.0, doc("a3", "a.com", "https://a.com/3", vec![])),
+            (2.0, doc("b1", "b.com", "https://b.com/1", vec![])),
+            (1.0, doc("a4", "a.com", "https://a.com/4", vec![])),
+        ];
+
+        let collapse = Collapse::new(CollapseKey::Domain, 2);
+        assert_eq!(ids(&collapse.apply(results)), vec!["a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn test_collapse_by_tag() {
+        let results = vec![
+            (5.0, doc("r1", "gh.com", "https://gh.com/r/1", vec![1, 10])),
+            (4.0, doc("r2", "gh.com", "https://gh.com/r/2", vec![10])),
+            (3.0, doc("s1", "gh.com", "https://gh.com/s/1", vec![11])),
+            (2.0, doc("n1", "gh.com", "https://gh.com/n/1", vec![1])),
+            (1.0, doc("n2", "gh.com", "https://gh.com/n/2", vec![])),
+        ];
+
+        // Documents without a repository tag are never collapsed
+        let collapse = Collapse::new(CollapseKey::Tag(HashSet::from([10, 11])), 1);
+        assert_eq!(ids(&collapse.apply(results)), vec!["r1", "s1", "n1", "n2"]);
+    }
+
+    #[test]
+    fn test_candidate_limit() {
+        let collapse = Collapse::new(CollapseKey::ParentUrl, 0);
+        assert_eq!(collapse.max_per_group, 1);
+        assert_eq!(collapse.candidate_limit(0, 5), 20);
+        assert_eq!(collapse.candidate_limit(100, 100), 500);
+        assert_eq!(collapse.candidate_limit(1000, 10), 1010);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::fastfield::MultiValuedFastFieldReader;
+use tantivy::schema::IndexRecordOption;
+use tantivy::{DocId, DocSet, Score, SegmentOrdinal, SegmentReader, TERMINATED};
+
+use crate::schema::DocFields;
+
+/// Number of matching documents per tag id & domain
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FacetCounts {
+    pub tags: HashMap<u64, usize>,
+    pub domains: HashMap<String, usize>,
+}
+
+impl FacetCounts {
+    pub fn merge(&mut self, other: FacetCounts) {
+        for (tag_id, count) in other.tags {
+            *self.tags.entry(tag_id).or_default() += count;
+        }
+
+        for (domain, count) in other.domains {
+            *self.domains.entry(domain).or_default() += count;
+        }
+    }
+}
+
+/// Counts the tags & domains of every document matching a query.
+pub struct FacetCollector {
+    fields: DocFields,
+}
+
+impl FacetCollector {
+    pub fn new(fields: DocFields) -> Self {
+        FacetCollector { fields }
+    }
+}
+
+pub struct FacetSegmentCollector {
+    tags_reader: MultiValuedFastFieldReader<u64>,
+    /// Domain term ordinal for each doc in the segment
+    doc_domains: Vec<Option<usize>>,
+    domain_names: Vec<String>,
+    tag_buffer: Vec<u64>,
+    tags: HashMap<u64, usize>,
+    domains: HashMap<usize, usize>,
+}
+
+impl Collector for FacetCollector {
+    type Fruit = FacetCounts;
+    type Child = FacetSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<FacetSegmentCollector> {
+        let tags_reader = segment.fast_fields().u64s(self.fields.tags)?;
+
+        // Domains are only indexed as raw strings, so walk through the term dictionary
+        // once to map each document to its domain.
+        let mut doc_domains = vec![None; segment.max_doc() as usize];
+        let mut domain_names = Vec::new();
+        let inverted_index = segment.inverted_index(self.fields.domain)?;
+        let mut terms = inverted_index.terms().stream()?;
+        while terms.advance() {
+            let ordinal = domain_names.len();
+            domain_names.push(String::from_utf8_lossy(terms.key()).to_string());
+
+            let mut postings = inverted_index
+                .read_postings_from_terminfo(terms.value(), IndexRecordOption::Basic)?;
+            while postings.doc() != TERMINATED {
+                doc_domains[postings.doc() as usize] = Some(ordinal);
+                postings.advance();
+            }
+        }
+
+        Ok(FacetSegmentCollector {
+            tags_reader,
+            doc_domains,
+            domain_names,
+            tag_buffer: Vec::new(),
+            tags: HashMap::new(),
+            domains: HashMap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<FacetCounts>) -> tantivy::Result<FacetCounts> {
+        let mut counts = FacetCounts::default();
+        for fruit in segment_fruits {
+            counts.merge(fruit);
+        }
+
+        Ok(counts)
+    }
+}
+
+impl SegmentCollector for FacetSegmentCollector {
+    type Fruit = FacetCounts;
+
+    fn collect(&mut self, doc: DocId, _score: Score) {
+        self.tags_reader.get_vals(doc, &mut self.tag_buffer);
+        for tag_id in &self.tag_buffer {
+            *self.tags.entry(*tag_id).or_default() += 1;
+        }
+
+        if let Some(Some(ordinal)) = self.doc_domains.get(doc as usize) {
+            *self.domains.entry(*ordinal).or_default() += 1;
+        }
+    }
+
+    fn harvest(self) -> FacetCounts {
+        let domains = self
+            .domains
+            .into_iter()
+            .map(|(ordinal, count)| (self.domain_names[ordinal].clone(), count))
+            .collect();
+
+        FacetCounts {
+            tags: self.tags,
+            domains,
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! Language detection & language specific text analysis.
+//!
+//! The `content` field is always analyzed as English. Content detected to be in
+//! another supported language is also indexed into a `content_<analyzer>` field
+//! w/ the stemmer & stop words for that language, or split into bigrams for
+//! CJK text which doesn't separate words w/ spaces. Queries are analyzed w/
+//! every analyzer so they match documents in whichever language they're in.
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, BoxTokenStream, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer,
+    Stemmer, TextAnalyzer, Token, TokenStream, Tokenizer,
+};
+
+use whatlang::Script;
+
+use crate::stop_word_filter::StopWordFilter;
+
+/// Only the start of the content is used to detect its language.
+const DETECTION_SAMPLE_LEN: usize = 2048;
+
+/// Analyzers for content in languages other than English.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LanguageAnalyzer {
+    German,
+    French,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+    Swedish,
+    Russian,
+    /// Chinese, Japanese & Korean
+    Cjk,
+}
+
+impl LanguageAnalyzer {
+    /// Every analyzer, in the order their fields were added to the schema.
+    pub const ALL: [LanguageAnalyzer; 9] = [
+        LanguageAnalyzer::German,
+        LanguageAnalyzer::French,
+        LanguageAnalyzer::Spanish,
+        LanguageAnalyzer::Italian,
+        LanguageAnalyzer::Portuguese,
+        LanguageAnalyzer::Dutch,
+        LanguageAnalyzer::Swedish,
+        LanguageAnalyzer::Russian,
+        LanguageAnalyzer::Cjk,
+    ];
+
+    /// Analyzer for an ISO 639-1 language code, `None` for English & languages
+    /// that only get the default analysis.
+    pub fn for_language(code: &str) -> Option<Self> {
+        let analyzer = match code {
+            "de" => LanguageAnalyzer::German,
+            "fr" => LanguageAnalyzer::French,
+            "es" => LanguageAnalyzer::Spanish,
+            "it" => LanguageAnalyzer::Italian,
+            "pt" => LanguageAnalyzer::Portuguese,
+            "nl" => LanguageAnalyzer::Dutch,
+            "sv" => LanguageAnalyzer::Swedish,
+            "ru" => LanguageAnalyzer::Russian,
+            "zh" | "ja" | "ko" => LanguageAnalyzer::Cjk,
+            _ => return None,
+        };
+
+        Some(analyzer)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LanguageAnalyzer::German => "de",
+            LanguageAnalyzer::French => "fr",
+            LanguageAnalyzer::Spanish => "es",
+            LanguageAnalyzer::Italian => "it",
+            LanguageAnalyzer::Portuguese => "pt",
+            LanguageAnalyzer::Dutch => "nl",
+            LanguageAnalyzer::Swedish => "sv",
+            LanguageAnalyzer::Russian => "ru",
+            LanguageAnalyzer::Cjk => "cjk",
+        }
+    }
+
+    pub fn tokenizer_name(&self) -> String {
+        format!("spyglass_tokenizer_{}", self.name())
+    }
+
+    /// Field content in this language is indexed into
+    pub fn field_name(&self) -> String {
+        format!("content_{}", self.name())
+    }
+
+    fn stemmer_language(&self) -> Option<Language> {
+        match self {
+            LanguageAnalyzer::German => Some(Language::German),
+            LanguageAnalyzer::French => Some(Language::French),
+            LanguageAnalyzer::Spanish => Some(Language::Spanish),
+            LanguageAnalyzer::Italian => Some(Language::Italian),
+            LanguageAnalyzer::Portuguese => Some(Language::Portuguese),
+            LanguageAnalyzer::Dutch => Some(Language::Dutch),
+            LanguageAnalyzer::Swedish => Some(Language::Swedish),
+            LanguageAnalyzer::Russian => Some(Language::Russian),
+            LanguageAnalyzer::Cjk => None,
+        }
+    }
+
+    pub fn text_analyzer(&self) -> TextAnalyzer {
+        match self.stemmer_language() {
+            // Stop words & stemmers expect accents, so those are only folded
+            // afterwards to match queries typed w/o them.
+            Some(language) => TextAnalyzer::from(SimpleTokenizer)
+                .filter(RemoveLongFilter::limit(40))
+                .filter(LowerCaser)
+                .filter(StopWordFilter::for_language(language))
+                .filter(Stemmer::new(language))
+                .filter(AsciiFoldingFilter),
+            None => TextAnalyzer::from(CjkBigramTokenizer)
+                .filter(RemoveLongFilter::limit(40))
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter),
+        }
+    }
+}
+
+/// Maps a detected language to its ISO 639-1 code, only languages w/ an
+/// analyzer or that are common enough to be worth filtering on are kept.
+fn iso_code(lang: whatlang::Lang) -> Option<&'static str> {
+    use whatlang::Lang;
+
+    let code = match lang {
+        Lang::Eng => "en",
+        Lang::Deu => "de",
+        Lang::Fra => "fr",
+        Lang::Spa => "es",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Nld => "nl",
+        Lang::Swe => "sv",
+        Lang::Rus => "ru",
+        Lang::Cmn => "zh",
+        Lang::Jpn => "ja",
+        Lang::Kor => "ko",
+        Lang::Pol => "pl",
+        Lang::Ukr => "uk",
+        Lang::Tur => "tr",
+        Lang::Dan => "da",
+        Lang::Fin => "fi",
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// Detects the language of `text`, returning its ISO 639-1 code. Returns `None`
+/// if the detection isn't reliable, e.g. for very short text.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut end = text.len().min(DETECTION_SAMPLE_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let info = whatlang::detect(&text[..end])?;
+    // CJK languages are told apart by their script, which is dependable even
+    // when there's too little text for a confident guess.
+    let by_script = matches!(
+        info.script(),
+        Script::Mandarin | Script::Hiragana | Script::Katakana | Script::Hangul
+    );
+    if info.is_reliable() || by_script {
+        iso_code(info.lang())
+    } else {
+        None
+    }
+}
+
+/// Normalizes a declared language, such as `en-US` from a `Content-Language`
+/// header, to its ISO 639-1 code.
+pub fn normalize_language(value: &str) -> Option<String> {
+    let code = value
+        .split(',')
+        .next()?
+        .trim()
+        .split(['-', '_'])
+        .next()?
+        .to_lowercase();
+
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(code)
+    } else {
+        None
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        // Hiragana & Katakana
+        0x3040..=0x30FF
+        // CJK unified ideographs & extension A
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        // Hangul syllables
+        | 0xAC00..=0xD7AF
+        // CJK compatibility ideographs
+        | 0xF900..=0xFAFF
+        // CJK unified ideographs extension B
+        | 0x20000..=0x2A6DF)
+}
+
+/// Splits runs of CJK characters into overlapping bigrams, since words aren't
+/// separated by spaces. Other alphanumeric runs are kept whole, like the
+/// `SimpleTokenizer` does.
+#[derive(Clone)]
+pub struct CjkBigramTokenizer;
+
+pub struct CjkTokenStream {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+impl CjkBigramTokenizer {
+    fn tokenize(text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let push = |tokens: &mut Vec<Token>, from: usize, to: usize| {
+            tokens.push(Token {
+                offset_from: from,
+                offset_to: to,
+                position: tokens.len(),
+                text: text[from..to].to_string(),
+                position_length: 1,
+            });
+        };
+
+        let chars = text.char_indices().collect::<Vec<_>>();
+        let end_of = |idx: usize| chars.get(idx).map(|(pos, _)| *pos).unwrap_or(text.len());
+
+        let mut idx = 0;
+        while idx < chars.len() {
+            let (start, c) = chars[idx];
+            if is_cjk(c) {
+                let mut run_end = idx;
+                while run_end < chars.len() && is_cjk(chars[run_end].1) {
+                    run_end += 1;
+                }
+
+                if run_end - idx == 1 {
+                    push(&mut tokens, start, end_of(idx + 1));
+                } else {
+                    for bigram in idx..run_end - 1 {
+                        push(&mut tokens, chars[bigram].0, end_of(bigram + 2));
+                    }
+                }
+                idx = run_end;
+            } else if c.is_alphanumeric() {
+                let mut run_end = idx;
+                while run_end < chars.len()
+                    && chars[run_end].1.is_alphanumeric()
+                    && !is_cjk(chars[run_end].1)
+                {
+                    run_end += 1;
+                }
+
+                push(&mut tokens, start, end_of(run_end));
+                idx = run_end;
+            } else {
+                idx += 1;
+            }
+        }
+
+        tokens
+    }
+}
+
+impl Tokenizer for CjkBigramTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(CjkTokenStream {
+            tokens: CjkBigramTokenizer::tokenize(text),
+            cursor: 0,
+        })
+    }
+}
+
+impl TokenStream for CjkTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.cursor < self.tokens.len() {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.cursor - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.cursor - 1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect_language, normalize_language, LanguageAnalyzer};
+
+    fn tokens(analyzer: LanguageAnalyzer, text: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        analyzer
+            .text_analyzer()
+            .token_stream(text)
+            .process(&mut |token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language(
+                "Der schnelle braune Fuchs springt über den faulen Hund, \
+                während der Bauer auf der Veranda sitzt und zuschaut."
+            ),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language(
+                "Le renard brun rapide saute par-dessus le chien paresseux \
+                pendant que le fermier regarde depuis la véranda."
+            ),
+            Some("fr")
+        );
+        assert_eq!(
+            detect_language("東京は日本の首都であり、世界最大の都市圏の一つです。"),
+            Some("ja")
+        );
+        assert_eq!(detect_language("ok"), None);
+
+        assert_eq!(normalize_language("en-US"), Some("en".into()));
+        assert_eq!(normalize_language("DE_de, en"), Some("de".into()));
+        assert_eq!(normalize_language("english"), None);
+    }
+
+    #[test]
+    fn test_language_analyzers() {
+        // Stop words are removed & words are stemmed, then accents are folded
+        assert_eq!(
+            tokens(LanguageAnalyzer::German, "Die Häuser und die Bäume"),
+            vec!["haus", "baum"]
+        );
+        assert_eq!(
+            tokens(LanguageAnalyzer::French, "les maisons"),
+            vec!["maison"]
+        );
+
+        assert_eq!(
+            tokens(LanguageAnalyzer::Cjk, "東京都 Tokyo 2023"),
+            vec!["東京", "京都", "tokyo", "2023"]
+        );
+        assert_eq!(tokens(LanguageAnalyzer::Cjk, "日"), vec!["日"]);
+        assert_eq!(
+            LanguageAnalyzer::for_language("ja"),
+            Some(LanguageAnalyzer::Cjk)
+        );
+        assert_eq!(LanguageAnalyzer::for_language("en"), None);
+    }
+}
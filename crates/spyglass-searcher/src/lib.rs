@@ -1,16 +1,30 @@
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::path::PathBuf;
 use tantivy::schema::*;
 use thiserror::Error;
 use url::Url;
 
+pub mod batch;
 pub mod client;
+pub mod collapse;
+pub mod facets;
+pub mod language;
 pub mod schema;
+pub mod schema_migration;
 pub mod stop_word_filter;
+pub mod suggest;
+pub mod symbol_tokenizer;
+use collapse::Collapse;
+use facets::FacetCounts;
+use query_parser::ParsedQuery;
 use schema::{DocFields, SearchDocument};
 
 mod query;
+pub mod query_parser;
+pub mod ranking;
 pub mod similarity;
 pub mod utils;
 
@@ -42,6 +56,9 @@ impl QueryBoost {
             Boost::Tag(_) => 1.5,
             Boost::Url(_) => 3.0,
             Boost::CustomField { .. } => 0.0,
+            Boost::DateRange { .. } => 1.0,
+            Boost::Recency(_) => 1.0,
+            Boost::Opened(_) => 1.0,
         };
 
         QueryBoost {
@@ -54,11 +71,44 @@ impl QueryBoost {
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Boost {
     // If required is set to true, _only_ favorites will be searched.
-    Favorite { id: u64, required: bool },
+    Favorite {
+        id: u64,
+        required: bool,
+    },
     Url(String),
     DocId(String),
     Tag(u64),
-    CustomField { field_name: String, value: u64 },
+    CustomField {
+        field_name: String,
+        value: u64,
+    },
+    /// Documents dated within `start..end`, either bound can be left open.
+    DateRange {
+        field: DateField,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    },
+    /// Favor more recent documents, only considered as a boost.
+    Recency(DateField),
+    /// Favor a document the user opened for similar queries, only considered
+    /// as a boost.
+    Opened(String),
+}
+
+/// Date fields that can be used to filter or boost documents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateField {
+    Published,
+    LastModified,
+}
+
+impl DateField {
+    pub fn as_field(&self, fields: &DocFields) -> Field {
+        match self {
+            DateField::Published => fields.published,
+            DateField::LastModified => fields.lastmodified,
+        }
+    }
 }
 
 /// Contains stats & results for a search request
@@ -66,6 +116,8 @@ pub enum Boost {
 pub struct SearchQueryResult {
     pub wall_time_ms: u128,
     pub num_docs: u64,
+    /// Total number of documents matching the query, regardless of paging
+    pub total_hits: usize,
     pub term_counts: usize,
     pub documents: Vec<(Score, RetrievedDocument)>,
 }
@@ -79,6 +131,10 @@ pub enum SearchError {
     ReadOnly,
     #[error("Index writer is deadlocked")]
     WriterLocked,
+    #[error("Index backend not supported by this client: {0}")]
+    UnsupportedBackend(String),
+    #[error("Unable to reach remote index: {0}")]
+    RequestError(#[from] reqwest::Error),
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
@@ -88,14 +144,61 @@ pub enum SearchError {
 pub trait SearchTrait {
     /// Get a single document by id
     async fn get(&self, doc_id: &str) -> Option<RetrievedDocument>;
-    /// Runs a search against the index
+    /// Runs a search against the index, skipping the first `offset` results
+    /// and returning at most `num_results`.
     async fn search(
         &self,
         query: &str,
         filters: &[QueryBoost],
         boosts: &[QueryBoost],
+        offset: usize,
+        num_results: usize,
+    ) -> SearchQueryResult {
+        let query = ParsedQuery::parse(query);
+        self.search_with_query(&query, filters, boosts, offset, num_results)
+            .await
+    }
+    /// Runs an already parsed query against the index. Any tags in the query
+    /// should be resolved to ids beforehand.
+    async fn search_with_query(
+        &self,
+        query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+        offset: usize,
         num_results: usize,
     ) -> SearchQueryResult;
+    /// Runs an already parsed query, limiting the number of results from the
+    /// same group (domain, repository, etc.). Extra candidates are fetched &
+    /// collapsed before paging so the returned page stays in ranked order.
+    async fn search_collapsed(
+        &self,
+        query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+        offset: usize,
+        num_results: usize,
+        collapse: &Collapse,
+    ) -> SearchQueryResult {
+        let candidates = collapse.candidate_limit(offset, num_results);
+        let mut result = self
+            .search_with_query(query, filters, boosts, 0, candidates)
+            .await;
+        result.documents = collapse
+            .apply(result.documents)
+            .into_iter()
+            .skip(offset)
+            .take(num_results)
+            .collect();
+        result
+    }
+    /// Counts the tags & domains across all documents matching the query.
+    async fn facet_counts(
+        &self,
+        query: &ParsedQuery,
+        filters: &[QueryBoost],
+        boosts: &[QueryBoost],
+    ) -> SearcherResult<FacetCounts>;
 }
 
 #[async_trait::async_trait]
@@ -117,6 +220,33 @@ pub trait WriteTrait {
     async fn upsert_many(&self, updates: &[Document]) -> SearcherResult<Vec<String>>;
 }
 
+/// An index the app can search & write to, whether it's local or remote.
+#[async_trait::async_trait]
+pub trait IndexClient: SearchTrait + WriteTrait + Send + Sync {
+    /// Commits pending writes & makes them visible to searches.
+    async fn save(&self) -> SearcherResult<()>;
+    /// Total number of documents in the index.
+    async fn num_docs(&self) -> SearcherResult<u64>;
+    /// Documents w/ any of the urls & ids that have all of `has_tags` & none of
+    /// `exclude_tags`, in no particular order. Empty urls/ids match any doc.
+    async fn search_by_query(
+        &self,
+        urls: Option<Vec<String>>,
+        ids: Option<Vec<String>>,
+        has_tags: &[u64],
+        exclude_tags: &[u64],
+    ) -> Vec<(Score, RetrievedDocument)>;
+    /// Number of writes waiting on the next commit.
+    fn num_pending_writes(&self) -> usize {
+        0
+    }
+    /// The local index, for features that need direct access to it
+    /// (suggestions, hybrid search, snapshots, etc.).
+    fn as_local(&self) -> Option<&client::Searcher> {
+        None
+    }
+}
+
 type SearcherResult<T> = Result<T, SearchError>;
 
 #[derive(Clone, Serialize)]
@@ -128,6 +258,41 @@ pub struct RetrievedDocument {
     pub content: String,
     pub url: String,
     pub tags: Vec<u64>,
+    pub published: Option<DateTime<Utc>>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub metadata: BTreeMap<String, String>,
+    pub symbols: Vec<String>,
+    pub segments: Vec<schema::TranscriptSegment>,
+    /// Byte offset in the content where each page starts, for paged documents
+    pub page_offsets: Vec<u64>,
+    /// ISO 639-1 code of the detected language, if any
+    pub lang: Option<String>,
+}
+
+impl RetrievedDocument {
+    /// Checks the document against a filter outside of the index, used when
+    /// results don't come from a tantivy query (e.g. semantic search).
+    fn matches_filter(&self, filter: &Boost) -> bool {
+        match filter {
+            Boost::Favorite { id, required } => !required || self.tags.contains(id),
+            Boost::Url(url) => &self.url == url,
+            Boost::DocId(doc_id) => &self.doc_id == doc_id,
+            Boost::Tag(tag_id) => self.tags.contains(tag_id),
+            Boost::DateRange { field, start, end } => {
+                let date = match field {
+                    DateField::Published => self.published,
+                    DateField::LastModified => self.last_modified,
+                };
+
+                date.map(|date| {
+                    start.map(|start| date >= start).unwrap_or(true)
+                        && end.map(|end| date < end).unwrap_or(true)
+                })
+                .unwrap_or_default()
+            }
+            Boost::CustomField { .. } | Boost::Recency(_) | Boost::Opened(_) => true,
+        }
+    }
 }
 
 // Helper method used to get the string value from a field
@@ -138,6 +303,23 @@ fn field_to_string(doc: &Document, field: Field) -> String {
         .unwrap_or_default()
 }
 
+// Helper method used to get a date from a field.
+fn field_to_date(doc: &Document, field: Field) -> Option<DateTime<Utc>> {
+    doc.get_first(field)
+        .and_then(|x| x.as_date())
+        .and_then(from_tantivy_date)
+}
+
+// Convert between the chrono dates we use everywhere else & tantivy dates.
+fn to_tantivy_date(date: &DateTime<Utc>) -> tantivy::DateTime {
+    tantivy::DateTime::from_timestamp_micros(date.timestamp_micros())
+}
+
+fn from_tantivy_date(date: tantivy::DateTime) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(date.into_timestamp_millis())
+        .single()
+}
+
 // Helper method used to get the u64 vector from a field.
 fn field_to_u64vec(doc: &Document, field: Field) -> Vec<u64> {
     doc.get_all(field).filter_map(|val| val.as_u64()).collect()
@@ -157,6 +339,25 @@ pub fn document_to_struct(doc: &Document) -> Option<RetrievedDocument> {
     let url = field_to_string(doc, fields.url);
     let content = field_to_string(doc, fields.content);
     let tags = field_to_u64vec(doc, fields.tags);
+    let published = field_to_date(doc, fields.published);
+    let last_modified = field_to_date(doc, fields.lastmodified);
+    let metadata = doc
+        .get_all(fields.metadata)
+        .filter_map(|val| val.as_text())
+        .filter_map(schema::parse_metadata_entry)
+        .collect();
+    let symbols = doc
+        .get_all(fields.symbols)
+        .filter_map(|val| val.as_text())
+        .map(|val| val.to_string())
+        .collect();
+    let segments = doc
+        .get_all(fields.segments)
+        .filter_map(|val| val.as_text())
+        .filter_map(schema::parse_segment_entry)
+        .collect();
+    let page_offsets = field_to_u64vec(doc, fields.page_offsets);
+    let lang = Some(field_to_string(doc, fields.lang)).filter(|lang| !lang.is_empty());
 
     Some(RetrievedDocument {
         doc_id,
@@ -166,14 +367,31 @@ pub fn document_to_struct(doc: &Document) -> Option<RetrievedDocument> {
         content,
         url,
         tags,
+        published,
+        last_modified,
+        metadata,
+        symbols,
+        segments,
+        page_offsets,
+        lang,
     })
 }
 
 #[cfg(test)]
 mod test {
+    use chrono::{Duration, Utc};
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+
+    use crate::batch::BatchConfig;
     use crate::client::Searcher;
-    use crate::schema::{DocFields, DocumentUpdate, SearchDocument, ToDocument};
-    use crate::{Boost, IndexBackend, QueryBoost, SearchTrait, WriteTrait};
+    use crate::query_parser::ParsedQuery;
+    use crate::schema::{
+        self, DocFields, DocumentUpdate, SearchDocument, ToDocument, META_AUTHOR, META_PAGE_COUNT,
+    };
+    use crate::similarity::HashEmbedder;
+    use crate::utils;
+    use crate::{Boost, DateField, IndexBackend, QueryBoost, SearchTrait, WriteTrait};
 
     async fn _build_test_index(searcher: &mut Searcher) {
         searcher
@@ -191,9 +409,14 @@ mod test {
             fresh and green with every spring, carrying in their lower leaf junctures the
             debris of the winter’s flooding; and sycamores with mottled, white, recumbent
             limbs and branches that arch over the pool",
+                description: None,
+                metadata: &Default::default(),
                 tags: &vec![1_i64],
                 published_at: None,
                 last_modified: None,
+                page_offsets: &[],
+                segments: &[],
+                symbols: &[],
             }.to_document())
             .await
             .expect("Unable to add doc");
@@ -213,9 +436,14 @@ mod test {
             fresh and green with every spring, carrying in their lower leaf junctures the
             debris of the winter’s flooding; and sycamores with mottled, white, recumbent
             limbs and branches that arch over the pool",
+                description: None,
+                metadata: &Default::default(),
                 tags: &vec![2_i64],
                 published_at: None,
                 last_modified: None,
+                page_offsets: &[],
+                segments: &[],
+                symbols: &[],
             }.to_document())
             .await
             .expect("Unable to add doc");
@@ -234,9 +462,14 @@ mod test {
             eros. Donec rhoncus mauris libero, et imperdiet neque sagittis sed. Nulla
             ac volutpat massa. Vivamus sed imperdiet est, id pretium ex. Praesent suscipit
             mattis ipsum, a lacinia nunc semper vitae.",
+                    description: None,
+                    metadata: &Default::default(),
                     tags: &vec![2_i64],
                     published_at: None,
                     last_modified: None,
+                    page_offsets: &[],
+                    segments: &[],
+                    symbols: &[],
                 }
                 .to_document(),
             )
@@ -253,6 +486,11 @@ mod test {
              enterprise which you have regarded with such evil forebodings.  I arrived here
              yesterday, and my first task is to assure my dear sister of my welfare and
              increasing confidence in the success of my undertaking.",
+             description: None,
+             metadata: &Default::default(),
+             symbols: &[],
+             segments: &[],
+             page_offsets: &[],
              tags: &vec![1_i64],
              published_at: None,
              last_modified: None
@@ -271,39 +509,688 @@ mod test {
     #[tokio::test]
     pub async fn test_basic_lense_search() {
         let mut searcher =
-            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
                 .expect("Unable to open index");
         _build_test_index(&mut searcher).await;
 
         let query = "salinas";
         let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
-        let results = searcher.search(query, &filters, &[], 5).await;
+        let results = searcher.search(query, &filters, &[], 0, 5).await;
         assert_eq!(results.documents.len(), 1);
     }
 
     #[tokio::test]
     pub async fn test_url_lens_search() {
         let mut searcher =
-            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
                 .expect("Unable to open index");
         _build_test_index(&mut searcher).await;
 
         let query = "salinas";
         let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
-        let results = searcher.search(query, &filters, &[], 5).await;
+        let results = searcher.search(query, &filters, &[], 0, 5).await;
         assert_eq!(results.documents.len(), 1);
     }
 
     #[tokio::test]
     pub async fn test_singular_url_lens_search() {
         let mut searcher =
-            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
                 .expect("Unable to open index");
         _build_test_index(&mut searcher).await;
 
+        // Matched w/ a typo
         let query = "salinasd";
         let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
-        let results = searcher.search(query, &filters, &[], 5).await;
+        let results = searcher.search(query, &filters, &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 1);
+
+        let query = "pizza";
+        let results = searcher.search(query, &filters, &[], 0, 5).await;
         assert_eq!(results.documents.len(), 0);
     }
+
+    #[tokio::test]
+    pub async fn test_fuzzy_and_prefix_search() {
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        // Typo before the last word
+        let results = searcher.search("salnas xyzzyq", &[], &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert!(results
+            .documents
+            .iter()
+            .all(|(_, doc)| doc.title == "Of Mice and Men"));
+
+        // Last word still being typed
+        let results = searcher.search("modern frankenst", &[], &[], 0, 5).await;
+        assert_eq!(
+            results.documents[0].1.url,
+            "https://example.com/frankenstein"
+        );
+
+        // Exact matches rank above partial ones
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        for (url, content) in [
+            ("https://example.com/typo", "Notes on the Salinos valley"),
+            ("https://example.com/exact", "Notes on the Salinas valley"),
+        ] {
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: "Notes",
+                        domain: "example.com",
+                        url,
+                        content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        let results = searcher.search("salinas", &[], &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert_eq!(results.documents[0].1.url, "https://example.com/exact");
+    }
+
+    #[tokio::test]
+    pub async fn test_paginated_search() {
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        let query = "salinas";
+        let first_page = searcher.search(query, &[], &[], 0, 1).await;
+        assert_eq!(first_page.documents.len(), 1);
+        assert_eq!(first_page.total_hits, 2);
+
+        let second_page = searcher.search(query, &[], &[], 1, 1).await;
+        assert_eq!(second_page.documents.len(), 1);
+        assert_eq!(second_page.total_hits, 2);
+        assert_ne!(
+            first_page.documents[0].1.doc_id,
+            second_page.documents[0].1.doc_id
+        );
+
+        let past_end = searcher.search(query, &[], &[], 2, 1).await;
+        assert_eq!(past_end.documents.len(), 0);
+        assert_eq!(past_end.total_hits, 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_date_range_search() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        let now = Utc::now();
+        let last_year = now - Duration::days(365);
+        for (url, last_modified) in [
+            ("https://example.com/new", now),
+            ("https://example.com/old", last_year),
+        ] {
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: "Salinas River",
+                        domain: "example.com",
+                        url,
+                        content: "the salinas river drops in close to the hillside",
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: Some(last_modified),
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        let filters = vec![QueryBoost::new(Boost::DateRange {
+            field: DateField::LastModified,
+            start: Some(now - Duration::days(7)),
+            end: None,
+        })];
+        let results = searcher.search("salinas", &filters, &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 1);
+        let (_, doc) = &results.documents[0];
+        assert_eq!(doc.url, "https://example.com/new");
+        assert!(doc.last_modified.is_some());
+
+        // Both docs match, but the newer one should rank first.
+        let boosts = vec![QueryBoost::new(Boost::Recency(DateField::LastModified))];
+        let results = searcher.search("salinas", &[], &boosts, 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert_eq!(results.documents[0].1.url, "https://example.com/new");
+    }
+
+    #[tokio::test]
+    pub async fn test_opened_boost() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        let mut doc_ids = Vec::new();
+        for (url, content) in [
+            ("https://example.com/first", "the salinas river"),
+            ("https://example.com/second", "the salinas river"),
+            ("https://example.com/other", "a few miles south of soledad"),
+        ] {
+            let doc_id = searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: "Salinas",
+                        domain: "example.com",
+                        url,
+                        content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+            doc_ids.push(doc_id);
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        // The opened doc ranks first, but docs that don't match aren't added
+        let boosts = vec![
+            QueryBoost::new(Boost::Opened(doc_ids[1].clone())),
+            QueryBoost::new(Boost::Opened(doc_ids[2].clone())),
+        ];
+        let results = searcher.search("river", &[], &boosts, 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert_eq!(results.documents[0].1.url, "https://example.com/second");
+    }
+
+    #[tokio::test]
+    pub async fn test_symbol_search() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        let defines = vec!["handle_fetch".to_string(), "FetchError".to_string()];
+        for (url, content, symbols) in [
+            (
+                "file:///repo/src/client.rs",
+                "let res = handle_fetch(url).await;",
+                &[] as &[String],
+            ),
+            (
+                "file:///repo/src/fetch.rs",
+                "pub async fn handle_fetch(url: &str) {}",
+                &defines,
+            ),
+        ] {
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: url,
+                        domain: "localhost",
+                        url,
+                        content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols,
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                        page_offsets: &[],
+                        segments: &[],
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        // The file defining the symbol ranks above the one using it
+        let results = searcher.search("handle_fetch", &[], &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert_eq!(results.documents[0].1.url, "file:///repo/src/fetch.rs");
+        assert_eq!(results.documents[0].1.symbols, defines);
+
+        // Words in a symbol match it too
+        let results = searcher.search("fetch error", &[], &[], 0, 5).await;
+        assert_eq!(results.documents[0].1.url, "file:///repo/src/fetch.rs");
+    }
+
+    #[tokio::test]
+    pub async fn test_structured_search() {
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        let results = searcher
+            .search("salinas domain:example.com", &[], &[], 0, 5)
+            .await;
+        assert!(results
+            .documents
+            .iter()
+            .all(|(_, doc)| doc.tags.contains(&2)));
+        assert_eq!(results.documents[0].1.domain, "example.com");
+
+        let results = searcher
+            .search("salinas -domain:example.com", &[], &[], 0, 5)
+            .await;
+        assert!(results
+            .documents
+            .iter()
+            .all(|(_, doc)| doc.tags.contains(&2)));
+        assert_eq!(results.documents[0].1.domain, "en.wikipedia.org");
+
+        // Only field clauses
+        let results = searcher
+            .search("url:https://en.wikipedia.org/", &[], &[], 0, 5)
+            .await;
+        assert_eq!(results.documents.len(), 2);
+
+        let results = searcher
+            .search(
+                r#"title:"modern prometheus" OR title:cheese"#,
+                &[],
+                &[],
+                0,
+                5,
+            )
+            .await;
+        assert_eq!(results.documents.len(), 2);
+
+        // Unresolved tags don't match anything
+        let results = searcher
+            .search("salinas lens:unknown", &[], &[], 0, 5)
+            .await;
+        assert_eq!(results.documents.len(), 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_facet_counts() {
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        let query = ParsedQuery::parse("salinas");
+        let counts = searcher
+            .facet_counts(&query, &[], &[])
+            .await
+            .expect("Unable to count facets");
+        assert_eq!(counts.tags.get(&1), Some(&1));
+        assert_eq!(counts.tags.get(&2), Some(&1));
+        assert_eq!(counts.domains.get("example.com"), Some(&1));
+        assert_eq!(counts.domains.get("en.wikipedia.org"), Some(&1));
+
+        // Filters narrow down the counts
+        let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
+        let counts = searcher
+            .facet_counts(&query, &filters, &[])
+            .await
+            .expect("Unable to count facets");
+        assert_eq!(counts.tags.get(&1), None);
+        assert_eq!(counts.domains.len(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn test_semantic_search() {
+        let embedder = Arc::new(HashEmbedder::default());
+        let mut searcher = Searcher::with_index(
+            &IndexBackend::Memory,
+            DocFields::as_schema(),
+            Some(embedder),
+            false,
+        )
+        .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        let results = searcher
+            .semantic_search("salinas river valley", &[], 0, 5)
+            .await
+            .expect("Unable to run semantic search");
+        let urls = results
+            .documents
+            .iter()
+            .take(2)
+            .map(|(_, doc)| doc.url.as_str())
+            .collect::<Vec<_>>();
+        assert!(urls.contains(&"https://example.com/mice_and_men"));
+        assert!(urls.contains(&"https://en.wikipedia.org/mice_and_men"));
+
+        let filters = vec![QueryBoost::new(Boost::Tag(2_u64))];
+        let results = searcher
+            .semantic_search("salinas river valley", &filters, 0, 5)
+            .await
+            .expect("Unable to run semantic search");
+        assert!(results
+            .documents
+            .iter()
+            .all(|(_, doc)| doc.tags.contains(&2)));
+        assert_eq!(
+            results.documents[0].1.url,
+            "https://en.wikipedia.org/mice_and_men"
+        );
+
+        // Deleted docs are removed from the vector store as well
+        let doc_id = results.documents[0].1.doc_id.clone();
+        searcher
+            .delete(&doc_id)
+            .await
+            .expect("Unable to delete doc");
+        let results = searcher
+            .semantic_search("salinas river valley", &filters, 0, 5)
+            .await
+            .expect("Unable to run semantic search");
+        assert!(results
+            .documents
+            .iter()
+            .all(|(_, doc)| doc.doc_id != doc_id));
+    }
+
+    #[tokio::test]
+    pub async fn test_pagerank_boost() {
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        let wiki_url = "https://en.wikipedia.org/mice_and_men";
+        let mut scores = HashMap::new();
+        scores.insert(wiki_url.to_string(), 1.0);
+        let updated = searcher
+            .update_pagerank(scores.clone())
+            .await
+            .expect("Unable to update pagerank");
+        assert_eq!(updated, 1);
+        searcher.save().await.expect("Unable to save index");
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        // Same content, but the better linked doc should come out on top.
+        let results = searcher.search("salinas river", &[], &[], 0, 5).await;
+        assert_eq!(results.documents.len(), 2);
+        assert_eq!(results.documents[0].1.url, wiki_url);
+
+        // Nothing changes while the scores stay in the same authority bucket
+        scores.insert(wiki_url.to_string(), 0.99);
+        let updated = searcher
+            .update_pagerank(scores)
+            .await
+            .expect("Unable to update pagerank");
+        assert_eq!(updated, 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_description_and_metadata() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        let metadata = BTreeMap::from([
+            (META_AUTHOR.to_string(), "John Steinbeck".to_string()),
+            (META_PAGE_COUNT.to_string(), "107".to_string()),
+        ]);
+        searcher
+            .upsert(
+                &DocumentUpdate {
+                    doc_id: None,
+                    title: "Of Mice and Men",
+                    domain: "example.com",
+                    url: "https://example.com/mice_and_men",
+                    content: "A few miles south of Soledad, the Salinas River drops in close",
+                    description: Some("A novella about two displaced migrant ranch workers"),
+                    metadata: &metadata,
+                    symbols: &[],
+                    segments: &[],
+                    page_offsets: &[],
+                    tags: &[],
+                    published_at: None,
+                    last_modified: None,
+                }
+                .to_document(),
+            )
+            .await
+            .expect("Unable to add doc");
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        // Only found in the description & metadata respectively
+        for query in ["novella migrant", "steinbeck"] {
+            let results = searcher.search(query, &[], &[], 0, 5).await;
+            assert_eq!(results.documents.len(), 1, "no results for `{query}`");
+
+            let (_, doc) = &results.documents[0];
+            assert_eq!(
+                doc.description,
+                "A novella about two displaced migrant ranch workers"
+            );
+            assert_eq!(doc.metadata, metadata);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_language_search() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        let docs = [
+            (
+                "https://example.de/altstadt",
+                "Die Altstadt",
+                "Die alten Häuser der Altstadt stehen dicht beieinander und werden von \
+                schmalen Gassen getrennt, in denen sich am Wochenende viele Besucher drängen.",
+            ),
+            (
+                "https://example.jp/tokyo",
+                "東京",
+                "東京都は日本の首都であり、世界で最も人口の多い都市圏の一つです。",
+            ),
+        ];
+        for (url, title, content) in docs {
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title,
+                        domain: "example.com",
+                        url,
+                        content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        // Only matched once stemmed as German / split into bigrams
+        for (query, url) in [
+            ("haus", "https://example.de/altstadt"),
+            ("首都", "https://example.jp/tokyo"),
+            ("altstadt lang:de", "https://example.de/altstadt"),
+        ] {
+            let results = searcher.search(query, &[], &[], 0, 5).await;
+            assert_eq!(results.documents.len(), 1, "no results for `{query}`");
+            assert_eq!(results.documents[0].1.url, url);
+        }
+
+        let results = searcher.search("altstadt lang:ja", &[], &[], 0, 5).await;
+        assert!(results.documents.is_empty());
+
+        // Previews match the query w/ the analyzer the content was indexed with
+        let results = searcher.search("haus", &[], &[], 0, 5).await;
+        let doc = &results.documents[0].1;
+        assert_eq!(doc.lang.as_deref(), Some("de"));
+        let tokenizer = schema::content_tokenizer_for(doc.lang.as_deref());
+        let preview = utils::generate_highlight_preview(&tokenizer, "haus", &doc.content);
+        assert!(preview.contains("<mark>Häuser</mark>"));
+    }
+
+    #[tokio::test]
+    pub async fn test_journal_replay() {
+        let dir = std::env::temp_dir().join(format!("spyglass-index-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Unable to create dir");
+        let backend = IndexBackend::LocalPath(dir.clone());
+
+        let published = Utc::now() - Duration::days(3);
+        let doc_id = {
+            let searcher = Searcher::with_index(&backend, DocFields::as_schema(), None, false)
+                .expect("Unable to open index")
+                .with_batch_config(BatchConfig {
+                    max_pending: 100,
+                    max_delay: std::time::Duration::from_secs(60),
+                    ..Default::default()
+                });
+
+            let doc_id = searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: "Of Mice and Men",
+                        domain: "example.com",
+                        url: "https://example.com/mice_and_men",
+                        content: "A few miles south of Soledad",
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[1],
+                        published_at: Some(published),
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+            // Below the batch thresholds, nothing is committed.
+            assert_eq!(searcher.num_pending_writes(), 1);
+            assert!(searcher.get(&doc_id).await.is_none());
+            doc_id
+            // Searcher is dropped w/o committing, as if the app crashed.
+        };
+
+        let searcher = Searcher::with_index(&backend, DocFields::as_schema(), None, false)
+            .expect("Unable to open index");
+        assert_eq!(searcher.num_pending_writes(), 0);
+        let doc = searcher.get(&doc_id).await.expect("doc was not replayed");
+        assert_eq!(doc.title, "Of Mice and Men");
+        assert_eq!(doc.tags, vec![1]);
+        assert_eq!(
+            doc.published.map(|date| date.timestamp()),
+            Some(published.timestamp())
+        );
+        drop(searcher);
+
+        // Replaying is idempotent
+        let searcher = Searcher::with_index(&backend, DocFields::as_schema(), None, false)
+            .expect("Unable to open index");
+        assert_eq!(searcher.reader().searcher().num_docs(), 1);
+
+        drop(searcher);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    pub async fn test_snapshot() {
+        let dir = std::env::temp_dir().join(format!("spyglass-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        _build_test_index(&mut searcher).await;
+
+        // Pending writes are committed as part of the snapshot
+        let doc_id = searcher
+            .upsert(
+                &DocumentUpdate {
+                    doc_id: None,
+                    title: "Draft",
+                    domain: "example.com",
+                    url: "https://example.com/draft",
+                    content: "Not ready yet",
+                    description: None,
+                    metadata: &Default::default(),
+                    symbols: &[],
+                    segments: &[],
+                    page_offsets: &[],
+                    tags: &[],
+                    published_at: None,
+                    last_modified: None,
+                }
+                .to_document(),
+            )
+            .await
+            .expect("Unable to add doc");
+        searcher
+            .delete_many_by_id(&[doc_id.clone()])
+            .await
+            .expect("Unable to delete");
+        searcher
+            .snapshot(&dir.join("index"))
+            .await
+            .expect("Unable to snapshot");
+        assert_eq!(searcher.num_pending_writes(), 0);
+
+        let copy = Searcher::with_index(
+            &IndexBackend::LocalPath(dir.join("index")),
+            DocFields::as_schema(),
+            None,
+            true,
+        )
+        .expect("Unable to open snapshot");
+        assert_eq!(copy.reader().searcher().num_docs(), 4);
+        assert!(copy.get(&doc_id).await.is_none());
+
+        drop(copy);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,10 +1,18 @@
-use tantivy::query::{BooleanQuery, BoostQuery, Occur, PhraseQuery, Query, TermQuery};
+use std::ops::Bound;
+
+use chrono::{DateTime, Duration, Utc};
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, EmptyQuery, FuzzyTermQuery, Occur, PhraseQuery, Query,
+    RangeQuery, RegexQuery, TermQuery,
+};
 use tantivy::tokenizer::*;
 use tantivy::Score;
 use tantivy::{schema::*, Index};
 
+use crate::language::LanguageAnalyzer;
+use crate::query_parser::{ParsedQuery, QueryTerm};
 use crate::schema::SearchDocument;
-use crate::{Boost, QueryBoost};
+use crate::{to_tantivy_date, Boost, QueryBoost};
 
 use super::DocFields;
 
@@ -32,6 +40,119 @@ fn _boosted_phrase(terms: Vec<(usize, Term)>, boost: Score) -> Box<BoostQuery> {
     ))
 }
 
+/// Terms shorter than this are not prefix matched, they'd match too much.
+const MIN_PREFIX_LEN: usize = 3;
+
+/// Max number of edits for a fuzzy match, based on the term length. Short terms
+/// are only matched exactly since almost anything is a typo away from them.
+fn _fuzzy_distance(term_len: usize) -> u8 {
+    match term_len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Typo tolerant matches for each term, plus prefix matches for the last term
+/// since it's likely still being typed. These match w/ a constant score so
+/// exact matches, which are also matched here, always rank higher.
+fn _fuzzy_terms(terms: &[(usize, Term)], fuzzy_boost: Score, prefix_boost: Score) -> QueryVec {
+    let mut queries: QueryVec = Vec::new();
+    for (idx, (_, term)) in terms.iter().enumerate() {
+        let term_len = term
+            .as_str()
+            .map(|text| text.chars().count())
+            .unwrap_or_default();
+        let distance = _fuzzy_distance(term_len);
+
+        if idx + 1 == terms.len() && term_len >= MIN_PREFIX_LEN && prefix_boost > 0.0 {
+            // A partial word is usually short, so allow fewer typos in it
+            let query = FuzzyTermQuery::new_prefix(term.clone(), distance.min(1), true);
+            queries.push((
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(query), prefix_boost)),
+            ));
+        } else if distance > 0 && fuzzy_boost > 0.0 {
+            let query = FuzzyTermQuery::new(term.clone(), distance, true);
+            queries.push((
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(query), fuzzy_boost)),
+            ));
+        }
+    }
+
+    queries
+}
+
+/// Age buckets used to approximate a recency decay, (max age in days, boost).
+/// Buckets are cumulative so newer documents pick up the boost for each bucket
+/// they fall into.
+const RECENCY_BUCKETS: [(i64, Score); 3] = [(7, 1.0), (30, 0.5), (365, 0.25)];
+
+fn _date_range(
+    field: Field,
+    start: &Option<DateTime<Utc>>,
+    end: &Option<DateTime<Utc>>,
+    boost: Score,
+) -> Box<BoostQuery> {
+    let start = start.as_ref().map_or(Bound::Unbounded, |date| {
+        Bound::Included(to_tantivy_date(date))
+    });
+    let end = end.as_ref().map_or(Bound::Unbounded, |date| {
+        Bound::Excluded(to_tantivy_date(date))
+    });
+
+    Box::new(BoostQuery::new(
+        Box::new(RangeQuery::new_date_bounds(field, start, end)),
+        boost,
+    ))
+}
+
+fn _recency_boost(field: Field, boost: Score) -> Box<BooleanQuery> {
+    let now = Utc::now();
+    let buckets: QueryVec = RECENCY_BUCKETS
+        .iter()
+        .map(|(days, bucket_boost)| {
+            let since = Some(now - Duration::days(*days));
+            let query: Box<dyn Query> = _date_range(field, &since, &None, boost * bucket_boost);
+            (Occur::Should, query)
+        })
+        .collect();
+
+    Box::new(BooleanQuery::new(buckets))
+}
+
+/// Authority buckets used to favor well linked documents, (min pagerank, boost).
+/// Like the recency buckets these are cumulative.
+const AUTHORITY_BUCKETS: [(f64, Score); 3] = [(0.05, 0.25), (0.2, 0.5), (0.5, 1.0)];
+
+/// Authority bucket the pagerank falls in, 0 if it's below all of them.
+pub fn authority_bucket(pagerank: f64) -> usize {
+    AUTHORITY_BUCKETS
+        .iter()
+        .filter(|(min_rank, _)| pagerank >= *min_rank)
+        .count()
+}
+
+fn _authority_boost(field: Field, boost: Score) -> Box<BooleanQuery> {
+    let buckets: QueryVec = AUTHORITY_BUCKETS
+        .iter()
+        .map(|(min_rank, bucket_boost)| {
+            let query: Box<dyn Query> = Box::new(BoostQuery::new(
+                Box::new(RangeQuery::new_f64_bounds(
+                    field,
+                    Bound::Included(*min_rank),
+                    Bound::Unbounded,
+                )),
+                boost * bucket_boost,
+            ));
+            (Occur::Should, query)
+        })
+        .collect();
+
+    Box::new(BooleanQuery::new(buckets))
+}
+
 pub struct QueryOptions {
     /// single term matches in the content
     content_boost: f32,
@@ -41,6 +162,21 @@ pub struct QueryOptions {
     title_boost: f32,
     /// full phrase matches in the title
     title_phrase_boost: f32,
+    /// single term matches in the document description
+    description_boost: f32,
+    /// single term matches in the metadata (author, language, etc.)
+    metadata_boost: f32,
+    /// names of the functions, types, etc. defined in source code
+    symbol_boost: f32,
+    /// single term matches in content analyzed for its language, on top of the
+    /// match in the English analyzed content
+    lang_content_boost: f32,
+    /// documents with a high pagerank, 0.0 to ignore the link graph
+    authority_boost: f32,
+    /// terms within a few typos of a query term, 0.0 to only match exactly
+    fuzzy_boost: f32,
+    /// terms starting w/ the last query term, 0.0 to only match whole words
+    prefix_boost: f32,
 }
 
 impl Default for QueryOptions {
@@ -51,13 +187,140 @@ impl Default for QueryOptions {
             // weight title matches a little more
             title_boost: 2.0,
             title_phrase_boost: 2.5,
+            // descriptions are short & written to summarize the document
+            description_boost: 1.25,
+            metadata_boost: 1.5,
+            // a symbol defined in a file is usually what the user is looking for
+            symbol_boost: 3.0,
+            // kept low since non-English docs usually match the content too
+            lang_content_boost: 0.5,
+            authority_boost: 0.5,
+            // well below a typical exact match
+            fuzzy_boost: 0.25,
+            prefix_boost: 0.5,
+        }
+    }
+}
+
+/// Language specific content fields in the index. Indices created before these
+/// were added to the schema don't have them until they're migrated.
+fn _language_fields(schema: &Schema) -> Vec<Field> {
+    LanguageAnalyzer::ALL
+        .iter()
+        .filter_map(|analyzer| schema.get_field(&analyzer.field_name()))
+        .collect()
+}
+
+/// Field w/ the symbols defined in source code. Indices created before it was
+/// added to the schema don't have it until they're migrated.
+fn _symbols_field(schema: &Schema) -> Option<Field> {
+    schema.get_field("symbols")
+}
+
+/// Field w/ the searchable document metadata. Indices created before it was
+/// added to the schema don't have it until they're migrated.
+fn _metadata_field(schema: &Schema) -> Option<Field> {
+    schema.get_field("metadata")
+}
+
+/// Field w/ the authority score of each document. Indices created before it was
+/// added to the schema don't have it until they're migrated.
+fn _pagerank_field(schema: &Schema) -> Option<Field> {
+    schema.get_field("pagerank")
+}
+
+/// Matches the terms against a single field, as a phrase if there is more than
+/// one term.
+fn _field_match(terms: Vec<(usize, Term)>) -> Option<Box<dyn Query>> {
+    match terms.len() {
+        0 => None,
+        1 => terms.into_iter().next().map(|(_, term)| {
+            Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)) as Box<dyn Query>
+        }),
+        _ => Some(Box::new(PhraseQuery::new_with_offset(terms))),
+    }
+}
+
+/// Converts a clause from the query language into a tantivy query. Returns `None`
+/// if there is nothing to match on, e.g. text made up of only stop words.
+fn _term_to_query(index: &Index, fields: &DocFields, term: &QueryTerm) -> Option<Box<dyn Query>> {
+    let schema = index.schema();
+    let tokenizers = index.tokenizers();
+
+    match term {
+        QueryTerm::Text(text) => {
+            let mut queries: QueryVec = Vec::new();
+            let text_fields = [fields.content, fields.title, fields.description];
+            for field in text_fields
+                .into_iter()
+                .chain(_metadata_field(&schema))
+                .chain(_language_fields(&schema))
+                .chain(_symbols_field(&schema))
+            {
+                for (_, term) in terms_for_field(&schema, tokenizers, text, field) {
+                    queries.push((Occur::Should, _boosted_term(term, 1.0)));
+                }
+            }
+
+            if queries.is_empty() {
+                None
+            } else {
+                Some(Box::new(BooleanQuery::new(queries)))
+            }
+        }
+        QueryTerm::Phrase(phrase) => {
+            let queries: QueryVec = [fields.content, fields.title, fields.description]
+                .into_iter()
+                .chain(_language_fields(&schema))
+                .filter_map(|field| {
+                    _field_match(terms_for_field(&schema, tokenizers, phrase, field))
+                })
+                .map(|query| (Occur::Should, query))
+                .collect();
+
+            if queries.is_empty() {
+                None
+            } else {
+                Some(Box::new(BooleanQuery::new(queries)))
+            }
+        }
+        QueryTerm::Title(title) => {
+            _field_match(terms_for_field(&schema, tokenizers, title, fields.title))
+        }
+        QueryTerm::Domain(domain) => Some(_boosted_term(
+            Term::from_field_text(fields.domain, domain),
+            0.0,
+        )),
+        QueryTerm::Url(prefix) => {
+            let pattern = format!("{}.*", regex::escape(prefix));
+            match RegexQuery::from_pattern(&pattern, fields.url) {
+                Ok(query) => Some(Box::new(BoostQuery::new(Box::new(query), 0.0))),
+                Err(err) => {
+                    log::warn!("Invalid url prefix `{prefix}`: {err}");
+                    None
+                }
+            }
+        }
+        QueryTerm::Lang(code) => match schema.get_field("lang") {
+            Some(field) => Some(_boosted_term(Term::from_field_text(field, code), 0.0)),
+            // Nothing has a language until the index is migrated
+            None => Some(Box::new(EmptyQuery)),
+        },
+        QueryTerm::TagId(tag_id) => Some(_boosted_term(
+            Term::from_field_u64(fields.tags, *tag_id),
+            0.0,
+        )),
+        // Tag doesn't exist, so nothing will match it.
+        QueryTerm::Tag { .. } => Some(Box::new(EmptyQuery)),
+        QueryTerm::Date { field, start, end } => {
+            Some(_date_range(field.as_field(fields), start, end, 0.0))
         }
     }
 }
 
 pub fn build_query(
     index: &Index,
-    query_string: &str,
+    query: &ParsedQuery,
     // Applied filters
     filters: &[QueryBoost],
     // Applied boosts,
@@ -68,9 +331,14 @@ pub fn build_query(
     let schema = index.schema();
     let tokenizers = index.tokenizers();
     let fields = DocFields::as_fields();
+    let query_string = query.text.as_str();
 
     let content_terms = terms_for_field(&schema, tokenizers, query_string, fields.content);
     let title_terms = terms_for_field(&schema, tokenizers, query_string, fields.title);
+    let description_terms = terms_for_field(&schema, tokenizers, query_string, fields.description);
+    let metadata_terms = _metadata_field(&schema)
+        .map(|field| terms_for_field(&schema, tokenizers, query_string, field))
+        .unwrap_or_default();
 
     let term_count = content_terms.len();
 
@@ -93,6 +361,18 @@ pub fn build_query(
         term_query.push((Occur::Should, _boosted_phrase(title_terms.clone(), boost)));
     }
 
+    // Partial words & typos, matched in the content & title only
+    term_query.extend(_fuzzy_terms(
+        &content_terms,
+        opts.fuzzy_boost * opts.content_boost,
+        opts.prefix_boost * opts.content_boost,
+    ));
+    term_query.extend(_fuzzy_terms(
+        &title_terms,
+        opts.fuzzy_boost * opts.title_boost,
+        opts.prefix_boost * opts.title_boost,
+    ));
+
     for (_position, term) in content_terms {
         term_query.push((Occur::Should, _boosted_term(term, opts.content_boost)));
     }
@@ -101,6 +381,41 @@ pub fn build_query(
         term_query.push((Occur::Should, _boosted_term(term, opts.title_boost)));
     }
 
+    for (_position, term) in description_terms {
+        term_query.push((Occur::Should, _boosted_term(term, opts.description_boost)));
+    }
+
+    for (_position, term) in metadata_terms {
+        term_query.push((Occur::Should, _boosted_term(term, opts.metadata_boost)));
+    }
+
+    if let Some(field) = _symbols_field(&schema) {
+        for (_position, term) in terms_for_field(&schema, tokenizers, query_string, field) {
+            term_query.push((Occur::Should, _boosted_term(term, opts.symbol_boost)));
+        }
+    }
+
+    // Content in other languages is also indexed w/ the analyzer for its
+    // language, so the query is analyzed the same way for each of them.
+    for field in _language_fields(&schema) {
+        let lang_terms = terms_for_field(&schema, tokenizers, query_string, field);
+        if lang_terms.len() > 1 {
+            let boost = opts.content_phrase_boost * lang_terms.len() as f32;
+            term_query.push((Occur::Should, _boosted_phrase(lang_terms.clone(), boost)));
+        }
+
+        for (_position, term) in lang_terms {
+            term_query.push((Occur::Should, _boosted_term(term, opts.lang_content_boost)));
+        }
+    }
+
+    // Queries made up of only field clauses (e.g. `lens:rust`) match everything that
+    // passes those clauses.
+    if term_query.is_empty() && query.is_structured() {
+        term_query.push((Occur::Should, Box::new(AllQuery)));
+    }
+
+    let mut rank_only: QueryVec = Vec::new();
     // Boost fields that happen to have a value, such as
     // - Tags that might be represented by search terms (e.g. "repository" or "file")
     // - Certain URLs or documents we want to focus on
@@ -128,6 +443,26 @@ pub fn build_query(
                     continue;
                 }
             }
+            Boost::DateRange { field, start, end } => {
+                _date_range(field.as_field(&fields), start, end, boost.value)
+            }
+            Boost::Recency(field) => {
+                // Added alongside the term matches so that recency only affects the
+                // ranking of documents that already match.
+                rank_only.push((
+                    Occur::Should,
+                    _recency_boost(field.as_field(&fields), boost.value),
+                ));
+                continue;
+            }
+            Boost::Opened(doc_id) => {
+                // Only changes the ranking of documents that already match
+                rank_only.push((
+                    Occur::Should,
+                    _boosted_term(Term::from_field_text(fields.id, doc_id), boost.value),
+                ));
+                continue;
+            }
         };
 
         term_query.push((Occur::Should, term));
@@ -135,6 +470,32 @@ pub fn build_query(
 
     // Must hit at least one of the terms
     let mut combined: QueryVec = vec![(Occur::Must, Box::new(BooleanQuery::new(term_query)))];
+    combined.extend(rank_only);
+    if let Some(field) = _pagerank_field(&schema).filter(|_| opts.authority_boost > 0.0) {
+        combined.push((Occur::Should, _authority_boost(field, opts.authority_boost)));
+    }
+
+    // Clauses from the query language
+    for group in &query.required {
+        let mut group_query: QueryVec = group
+            .iter()
+            .filter_map(|term| _term_to_query(index, &fields, term))
+            .map(|query| (Occur::Should, query))
+            .collect();
+
+        match group_query.len() {
+            0 => {}
+            1 => combined.push((Occur::Must, group_query.remove(0).1)),
+            _ => combined.push((Occur::Must, Box::new(BooleanQuery::new(group_query)))),
+        }
+    }
+
+    for term in &query.excluded {
+        if let Some(query) = _term_to_query(index, &fields, term) {
+            combined.push((Occur::MustNot, query));
+        }
+    }
+
     // Must have one of these, will filter out stuff that doesn't
     for filter in filters {
         let term = match &filter.field {
@@ -172,6 +533,11 @@ pub fn build_query(
                     continue;
                 }
             }
+            Boost::DateRange { field, start, end } => {
+                _date_range(field.as_field(&fields), start, end, 0.0)
+            }
+            // Only considered in boosts
+            Boost::Recency(_) | Boost::Opened(_) => continue,
         };
 
         combined.push((Occur::Must, term));
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+use crate::language::normalize_language;
+use crate::DateField;
+
+/// A single clause in a search query
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryTerm {
+    /// Free text matched against the title & content
+    Text(String),
+    /// Quoted phrase matched against the title & content
+    Phrase(String),
+    /// `title:<value>`
+    Title(String),
+    /// `domain:<value>`, exact match on the document domain
+    Domain(String),
+    /// `url:<value>`, matches any document whose url starts with the value
+    Url(String),
+    /// `lang:<code>`, documents detected to be in the language w/ this ISO
+    /// 639-1 code
+    Lang(String),
+    /// `tag:<label>=<value>` or `lens:<value>`, needs to be mapped to a tag id
+    /// before it'll match anything.
+    Tag { label: String, value: String },
+    /// A tag that has been resolved through the tag table
+    TagId(u64),
+    /// `modified:<range>`, `published:<range>`, `after:<date>` & `before:<date>`
+    Date {
+        field: DateField,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    },
+}
+
+/// Search query parsed from the query language used in the search bar, e.g.
+/// `title:"release notes" domain:github.com -tag:type=issue rust OR python`
+///
+/// * Free text is used for ranking, at least one word needs to match.
+/// * Field clauses & phrases are required, clauses joined by `OR` form a group
+///   where at least one of them needs to match.
+/// * Clauses prefixed with `-` are excluded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// Text used to rank results & generate previews
+    pub text: String,
+    /// Each group must have at least one matching clause
+    pub required: Vec<Vec<QueryTerm>>,
+    /// Documents matching any of these clauses are dropped
+    pub excluded: Vec<QueryTerm>,
+}
+
+impl ParsedQuery {
+    pub fn parse(query: &str) -> Self {
+        parse_query(query)
+    }
+
+    /// Whether there is anything besides free text in this query.
+    pub fn is_structured(&self) -> bool {
+        !self.required.is_empty() || !self.excluded.is_empty()
+    }
+
+    /// List of (label, value) tag pairs referenced in this query that still need
+    /// to be resolved.
+    pub fn tag_pairs(&self) -> Vec<(String, String)> {
+        self.required
+            .iter()
+            .flatten()
+            .chain(self.excluded.iter())
+            .filter_map(|term| match term {
+                QueryTerm::Tag { label, value } => Some((label.clone(), value.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replace tags w/ their ids. Tags that can't be found are left as is & will
+    /// not match any document.
+    pub fn resolve_tags(&mut self, tag_ids: &HashMap<(String, String), u64>) {
+        let resolve = |term: &mut QueryTerm| {
+            if let QueryTerm::Tag { label, value } = term {
+                let key = (label.to_lowercase(), value.to_lowercase());
+                if let Some(id) = tag_ids.get(&key) {
+                    *term = QueryTerm::TagId(*id);
+                }
+            }
+        };
+
+        self.required.iter_mut().flatten().for_each(resolve);
+        self.excluded.iter_mut().for_each(resolve);
+    }
+}
+
+/// Raw clause pulled out of the query string
+struct Token {
+    negated: bool,
+    key: Option<String>,
+    value: String,
+    quoted: bool,
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let chars = query.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        if chars[idx].is_whitespace() {
+            idx += 1;
+            continue;
+        }
+
+        let negated = chars[idx] == '-' && idx + 1 < chars.len() && !chars[idx + 1].is_whitespace();
+        if negated {
+            idx += 1;
+        }
+
+        let mut key = None;
+        let mut value = String::new();
+        let mut quoted = false;
+        while idx < chars.len() && !chars[idx].is_whitespace() {
+            let c = chars[idx];
+            if c == '"' {
+                // Read until the closing quote, or the end of the query.
+                quoted = true;
+                idx += 1;
+                while idx < chars.len() && chars[idx] != '"' {
+                    value.push(chars[idx]);
+                    idx += 1;
+                }
+            } else if c == ':' && key.is_none() && !quoted && !value.is_empty() {
+                key = Some(std::mem::take(&mut value));
+            } else {
+                value.push(c);
+            }
+            idx += 1;
+        }
+
+        tokens.push(Token {
+            negated,
+            key,
+            value,
+            quoted,
+        });
+    }
+
+    tokens
+}
+
+/// Parses dates in the form of `YYYY-MM-DD`, `YYYY-MM` or `YYYY`, returning the
+/// start of the period along with the start of the next one.
+fn parse_date(value: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let parts = value
+        .split('-')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+
+    let (start, end) = match parts.as_slice() {
+        [year] => (
+            NaiveDate::from_ymd_opt(*year as i32, 1, 1)?,
+            NaiveDate::from_ymd_opt(*year as i32 + 1, 1, 1)?,
+        ),
+        [year, month] => {
+            let start = NaiveDate::from_ymd_opt(*year as i32, *month, 1)?;
+            let end = if *month == 12 {
+                NaiveDate::from_ymd_opt(*year as i32 + 1, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(*year as i32, month + 1, 1)?
+            };
+            (start, end)
+        }
+        [year, month, day] => {
+            let start = NaiveDate::from_ymd_opt(*year as i32, *month, *day)?;
+            (start, start + Duration::days(1))
+        }
+        _ => return None,
+    };
+
+    let to_utc = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|date| Utc.from_local_datetime(&date).single())
+    };
+
+    Some((to_utc(start)?, to_utc(end)?))
+}
+
+/// Parses a date range, `<start>..<end>` with either end optional, or a single
+/// date which covers the whole period.
+fn parse_date_range(value: &str) -> Option<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)> {
+    if let Some((start, end)) = value.split_once("..") {
+        let start = if start.is_empty() {
+            None
+        } else {
+            Some(parse_date(start)?.0)
+        };
+
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(parse_date(end)?.1)
+        };
+
+        if start.is_none() && end.is_none() {
+            return None;
+        }
+
+        Some((start, end))
+    } else {
+        let (start, end) = parse_date(value)?;
+        Some((Some(start), Some(end)))
+    }
+}
+
+fn token_to_term(token: &Token) -> QueryTerm {
+    let value = token.value.trim();
+    let key = match &token.key {
+        Some(key) if !value.is_empty() => key.to_lowercase(),
+        // Not a field, treat as text
+        _ => {
+            let raw = match &token.key {
+                Some(key) => format!("{key}:{value}"),
+                None => value.to_string(),
+            };
+
+            return if token.quoted {
+                QueryTerm::Phrase(raw)
+            } else {
+                QueryTerm::Text(raw)
+            };
+        }
+    };
+
+    let date_term = |field: DateField, range: Option<(Option<_>, Option<_>)>| {
+        range.map(|(start, end)| QueryTerm::Date { field, start, end })
+    };
+
+    let term = match key.as_str() {
+        "title" => Some(QueryTerm::Title(value.to_string())),
+        "domain" => Some(QueryTerm::Domain(value.to_lowercase())),
+        "url" => Some(QueryTerm::Url(value.to_string())),
+        "lang" => normalize_language(value).map(QueryTerm::Lang),
+        "lens" => Some(QueryTerm::Tag {
+            label: "lens".into(),
+            value: value.to_string(),
+        }),
+        "tag" => value
+            .split_once('=')
+            .filter(|(label, value)| !label.is_empty() && !value.is_empty())
+            .map(|(label, value)| QueryTerm::Tag {
+                label: label.to_lowercase(),
+                value: value.to_string(),
+            }),
+        "modified" => date_term(DateField::LastModified, parse_date_range(value)),
+        "published" => date_term(DateField::Published, parse_date_range(value)),
+        "after" => date_term(
+            DateField::LastModified,
+            parse_date(value).map(|(start, _)| (Some(start), None)),
+        ),
+        "before" => date_term(
+            DateField::LastModified,
+            parse_date(value).map(|(start, _)| (None, Some(start))),
+        ),
+        _ => None,
+    };
+
+    // Unknown fields or values that can't be parsed (e.g. urls) are left as text
+    term.unwrap_or_else(|| {
+        let raw = format!("{}:{}", token.key.clone().unwrap_or_default(), value);
+        if token.quoted {
+            QueryTerm::Phrase(raw)
+        } else {
+            QueryTerm::Text(raw)
+        }
+    })
+}
+
+/// Parses the query string into free text & a list of required/excluded clauses.
+pub fn parse_query(query: &str) -> ParsedQuery {
+    let mut groups: Vec<Vec<QueryTerm>> = Vec::new();
+    let mut excluded = Vec::new();
+    let mut join_next = false;
+
+    for token in tokenize(query) {
+        if !token.negated && !token.quoted && token.key.is_none() && token.value == "OR" {
+            join_next = !groups.is_empty();
+            continue;
+        }
+
+        let term = token_to_term(&token);
+        if token.negated {
+            excluded.push(term);
+        } else if join_next {
+            if let Some(group) = groups.last_mut() {
+                group.push(term);
+            }
+        } else {
+            groups.push(vec![term]);
+        }
+
+        join_next = false;
+    }
+
+    let mut text = Vec::new();
+    let mut required = Vec::new();
+    for group in groups {
+        for term in &group {
+            match term {
+                QueryTerm::Text(value) | QueryTerm::Phrase(value) => text.push(value.clone()),
+                _ => {}
+            }
+        }
+
+        // Plain words are only used for ranking
+        if !group.iter().all(|term| matches!(term, QueryTerm::Text(_))) {
+            required.push(group);
+        }
+    }
+
+    ParsedQuery {
+        text: text.join(" "),
+        required,
+        excluded,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{parse_query, QueryTerm};
+    use crate::DateField;
+
+    #[test]
+    fn test_parse_free_text() {
+        let query = parse_query("salinas river");
+        assert_eq!(query.text, "salinas river");
+        assert!(!query.is_structured());
+
+        // Plain words joined by OR are already optional
+        let query = parse_query("rust OR python");
+        assert_eq!(query.text, "rust python");
+        assert!(query.required.is_empty());
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let query = parse_query(
+            r#"title:"release notes" domain:GitHub.com url:https://docs.rs/ "async trait" rust"#,
+        );
+
+        assert_eq!(query.text, "async trait rust");
+        assert_eq!(
+            query.required,
+            vec![
+                vec![QueryTerm::Title("release notes".into())],
+                vec![QueryTerm::Domain("github.com".into())],
+                vec![QueryTerm::Url("https://docs.rs/".into())],
+                vec![QueryTerm::Phrase("async trait".into())],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        let query = parse_query("lens:rust OR tag:type=issue -domain:example.com -spam tokio");
+        assert_eq!(query.text, "tokio");
+        assert_eq!(
+            query.required,
+            vec![vec![
+                QueryTerm::Tag {
+                    label: "lens".into(),
+                    value: "rust".into()
+                },
+                QueryTerm::Tag {
+                    label: "type".into(),
+                    value: "issue".into()
+                },
+            ]]
+        );
+        assert_eq!(
+            query.excluded,
+            vec![
+                QueryTerm::Domain("example.com".into()),
+                QueryTerm::Text("spam".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let query = parse_query("modified:2023-01..2023-02-15 before:2020");
+        assert_eq!(
+            query.required,
+            vec![
+                vec![QueryTerm::Date {
+                    field: DateField::LastModified,
+                    start: Some(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap()),
+                    end: Some(Utc.with_ymd_and_hms(2023, 2, 16, 0, 0, 0).unwrap()),
+                }],
+                vec![QueryTerm::Date {
+                    field: DateField::LastModified,
+                    start: None,
+                    end: Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+                }],
+            ]
+        );
+
+        // Invalid dates are left as text
+        let query = parse_query("published:yesterday");
+        assert_eq!(query.text, "published:yesterday");
+        assert!(query.required.is_empty());
+    }
+
+    #[test]
+    fn test_parse_lang() {
+        let query = parse_query("lang:DE -lang:fr-CA wetter");
+        assert_eq!(query.text, "wetter");
+        assert_eq!(query.required, vec![vec![QueryTerm::Lang("de".into())]]);
+        assert_eq!(query.excluded, vec![QueryTerm::Lang("fr".into())]);
+
+        // Not a language code
+        let query = parse_query("lang:klingon");
+        assert_eq!(query.text, "lang:klingon");
+    }
+
+    #[test]
+    fn test_resolve_tags() {
+        let mut query = parse_query("lens:Rust -tag:type=issue tag:owner=nobody");
+        let mut tag_ids = HashMap::new();
+        tag_ids.insert(("lens".to_string(), "rust".to_string()), 1);
+        tag_ids.insert(("type".to_string(), "issue".to_string()), 2);
+
+        assert_eq!(query.tag_pairs().len(), 3);
+        query.resolve_tags(&tag_ids);
+        assert_eq!(
+            query.required,
+            vec![
+                vec![QueryTerm::TagId(1)],
+                vec![QueryTerm::Tag {
+                    label: "owner".into(),
+                    value: "nobody".into()
+                }]
+            ]
+        );
+        assert_eq!(query.excluded, vec![QueryTerm::TagId(2)]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use crate::{RetrievedDocument, Score};
+
+/// Constant used by reciprocal rank fusion to dampen the impact of top ranks.
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// How results from the lexical & vector searches are combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FusionStrategy {
+    /// Sums `1 / (k + rank)` for each list a document appears in.
+    ReciprocalRank { k: f32 },
+    /// Min-max normalizes the scores of each list and combines them, where
+    /// `lexical_weight` (0.0 - 1.0) is the weight given to the lexical score.
+    WeightedScore { lexical_weight: f32 },
+}
+
+impl Default for FusionStrategy {
+    fn default() -> Self {
+        FusionStrategy::ReciprocalRank { k: DEFAULT_RRF_K }
+    }
+}
+
+/// Fuses the lexical & vector results into a single ranked list. Both lists are
+/// expected to be sorted by score, best first.
+pub fn fuse_results(
+    strategy: FusionStrategy,
+    lexical: Vec<(Score, RetrievedDocument)>,
+    vector: Vec<(Score, RetrievedDocument)>,
+) -> Vec<(Score, RetrievedDocument)> {
+    let (lexical_scores, vector_scores) = match strategy {
+        FusionStrategy::ReciprocalRank { k } => (rank_scores(&lexical, k), rank_scores(&vector, k)),
+        FusionStrategy::WeightedScore { lexical_weight } => {
+            let lexical_weight = lexical_weight.clamp(0.0, 1.0);
+            let mut lexical_scores = normalized_scores(&lexical);
+            lexical_scores
+                .iter_mut()
+                .for_each(|score| *score *= lexical_weight);
+            let mut vector_scores = normalized_scores(&vector);
+            vector_scores
+                .iter_mut()
+                .for_each(|score| *score *= 1.0 - lexical_weight);
+            (lexical_scores, vector_scores)
+        }
+    };
+
+    let mut fused: HashMap<String, (Score, RetrievedDocument)> = HashMap::new();
+    for (scores, results) in [(lexical_scores, lexical), (vector_scores, vector)] {
+        for (score, (_, doc)) in scores.into_iter().zip(results) {
+            fused
+                .entry(doc.doc_id.clone())
+                .and_modify(|(existing, _)| *existing += score)
+                .or_insert((score, doc));
+        }
+    }
+
+    let mut fused = fused.into_values().collect::<Vec<_>>();
+    fused.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1.doc_id.cmp(&b.1.doc_id))
+    });
+    fused
+}
+
+fn rank_scores(results: &[(Score, RetrievedDocument)], k: f32) -> Vec<Score> {
+    (0..results.len())
+        .map(|rank| 1.0 / (k + rank as f32 + 1.0))
+        .collect()
+}
+
+fn normalized_scores(results: &[(Score, RetrievedDocument)]) -> Vec<Score> {
+    let min = results
+        .iter()
+        .map(|(score, _)| *score)
+        .fold(f32::MAX, f32::min);
+    let max = results
+        .iter()
+        .map(|(score, _)| *score)
+        .fold(f32::MIN, f32::max);
+
+    results
+        .iter()
+        .map(|(score, _)| {
+            if max > min {
+                (score - min) / (max - min)
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    use super::{fuse_results, FusionStrategy};
+    use crate::client::Searcher;
+    use crate::query_parser::ParsedQuery;
+    use crate::schema::{DocFields, DocumentUpdate, ToDocument};
+    use crate::similarity::HashEmbedder;
+    use crate::{IndexBackend, RetrievedDocument, SearchTrait, WriteTrait};
+
+    #[derive(Deserialize)]
+    struct EvalCorpus {
+        documents: Vec<EvalDocument>,
+        queries: Vec<EvalQuery>,
+    }
+
+    #[derive(Deserialize)]
+    struct EvalDocument {
+        id: String,
+        title: String,
+        url: String,
+        content: String,
+    }
+
+    #[derive(Deserialize)]
+    struct EvalQuery {
+        query: String,
+        relevant: String,
+    }
+
+    fn doc(doc_id: &str) -> RetrievedDocument {
+        RetrievedDocument {
+            doc_id: doc_id.into(),
+            domain: String::new(),
+            title: String::new(),
+            description: String::new(),
+            content: String::new(),
+            url: String::new(),
+            tags: Vec::new(),
+            published: None,
+            last_modified: None,
+            metadata: Default::default(),
+            symbols: Vec::new(),
+            segments: Vec::new(),
+            page_offsets: Vec::new(),
+            lang: None,
+        }
+    }
+
+    fn ranked_ids(results: &[(f32, RetrievedDocument)]) -> Vec<&str> {
+        results.iter().map(|(_, doc)| doc.doc_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let lexical = vec![(9.0, doc("a")), (5.0, doc("b")), (1.0, doc("c"))];
+        let vector = vec![(0.9, doc("c")), (0.8, doc("b")), (0.1, doc("d"))];
+
+        let fused = fuse_results(FusionStrategy::default(), lexical, vector);
+        // Documents found by both retrievers are ranked above the rest
+        assert_eq!(ranked_ids(&fused), vec!["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn test_weighted_score_fusion() {
+        let lexical = vec![(9.0, doc("a")), (1.0, doc("b"))];
+        let vector = vec![(0.9, doc("b")), (0.1, doc("a"))];
+
+        let fused = fuse_results(
+            FusionStrategy::WeightedScore {
+                lexical_weight: 1.0,
+            },
+            lexical.clone(),
+            vector.clone(),
+        );
+        assert_eq!(ranked_ids(&fused), vec!["a", "b"]);
+
+        let fused = fuse_results(
+            FusionStrategy::WeightedScore {
+                lexical_weight: 0.0,
+            },
+            lexical,
+            vector,
+        );
+        assert_eq!(ranked_ids(&fused), vec!["b", "a"]);
+    }
+
+    /// Runs the judged queries in the fixture corpus against both ranking modes,
+    /// making sure hybrid ranking doesn't regress relevance.
+    #[tokio::test]
+    async fn test_hybrid_ranking_eval() {
+        let corpus: EvalCorpus =
+            serde_json::from_str(include_str!("../../../fixtures/search/eval_corpus.json"))
+                .expect("Invalid eval corpus");
+
+        let embedder = Arc::new(HashEmbedder::default());
+        let searcher = Searcher::with_index(
+            &IndexBackend::Memory,
+            DocFields::as_schema(),
+            Some(embedder),
+            false,
+        )
+        .expect("Unable to open index");
+        for eval_doc in &corpus.documents {
+            let domain = url::Url::parse(&eval_doc.url)
+                .ok()
+                .and_then(|url| url.host_str().map(|host| host.to_string()))
+                .unwrap_or_default();
+
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: Some(eval_doc.id.clone()),
+                        title: &eval_doc.title,
+                        domain: &domain,
+                        url: &eval_doc.url,
+                        content: &eval_doc.content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save index");
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let mut lexical_mrr = 0.0;
+        let mut hybrid_mrr = 0.0;
+        for eval_query in &corpus.queries {
+            let lexical = searcher.search(&eval_query.query, &[], &[], 0, 5).await;
+            let hybrid = searcher
+                .hybrid_search(
+                    &ParsedQuery::parse(&eval_query.query),
+                    &[],
+                    &[],
+                    0,
+                    5,
+                    FusionStrategy::default(),
+                )
+                .await
+                .expect("Unable to run hybrid search");
+
+            let hybrid_ids = ranked_ids(&hybrid.documents);
+            assert!(
+                hybrid_ids.contains(&eval_query.relevant.as_str()),
+                "`{}` missing from hybrid results for `{}`",
+                eval_query.relevant,
+                eval_query.query
+            );
+
+            lexical_mrr += reciprocal_rank(&ranked_ids(&lexical.documents), &eval_query.relevant);
+            hybrid_mrr += reciprocal_rank(&hybrid_ids, &eval_query.relevant);
+        }
+
+        let num_queries = corpus.queries.len() as f32;
+        lexical_mrr /= num_queries;
+        hybrid_mrr /= num_queries;
+        assert!(hybrid_mrr >= 0.8, "hybrid MRR too low: {hybrid_mrr}");
+        assert!(
+            hybrid_mrr + 0.1 >= lexical_mrr,
+            "hybrid MRR {hybrid_mrr} regressed vs lexical {lexical_mrr}"
+        );
+    }
+
+    fn reciprocal_rank(ranked: &[&str], relevant: &str) -> f32 {
+        ranked
+            .iter()
+            .position(|doc_id| *doc_id == relevant)
+            .map(|pos| 1.0 / (pos as f32 + 1.0))
+            .unwrap_or_default()
+    }
+}
@@ -1,5 +1,7 @@
 use super::stop_word_filter::StopWordFilter;
 use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use tantivy::{
     directory::MmapDirectory,
@@ -8,22 +10,76 @@ use tantivy::{
         AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer,
         TextAnalyzer,
     },
-    Index,
+    Index, IndexSettings,
 };
 use uuid::Uuid;
 
 use crate::client::SPYGLASS_NS;
+use crate::language::{detect_language, normalize_language, LanguageAnalyzer};
+use crate::symbol_tokenizer::SymbolTokenizer;
+use crate::to_tantivy_date;
 
 pub type FieldName = String;
 
 pub const TOKENIZER_NAME: &str = "spyglass_tokenizer_en";
+pub const SYMBOL_TOKENIZER_NAME: &str = "spyglass_tokenizer_symbol";
 
 /// The current schema version
-pub const SCHEMA_VERSION: &str = "4";
+pub const SCHEMA_VERSION: &str = "11";
+
+/// Well known keys in the document metadata map
+pub const META_AUTHOR: &str = "author";
+pub const META_LANGUAGE: &str = "language";
+pub const META_PAGE_COUNT: &str = "page_count";
+pub const META_MIME_TYPE: &str = "mime_type";
+/// Separates the key & value of an entry in the metadata field
+const METADATA_SEPARATOR: &str = ": ";
+
+/// Metadata is stored as one `key: value` entry per value in the metadata field.
+pub fn metadata_entry(key: &str, value: &str) -> String {
+    format!("{key}{METADATA_SEPARATOR}{value}")
+}
+
+pub fn parse_metadata_entry(entry: &str) -> Option<(String, String)> {
+    entry
+        .split_once(METADATA_SEPARATOR)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+}
+
+/// A timestamped segment of an audio transcript.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TranscriptSegment {
+    /// Start of the segment, in milliseconds from the start of the audio
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// Transcript segments are stored as one `start_ms end_ms text` entry per
+/// value in the segments field.
+pub fn segment_entry(segment: &TranscriptSegment) -> String {
+    format!("{} {} {}", segment.start_ms, segment.end_ms, segment.text)
+}
+
+pub fn parse_segment_entry(entry: &str) -> Option<TranscriptSegment> {
+    let mut parts = entry.splitn(3, ' ');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(start), Some(end), Some(text)) => Some(TranscriptSegment {
+            start_ms: start.parse().ok()?,
+            end_ms: end.parse().ok()?,
+            text: text.to_string(),
+        }),
+        _ => None,
+    }
+}
 pub struct SchemaMapping {
     pub text_fields: Option<Vec<(FieldName, TextOptions)>>,
     pub date_fields: Option<Vec<(FieldName, DateOptions)>>,
     pub unsigned_fields: Option<Vec<(FieldName, NumericOptions)>>,
+    /// Fields added since schema v4, in the order they were added. These come
+    /// after all other fields so the ids of existing fields never change, which
+    /// lets an index w/ an older schema keep serving while it's migrated.
+    pub appended_fields: Option<Vec<FieldEntry>>,
 }
 pub trait SearchDocument {
     fn as_field_vec() -> SchemaMapping;
@@ -54,13 +110,25 @@ pub fn mapping_to_schema(mapping: &SchemaMapping) -> Schema {
             schema_builder.add_u64_field(name, opts.clone());
         }
     }
+
+    if let Some(fields) = &mapping.appended_fields {
+        for entry in fields {
+            schema_builder.add_field(entry.clone());
+        }
+    }
     schema_builder.build()
 }
 
-/// Helper used to create and configure an index from a path
+/// Helper used to create and configure an index from a path. An existing index
+/// is opened w/ the schema it was created with, which may be older than
+/// `schema` until it's migrated. `schema` is only used to create a new index.
 pub fn initialize_index(schema: Schema, index_path: &PathBuf) -> anyhow::Result<Index> {
     let dir = MmapDirectory::open(index_path)?;
-    let index = Index::open_or_create(dir, schema)?;
+    let index = if Index::exists(&dir)? {
+        Index::open(dir)?
+    } else {
+        Index::create(dir, schema, IndexSettings::default())?
+    };
     register_tokenizer(&index);
 
     Ok(index)
@@ -74,18 +142,149 @@ pub fn initialize_in_memory_index(schema: Schema) -> Index {
     index
 }
 
-/// Register custom tokenizer
-pub fn register_tokenizer(index: &Index) {
-    let full_content_tokenizer_en = TextAnalyzer::from(SimpleTokenizer)
+/// Analyzer used for the content field, also used to highlight matches in
+/// result previews.
+pub fn content_tokenizer() -> TextAnalyzer {
+    TextAnalyzer::from(SimpleTokenizer)
         .filter(RemoveLongFilter::limit(40))
         .filter(LowerCaser)
         .filter(AsciiFoldingFilter)
         .filter(StopWordFilter::default())
-        .filter(Stemmer::new(Language::English));
+        .filter(Stemmer::new(Language::English))
+}
+
+/// Analyzer the content of a document in `lang` is indexed w/, used to match
+/// the query against it for result previews.
+pub fn content_tokenizer_for(lang: Option<&str>) -> TextAnalyzer {
+    lang.and_then(LanguageAnalyzer::for_language)
+        .map(|analyzer| analyzer.text_analyzer())
+        .unwrap_or_else(content_tokenizer)
+}
 
+/// Register custom tokenizer
+pub fn register_tokenizer(index: &Index) {
     index
         .tokenizers()
-        .register(TOKENIZER_NAME, full_content_tokenizer_en);
+        .register(TOKENIZER_NAME, content_tokenizer());
+
+    for analyzer in LanguageAnalyzer::ALL {
+        index
+            .tokenizers()
+            .register(&analyzer.tokenizer_name(), analyzer.text_analyzer());
+    }
+
+    let symbol_tokenizer = TextAnalyzer::from(SymbolTokenizer)
+        .filter(RemoveLongFilter::limit(100))
+        .filter(LowerCaser);
+    index
+        .tokenizers()
+        .register(SYMBOL_TOKENIZER_NAME, symbol_tokenizer);
+}
+
+/// Only the start of the content is added to the vocabulary used for
+/// suggestions, which is plenty to pick up the words used in a document.
+const VOCAB_SAMPLE_LEN: usize = 10_000;
+
+/// Fills in fields derived from the rest of the document, e.g. when it's
+/// created or migrated from an older schema.
+pub fn add_derived_fields(schema: &Schema, doc: &mut Document) {
+    add_language_fields(schema, doc);
+    add_vocab_field(schema, doc);
+}
+
+/// Rebuilds a document from the values stored in the index. Fields derived from
+/// the content are only partly stored, so they're generated again the same way
+/// `to_document` does.
+pub fn regenerate_document(schema: &Schema, stored: &Document) -> Document {
+    let derived = [schema.get_field("lang"), schema.get_field("vocab")];
+    let mut doc = Document::default();
+    for value in stored.field_values() {
+        if !derived.contains(&Some(value.field())) {
+            doc.add_field_value(value.field(), value.value().clone());
+        }
+    }
+
+    add_derived_fields(schema, &mut doc);
+    doc
+}
+
+/// Adds the words in the title & content, as typed, to the vocabulary field
+/// used for autocomplete & spelling suggestions.
+fn add_vocab_field(schema: &Schema, doc: &mut Document) {
+    let (vocab_field, title_field, content_field) = match (
+        schema.get_field("vocab"),
+        schema.get_field("title"),
+        schema.get_field("content"),
+    ) {
+        (Some(vocab), Some(title), Some(content)) => (vocab, title, content),
+        _ => return,
+    };
+
+    if doc.get_first(vocab_field).is_some() {
+        return;
+    }
+
+    let mut values = Vec::new();
+    for field in [title_field, content_field] {
+        if let Some(text) = doc.get_first(field).and_then(|value| value.as_text()) {
+            let mut end = text.len().min(VOCAB_SAMPLE_LEN);
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            values.push(text[..end].to_string());
+        }
+    }
+
+    for value in values {
+        doc.add_text(vocab_field, value);
+    }
+}
+
+/// Adds the language of the document & indexes its content w/ the analyzer for
+/// that language. The language is detected from the content, falling back to
+/// the language declared in the metadata. Documents that already have a
+/// language are left as is.
+fn add_language_fields(schema: &Schema, doc: &mut Document) {
+    let (lang_field, content_field, metadata_field) = match (
+        schema.get_field("lang"),
+        schema.get_field("content"),
+        schema.get_field("metadata"),
+    ) {
+        (Some(lang), Some(content), Some(metadata)) => (lang, content, metadata),
+        _ => return,
+    };
+
+    if doc.get_first(lang_field).is_some() {
+        return;
+    }
+
+    let content = doc
+        .get_first(content_field)
+        .and_then(|value| value.as_text())
+        .unwrap_or_default()
+        .to_string();
+
+    let declared = doc
+        .get_all(metadata_field)
+        .filter_map(|value| value.as_text())
+        .filter_map(parse_metadata_entry)
+        .find(|(key, _)| key == META_LANGUAGE)
+        .and_then(|(_, value)| normalize_language(&value));
+
+    let lang = match detect_language(&content) {
+        Some(lang) => lang.to_string(),
+        None => match declared {
+            Some(lang) => lang,
+            None => return,
+        },
+    };
+
+    if let Some(field) = LanguageAnalyzer::for_language(&lang)
+        .and_then(|analyzer| schema.get_field(&analyzer.field_name()))
+    {
+        doc.add_text(field, &content);
+    }
+    doc.add_text(lang_field, lang);
 }
 
 pub trait ToDocument {
@@ -100,6 +299,17 @@ pub struct DocumentUpdate<'a> {
     pub domain: &'a str,
     pub url: &'a str,
     pub content: &'a str,
+    /// Short summary provided by the document, e.g. from a `<meta>` tag
+    pub description: Option<&'a str>,
+    /// Extracted metadata such as the author, language, page count, etc.
+    pub metadata: &'a BTreeMap<String, String>,
+    /// Names of the functions, types, etc. defined in source code
+    pub symbols: &'a [String],
+    /// Timestamped segments of an audio transcript, the full transcript is the
+    /// content
+    pub segments: &'a [TranscriptSegment],
+    /// Byte offset in the content where each page starts, for paged documents
+    pub page_offsets: &'a [u64],
     pub tags: &'a [i64],
     pub published_at: Option<chrono::DateTime<Utc>>,
     pub last_modified: Option<chrono::DateTime<Utc>>,
@@ -124,25 +334,143 @@ impl<'a> ToDocument for DocumentUpdate<'a> {
         doc.add_text(fields.id, &doc_id);
         doc.add_text(fields.title, self.title);
         doc.add_text(fields.url, self.url);
+        if let Some(description) = self.description.filter(|desc| !desc.trim().is_empty()) {
+            doc.add_text(fields.description, description);
+        }
+
+        for (key, value) in self.metadata {
+            doc.add_text(fields.metadata, metadata_entry(key, value));
+        }
+
+        for symbol in self.symbols {
+            doc.add_text(fields.symbols, symbol);
+        }
+
+        for segment in self.segments {
+            doc.add_text(fields.segments, segment_entry(segment));
+        }
+
+        for offset in self.page_offsets {
+            doc.add_u64(fields.page_offsets, *offset);
+        }
+
         for t in self.tags {
             doc.add_u64(fields.tags, *t as u64);
         }
 
+        if let Some(published_at) = &self.published_at {
+            doc.add_date(fields.published, to_tantivy_date(published_at));
+        }
+
+        if let Some(last_modified) = &self.last_modified {
+            doc.add_date(fields.lastmodified, to_tantivy_date(last_modified));
+        }
+
+        add_derived_fields(&DocFields::as_schema(), &mut doc);
+
         doc
     }
 }
 
+/// Field added in schema v5 w/ the authority score computed from the link
+/// graph, normalized to 0.0 - 1.0
+fn pagerank_field() -> FieldEntry {
+    FieldEntry::new_f64(
+        "pagerank".into(),
+        NumericOptions::default()
+            .set_fast(Cardinality::SingleValue)
+            .set_indexed()
+            .set_stored(),
+    )
+}
+
+/// Field added in schema v6 w/ searchable `key: value` metadata entries,
+/// indexed like the content.
+fn metadata_field(text_options: TextOptions) -> FieldEntry {
+    FieldEntry::new_text("metadata".into(), text_options)
+}
+
+/// Fields added in schema v7 for documents in languages other than English
+fn language_fields() -> Vec<FieldEntry> {
+    let mut fields = vec![FieldEntry::new_text("lang".into(), STRING | STORED | FAST)];
+    for analyzer in LanguageAnalyzer::ALL {
+        // Only indexed, the content itself is stored in the `content` field
+        let indexing = TextFieldIndexing::default()
+            .set_tokenizer(&analyzer.tokenizer_name())
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+        fields.push(FieldEntry::new_text(
+            analyzer.field_name(),
+            TextOptions::default().set_indexing_options(indexing),
+        ));
+    }
+
+    fields
+}
+
+/// Field added in schema v8 w/ the words of the title & content, lowercased
+/// but not stemmed, used for autocomplete & spelling suggestions.
+fn vocab_field() -> FieldEntry {
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer("default")
+        .set_index_option(IndexRecordOption::Basic);
+    FieldEntry::new_text(
+        "vocab".into(),
+        TextOptions::default().set_indexing_options(indexing),
+    )
+}
+
+/// Field added in schema v9 w/ the names of the symbols defined in source
+/// code. Stored so they can be carried over when the schema is migrated.
+fn symbols_field() -> FieldEntry {
+    let indexing = TextFieldIndexing::default()
+        .set_tokenizer(SYMBOL_TOKENIZER_NAME)
+        .set_index_option(IndexRecordOption::WithFreqs);
+    FieldEntry::new_text(
+        "symbols".into(),
+        TextOptions::default()
+            .set_indexing_options(indexing)
+            .set_stored(),
+    )
+}
+
+/// Field added in schema v10 w/ the timestamped segments of audio
+/// transcripts. Only stored, the transcript is searched through the content.
+fn segments_field() -> FieldEntry {
+    FieldEntry::new_text("segments".into(), TextOptions::default().set_stored())
+}
+
+/// Field added in schema v11 w/ the byte offset in the content where each page
+/// of a PDF starts, used to point matches to the page they're on.
+fn page_offsets_field() -> FieldEntry {
+    FieldEntry::new_u64(
+        "page_offsets".into(),
+        NumericOptions::default().set_stored(),
+    )
+}
+
 #[derive(Clone)]
 pub struct DocFields {
     pub id: Field,
     pub domain: Field,
     pub content: Field,
     pub description: Field,
+    pub metadata: Field,
     pub title: Field,
     pub url: Field,
     pub tags: Field,
     pub published: Field,
     pub lastmodified: Field,
+    pub pagerank: Field,
+    /// ISO 639-1 code of the detected language
+    pub lang: Field,
+    /// Words used for suggestions, not stored
+    pub vocab: Field,
+    /// Symbols defined in source code
+    pub symbols: Field,
+    /// Timestamped segments of audio transcripts
+    pub segments: Field,
+    /// Byte offsets of the pages of paged documents
+    pub page_offsets: Field,
 }
 
 impl SearchDocument for DocFields {
@@ -174,7 +502,7 @@ impl SearchDocument for DocFields {
                 ("description".into(), text_options.clone()),
                 ("url".into(), STRING | STORED | FAST),
                 // Indexed
-                ("content".into(), text_options),
+                ("content".into(), text_options.clone()),
             ]),
             date_fields: Some(vec![
                 (
@@ -201,6 +529,20 @@ impl SearchDocument for DocFields {
                     .set_indexed()
                     .set_stored(),
             )]),
+            // Never reorder these, only add to the end
+            appended_fields: Some(
+                [
+                    vec![pagerank_field(), metadata_field(text_options)],
+                    language_fields(),
+                    vec![
+                        vocab_field(),
+                        symbols_field(),
+                        segments_field(),
+                        page_offsets_field(),
+                    ],
+                ]
+                .concat(),
+            ),
         }
     }
 
@@ -213,6 +555,7 @@ impl SearchDocument for DocFields {
             description: schema
                 .get_field("description")
                 .expect("No description in schema"),
+            metadata: schema.get_field("metadata").expect("No metadata in schema"),
             title: schema.get_field("title").expect("No title in schema"),
             url: schema.get_field("url").expect("No url in schema"),
             tags: schema.get_field("tags").expect("No tags in schema"),
@@ -222,6 +565,14 @@ impl SearchDocument for DocFields {
             lastmodified: schema
                 .get_field("lastmodified")
                 .expect("No last modified date in schema"),
+            pagerank: schema.get_field("pagerank").expect("No pagerank in schema"),
+            lang: schema.get_field("lang").expect("No lang in schema"),
+            vocab: schema.get_field("vocab").expect("No vocab in schema"),
+            symbols: schema.get_field("symbols").expect("No symbols in schema"),
+            segments: schema.get_field("segments").expect("No segments in schema"),
+            page_offsets: schema
+                .get_field("page_offsets")
+                .expect("No page offsets in schema"),
         }
     }
 }
@@ -0,0 +1,435 @@
+//! Online migration of the search index to the current schema.
+//!
+//! Documents are copied from the stored fields of the index being served into
+//! a new index built w/ the current schema. The old index keeps serving queries
+//! & accepting writes in the meantime. Writes made during the copy are applied
+//! to the new index before it's swapped in, which only blocks writes for the
+//! last few of them.
+use std::fs;
+
+use tantivy::collector::DocSetCollector;
+use tantivy::query::AllQuery;
+use tantivy::schema::{Document, Schema};
+use tantivy::{IndexWriter, ReloadPolicy};
+
+use crate::batch::Journal;
+use crate::client::{apply_write, LiveIndex, Searcher};
+use crate::schema;
+use crate::{SearchError, SearcherResult};
+
+/// Directory, next to the index, the migrated index is built in.
+pub const MIGRATION_DIR: &str = "migrating_index";
+/// Directory the old index is moved to while the migrated index is swapped in.
+const RETIRED_DIR: &str = "retired_index";
+/// Progress is reported every this many documents.
+const PROGRESS_INTERVAL: usize = 1000;
+/// Writes made during the copy are applied w/o blocking new writes until fewer
+/// than this many are left.
+const MAX_BLOCKED_WRITES: usize = 100;
+/// Memory budget of the writer kept in place while the index directories move.
+const PLACEHOLDER_WRITER_MEMORY: usize = 15_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MigrationProgress {
+    pub num_migrated: usize,
+    pub total: usize,
+}
+
+impl MigrationProgress {
+    pub fn percent(&self) -> u8 {
+        if self.total == 0 {
+            100
+        } else {
+            ((self.num_migrated * 100) / self.total).min(100) as u8
+        }
+    }
+}
+
+/// Builds a doc for `to` from the values of a doc in `from`, matching fields by
+/// name. Fields that no longer exist are dropped and new fields are left empty.
+pub fn convert_document(from: &Schema, to: &Schema, doc: &Document) -> Document {
+    let mut converted = Document::default();
+    for value in doc.field_values() {
+        let name = from.get_field_name(value.field());
+        if let Some(field) = to.get_field(name) {
+            converted.add_field_value(field, value.value().clone());
+        }
+    }
+
+    converted
+}
+
+impl Searcher {
+    /// Whether the index being served was created w/ an older schema.
+    pub fn needs_schema_migration(&self) -> bool {
+        self.index().schema() != self.schema
+    }
+
+    /// Rebuilds the index w/ the current schema & swaps it in, returning the
+    /// number of documents migrated. Blocks until done, so should be run on a
+    /// blocking thread.
+    pub fn migrate_schema(
+        &self,
+        mut on_progress: impl FnMut(MigrationProgress),
+    ) -> SearcherResult<usize> {
+        if self.is_readonly() {
+            return Err(SearchError::ReadOnly);
+        }
+
+        if !self.needs_schema_migration() {
+            return Ok(0);
+        }
+
+        let target_dir = self
+            .path
+            .as_ref()
+            .map(|path| path.with_file_name(MIGRATION_DIR));
+        let target = match &target_dir {
+            Some(dir) => {
+                // Left over from a migration that was interrupted
+                let _ = fs::remove_dir_all(dir);
+                fs::create_dir_all(dir).map_err(|err| SearchError::Other(err.into()))?;
+                schema::initialize_index(self.schema.clone(), dir)?
+            }
+            None => schema::initialize_in_memory_index(self.schema.clone()),
+        };
+        let target_writer = target.writer(50_000_000)?;
+
+        // Keep a copy of the writes made from here on, they may not make it
+        // into the documents copied below.
+        self.lock_batch()?.start_tap();
+        let res = self.copy_and_swap(target, target_writer, &mut on_progress);
+        if let Ok(mut batch) = self.batch.lock() {
+            batch.stop_tap();
+        }
+
+        if res.is_err() {
+            if let Some(dir) = &target_dir {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+
+        res
+    }
+
+    fn copy_and_swap(
+        &self,
+        target: tantivy::Index,
+        mut target_writer: IndexWriter,
+        on_progress: &mut impl FnMut(MigrationProgress),
+    ) -> SearcherResult<usize> {
+        let live = self.live();
+        let from_schema = live.index.schema();
+
+        // Commit so the copy includes every write made before the tap started
+        {
+            let mut writer = self.lock_writer()?;
+            self.commit_writes(&mut writer)?;
+        }
+        live.reader.reload()?;
+
+        let searcher = live.reader.searcher();
+        let mut progress = MigrationProgress {
+            num_migrated: 0,
+            total: searcher.num_docs() as usize,
+        };
+        log::info!("Migrating {} docs to the current schema", progress.total);
+
+        for addr in searcher.search(&AllQuery, &DocSetCollector)? {
+            let doc = searcher.doc(addr)?;
+            let mut converted = convert_document(&from_schema, &self.schema, &doc);
+            // Fields older schemas didn't have
+            schema::add_derived_fields(&self.schema, &mut converted);
+            target_writer.add_document(converted)?;
+
+            progress.num_migrated += 1;
+            if progress.num_migrated % PROGRESS_INTERVAL == 0 {
+                on_progress(progress);
+            }
+        }
+        drop(searcher);
+
+        // Catch up on writes made during the copy while still accepting new ones
+        loop {
+            let writes = self.lock_batch()?.drain_tap();
+            let num_writes = writes.len();
+            for write in writes {
+                apply_write(&self.schema, &self.schema, &target_writer, None, write)?;
+            }
+
+            if num_writes < MAX_BLOCKED_WRITES {
+                break;
+            }
+        }
+
+        // Block writes while the last few are applied & the index is swapped
+        let mut writer = self.lock_writer()?;
+        for write in self.lock_batch()?.drain_tap() {
+            apply_write(&self.schema, &self.schema, &target_writer, None, write)?;
+        }
+        target_writer.commit()?;
+
+        // Searches wait on the lock until the new index is in place
+        drop(live);
+        let mut live = match self.live.write() {
+            Ok(live) => live,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match &self.path {
+            Some(path) => {
+                // Directories can't be moved while files in them are still open
+                // on Windows, so every handle on both indices is released first.
+                target_writer.wait_merging_threads()?;
+                drop(target);
+                // Writes made during the copy are kept in case the move fails
+                writer.commit()?;
+
+                let placeholder = schema::initialize_in_memory_index(self.schema.clone());
+                let placeholder_writer =
+                    placeholder.writer_with_num_threads(1, PLACEHOLDER_WRITER_MEMORY)?;
+                std::mem::replace(&mut *writer, placeholder_writer).wait_merging_threads()?;
+                *live = LiveIndex {
+                    reader: placeholder.reader()?,
+                    index: placeholder,
+                };
+                self.lock_batch()?.set_journal(None);
+
+                let retired = path.with_file_name(RETIRED_DIR);
+                let _ = fs::remove_dir_all(&retired);
+                let moved = fs::rename(path, &retired).and_then(|_| {
+                    fs::rename(path.with_file_name(MIGRATION_DIR), path).map_err(|err| {
+                        // Put the old index back
+                        let _ = fs::rename(&retired, path);
+                        err
+                    })
+                });
+
+                // Whichever index ended up in place is served from here on
+                let index = schema::initialize_index(self.schema.clone(), path)?;
+                *writer = index.writer(50_000_000)?;
+                *live = LiveIndex {
+                    reader: index
+                        .reader_builder()
+                        .reload_policy(ReloadPolicy::OnCommit)
+                        .try_into()?,
+                    index,
+                };
+
+                if let Err(err) = moved {
+                    let (journal, _) =
+                        Journal::open(path).map_err(|err| SearchError::Other(err.into()))?;
+                    let mut batch = self.lock_batch()?;
+                    batch.set_journal(Some(journal));
+                    batch
+                        .committed()
+                        .map_err(|err| SearchError::Other(err.into()))?;
+                    return Err(SearchError::Other(err.into()));
+                }
+            }
+            None => {
+                *writer = target_writer;
+                *live = LiveIndex {
+                    reader: target
+                        .reader_builder()
+                        .reload_policy(ReloadPolicy::OnCommit)
+                        .try_into()?,
+                    index: target,
+                };
+            }
+        }
+        drop(live);
+
+        // The journal & embeddings moved w/ the old index
+        let journal = match &self.path {
+            Some(path) => Some(
+                Journal::open(path)
+                    .map(|(journal, _)| journal)
+                    .map_err(|err| SearchError::Other(err.into()))?,
+            ),
+            None => None,
+        };
+        {
+            let mut batch = self.lock_batch()?;
+            batch.set_journal(journal);
+            // Pending writes were applied to the migrated index above
+            batch
+                .committed()
+                .map_err(|err| SearchError::Other(err.into()))?;
+        }
+        if let Some(vectors) = &self.vectors {
+            vectors.compact()?;
+        }
+
+        if let Some(path) = &self.path {
+            let _ = fs::remove_dir_all(path.with_file_name(RETIRED_DIR));
+        }
+
+        log::info!("Migrated {} docs", progress.num_migrated);
+        on_progress(MigrationProgress {
+            num_migrated: progress.total,
+            ..progress
+        });
+        Ok(progress.num_migrated)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tantivy::schema::{FieldEntry, STORED, TEXT};
+
+    use super::{convert_document, MigrationProgress};
+    use crate::client::Searcher;
+    use crate::schema::{
+        self, mapping_to_schema, DocFields, DocumentUpdate, SearchDocument, ToDocument,
+    };
+    use crate::{IndexBackend, SearchTrait, WriteTrait};
+
+    /// Current schema w/ an extra field appended, as a future version would
+    fn next_schema() -> tantivy::schema::Schema {
+        let mut mapping = DocFields::as_field_vec();
+        mapping
+            .appended_fields
+            .get_or_insert_with(Vec::new)
+            .push(FieldEntry::new_text("summary".into(), TEXT | STORED));
+        mapping_to_schema(&mapping)
+    }
+
+    #[test]
+    fn test_convert_document() {
+        let old_schema = DocFields::as_schema();
+        let new_schema = next_schema();
+        let fields = DocFields::as_fields();
+
+        let mut doc = tantivy::Document::default();
+        doc.add_text(fields.id, "doc");
+        doc.add_text(fields.title, "Title");
+
+        let converted = convert_document(&old_schema, &new_schema, &doc);
+        assert_eq!(new_schema.to_json(&converted), old_schema.to_json(&doc));
+        assert_eq!(
+            MigrationProgress {
+                num_migrated: 1,
+                total: 4
+            }
+            .percent(),
+            25
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_index_older_schema() {
+        let dir = std::env::temp_dir().join(format!("spyglass-older-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Unable to create dir");
+
+        // Schema w/o any of the appended fields, as an older version wrote it
+        let mut mapping = DocFields::as_field_vec();
+        mapping.appended_fields = None;
+        let old_schema = mapping_to_schema(&mapping);
+        {
+            let index = tantivy::Index::create_in_dir(&dir, old_schema.clone())
+                .expect("Unable to create index");
+            schema::register_tokenizer(&index);
+            let mut writer = index.writer(15_000_000).expect("Unable to create writer");
+            let mut doc = tantivy::Document::default();
+            doc.add_text(old_schema.get_field("id").unwrap(), "old_doc");
+            doc.add_text(old_schema.get_field("title").unwrap(), "Of Mice and Men");
+            writer.add_document(doc).expect("Unable to add doc");
+            writer.commit().expect("Unable to commit");
+        }
+
+        let searcher = Searcher::with_index(
+            &IndexBackend::LocalPath(dir.clone()),
+            DocFields::as_schema(),
+            None,
+            false,
+        )
+        .expect("Unable to open index");
+        assert_eq!(searcher.index().schema(), old_schema);
+        assert!(searcher.needs_schema_migration());
+        let doc = searcher.get("old_doc").await.expect("doc in old index");
+        assert_eq!(doc.title, "Of Mice and Men");
+
+        drop(searcher);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_schema() {
+        let dir = std::env::temp_dir().join(format!("spyglass-migrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Unable to create dir");
+        let index_dir = dir.join("index");
+
+        let doc_id = {
+            let searcher = Searcher::with_index(
+                &IndexBackend::LocalPath(index_dir.clone()),
+                DocFields::as_schema(),
+                None,
+                false,
+            )
+            .expect("Unable to open index");
+            let doc_id = searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title: "Of Mice and Men",
+                        domain: "example.com",
+                        url: "https://example.com/mice_and_men",
+                        content: "A few miles south of Soledad",
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[1],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+            searcher.save().await.expect("Unable to save");
+            doc_id
+        };
+
+        let searcher = Searcher::with_index(
+            &IndexBackend::LocalPath(index_dir.clone()),
+            next_schema(),
+            None,
+            false,
+        )
+        .expect("Unable to open index");
+        assert!(searcher.needs_schema_migration());
+        // The old index keeps serving until it's migrated
+        assert!(searcher.get(&doc_id).await.is_some());
+
+        let mut updates = Vec::new();
+        let num_docs = searcher
+            .migrate_schema(|progress| updates.push(progress.percent()))
+            .expect("Unable to migrate");
+        assert_eq!(num_docs, 1);
+        assert_eq!(updates.last(), Some(&100));
+        assert!(!searcher.needs_schema_migration());
+        assert!(!dir.join(super::MIGRATION_DIR).exists());
+
+        let doc = searcher.get(&doc_id).await.expect("doc was migrated");
+        assert_eq!(doc.title, "Of Mice and Men");
+        assert_eq!(doc.tags, vec![1]);
+
+        // Writes go to the migrated index
+        searcher
+            .delete_many_by_id(&[doc_id.clone()])
+            .await
+            .expect("Unable to delete");
+        searcher.save().await.expect("Unable to save");
+        assert!(searcher.get(&doc_id).await.is_none());
+
+        drop(searcher);
+        let index = schema::initialize_index(next_schema(), &index_dir).expect("Unable to open");
+        assert_eq!(index.schema(), next_schema());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
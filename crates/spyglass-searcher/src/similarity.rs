@@ -1,143 +1,578 @@
-use reqwest::Client;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use fnv::FnvHasher;
 use serde::{Deserialize, Serialize};
-use shared::response::SimilaritySearchResult;
-use std::env;
-use std::time::SystemTime;
 
 use super::client::Searcher;
-use crate::SearchTrait;
+use crate::{Score, SearchTrait};
+
+pub type Embedding = Vec<f32>;
+
+/// Name of the file used to store embeddings, alongside the tantivy index files.
+pub const VECTOR_STORE_FILE: &str = "embeddings.jsonl";
+/// The log is compacted once it has this many entries per stored document...
+const COMPACT_RATIO: usize = 2;
+/// ...and at least this many entries.
+const MIN_COMPACT_ENTRIES: usize = 1_000;
+
+/// Number of words in each chunk of content that is embedded.
+const CHUNK_SIZE: usize = 128;
+/// Number of words shared between consecutive chunks so that sentences spanning
+/// two chunks are still matched.
+const CHUNK_OVERLAP: usize = 32;
+/// Number of chunks used as context for each document
+const CONTEXT_CHUNKS: usize = 3;
+
+/// Provides embeddings for chunks of text.
+pub trait Embedder: Send + Sync {
+    /// Unique name for the model/configuration. Stored vectors produced by a
+    /// different embedder are discarded.
+    fn name(&self) -> String;
+    /// Size of the vectors produced by this embedder
+    fn dimensions(&self) -> usize;
+    fn embed(&self, text: &str) -> anyhow::Result<Embedding>;
+}
+
+/// Deterministic embedder which hashes words into a fixed size vector. Doesn't
+/// need any model files, which makes it useful for tests.
+#[derive(Clone, Debug)]
+pub struct HashEmbedder {
+    dimensions: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        HashEmbedder {
+            dimensions: dimensions.max(1),
+        }
+    }
+}
 
-const EMBEDDING_ENDPOINT: &str = "SIMILARITY_SEARCH_ENDPOINT";
-const EMBEDDING_PORT: &str = "SIMILARITY_SEARCH_PORT";
-const DEFAULT_HOST: &str = "localhost";
-const DEFAULT_PORT: &str = "8000";
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl Embedder for HashEmbedder {
+    fn name(&self) -> String {
+        format!("hash-{}", self.dimensions)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
 
+    fn embed(&self, text: &str) -> anyhow::Result<Embedding> {
+        let mut embedding = vec![0.0; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            let mut hasher = FnvHasher::default();
+            hasher.write(word.to_lowercase().as_bytes());
+            let hash = hasher.finish();
+
+            // Use the top bit to pick a sign so collisions tend to cancel out.
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            embedding[(hash % self.dimensions as u64) as usize] += sign;
+        }
+
+        normalize(&mut embedding);
+        Ok(embedding)
+    }
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Splits the text into chunks of `chunk_size` words, with `overlap` words
+/// shared between consecutive chunks.
+pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    let chunk_size = chunk_size.max(1);
+    let step = chunk_size.saturating_sub(overlap).max(1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + chunk_size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start += step;
+    }
+
+    chunks
+}
+
+/// First line of the vector log, identifying the embedder that produced it.
 #[derive(Deserialize, Serialize)]
-struct SimilarityContext {
-    content: String,
-    document: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct SimilarityContextResponse {
-    context: Vec<String>,
-}
-
-pub async fn similarity_search(query: &str) -> Vec<SimilaritySearchResult> {
-    let bench_start = SystemTime::now();
-    let endpoint = env::var(EMBEDDING_ENDPOINT).unwrap_or(DEFAULT_HOST.into());
-    let port = env::var(EMBEDDING_PORT).unwrap_or(DEFAULT_PORT.into());
-    log::info!("using {}:{}", endpoint, port);
-    log::info!(
-        "env_check: {}ms",
-        bench_start.elapsed().unwrap().as_millis()
-    );
-
-    // search vector db, todo: if enabled/available
-    let bench_start = SystemTime::now();
-    let client = reqwest::Client::builder().build().unwrap();
-    log::info!(
-        "client_build: {}ms",
-        bench_start.elapsed().unwrap().as_millis()
-    );
-
-    // todo: pull endpoint from environment / configuration
-    let bench_start = SystemTime::now();
-    let resp = client
-        .post(format!("http://{}:{}/search", endpoint, port))
-        .json(&serde_json::json!({ "query": query }))
-        .send()
-        .await
-        .unwrap();
-    log::info!(
-        "similarity_search_call: {}ms",
-        bench_start.elapsed().unwrap().as_millis()
-    );
-
-    let bench_start = SystemTime::now();
-    let results = resp.json().await.unwrap_or_default();
-    log::info!(
-        "similarity_json: {}ms",
-        bench_start.elapsed().unwrap().as_millis()
-    );
-
-    results
+struct LogHeader {
+    embedder: String,
 }
 
+/// Change to the stored vectors, appended to the log on save.
+#[derive(Deserialize, Serialize)]
+enum LogEntry {
+    Upsert {
+        doc_id: String,
+        vectors: Vec<Embedding>,
+    },
+    Delete {
+        doc_id: String,
+    },
+}
+
+#[derive(Default)]
+struct StoredVectors {
+    /// Chunk embeddings per document id
+    documents: HashMap<String, Vec<Embedding>>,
+    /// Documents changed since the last save
+    dirty: HashSet<String>,
+    /// Number of entries in the log, including ones that have been overwritten
+    /// since. `None` if the log needs to be rewritten from scratch.
+    num_logged: Option<usize>,
+}
+
+/// In-process store for document embeddings. Content is chunked & embedded as
+/// documents are added. Changes are appended to a log next to the search index
+/// on save, which is compacted once it's mostly overwritten entries.
+pub struct VectorStore {
+    path: Option<PathBuf>,
+    embedder: Arc<dyn Embedder>,
+    vectors: RwLock<StoredVectors>,
+    /// Set when the stored vectors couldn't be used & documents need to be
+    /// embedded again.
+    needs_rebuild: bool,
+}
+
+impl VectorStore {
+    /// Store that lives only in memory, used for testing.
+    pub fn in_memory(embedder: Arc<dyn Embedder>) -> Self {
+        VectorStore {
+            path: None,
+            vectors: RwLock::new(StoredVectors::default()),
+            embedder,
+            needs_rebuild: false,
+        }
+    }
+
+    /// Opens the store persisted in `index_dir`. If the stored vectors are
+    /// missing, unreadable or were produced by a different embedder, the store
+    /// starts out empty & is flagged as needing a rebuild.
+    pub fn open(index_dir: &Path, embedder: Arc<dyn Embedder>) -> Self {
+        let path = index_dir.join(VECTOR_STORE_FILE);
+        let loaded = if path.exists() {
+            read_log(&path, &embedder.name())
+        } else {
+            Ok(None)
+        };
+
+        let (vectors, needs_rebuild) = match loaded {
+            Ok(Some(vectors)) => (vectors, false),
+            Ok(None) => (StoredVectors::default(), true),
+            Err(err) => {
+                log::warn!("Unable to read stored vectors, rebuilding: {err}");
+                (StoredVectors::default(), true)
+            }
+        };
+
+        VectorStore {
+            path: Some(path),
+            embedder,
+            vectors: RwLock::new(vectors),
+            needs_rebuild,
+        }
+    }
+
+    pub fn embedder(&self) -> Arc<dyn Embedder> {
+        self.embedder.clone()
+    }
+
+    /// Whether the documents in the index need to be embedded again.
+    pub fn needs_rebuild(&self) -> bool {
+        self.needs_rebuild
+    }
+
+    /// Number of documents in the store
+    pub fn len(&self) -> usize {
+        self.vectors
+            .read()
+            .map(|vectors| vectors.documents.len())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Chunks & embeds the content, replacing any existing vectors for the doc.
+    pub fn upsert(&self, doc_id: &str, content: &str) -> anyhow::Result<()> {
+        let embeddings = chunk_text(content, CHUNK_SIZE, CHUNK_OVERLAP)
+            .iter()
+            .map(|chunk| self.embedder.embed(chunk))
+            .collect::<anyhow::Result<Vec<Embedding>>>()?;
+
+        let mut vectors = self
+            .vectors
+            .write()
+            .map_err(|_| anyhow::anyhow!("Vector store lock poisoned"))?;
+        if embeddings.is_empty() {
+            vectors.documents.remove(doc_id);
+        } else {
+            vectors.documents.insert(doc_id.to_string(), embeddings);
+        }
+        vectors.dirty.insert(doc_id.to_string());
+
+        Ok(())
+    }
+
+    pub fn delete(&self, doc_ids: &[String]) {
+        if let Ok(mut vectors) = self.vectors.write() {
+            for doc_id in doc_ids {
+                vectors.documents.remove(doc_id);
+                vectors.dirty.insert(doc_id.clone());
+            }
+        }
+    }
+
+    /// Appends the changes made since the last save to the log, a no-op for in
+    /// memory stores.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut vectors = self
+            .vectors
+            .write()
+            .map_err(|_| anyhow::anyhow!("Vector store lock poisoned"))?;
+        let num_logged = match vectors.num_logged {
+            Some(num_logged) => num_logged + vectors.dirty.len(),
+            None => usize::MAX,
+        };
+        if num_logged > (vectors.documents.len() * COMPACT_RATIO).max(MIN_COMPACT_ENTRIES) {
+            write_log(path, &self.embedder.name(), &vectors.documents)?;
+            vectors.num_logged = Some(vectors.documents.len());
+        } else if !vectors.dirty.is_empty() {
+            let mut buf = Vec::new();
+            for doc_id in &vectors.dirty {
+                let entry = match vectors.documents.get(doc_id) {
+                    Some(embeddings) => LogEntry::Upsert {
+                        doc_id: doc_id.clone(),
+                        vectors: embeddings.clone(),
+                    },
+                    None => LogEntry::Delete {
+                        doc_id: doc_id.clone(),
+                    },
+                };
+                serde_json::to_writer(&mut buf, &entry)?;
+                buf.push(b'\n');
+            }
+
+            let mut file = OpenOptions::new().append(true).open(path)?;
+            file.write_all(&buf)?;
+            file.sync_data()?;
+            vectors.num_logged = Some(num_logged);
+        }
+
+        vectors.dirty.clear();
+        Ok(())
+    }
+
+    /// Rewrites the log w/ only the current vectors, e.g. after the index
+    /// directory was replaced.
+    pub fn compact(&self) -> anyhow::Result<()> {
+        if let Ok(mut vectors) = self.vectors.write() {
+            vectors.num_logged = None;
+        }
+        self.save()
+    }
+
+    /// Writes a copy of the vectors into `index_dir`, e.g. for a snapshot.
+    pub fn save_to(&self, index_dir: &Path) -> anyhow::Result<()> {
+        let vectors = self
+            .vectors
+            .read()
+            .map_err(|_| anyhow::anyhow!("Vector store lock poisoned"))?;
+        write_log(
+            &index_dir.join(VECTOR_STORE_FILE),
+            &self.embedder.name(),
+            &vectors.documents,
+        )
+    }
+
+    /// Returns the `limit` closest documents to the query, scored by their best
+    /// matching chunk.
+    pub fn nearest(&self, query: &str, limit: usize) -> anyhow::Result<Vec<(Score, String)>> {
+        let query = self.embedder.embed(query)?;
+        let vectors = self
+            .vectors
+            .read()
+            .map_err(|_| anyhow::anyhow!("Vector store lock poisoned"))?;
+
+        let mut scored = vectors
+            .documents
+            .iter()
+            .map(|(doc_id, chunks)| {
+                let score = chunks
+                    .iter()
+                    .map(|chunk| cosine_similarity(&query, chunk))
+                    .fold(f32::MIN, f32::max);
+                (score, doc_id.clone())
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+}
+
+/// Reads the vector log, returning `None` if it was written by a different embedder.
+fn read_log(path: &Path, embedder: &str) -> anyhow::Result<Option<StoredVectors>> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let header = match lines.next() {
+        Some(line) => serde_json::from_str::<LogHeader>(&line?)?,
+        None => return Err(anyhow::anyhow!("Vector log is empty")),
+    };
+
+    if header.embedder != embedder {
+        log::warn!(
+            "Embedder changed from `{}` to `{embedder}`, dropping stored vectors",
+            header.embedder
+        );
+        return Ok(None);
+    }
+
+    let mut stored = StoredVectors::default();
+    let mut num_logged = 0;
+    for line in lines {
+        // A partial entry left by a crash fails here too, in which case the
+        // whole store is rebuilt.
+        match serde_json::from_str::<LogEntry>(&line?)? {
+            LogEntry::Upsert { doc_id, vectors } => {
+                stored.documents.insert(doc_id, vectors);
+            }
+            LogEntry::Delete { doc_id } => {
+                stored.documents.remove(&doc_id);
+            }
+        }
+        num_logged += 1;
+    }
+
+    stored.num_logged = Some(num_logged);
+    Ok(Some(stored))
+}
+
+/// Writes a new log w/ an entry for every document.
+fn write_log(
+    path: &Path,
+    embedder: &str,
+    documents: &HashMap<String, Vec<Embedding>>,
+) -> anyhow::Result<()> {
+    // Write to a temp file first so a crash doesn't leave a partial file behind
+    let tmp_path = path.with_extension("jsonl.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    serde_json::to_writer(
+        &mut writer,
+        &LogHeader {
+            embedder: embedder.to_string(),
+        },
+    )?;
+    writer.write_all(b"\n")?;
+    for (doc_id, embeddings) in documents {
+        serde_json::to_writer(
+            &mut writer,
+            &LogEntry::Upsert {
+                doc_id: doc_id.clone(),
+                vectors: embeddings.clone(),
+            },
+        )?;
+        writer.write_all(b"\n")?;
+    }
+
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Pulls out the chunks of each document that best match the query, to be used
+/// as context for a question.
 pub async fn generate_similarity_context(
     searcher: &Searcher,
     query: &str,
     doc_ids: &Vec<String>,
 ) -> String {
-    let client = Client::new();
-    let mut context = Vec::new();
-    let doc_sim_start = SystemTime::now();
-    log::info!("Generate Similarity for {} docs", doc_ids.len());
+    let embedder = match &searcher.vectors {
+        Some(store) => store.embedder(),
+        None => {
+            log::warn!("No embedder configured, unable to generate context");
+            return String::new();
+        }
+    };
 
+    let mut context = Vec::new();
     for doc_id in doc_ids {
         if let Some(doc) = searcher.get(doc_id).await {
-            if let Some(doc_context) =
-                generate_similarity_context_for_doc(&client, query, &doc.content, &doc.url).await
-            {
+            if let Some(doc_context) = generate_similarity_context_for_doc(
+                embedder.as_ref(),
+                query,
+                &doc.content,
+                &doc.url,
+            ) {
                 context.push(doc_context);
             }
         }
     }
-    log::info!(
-        "generate_similarity_context: {}ms",
-        doc_sim_start.elapsed().unwrap().as_millis()
-    );
+
     context.join("\n")
 }
 
-pub async fn generate_similarity_context_for_doc(
-    client: &Client,
+pub fn generate_similarity_context_for_doc(
+    embedder: &dyn Embedder,
     query: &str,
     content: &str,
     url: &str,
 ) -> Option<String> {
-    let doc_sim_start = SystemTime::now();
-    let endpoint = env::var(EMBEDDING_ENDPOINT).unwrap_or(DEFAULT_HOST.into());
-    let port = env::var(EMBEDDING_PORT).unwrap_or(DEFAULT_PORT.into());
-    log::info!("Generate Similarity Using {}:{}", endpoint, port);
-    log::info!(
-        "context env_check: {}ms",
-        doc_sim_start.elapsed().unwrap().as_millis()
-    );
-    let body = SimilarityContext {
-        content: String::from(query),
-        document: String::from(content),
-    };
-    let request = client
-        .post(format!("http://{}:{}/compare", endpoint, port))
-        .json(&body);
-
-    log::info!(
-        "generate_similarity_context_for_doc: {}ms",
-        doc_sim_start.elapsed().unwrap().as_millis()
-    );
-
-    let doc_sim_start = SystemTime::now();
-
-    match request.send().await {
-        Ok(response) => match response.json::<SimilarityContextResponse>().await {
-            Ok(json) => {
-                let context = json.context.join("\n");
-                let doc_context = format!("URL: {}\n{}", url, context);
-                log::info!(
-                    "generate_similarity_context_for_doc: {}ms",
-                    doc_sim_start.elapsed().unwrap().as_millis()
-                );
-                Some(doc_context)
-            }
-            Err(err) => {
-                log::error!("Error processing response {:?}", err);
-                None
-            }
-        },
+    let query = match embedder.embed(query) {
+        Ok(query) => query,
         Err(err) => {
-            log::warn!("Error sending request {:?}", err);
-            None
+            log::warn!("Unable to embed query: {err}");
+            return None;
         }
+    };
+
+    let mut chunks = chunk_text(content, CHUNK_SIZE, CHUNK_OVERLAP)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, chunk)| {
+            let embedding = embedder.embed(&chunk).ok()?;
+            Some((cosine_similarity(&query, &embedding), idx, chunk))
+        })
+        .filter(|(score, _, _)| *score > 0.0)
+        .collect::<Vec<_>>();
+
+    if chunks.is_empty() {
+        return None;
+    }
+
+    // Keep the best chunks, but in the order they appear in the document.
+    chunks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    chunks.truncate(CONTEXT_CHUNKS);
+    chunks.sort_by_key(|(_, idx, _)| *idx);
+
+    let context = chunks
+        .into_iter()
+        .map(|(_, _, chunk)| chunk)
+        .collect::<Vec<String>>()
+        .join("\n");
+    Some(format!("URL: {url}\n{context}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{
+        chunk_text, cosine_similarity, Embedder, HashEmbedder, VectorStore, VECTOR_STORE_FILE,
+    };
+
+    #[test]
+    fn test_hash_embedder() {
+        let embedder = HashEmbedder::default();
+        let a = embedder.embed("The Salinas river").unwrap();
+        let b = embedder.embed("the salinas RIVER").unwrap();
+        let c = embedder.embed("cheese and crackers").unwrap();
+
+        assert_eq!(a.len(), embedder.dimensions());
+        assert_eq!(a, b);
+        assert!((cosine_similarity(&a, &b) - 1.0).abs() < 1e-5);
+        assert!(cosine_similarity(&a, &c) < 0.5);
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        let text = (0..10).map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+        let chunks = chunk_text(&text, 4, 1);
+        assert_eq!(chunks, vec!["0 1 2 3", "3 4 5 6", "6 7 8 9"]);
+        assert!(chunk_text("", 4, 1).is_empty());
+    }
+
+    #[test]
+    fn test_nearest_and_persist() {
+        let embedder = Arc::new(HashEmbedder::default());
+        let store = VectorStore::in_memory(embedder.clone());
+        store
+            .upsert(
+                "mice",
+                "A few miles south of Soledad, the Salinas River drops",
+            )
+            .unwrap();
+        store
+            .upsert("cheese", "Cheese and crackers with a glass of wine")
+            .unwrap();
+
+        let results = store.nearest("salinas river", 5).unwrap();
+        assert_eq!(results.first().map(|(_, id)| id.as_str()), Some("mice"));
+
+        store.delete(&["mice".into()]);
+        assert_eq!(store.len(), 1);
+
+        let dir = std::env::temp_dir().join(format!("spyglass-vectors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = VectorStore::open(&dir, embedder.clone());
+        assert!(store.needs_rebuild());
+        store.upsert("cheese", "Cheese and crackers").unwrap();
+        store.upsert("mice", "Of mice and men").unwrap();
+        store.save().unwrap();
+        // Later changes are appended to the log
+        store.delete(&["mice".into()]);
+        store.save().unwrap();
+
+        let reopened = VectorStore::open(&dir, embedder.clone());
+        assert!(!reopened.needs_rebuild());
+        assert_eq!(reopened.len(), 1);
+
+        // Vectors from a different embedder are dropped
+        let reopened = VectorStore::open(&dir, Arc::new(HashEmbedder::new(16)));
+        assert!(reopened.is_empty());
+        assert!(reopened.needs_rebuild());
+
+        // As are vectors that can't be read
+        std::fs::write(dir.join(VECTOR_STORE_FILE), "{\"embedder\":").unwrap();
+        let reopened = VectorStore::open(&dir, embedder);
+        assert!(reopened.is_empty());
+        assert!(reopened.needs_rebuild());
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
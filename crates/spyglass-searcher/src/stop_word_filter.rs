@@ -1,7 +1,7 @@
 use fnv::FnvHasher;
 use std::collections::HashSet;
 use std::hash::BuildHasherDefault;
-use tantivy::tokenizer::{BoxTokenStream, Token, TokenFilter, TokenStream};
+use tantivy::tokenizer::{BoxTokenStream, Language, Token, TokenFilter, TokenStream};
 
 // configure our hashers for SPEED
 type StopWordHasher = BuildHasherDefault<FnvHasher>;
@@ -35,6 +35,60 @@ impl StopWordFilter {
 
         StopWordFilter::remove(words.iter().map(|&s| s.to_string()).collect())
     }
+
+    /// Most common words for the language, before any accents are folded.
+    pub fn for_language(language: Language) -> StopWordFilter {
+        let words: &[&'static str] = match language {
+            Language::German => &[
+                "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "das",
+                "dass", "dem", "den", "der", "des", "die", "du", "ein", "eine", "einem", "einen",
+                "einer", "er", "es", "für", "hat", "ich", "im", "in", "ist", "mit", "nach",
+                "nicht", "noch", "oder", "sich", "sie", "sind", "so", "und", "von", "war", "was",
+                "wie", "wir", "zu", "zum", "zur",
+            ],
+            Language::French => &[
+                "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et",
+                "est", "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me",
+                "même", "mes", "mon", "ne", "nous", "on", "ou", "par", "pas", "pour", "qu", "que",
+                "qui", "sa", "se", "ses", "son", "sur", "ta", "te", "tu", "un", "une", "vous",
+            ],
+            Language::Spanish => &[
+                "al", "como", "con", "de", "del", "el", "en", "es", "esta", "este", "la", "las",
+                "le", "lo", "los", "me", "mi", "más", "no", "o", "para", "pero", "por", "que",
+                "se", "su", "sus", "sin", "sobre", "también", "te", "un", "una", "y", "ya",
+            ],
+            Language::Italian => &[
+                "a", "al", "alla", "che", "ci", "con", "da", "del", "della", "di", "e", "è", "gli",
+                "i", "il", "in", "la", "le", "lo", "ma", "mi", "ne", "non", "per", "più", "si",
+                "sono", "su", "tra", "un", "una", "uno",
+            ],
+            Language::Portuguese => &[
+                "a", "ao", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "é", "em",
+                "mais", "mas", "na", "nas", "no", "nos", "não", "o", "os", "ou", "para", "por",
+                "que", "se", "sua", "seu", "um", "uma",
+            ],
+            Language::Dutch => &[
+                "aan", "als", "bij", "dat", "de", "den", "der", "die", "dit", "een", "en", "er",
+                "het", "hij", "in", "is", "je", "maar", "met", "na", "niet", "of", "om", "op",
+                "te", "tot", "uit", "van", "voor", "was", "wat", "ze", "zich", "zijn",
+            ],
+            Language::Swedish => &[
+                "att", "av", "de", "den", "det", "du", "där", "en", "ett", "för", "han", "har",
+                "hon", "i", "inte", "jag", "med", "men", "om", "på", "sig", "som", "så", "till",
+                "var", "vi", "är", "och",
+            ],
+            Language::Russian => &[
+                "а", "без", "бы", "в", "во", "вы", "да", "для", "до", "его", "же", "за", "и", "из",
+                "или", "к", "как", "на", "не", "но", "о", "об", "он", "она", "они", "от", "по",
+                "с", "так", "то", "у", "что", "это", "я",
+            ],
+            Language::English => return StopWordFilter::english(),
+            // No list for the rest of the stemmer languages
+            _ => &[],
+        };
+
+        StopWordFilter::remove(words.iter().map(|&s| s.to_string()).collect())
+    }
 }
 
 pub struct StopWordFilterStream<'a> {
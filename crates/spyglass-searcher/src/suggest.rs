@@ -0,0 +1,352 @@
+//! Autocomplete & spelling suggestions from the term dictionary of the index.
+//!
+//! Words are suggested from the `vocab` field, which holds the words of the
+//! title & content as typed. Indices that haven't been migrated to a schema w/
+//! that field yet fall back to the words in the titles.
+use std::collections::HashMap;
+
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::{TantivyError, Term};
+
+use crate::client::Searcher;
+use crate::schema::{DocFields, SearchDocument};
+use crate::SearcherResult;
+
+/// Words shorter than this aren't completed, they'd match too much.
+pub const MIN_PREFIX_LEN: usize = 2;
+/// Max number of terms looked at per segment when completing a word.
+const MAX_COMPLETION_SCAN: usize = 5_000;
+/// Max number of terms looked at per segment when correcting a word.
+const MAX_CORRECTION_SCAN: usize = 50_000;
+
+/// A word from the index & the number of documents it appears in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TermSuggestion {
+    pub term: String,
+    pub doc_freq: u64,
+}
+
+/// Number of single character insertions, deletions or substitutions needed
+/// to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+    let mut row = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(a_char != *b_char);
+            row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev_row, &mut row);
+    }
+
+    prev_row[b.len()]
+}
+
+/// Max number of typos corrected in a word, based on its length. Short words
+/// are left alone since almost anything is a typo away from them.
+fn max_correction_distance(word_len: usize) -> usize {
+    match word_len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+impl Searcher {
+    fn suggest_field(&self) -> Option<Field> {
+        let schema = self.index().schema();
+        schema
+            .get_field("vocab")
+            .or_else(|| schema.get_field("title"))
+    }
+
+    /// Visits the terms of the suggestion field that start w/ `prefix`, up to
+    /// `max_scanned` terms per segment, summing their document frequencies.
+    fn scan_terms(
+        &self,
+        field: Field,
+        prefix: &str,
+        max_scanned: usize,
+        mut visit: impl FnMut(&str, u64),
+    ) -> tantivy::Result<()> {
+        let searcher = self.reader().searcher();
+        for segment in searcher.segment_readers() {
+            let inverted_index = segment.inverted_index(field)?;
+            let mut terms = inverted_index
+                .terms()
+                .range()
+                .ge(prefix.as_bytes())
+                .into_stream()
+                .map_err(TantivyError::from)?;
+
+            let mut num_scanned = 0;
+            while num_scanned < max_scanned && terms.advance() {
+                if !terms.key().starts_with(prefix.as_bytes()) {
+                    break;
+                }
+
+                if let Ok(term) = std::str::from_utf8(terms.key()) {
+                    visit(term, terms.value().doc_freq as u64);
+                }
+                num_scanned += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Words in the index starting w/ `prefix`, most common first.
+    pub fn complete_term(&self, prefix: &str, limit: usize) -> SearcherResult<Vec<TermSuggestion>> {
+        let prefix = prefix.to_lowercase();
+        let field = match self.suggest_field() {
+            Some(field) if prefix.chars().count() >= MIN_PREFIX_LEN => field,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut doc_freqs: HashMap<String, u64> = HashMap::new();
+        self.scan_terms(field, &prefix, MAX_COMPLETION_SCAN, |term, doc_freq| {
+            if term != prefix {
+                *doc_freqs.entry(term.to_string()).or_default() += doc_freq;
+            }
+        })?;
+
+        let mut suggestions = doc_freqs
+            .into_iter()
+            .map(|(term, doc_freq)| TermSuggestion { term, doc_freq })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| b.doc_freq.cmp(&a.doc_freq).then(a.term.cmp(&b.term)));
+        suggestions.truncate(limit);
+
+        Ok(suggestions)
+    }
+
+    /// Titles of documents matching all the words in `query`, the last of
+    /// which may be partially typed.
+    pub fn complete_title(&self, query: &str, limit: usize) -> SearcherResult<Vec<String>> {
+        let fields = DocFields::as_fields();
+        let tokenizer = self.index().tokenizer_for_field(fields.title)?;
+
+        let mut words = Vec::new();
+        tokenizer
+            .token_stream(query)
+            .process(&mut |token| words.push(token.text.clone()));
+        let last = match words.pop() {
+            Some(word) if word.chars().count() >= MIN_PREFIX_LEN => word,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = words
+            .iter()
+            .map(|word| {
+                let term = Term::from_field_text(fields.title, word);
+                let query: Box<dyn Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                (Occur::Must, query)
+            })
+            .collect();
+        clauses.push((
+            Occur::Must,
+            Box::new(FuzzyTermQuery::new_prefix(
+                Term::from_field_text(fields.title, &last),
+                0,
+                false,
+            )),
+        ));
+
+        let searcher = self.reader().searcher();
+        // Over-fetch since the same title is often used by several documents
+        let top_docs = searcher.search(
+            &BooleanQuery::new(clauses),
+            &TopDocs::with_limit(limit.max(1) * 2),
+        )?;
+
+        let mut titles: Vec<String> = Vec::new();
+        for (_, addr) in top_docs {
+            let doc = searcher.doc(addr)?;
+            if let Some(title) = doc
+                .get_first(fields.title)
+                .and_then(|value| value.as_text())
+            {
+                let title = title.trim();
+                if !title.is_empty() && !titles.iter().any(|seen| seen.eq_ignore_ascii_case(title))
+                {
+                    titles.push(title.to_string());
+                }
+            }
+
+            if titles.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(titles)
+    }
+
+    /// Closest word in the index to `word`, preferring the most common one.
+    /// Returns `None` if the word is already in the index or nothing is close.
+    pub fn correct_term(&self, word: &str) -> SearcherResult<Option<String>> {
+        let word = word.to_lowercase();
+        let word_len = word.chars().count();
+        let max_distance = max_correction_distance(word_len);
+        let (field, first_char) = match (self.suggest_field(), word.chars().next()) {
+            (Some(field), Some(first_char)) if max_distance > 0 => (field, first_char),
+            _ => return Ok(None),
+        };
+
+        let searcher = self.reader().searcher();
+        if searcher.doc_freq(&Term::from_field_text(field, &word))? > 0 {
+            return Ok(None);
+        }
+
+        // Typos rarely change the first letter, so only words that start w/ it
+        // are checked.
+        let mut candidates: HashMap<String, (usize, u64)> = HashMap::new();
+        self.scan_terms(
+            field,
+            &first_char.to_string(),
+            MAX_CORRECTION_SCAN,
+            |term, doc_freq| {
+                if term.chars().count().abs_diff(word_len) > max_distance {
+                    return;
+                }
+
+                let distance = edit_distance(&word, term);
+                if distance <= max_distance {
+                    let entry = candidates.entry(term.to_string()).or_insert((distance, 0));
+                    entry.1 += doc_freq;
+                }
+            },
+        )?;
+
+        let closest = candidates
+            .into_iter()
+            .min_by(|(a, (a_dist, a_freq)), (b, (b_dist, b_freq))| {
+                a_dist.cmp(b_dist).then(b_freq.cmp(a_freq)).then(a.cmp(b))
+            })
+            .map(|(term, _)| term);
+
+        Ok(closest)
+    }
+
+    /// The query w/ each word that isn't in the index replaced by the closest
+    /// one that is, or `None` if there's nothing to correct. Field clauses,
+    /// phrases & other syntax are left as is.
+    pub fn correct_query(&self, query: &str) -> SearcherResult<Option<String>> {
+        let mut corrected = false;
+        let mut words = Vec::new();
+        for word in query.split_whitespace() {
+            let correction = if word.chars().all(char::is_alphanumeric) {
+                self.correct_term(word)?
+            } else {
+                None
+            };
+
+            match correction {
+                Some(correction) => {
+                    corrected = true;
+                    words.push(correction);
+                }
+                None => words.push(word.to_string()),
+            }
+        }
+
+        Ok(corrected.then(|| words.join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::edit_distance;
+    use crate::client::Searcher;
+    use crate::schema::{DocFields, DocumentUpdate, SearchDocument, ToDocument};
+    use crate::{IndexBackend, WriteTrait};
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("salinas", "salinas"), 0);
+        assert_eq!(edit_distance("salnas", "salinas"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_suggestions() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+
+        for (url, title, content) in [
+            (
+                "https://example.com/mice",
+                "Of Mice and Men",
+                "A few miles south of Soledad, the Salinas River drops in close",
+            ),
+            (
+                "https://example.com/salinas",
+                "Salinas Valley",
+                "The Salinas valley lies between two ranges of mountains",
+            ),
+            (
+                "https://example.com/salads",
+                "Salads",
+                "Recipes for summer salads",
+            ),
+        ] {
+            searcher
+                .upsert(
+                    &DocumentUpdate {
+                        doc_id: None,
+                        title,
+                        domain: "example.com",
+                        url,
+                        content,
+                        description: None,
+                        metadata: &Default::default(),
+                        symbols: &[],
+                        segments: &[],
+                        page_offsets: &[],
+                        tags: &[],
+                        published_at: None,
+                        last_modified: None,
+                    }
+                    .to_document(),
+                )
+                .await
+                .expect("Unable to add doc");
+        }
+        searcher.save().await.expect("Unable to save");
+        searcher.reader().reload().expect("Unable to reload");
+
+        // Words as typed, not stemmed, most common first
+        let terms = searcher
+            .complete_term("Sal", 5)
+            .expect("Unable to complete")
+            .into_iter()
+            .map(|suggestion| suggestion.term)
+            .collect::<Vec<_>>();
+        assert_eq!(terms, vec!["salinas", "salads"]);
+        assert!(searcher.complete_term("s", 5).unwrap().is_empty());
+
+        let titles = searcher
+            .complete_title("salinas val", 5)
+            .expect("Unable to complete");
+        assert_eq!(titles, vec!["Salinas Valley"]);
+
+        assert_eq!(
+            searcher.correct_term("salnas").unwrap(),
+            Some("salinas".into())
+        );
+        assert_eq!(searcher.correct_term("salinas").unwrap(), None);
+        assert_eq!(
+            searcher.correct_query("salnas rivr lens:rust").unwrap(),
+            Some("salinas river lens:rust".into())
+        );
+        assert_eq!(searcher.correct_query("salinas river").unwrap(), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! Tokenizer for code symbols, e.g. function & type names.
+use tantivy::tokenizer::{BoxTokenStream, Token, TokenStream, Tokenizer};
+
+/// Splits text into identifiers. Each identifier is kept whole so searching
+/// for `handle_fetch` matches that symbol exactly, and is also split into the
+/// words it's made of, at `_` & camelCase boundaries, so `fetch` finds it too.
+#[derive(Clone)]
+pub struct SymbolTokenizer;
+
+pub struct SymbolTokenStream {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Byte ranges of the words in an identifier, e.g. `parseHTTPRequest` is split
+/// into `parse`, `HTTP` & `Request`.
+fn identifier_words(ident: &str) -> Vec<(usize, usize)> {
+    let chars = ident.char_indices().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    for (idx, &(pos, c)) in chars.iter().enumerate() {
+        if c == '_' || c == '$' {
+            if let Some(from) = start.take() {
+                words.push((from, pos));
+            }
+            continue;
+        }
+
+        let prev = idx.checked_sub(1).map(|prev| chars[prev].1);
+        let next = chars.get(idx + 1).map(|(_, c)| *c);
+        let is_boundary = match prev {
+            // fooBar, foo2Bar
+            Some(prev) if c.is_uppercase() && (prev.is_lowercase() || prev.is_numeric()) => true,
+            // HTTPRequest: the `R` starts a new word
+            Some(prev) if c.is_uppercase() && prev.is_uppercase() => {
+                next.map(|next| next.is_lowercase()).unwrap_or_default()
+            }
+            _ => false,
+        };
+
+        match start {
+            Some(from) if is_boundary => {
+                words.push((from, pos));
+                start = Some(pos);
+            }
+            Some(_) => {}
+            None => start = Some(pos),
+        }
+    }
+
+    if let Some(from) = start {
+        words.push((from, ident.len()));
+    }
+
+    words
+}
+
+impl SymbolTokenizer {
+    fn tokenize(text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut position = 0;
+        let push = |tokens: &mut Vec<Token>, from: usize, to: usize, position: usize| {
+            tokens.push(Token {
+                offset_from: from,
+                offset_to: to,
+                position,
+                text: text[from..to].to_string(),
+                position_length: 1,
+            });
+        };
+
+        let mut ident_start = None;
+        for (pos, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            match (ident_start, is_identifier_char(c)) {
+                (None, true) => ident_start = Some(pos),
+                (Some(from), false) => {
+                    ident_start = None;
+                    push(&mut tokens, from, pos, position);
+
+                    let words = identifier_words(&text[from..pos]);
+                    if words.len() > 1 {
+                        // Words share the position of their identifier
+                        for (word_from, word_to) in words {
+                            push(&mut tokens, from + word_from, from + word_to, position);
+                        }
+                    }
+                    position += 1;
+                }
+                _ => {}
+            }
+        }
+
+        tokens
+    }
+}
+
+impl Tokenizer for SymbolTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(SymbolTokenStream {
+            tokens: SymbolTokenizer::tokenize(text),
+            cursor: 0,
+        })
+    }
+}
+
+impl TokenStream for SymbolTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.cursor < self.tokens.len() {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.cursor - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.cursor - 1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tantivy::tokenizer::Tokenizer;
+
+    use super::SymbolTokenizer;
+
+    fn tokens(text: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        SymbolTokenizer
+            .token_stream(text)
+            .process(&mut |token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_symbol_tokenizer() {
+        assert_eq!(
+            tokens("handle_fetch"),
+            vec!["handle_fetch", "handle", "fetch"]
+        );
+        assert_eq!(
+            tokens("parseHTTPRequest main"),
+            vec!["parseHTTPRequest", "parse", "HTTP", "Request", "main"]
+        );
+        assert_eq!(tokens("__init__"), vec!["__init__"]);
+        assert_eq!(tokens("Vec2d::new"), vec!["Vec2d", "new"]);
+    }
+}
@@ -4,6 +4,8 @@ use tantivy::{
     fastfield::MultiValuedFastFieldReader, termdict::TermDictionary, tokenizer::TextAnalyzer, DocId,
 };
 
+use crate::schema::TranscriptSegment;
+
 /// Max number of tokens we'll look at for matches before stopping.
 const MAX_HIGHLIGHT_SCAN: usize = 10_000;
 /// Max number of matches we need to generate a decent preview.
@@ -137,6 +139,86 @@ pub fn generate_highlight_preview(tokenizer: &TextAnalyzer, query: &str, content
     format!("<span>{}</span>", desc.join(" "))
 }
 
+/// Finds the passage w/ the most matches for the search query terms & creates
+/// a preview from it & the `context` passages on either side. Returns the index
+/// of the passage, or `None` if no passage matches.
+pub fn matching_passage_preview(
+    tokenizer: &TextAnalyzer,
+    query: &str,
+    passages: &[&str],
+    context: usize,
+) -> Option<(usize, String)> {
+    let mut terms = HashSet::new();
+    let mut tokens = tokenizer.token_stream(query);
+    while let Some(t) = tokens.next() {
+        terms.insert(t.text.clone());
+    }
+
+    let mut best = None;
+    let mut best_matches = 0;
+    for (idx, passage) in passages.iter().enumerate() {
+        let mut num_matches = 0;
+        let mut tokens = tokenizer.token_stream(passage);
+        while let Some(t) = tokens.next() {
+            if terms.contains(&t.text) {
+                num_matches += 1;
+            }
+        }
+
+        if num_matches > best_matches {
+            best = Some(idx);
+            best_matches = num_matches;
+        }
+    }
+
+    let idx = best?;
+    let surrounding = passages
+        [idx.saturating_sub(context)..(idx + context + 1).min(passages.len())]
+        .iter()
+        .map(|passage| passage.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some((
+        idx,
+        generate_highlight_preview(tokenizer, query, &surrounding),
+    ))
+}
+
+/// Finds the transcript segment w/ the most matches for the search query
+/// terms & creates a preview from the transcript around it. Returns `None` if
+/// no segment matches.
+pub fn matching_segment_preview<'a>(
+    tokenizer: &TextAnalyzer,
+    query: &str,
+    segments: &'a [TranscriptSegment],
+) -> Option<(&'a TranscriptSegment, String)> {
+    let passages = segments
+        .iter()
+        .map(|segment| segment.text.as_str())
+        .collect::<Vec<_>>();
+    // Include the segments on either side, a segment is usually a few words.
+    let (idx, preview) = matching_passage_preview(tokenizer, query, &passages, 1)?;
+    Some((&segments[idx], preview))
+}
+
+/// Splits the content of a paged document into its pages, using the byte
+/// offset where each page starts. Offsets that don't fall on a character
+/// boundary give an empty page.
+pub fn split_pages<'a>(content: &'a str, page_offsets: &[u64]) -> Vec<&'a str> {
+    page_offsets
+        .iter()
+        .enumerate()
+        .map(|(idx, start)| {
+            let end = page_offsets
+                .get(idx + 1)
+                .map(|end| (*end as usize).min(content.len()))
+                .unwrap_or(content.len());
+            content.get(*start as usize..end).unwrap_or_default()
+        })
+        .collect()
+}
+
 pub fn group_urls_by_scheme(urls: Vec<&str>) -> HashMap<&str, Vec<&str>> {
     let mut grouping: HashMap<&str, Vec<&str>> = HashMap::new();
     urls.iter().for_each(|url| {
@@ -154,22 +236,92 @@ pub fn group_urls_by_scheme(urls: Vec<&str>) -> HashMap<&str, Vec<&str>> {
 #[cfg(test)]
 mod test {
     use crate::client::Searcher;
-    use crate::schema::{DocFields, SearchDocument};
-    use crate::utils::generate_highlight_preview;
+    use crate::schema::{DocFields, SearchDocument, TranscriptSegment};
+    use crate::utils::{
+        generate_highlight_preview, matching_passage_preview, matching_segment_preview, split_pages,
+    };
     use crate::IndexBackend;
 
     #[test]
     fn test_find_highlights() {
-        let searcher = Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
-            .expect("Unable to open index");
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
         let blurb = r#"Rust rust is a multi-paradigm, high-level, general-purpose programming"#;
 
         let fields = DocFields::as_fields();
         let tokenizer = searcher
-            .index
+            .index()
             .tokenizer_for_field(fields.content)
             .expect("Unable to get tokenizer for content field");
         let desc = generate_highlight_preview(&tokenizer, "rust programming", &blurb);
         assert_eq!(desc, "<span><mark>Rust</mark> <mark>rust</mark> is a multi-paradigm, high-level, general-purpose <mark>programming</mark> ...</span>");
     }
+
+    #[test]
+    fn test_matching_segment_preview() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        let fields = DocFields::as_fields();
+        let tokenizer = searcher
+            .index()
+            .tokenizer_for_field(fields.content)
+            .expect("Unable to get tokenizer for content field");
+
+        let segments = [
+            (0, " Thanks for joining."),
+            (2_000, " First up is the budget."),
+            (5_000, " The budget for the offsite is approved."),
+            (9_000, " Next is hiring."),
+            (12_000, " That's all."),
+        ]
+        .into_iter()
+        .map(|(start_ms, text)| TranscriptSegment {
+            start_ms,
+            end_ms: start_ms + 2_000,
+            text: text.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+        let (segment, preview) = matching_segment_preview(&tokenizer, "offsite budget", &segments)
+            .expect("No matching segment");
+        assert_eq!(segment.start_ms, 5_000);
+        assert_eq!(preview, "<span>First up is the <mark>budget.</mark> The <mark>budget</mark> for the <mark>offsite</mark> is approved. Next is hiring. ...</span>");
+
+        assert!(matching_segment_preview(&tokenizer, "lunch", &segments).is_none());
+    }
+
+    #[test]
+    fn test_matching_page_preview() {
+        let searcher =
+            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+                .expect("Unable to open index");
+        let fields = DocFields::as_fields();
+        let tokenizer = searcher
+            .index()
+            .tokenizer_for_field(fields.content)
+            .expect("Unable to get tokenizer for content field");
+
+        let content = "Quarterly report\n\nRevenue grew.\n\nHiring slowed this quarter.";
+        let pages = split_pages(content, &[0, 18, 33]);
+        assert_eq!(
+            pages,
+            vec![
+                "Quarterly report\n\n",
+                "Revenue grew.\n\n",
+                "Hiring slowed this quarter."
+            ]
+        );
+        // Offsets past the end of the content give empty pages
+        assert_eq!(split_pages("short", &[0, 100]), vec!["short", ""]);
+
+        let (page, preview) =
+            matching_passage_preview(&tokenizer, "hiring", &pages, 0).expect("No matching page");
+        assert_eq!(page, 2);
+        assert_eq!(
+            preview,
+            "<span><mark>Hiring</mark> slowed this quarter. ...</span>"
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use anyhow::anyhow;
 use clap::{Parser, Subcommand};
 use entities::models::{self, indexed_document::DocumentIdentifier, tag::check_query_for_tags};
+use libspyglass::snapshot;
 use libspyglass::state::AppState;
 use ron::ser::PrettyConfig;
 use shared::config::Config;
@@ -49,6 +50,23 @@ enum Command {
         name: String,
         archive_path: PathBuf,
     },
+    /// Write a snapshot of the index & database into an empty directory.
+    /// Spyglass must not be running, use the `index.create_snapshot` RPC instead.
+    CreateSnapshot {
+        output_dir: PathBuf,
+    },
+    /// Snapshot the index & database into a single `.tar.gz` archive
+    ExportSnapshot {
+        archive_path: PathBuf,
+    },
+    /// Merge a snapshot directory or archive into the library
+    RestoreSnapshot {
+        path: PathBuf,
+        /// Replace the library instead of merging into it. The previous index
+        /// & database are kept as backups.
+        #[arg(long)]
+        replace: bool,
+    },
 }
 
 #[tokio::main]
@@ -123,6 +141,7 @@ async fn main() -> anyhow::Result<ExitCode> {
                     let index = Searcher::with_index(
                         &IndexBackend::LocalPath(config.index_dir()),
                         schema,
+                        None,
                         true,
                     )
                     .expect("Unable to open index.");
@@ -162,9 +181,13 @@ async fn main() -> anyhow::Result<ExitCode> {
             };
 
             let schema = DocFields::as_schema();
-            let index =
-                Searcher::with_index(&IndexBackend::LocalPath(config.index_dir()), schema, true)
-                    .expect("Unable to open index.");
+            let index = Searcher::with_index(
+                &IndexBackend::LocalPath(config.index_dir()),
+                schema,
+                None,
+                true,
+            )
+            .expect("Unable to open index.");
 
             let docs = index
                 .search_by_query(doc_query.urls, doc_query.ids, &[], &[])
@@ -216,6 +239,60 @@ async fn main() -> anyhow::Result<ExitCode> {
             process_update(state.clone(), &lens, archive_path, true).await;
             let _ = state.index.save().await;
         }
+        Command::CreateSnapshot { output_dir } => {
+            let state = AppState::new(&config, false).await;
+            let info = snapshot::create_snapshot(&state, &output_dir).await?;
+            println!(
+                "Snapshot w/ {} documents written to {}",
+                info.num_documents,
+                info.path.display()
+            );
+        }
+        Command::ExportSnapshot { archive_path } => {
+            let state = AppState::new(&config, false).await;
+            let info = snapshot::export_snapshot(&state, &archive_path).await?;
+            println!(
+                "Snapshot w/ {} documents exported to {}",
+                info.num_documents,
+                info.path.display()
+            );
+        }
+        Command::RestoreSnapshot { path, replace } => {
+            if !path.exists() {
+                eprintln!("{} does not exist!", path.display());
+                return Err(anyhow!("PATH does not exist"));
+            }
+
+            if replace {
+                let snapshot_dir = if path.is_dir() {
+                    path
+                } else {
+                    let dir = config.cache_dir().join("snapshot-restore");
+                    let _ = std::fs::remove_dir_all(&dir);
+                    snapshot::unpack_snapshot(&path, &dir)?;
+                    dir
+                };
+
+                let info = snapshot::replace_library(&config, &snapshot_dir)?;
+                println!(
+                    "Library replaced w/ snapshot from {} ({} documents)",
+                    info.created_at, info.num_documents
+                );
+            } else {
+                let state = AppState::new(&config, false).await;
+                let res = snapshot::restore_snapshot(&state, &path).await?;
+                println!(
+                    "Restored {} documents, skipped {} already in the library",
+                    res.num_restored, res.num_skipped
+                );
+                if res.num_missing > 0 {
+                    println!(
+                        "{} documents were missing from the snapshot index",
+                        res.num_missing
+                    );
+                }
+            }
+        }
     }
 
     Ok(ExitCode::SUCCESS)
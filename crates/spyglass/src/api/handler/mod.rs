@@ -7,7 +7,7 @@ use entities::models::lens::LensType;
 use entities::models::tag::TagType;
 use entities::models::{
     bootstrap_queue, connection::get_all_connections, crawl_queue, fetch_history, indexed_document,
-    lens,
+    lens, link,
 };
 use entities::sea_orm::{prelude::*, sea_query, Set};
 use jsonrpsee::core::Error;
@@ -24,11 +24,10 @@ use shared::config::{self, Config, UserSettings};
 use shared::metrics::Event;
 use shared::request::{BatchDocumentRequest, RawDocType, RawDocumentRequest};
 use shared::response::{
-    AppStatus, DefaultIndices, InstallStatus, LensResult, LibraryStats, ListConnectionResult,
-    PluginResult, SupportedConnection, UserConnection,
+    AppStatus, Backlink, DefaultIndices, InstallStatus, LensResult, LibraryStats,
+    ListConnectionResult, PluginResult, SupportedConnection, UserConnection,
 };
 use spyglass_rpc::{RpcEvent, RpcEventType};
-use spyglass_searcher::WriteTrait;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -188,12 +187,13 @@ pub async fn authorize_connection(state: AppState, api_id: String) -> Result<(),
 #[instrument(skip(state))]
 pub async fn app_status(state: AppState) -> Result<AppStatus, Error> {
     // Grab details about index
-    let index = state.index;
-    let reader = index.reader.searcher();
+    let num_docs = state
+        .index
+        .num_docs()
+        .await
+        .map_err(|err| Error::Custom(format!("Unable to count docs: {err}")))?;
 
-    Ok(AppStatus {
-        num_docs: reader.num_docs(),
-    })
+    Ok(AppStatus { num_docs })
 }
 
 /// Remove a doc from the index
@@ -203,10 +203,49 @@ pub async fn delete_document(state: AppState, id: String) -> Result<(), Error> {
         log::error!("Unable to delete doc {} due to {}", id, e);
         return Err(Error::Custom(e.to_string()));
     }
+    if let Err(err) = state.index.save().await {
+        log::error!("Unable to commit index: {err}");
+    }
     let _ = indexed_document::delete_many_by_doc_id(&state.db, &[id]).await;
     Ok(())
 }
 
+/// Pages linking to `url`, w/ titles filled in for pages that have been indexed
+#[instrument(skip(state))]
+pub async fn backlinks(state: AppState, url: String) -> Result<Vec<Backlink>, Error> {
+    let links = link::find_backlinks(&state.db, &url)
+        .await
+        .map_err(|err| Error::Custom(format!("Unable to query links: {err}")))?;
+    if links.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let src_urls = links
+        .iter()
+        .map(|link| link.src_url.clone())
+        .collect::<Vec<String>>();
+    let indexed = state
+        .index
+        .search_by_query(Some(src_urls), None, &[], &[])
+        .await
+        .into_iter()
+        .map(|(_, doc)| (doc.url.clone(), doc))
+        .collect::<HashMap<_, _>>();
+
+    Ok(links
+        .into_iter()
+        .map(|link| {
+            let doc = indexed.get(&link.src_url);
+            Backlink {
+                doc_id: doc.map(|doc| doc.doc_id.clone()),
+                title: doc.map(|doc| doc.title.clone()),
+                url: link.src_url,
+                domain: link.src_domain,
+            }
+        })
+        .collect())
+}
+
 /// Remove a domain from crawl queue & index
 #[instrument(skip(state))]
 pub async fn delete_domain(state: AppState, domain: String) -> Result<(), Error> {
@@ -239,6 +278,9 @@ pub async fn delete_domain(state: AppState, domain: String) -> Result<(), Error>
 
         let doc_ids: Vec<String> = indexed.iter().map(|x| x.doc_id.to_string()).collect();
         let _ = state.index.delete_many_by_id(&doc_ids).await;
+        if let Err(err) = state.index.save().await {
+            log::error!("Unable to commit index: {err}");
+        }
         let _ = indexed_document::delete_many_by_doc_id(&state.db, &doc_ids).await;
 
         log::debug!("removed {} items from index", indexed_count);
@@ -593,6 +635,9 @@ pub async fn uninstall_lens(state: AppState, config: &Config, name: &str) -> Res
         if let Err(err) = state.index.delete_many_by_id(&doc_ids).await {
             return Err(Error::Custom(err.to_string()));
         }
+        if let Err(err) = state.index.save().await {
+            log::error!("Unable to commit index: {err}");
+        }
         // Remove from db
         let _ = indexed_document::delete_many_by_id(&state.db, &dbids).await;
     }
@@ -660,7 +705,6 @@ mod test {
     use libspyglass::state::AppState;
     use shared::config::{Config, LensConfig};
     use spyglass_searcher::schema::{DocumentUpdate, ToDocument};
-    use spyglass_searcher::WriteTrait;
 
     #[tokio::test]
     async fn test_uninstall_lens() {
@@ -683,6 +727,11 @@ mod test {
                     domain: "example.com",
                     url: "https://example.com/test",
                     content: "test content",
+                    description: None,
+                    metadata: &Default::default(),
+                    symbols: &[],
+                    segments: &[],
+                    page_offsets: &[],
                     tags: &[],
                     published_at: None,
                     last_modified: None,
@@ -691,7 +740,7 @@ mod test {
             )
             .await
             .expect("Unable to add doc");
-        let _ = state.index.save().await;
+        state.index.save().await.expect("Unable to save");
 
         let doc = indexed_document::ActiveModel {
             domain: Set("example.com".into()),
@@ -724,6 +773,6 @@ mod test {
         assert_eq!(indexed.len(), 0);
         // Add a small delay so that the documents can be properly committed
         std::thread::sleep(std::time::Duration::from_millis(500));
-        assert_eq!(state.index.reader.searcher().num_docs(), 0);
+        assert_eq!(state.index.num_docs().await.unwrap(), 0);
     }
 }
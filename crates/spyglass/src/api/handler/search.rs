@@ -1,19 +1,45 @@
+use chrono::{DateTime, TimeZone, Utc};
 use entities::models::tag::{check_query_for_tags, get_favorite_tag, TagType};
-use entities::models::{indexed_document, lens, tag};
+use entities::models::{indexed_document, lens, search_history, tag};
 use entities::sea_orm::{
     self, prelude::*, sea_query::Expr, FromQueryResult, JoinType, QueryOrder, QuerySelect,
 };
 use jsonrpsee::core::Error;
 use libspyglass::state::AppState;
 use libspyglass::task::{CleanupTask, ManagerCommand};
+use shared::config::RankingMode;
 use shared::metrics;
 use shared::request;
-use shared::response::{LensResult, SearchLensesResp, SearchMeta, SearchResult, SearchResults};
-use spyglass_searcher::schema::{DocFields, SearchDocument};
-use spyglass_searcher::{Boost, QueryBoost, SearchTrait};
-use std::collections::HashSet;
+use shared::response::{
+    FacetCount, LensResult, SearchFacets, SearchLensesResp, SearchMeta, SearchResult, SearchResults,
+};
+use spyglass_searcher::collapse::{Collapse, CollapseKey};
+use spyglass_searcher::facets::FacetCounts;
+use spyglass_searcher::query_parser::ParsedQuery;
+use spyglass_searcher::ranking::{FusionStrategy, DEFAULT_RRF_K};
+use spyglass_searcher::schema::content_tokenizer_for;
+use spyglass_searcher::{Boost, DateField, QueryBoost};
+use std::collections::{HashMap, HashSet};
+use std::sync::Once;
 use std::time::SystemTime;
 use tracing::instrument;
+use url::Url;
+
+/// Makes sure the hybrid ranking fallback is only logged once.
+static HYBRID_FALLBACK: Once = Once::new();
+
+/// Adds a fragment to the URL so it opens at the match, e.g. a media fragment
+/// (`#t=<seconds>`) for audio or `#page=<n>` for PDFs. The client passes it on
+/// to the app opening the file, apps that don't support it start at the top.
+fn url_with_fragment(url: &str, fragment: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            url.set_fragment(Some(fragment));
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
 
 /// Search the user's indexed documents
 #[instrument(skip(state))]
@@ -29,13 +55,28 @@ pub async fn search_docs(
         .await;
 
     let start = SystemTime::now();
-    let index = &state.index;
-    let searcher = index.reader.searcher();
-    let query = search_req.query.clone();
+
+    // Parse out any field/tag clauses, mapping the tags to their ids.
+    let mut parsed_query = ParsedQuery::parse(&search_req.query);
+    let tag_pairs = parsed_query.tag_pairs();
+    if !tag_pairs.is_empty() {
+        let tag_ids = tag::find_by_label_value_ignore_case(&state.db, &tag_pairs)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|model| {
+                let key = (model.label.to_lowercase(), model.value.to_lowercase());
+                (key, model.id as u64)
+            })
+            .collect::<HashMap<_, _>>();
+        parsed_query.resolve_tags(&tag_ids);
+    }
+    // Free text used for boosts & previews
+    let query = parsed_query.text.clone();
 
     let lens_ids = tag::Entity::find()
         .filter(tag::Column::Label.eq(tag::TagType::Lens.to_string()))
-        .filter(tag::Column::Value.is_in(search_req.lenses))
+        .filter(tag::Column::Value.is_in(search_req.lenses.clone()))
         .all(&state.db)
         .await
         .unwrap_or_default()
@@ -53,6 +94,36 @@ pub async fn search_docs(
         filters.push(QueryBoost::new(Boost::Tag(lens)));
     }
 
+    for (field, range) in [
+        (DateField::Published, &search_req.published),
+        (DateField::LastModified, &search_req.last_modified),
+    ] {
+        if let Some(range) = range {
+            filters.push(QueryBoost::new(Boost::DateRange {
+                field,
+                start: range.start.and_then(timestamp_to_date),
+                end: range.end.and_then(timestamp_to_date),
+            }));
+        }
+    }
+
+    if search_req.boost_recent {
+        boosts.push(QueryBoost::new(Boost::Recency(DateField::LastModified)));
+    }
+
+    let record_history = !state.user_settings.load().disable_search_history;
+    if record_history {
+        // Rank results the user picked for similar queries higher
+        for (doc_id, weight) in search_history::opened_for_query(&state.db, &query)
+            .await
+            .unwrap_or_default()
+        {
+            let mut boost = QueryBoost::new(Boost::Opened(doc_id));
+            boost.value *= weight;
+            boosts.push(boost);
+        }
+    }
+
     if let Some(tag_id) = get_favorite_tag(&state.db).await {
         filters.push(QueryBoost::new(Boost::Favorite {
             id: tag_id,
@@ -60,11 +131,94 @@ pub async fn search_docs(
         }));
     }
 
-    let search_result = state.index.search(&query, &filters, &boosts, 5).await;
+    let offset = search_req.offset();
+    let ranking = search_req.ranking.unwrap_or_else(|| {
+        search_req
+            .lenses
+            .iter()
+            .filter_map(|name| state.lenses.get(name).map(|lens| lens.ranking))
+            .find(|ranking| *ranking != RankingMode::Lexical)
+            .unwrap_or_default()
+    });
+
+    let collapse = match &search_req.collapse {
+        Some(param) => Some(build_collapse(&state, param).await),
+        None => None,
+    };
+
+    // Hybrid search needs the embeddings stored alongside the local index
+    let embedded_index = state
+        .index
+        .as_local()
+        .filter(|index| index.vectors.is_some());
+    let hybrid = fusion_strategy(ranking).zip(embedded_index);
+    let ranking = if hybrid.is_none() && ranking != RankingMode::Lexical {
+        HYBRID_FALLBACK.call_once(|| {
+            log::warn!("Hybrid ranking requested but the index has no embeddings, using lexical");
+        });
+        RankingMode::Lexical
+    } else {
+        ranking
+    };
+    let search_result = match (hybrid, &collapse) {
+        (Some((strategy, local)), _) => {
+            // Fused results are collapsed after the fact, so over-fetch the
+            // candidates & page through the collapsed list here.
+            let (hybrid_offset, hybrid_limit) = match &collapse {
+                Some(collapse) => (0, collapse.candidate_limit(offset, search_req.limit())),
+                None => (offset, search_req.limit()),
+            };
+
+            match local
+                .hybrid_search(
+                    &parsed_query,
+                    &filters,
+                    &boosts,
+                    hybrid_offset,
+                    hybrid_limit,
+                    strategy,
+                )
+                .await
+            {
+                Ok(mut result) => {
+                    if let Some(collapse) = &collapse {
+                        result.documents = collapse
+                            .apply(result.documents)
+                            .into_iter()
+                            .skip(offset)
+                            .take(search_req.limit())
+                            .collect();
+                    }
+                    result
+                }
+                Err(err) => return Err(Error::Custom(err.to_string())),
+            }
+        }
+        (None, Some(collapse)) => {
+            state
+                .index
+                .search_collapsed(
+                    &parsed_query,
+                    &filters,
+                    &boosts,
+                    offset,
+                    search_req.limit(),
+                    collapse,
+                )
+                .await
+        }
+        (None, None) => {
+            state
+                .index
+                .search_with_query(&parsed_query, &filters, &boosts, offset, search_req.limit())
+                .await
+        }
+    };
     log::debug!(
-        "query {}: {} results from {} docs in {}ms",
-        query,
+        "query {}: {}/{} results from {} docs in {}ms",
+        search_req.query,
         search_result.documents.len(),
+        search_result.total_hits,
         search_result.num_docs,
         search_result.wall_time_ms
     );
@@ -90,17 +244,45 @@ pub async fn search_docs(
                     .map(|tag| (tag.label.to_string(), tag.value.clone()))
                     .collect::<Vec<(String, String)>>();
 
-                let fields = DocFields::as_fields();
-                let tokenizer = index
-                    .index
-                    .tokenizer_for_field(fields.content)
-                    .expect("Unable to get tokenizer for content field");
+                // Matched w/ the analyzer the content was indexed with
+                let tokenizer = content_tokenizer_for(doc.lang.as_deref());
 
-                let description = spyglass_searcher::utils::generate_highlight_preview(
+                let mut description = spyglass_searcher::utils::generate_highlight_preview(
                     &tokenizer,
                     &query,
                     &doc.content,
                 );
+                // Nothing to preview, fallback to the description provided by the doc.
+                if description.trim().is_empty() {
+                    description = doc.description;
+                }
+
+                let mut url = indexed.open_url.unwrap_or_else(|| crawl_uri.clone());
+                // Point audio results at the part of the transcript that matched
+                let mut timestamp_ms = None;
+                if let Some((segment, preview)) = spyglass_searcher::utils::matching_segment_preview(
+                    &tokenizer,
+                    &query,
+                    &doc.segments,
+                ) {
+                    description = preview;
+                    timestamp_ms = Some(segment.start_ms);
+                    url = url_with_fragment(&url, &format!("t={}", segment.start_ms / 1000));
+                }
+
+                // Point results in multi-page documents at the page that matched
+                let mut page = None;
+                if timestamp_ms.is_none() && doc.page_offsets.len() > 1 {
+                    let pages =
+                        spyglass_searcher::utils::split_pages(&doc.content, &doc.page_offsets);
+                    if let Some((idx, preview)) = spyglass_searcher::utils::matching_passage_preview(
+                        &tokenizer, &query, &pages, 0,
+                    ) {
+                        description = preview;
+                        page = Some(idx as u32 + 1);
+                        url = url_with_fragment(&url, &format!("page={}", idx + 1));
+                    }
+                }
 
                 let result = SearchResult {
                     doc_id: doc.doc_id.clone(),
@@ -108,8 +290,11 @@ pub async fn search_docs(
                     title: doc.title,
                     crawl_uri: crawl_uri.clone(),
                     description,
-                    url: indexed.open_url.unwrap_or(crawl_uri),
+                    url,
                     tags,
+                    metadata: doc.metadata,
+                    timestamp_ms,
+                    page,
                     score,
                 };
 
@@ -125,13 +310,24 @@ pub async fn search_docs(
         .duration_since(start)
         .map_or_else(|_| 0, |duration| duration.as_millis() as u64);
 
-    let num_docs = searcher.num_docs();
+    let num_docs = search_result.num_docs;
     let meta = SearchMeta {
         query: search_req.query.clone(),
         num_docs: num_docs as u32,
         wall_time_ms: wall_time_ms as u32,
+        total_hits: search_result.total_hits as u32,
+        offset: offset as u32,
+        ranking,
     };
 
+    // Only the first page is recorded, later pages are the same search
+    if record_history && offset == 0 {
+        let shown = results.iter().map(|result| result.doc_id.clone()).collect();
+        if let Err(err) = search_history::record_search(&state.db, &search_req.query, shown).await {
+            log::warn!("Unable to record search: {err}");
+        }
+    }
+
     let domains: HashSet<String> = HashSet::from_iter(results.iter().map(|r| r.domain.clone()));
     state
         .metrics
@@ -157,7 +353,119 @@ pub async fn search_docs(
         }
     }
 
-    Ok(SearchResults { results, meta })
+    let facets = if search_req.include_facets {
+        match state
+            .index
+            .facet_counts(&parsed_query, &filters, &boosts)
+            .await
+        {
+            Ok(counts) => Some(resolve_facets(&state, counts).await),
+            Err(err) => {
+                log::warn!("Unable to count facets: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Ok(SearchResults {
+        results,
+        meta,
+        facets,
+    })
+}
+
+/// Records a result the user opened from a search
+#[instrument(skip(state))]
+pub async fn record_opened(
+    state: &AppState,
+    opened: request::ResultOpenedParam,
+) -> Result<(), Error> {
+    if state.user_settings.load().disable_search_history {
+        return Ok(());
+    }
+
+    search_history::record_opened(&state.db, &opened.query, &opened.doc_id)
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::Custom(format!("Unable to record opened result: {err}")))
+}
+
+/// Max number of values returned for each facet
+const FACET_LIMIT: usize = 25;
+
+/// Map the tag ids in the facet counts to their (label, value) through the tag table.
+async fn resolve_facets(state: &AppState, counts: FacetCounts) -> SearchFacets {
+    let tag_ids = counts.tags.keys().map(|id| *id as i64).collect::<Vec<_>>();
+    let tags = tag::Entity::find()
+        .filter(tag::Column::Id.is_in(tag_ids))
+        .all(&state.db)
+        .await
+        .unwrap_or_default();
+
+    let mut facets = SearchFacets::default();
+    for tag in tags {
+        let count = counts
+            .tags
+            .get(&(tag.id as u64))
+            .copied()
+            .unwrap_or_default();
+        let facet = FacetCount {
+            label: tag.label.clone(),
+            value: tag.value.clone(),
+            count: count as u32,
+        };
+
+        if tag.label == TagType::Lens.to_string() {
+            facets.lenses.push(facet);
+        } else {
+            facets.tags.push(facet);
+        }
+    }
+
+    facets.domains = counts
+        .domains
+        .into_iter()
+        .map(|(domain, count)| FacetCount {
+            label: "domain".into(),
+            value: domain,
+            count: count as u32,
+        })
+        .collect();
+
+    for list in [&mut facets.tags, &mut facets.domains, &mut facets.lenses] {
+        list.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        list.truncate(FACET_LIMIT);
+    }
+
+    facets
+}
+
+/// Maps the requested collapse field to the key used by the searcher,
+/// resolving `repository` tags to their ids.
+async fn build_collapse(state: &AppState, param: &request::CollapseParam) -> Collapse {
+    let key = match param.field {
+        request::CollapseField::Domain => CollapseKey::Domain,
+        request::CollapseField::ParentUrl => CollapseKey::ParentUrl,
+        request::CollapseField::Repository => {
+            let repo_ids = tag::Entity::find()
+                .filter(tag::Column::Label.eq(TagType::Repository.to_string()))
+                .all(&state.db)
+                .await
+                .unwrap_or_default()
+                .iter()
+                .map(|model| model.id as u64)
+                .collect();
+            CollapseKey::Tag(repo_ids)
+        }
+    };
+
+    Collapse::new(key, param.max_per_group())
+}
+
+fn timestamp_to_date(timestamp: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(timestamp, 0).single()
 }
 
 #[derive(FromQueryResult)]
@@ -167,6 +475,18 @@ struct LensSearch {
     description: Option<String>,
 }
 
+/// Maps the requested ranking mode to how lexical & vector results are fused,
+/// `None` for plain lexical ranking.
+fn fusion_strategy(ranking: RankingMode) -> Option<FusionStrategy> {
+    match ranking {
+        RankingMode::Lexical => None,
+        RankingMode::Hybrid => Some(FusionStrategy::ReciprocalRank { k: DEFAULT_RRF_K }),
+        RankingMode::HybridWeighted { lexical_weight } => {
+            Some(FusionStrategy::WeightedScore { lexical_weight })
+        }
+    }
+}
+
 /// Search the user's installed lenses
 #[instrument(skip(state))]
 pub async fn search_lenses(
@@ -208,3 +528,21 @@ pub async fn search_lenses(
 
     Ok(SearchLensesResp { results })
 }
+
+#[cfg(test)]
+mod test {
+    use super::url_with_fragment;
+
+    #[test]
+    fn test_url_with_fragment() {
+        assert_eq!(
+            url_with_fragment("file:///home/user/standup.mp3", "t=65"),
+            "file:///home/user/standup.mp3#t=65"
+        );
+        assert_eq!(
+            url_with_fragment("file:///home/user/report.pdf#page=1", "page=3"),
+            "file:///home/user/report.pdf#page=3"
+        );
+        assert_eq!(url_with_fragment("not a url", "t=1"), "not a url");
+    }
+}
@@ -7,15 +7,20 @@ use jsonrpsee::server::middleware::proxy_get_request::ProxyGetRequestLayer;
 use jsonrpsee::server::{ServerBuilder, ServerHandle};
 use jsonrpsee::types::{SubscriptionEmptyError, SubscriptionResult};
 use jsonrpsee::SubscriptionSink;
+use libspyglass::snapshot;
 use libspyglass::state::AppState;
+use libspyglass::suggest;
 use libspyglass::task::{CollectTask, ManagerCommand};
 use shared::config::{Config, UserSettings};
-use shared::request::{BatchDocumentRequest, RawDocumentRequest, SearchLensesParam, SearchParam};
+use shared::request::{
+    BatchDocumentRequest, RawDocumentRequest, ResultOpenedParam, SearchLensesParam, SearchParam,
+    SuggestParam,
+};
 use shared::response::{self as resp, DefaultIndices, LibraryStats};
 use spyglass_rpc::{RpcEventType, RpcServer};
-use spyglass_searcher::WriteTrait;
 use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 
 mod handler;
 mod response;
@@ -73,6 +78,28 @@ impl RpcServer for SpyglassRpc {
         }
     }
 
+    async fn backlinks(&self, url: String) -> Result<Vec<resp::Backlink>, Error> {
+        handler::backlinks(self.state.clone(), url).await
+    }
+
+    async fn create_snapshot(&self, path: PathBuf) -> Result<resp::SnapshotInfo, Error> {
+        snapshot::create_snapshot(&self.state, &path)
+            .await
+            .map_err(|err| Error::Custom(format!("Unable to create snapshot: {err}")))
+    }
+
+    async fn export_snapshot(&self, path: PathBuf) -> Result<resp::SnapshotInfo, Error> {
+        snapshot::export_snapshot(&self.state, &path)
+            .await
+            .map_err(|err| Error::Custom(format!("Unable to export snapshot: {err}")))
+    }
+
+    async fn restore_snapshot(&self, path: PathBuf) -> Result<resp::RestoreSnapshotResult, Error> {
+        snapshot::restore_snapshot(&self.state, &path)
+            .await
+            .map_err(|err| Error::Custom(format!("Unable to restore snapshot: {err}")))
+    }
+
     async fn get_library_stats(&self) -> Result<HashMap<String, LibraryStats>, Error> {
         match get_library_stats(&self.state.db).await {
             Ok(stats) => Ok(stats),
@@ -166,6 +193,9 @@ impl RpcServer for SpyglassRpc {
             .collect::<Vec<String>>();
         let _ = connection::revoke_connection(&self.state.db, &api_id, &account).await;
         let _ = self.state.index.delete_many_by_id(&doc_ids).await;
+        if let Err(err) = self.state.index.save().await {
+            log::error!("Unable to commit index: {err}");
+        }
         let _ = indexed_document::delete_many_by_doc_id(&self.state.db, &doc_ids).await;
         log::debug!("revoked & deleted {} docs", doc_ids.len());
         Ok(())
@@ -175,6 +205,16 @@ impl RpcServer for SpyglassRpc {
         handler::search::search_docs(self.state.clone(), query).await
     }
 
+    async fn suggest(&self, query: SuggestParam) -> Result<resp::SuggestResults, Error> {
+        suggest::suggest(&self.state, &query.query, query.limit())
+            .await
+            .map_err(|err| Error::Custom(format!("Unable to suggest: {err}")))
+    }
+
+    async fn record_opened(&self, opened: ResultOpenedParam) -> Result<(), Error> {
+        handler::search::record_opened(&self.state, opened).await
+    }
+
     async fn search_lenses(
         &self,
         query: SearchLensesParam,
@@ -9,7 +9,6 @@ use entities::{
 use jsonrpsee::core::async_trait;
 use libgithub::types::{Issue, Repo};
 use libgithub::GithubClient;
-use spyglass_searcher::WriteTrait;
 use strum_macros::{Display, EnumString};
 use url::Url;
 
@@ -326,6 +325,8 @@ fn issue_to_crawl(api_url: &Url, issue: &Issue) -> CrawlResult {
     result
         .tags
         .push((TagType::Type, GithubDocTypes::Issue.to_string()));
+    result.published_at = Some(issue.created_at);
+    result.last_modified = Some(issue.updated_at);
 
     result
 }
@@ -346,6 +347,8 @@ fn repo_to_crawl(api_url: &Url, repo: &Repo) -> CrawlResult {
     result
         .tags
         .push((TagType::Type, GithubDocTypes::Repository.to_string()));
+    result.published_at = Some(repo.created_at);
+    result.last_modified = Some(repo.updated_at);
 
     result
 }
@@ -2,9 +2,10 @@ use addr::parse_domain_name;
 use anyhow::Result;
 use chrono::prelude::*;
 use chrono::Duration;
+use entities::models::fetch_history::CacheValidators;
 use entities::models::tag::TagPair;
 use entities::models::tag::TagType;
-use entities::models::{crawl_queue, fetch_history};
+use entities::models::{crawl_queue, fetch_history, indexed_document};
 use entities::sea_orm::prelude::*;
 use governor::clock::QuantaClock;
 use governor::state::keyed::DashMapStateStore;
@@ -14,9 +15,11 @@ use libnetrunner::crawler::handle_crawl;
 use libnetrunner::parser::html::{html_to_text, DEFAULT_DESC_LENGTH};
 use nonzero_ext::nonzero;
 use percent_encoding::percent_decode_str;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use reqwest::{Client, StatusCode};
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
+use shared::ARCHIVE_SEPARATOR;
+use std::collections::{BTreeMap, HashSet};
 use std::num::NonZeroU32;
 use std::path::Path;
 use std::path::PathBuf;
@@ -29,8 +32,11 @@ use crate::crawler::bootstrap::create_archive_url;
 use crate::filesystem;
 use crate::state::{AppState, FetchLimitType};
 
-use spyglass_processor::parser;
+use spyglass_processor::parser::{self, archive_parser::ArchiveLimits, ParsedDocument};
 use spyglass_processor::utils::extensions::SupportedExt;
+use spyglass_searcher::schema::{
+    TranscriptSegment, META_AUTHOR, META_LANGUAGE, META_MIME_TYPE, META_PAGE_COUNT,
+};
 
 pub mod archive;
 pub mod bootstrap;
@@ -97,6 +103,24 @@ pub struct CrawlResult {
     pub links: HashSet<String>,
     /// Tags to apply to this document
     pub tags: Vec<TagPair>,
+    /// When the document was first published/created, if known.
+    pub published_at: Option<DateTime<Utc>>,
+    /// When the document was last modified, from HTTP headers, file metadata or
+    /// the connection API.
+    pub last_modified: Option<DateTime<Utc>>,
+    /// `ETag`/`Last-Modified` headers returned when fetching this page over HTTP.
+    pub cache_validators: CacheValidators,
+    /// Extracted metadata (author, language, page count, mime type, etc.)
+    pub metadata: BTreeMap<String, String>,
+    /// Names of the functions, types, etc. defined in a source file
+    pub symbols: Vec<String>,
+    /// Timestamped segments of an audio transcript
+    pub segments: Vec<TranscriptSegment>,
+    /// Byte offset in the content where each page starts, for paged documents
+    pub page_offsets: Vec<u64>,
+    /// Documents contained in this one that are indexed on their own, e.g. the
+    /// messages in a mailbox. Their URL is this one w/ a fragment.
+    pub parts: Vec<CrawlResult>,
 }
 
 impl CrawlResult {
@@ -256,12 +280,31 @@ impl Crawler {
         }
     }
 
-    /// Fetches and parses the content of a page.
-    async fn crawl(&self, url: &Url, parse_results: bool) -> Result<CrawlResult, CrawlError> {
-        match handle_crawl(&self.client, None, self.limiter.clone(), url).await {
-            Ok(crawl) => {
+    /// Fetches and parses the content of a page. When `validators` are provided
+    /// the request is made conditional & an unchanged page returns
+    /// `CrawlError::NotModified`.
+    async fn crawl(
+        &self,
+        url: &Url,
+        parse_results: bool,
+        validators: Option<&CacheValidators>,
+    ) -> Result<CrawlResult, CrawlError> {
+        let fetched = match validators.filter(|validators| !validators.is_empty()) {
+            Some(validators) => self.conditional_fetch(url, validators).await?,
+            None => None,
+        };
+
+        let fetched = match fetched {
+            Some(fetched) => Ok(fetched),
+            None => handle_crawl(&self.client, None, self.limiter.clone(), url)
+                .await
+                .map(|crawl| (crawl.url, crawl.headers, crawl.content)),
+        };
+
+        match fetched {
+            Ok((crawl_url, headers, content)) => {
                 if parse_results {
-                    let result = self.scrape_page(url, &crawl.headers, &crawl.content).await;
+                    let result = self.scrape_page(url, &headers, &content).await;
                     match result {
                         Some(crawl) => Ok(crawl),
                         None => Err(CrawlError::Unsupported(format!(
@@ -270,8 +313,9 @@ impl Crawler {
                     }
                 } else {
                     Ok(CrawlResult {
-                        url: crawl.url.clone(),
-                        open_url: Some(crawl.url),
+                        url: crawl_url.clone(),
+                        open_url: Some(crawl_url),
+                        cache_validators: CacheValidators::from_headers(&headers),
                         ..Default::default()
                     })
                 }
@@ -280,6 +324,58 @@ impl Crawler {
         }
     }
 
+    /// Makes a conditional GET request for a page we've fetched before. Returns
+    /// the final URL, headers & body if the page has changed. Any response
+    /// other than a success or `304 Not Modified` returns `None` so the
+    /// request is retried through the regular crawl path.
+    async fn conditional_fetch(
+        &self,
+        url: &Url,
+        validators: &CacheValidators,
+    ) -> Result<Option<(String, Vec<(String, String)>, String)>, CrawlError> {
+        if let Some(domain) = url.host_str() {
+            self.limiter.until_key_ready(&domain.to_string()).await;
+        }
+
+        let resp = match self
+            .client
+            .get(url.as_str())
+            .headers(conditional_headers(validators))
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                log::debug!("conditional request failed for {}: {}", url, err);
+                return Ok(None);
+            }
+        };
+
+        let status = resp.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Err(CrawlError::NotModified);
+        } else if !status.is_success() {
+            return Ok(None);
+        }
+
+        let final_url = resp.url().to_string();
+        let headers = resp
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str().to_string(),
+                    value.to_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        match resp.text().await {
+            Ok(body) => Ok(Some((final_url, headers, body))),
+            Err(err) => Err(CrawlError::FetchError(err.to_string())),
+        }
+    }
+
     pub async fn scrape_page(
         &self,
         url: &Url,
@@ -303,6 +399,25 @@ impl Crawler {
         let extracted = parse_result.canonical_url.and_then(|s| Url::parse(&s).ok());
         let canonical_url = determine_canonical(url, extracted);
 
+        let last_modified = headers
+            .iter()
+            .find(|(header, _value)| header.eq("last-modified"))
+            .and_then(|(_header, value)| parse_http_date(value));
+
+        let mut metadata = BTreeMap::new();
+        if let Some((_, value)) = content_type {
+            if let Some(mime) = value.split(';').next().map(|mime| mime.trim()) {
+                metadata.insert(META_MIME_TYPE.to_string(), mime.to_string());
+            }
+        }
+
+        if let Some((_, value)) = headers
+            .iter()
+            .find(|(header, _value)| header.eq("content-language"))
+        {
+            metadata.insert(META_LANGUAGE.to_string(), value.trim().to_string());
+        }
+
         Some(CrawlResult {
             content_hash: Some(parse_result.content_hash),
             content: Some(parse_result.content),
@@ -311,6 +426,9 @@ impl Crawler {
             url: canonical_url.clone(),
             open_url: Some(canonical_url),
             links: parse_result.links,
+            last_modified,
+            cache_validators: CacheValidators::from_headers(headers),
+            metadata,
             ..Default::default()
         })
     }
@@ -450,8 +568,40 @@ impl Crawler {
             return Err(CrawlError::Denied("robots.txt".to_string()));
         }
 
+        // Only make conditional requests for pages that are still indexed,
+        // otherwise a 304 would leave them out of the index.
+        let history = if crawl.crawl_type == crawl_queue::CrawlType::Bootstrap {
+            None
+        } else {
+            match indexed_document::Entity::find()
+                .filter(indexed_document::Column::Url.eq(url.as_str()))
+                .one(db)
+                .await
+            {
+                Ok(Some(_)) => fetch_history::find_by_url(db, &url).await.ok().flatten(),
+                _ => None,
+            }
+        };
+        let validators = history.as_ref().map(|history| history.validators());
+
         // Crawl & save the data
-        match self.crawl(&url, parse_results).await {
+        match self.crawl(&url, parse_results, validators.as_ref()).await {
+            Err(CrawlError::NotModified) => {
+                log::debug!("{} not modified since last fetch", url);
+                if let Some(history) = history {
+                    let _ = fetch_history::upsert(
+                        db,
+                        &history.domain,
+                        &history.path,
+                        history.hash.clone(),
+                        StatusCode::NOT_MODIFIED.as_u16(),
+                        &history.validators(),
+                    )
+                    .await;
+                }
+
+                Err(CrawlError::NotModified)
+            }
             Err(err) => {
                 log::debug!("issue fetching {:?} - {}", url, err.to_string());
                 Err(err)
@@ -494,8 +644,15 @@ impl Crawler {
                     path = format!("{path}?{query}");
                 }
 
-                let _ = fetch_history::upsert(db, domain, &path, result.content_hash.clone(), 200)
-                    .await;
+                let _ = fetch_history::upsert(
+                    db,
+                    domain,
+                    &path,
+                    result.content_hash.clone(),
+                    200,
+                    &result.cache_validators,
+                )
+                .await;
 
                 Ok(result)
             }
@@ -515,16 +672,27 @@ async fn _process_file(
     let mut content = None;
     let mut title = Some(file_name.clone());
     let mut tags = Vec::new();
+    let mut metadata = BTreeMap::new();
+    let mut symbols = Vec::new();
+    let mut segments = Vec::new();
+    let mut page_offsets = Vec::new();
+    let mut published_at = None;
+    let mut parts = Vec::new();
+    let mut module_doc = None;
+    if let Some(mime) = new_mime_guess::from_path(path).first_raw() {
+        metadata.insert(META_MIME_TYPE.to_string(), mime.to_string());
+    }
 
     if let Some(ext) = ext {
-        let extension = SupportedExt::from_ext(&ext.to_string_lossy());
+        let extension = SupportedExt::from_path(path);
 
         // Limit check
         let limit = match &extension {
             SupportedExt::Audio(_) => Some((FetchLimitType::Audio, AUDIO_TRANSCRIPTION_LIMIT)),
-            SupportedExt::Code(_) | SupportedExt::Document(_) | SupportedExt::Text(_) => {
-                Some((FetchLimitType::File, FILE_PROCESSING_LIMIT))
-            }
+            SupportedExt::Archive(_)
+            | SupportedExt::Code(_)
+            | SupportedExt::Document(_)
+            | SupportedExt::Text(_) => Some((FetchLimitType::File, FILE_PROCESSING_LIMIT)),
             _ => None,
         };
 
@@ -541,7 +709,28 @@ async fn _process_file(
             .await;
         }
 
-        match SupportedExt::from_ext(&ext.to_string_lossy()) {
+        let pdftotext = state
+            .user_settings
+            .load()
+            .filesystem_settings
+            .pdftotext_path
+            .clone();
+
+        match extension {
+            SupportedExt::Archive(_) => {
+                let limits = ArchiveLimits::default();
+                match parser::archive_parser::parse(path, pdftotext.as_deref(), &limits) {
+                    Ok(parsed) => {
+                        content = Some(parsed.content);
+                        parts = parsed
+                            .parts
+                            .into_iter()
+                            .map(|file| _archive_file_to_result(url, path, file))
+                            .collect();
+                    }
+                    Err(err) => log::warn!("Unable to read archive `{}`: {}", path.display(), err),
+                }
+            }
             SupportedExt::Audio(_) => {
                 log::debug!("starting transcription for `{}`", file_name);
                 // Attempt to transcribe audio, assumes the model has been downloaded
@@ -580,6 +769,18 @@ async fn _process_file(
                                 .collect::<Vec<String>>()
                                 .join("");
                             content = Some(combined);
+
+                            // Keep the timestamps so matches can point to where
+                            // they are in the recording.
+                            segments = result
+                                .segments
+                                .iter()
+                                .map(|x| TranscriptSegment {
+                                    start_ms: x.start_ms(),
+                                    end_ms: x.end_ms(),
+                                    text: x.segment.clone(),
+                                })
+                                .collect();
                         }
                         Err(err) => {
                             log::warn!(
@@ -591,20 +792,47 @@ async fn _process_file(
                     }
                 }
             }
-            SupportedExt::Document(_) => match parser::parse_file(ext, path) {
-                Ok(parsed) => {
-                    content = Some(parsed.content);
-                    if let Some(parsed_author) = parsed.author {
-                        tags.push((TagType::Author, parsed_author))
-                    }
-                    if let Some(parsed_title) = parsed.title {
-                        title = Some(parsed_title);
+            SupportedExt::Document(_) => {
+                match parser::parse_file(ext, path, pdftotext.as_deref()) {
+                    Ok(parsed) => {
+                        content = Some(parsed.content);
+                        page_offsets = parsed.page_offsets;
+                        if let Some(parsed_author) = parsed.author {
+                            metadata.insert(META_AUTHOR.to_string(), parsed_author.clone());
+                            tags.push((TagType::Author, parsed_author))
+                        }
+                        if let Some(page_count) = parsed.page_count {
+                            metadata.insert(META_PAGE_COUNT.to_string(), page_count.to_string());
+                        }
+                        if let Some(parsed_title) = parsed.title {
+                            title = Some(parsed_title);
+                        }
+                        for recipient in parsed.recipients {
+                            tags.push((TagType::SharedWith, recipient));
+                        }
+                        published_at = parsed
+                            .published_at
+                            .and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+                        parts = parsed
+                            .parts
+                            .into_iter()
+                            .map(|part| _part_to_result(url, path, part))
+                            .collect();
                     }
+                    Err(err) => log::warn!("Unable to parse `{}`: {}", path.display(), err),
+                }
+            }
+            SupportedExt::Code(code_ext) => match std::fs::read_to_string(path) {
+                Ok(x) => {
+                    let parsed = parser::code_parser::parse(&code_ext, &x);
+                    tags.push((TagType::Language, parsed.language.name().to_string()));
+                    symbols = parsed.symbol_names();
+                    module_doc = parsed.module_doc;
+                    content = Some(x);
                 }
                 Err(err) => log::warn!("Unable to parse `{}`: {}", path.display(), err),
             },
-            // todo: also parse symbols from code files.
-            SupportedExt::Code(_) | SupportedExt::Text(_) => match std::fs::read_to_string(path) {
+            SupportedExt::Text(_) => match std::fs::read_to_string(path) {
                 Ok(x) => {
                     content = Some(x);
                 }
@@ -622,19 +850,11 @@ async fn _process_file(
         }
     }
 
-    let content_hash = content.as_ref().map(|x| {
-        let mut hasher = Sha256::new();
-        hasher.update(x.as_bytes());
-        hex::encode(&hasher.finalize()[..])
-    });
+    let content_hash = content.as_deref().map(_content_hash);
 
     // TODO: Better description building for text files?
-    let description = content.as_ref().map(|x| {
-        x.split(' ')
-            .take(DEFAULT_DESC_LENGTH)
-            .collect::<Vec<&str>>()
-            .join(" ")
-    });
+    // Source files documented at the top use that as their description
+    let description = module_doc.or_else(|| content.as_deref().map(_default_description));
 
     tags.extend(filesystem::build_file_tags(path));
     Ok(CrawlResult {
@@ -647,9 +867,112 @@ async fn _process_file(
         open_url: Some(url.to_string()),
         links: Default::default(),
         tags,
+        published_at,
+        last_modified: Some(filesystem::utils::last_modified_time(path)),
+        metadata,
+        symbols,
+        segments,
+        page_offsets,
+        parts,
+        ..Default::default()
     })
 }
 
+fn _content_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    hex::encode(&hasher.finalize()[..])
+}
+
+fn _default_description(content: &str) -> String {
+    content
+        .split(' ')
+        .take(DEFAULT_DESC_LENGTH)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Builds the crawl result for a document contained in a file, e.g. a message
+/// in a mailbox. The part is addressed by a fragment on the file's URL & opens
+/// the file itself.
+fn _part_to_result(url: &Url, path: &Path, part: ParsedDocument) -> CrawlResult {
+    let mut part_url = url.clone();
+    part_url.set_fragment(part.part_id.as_deref());
+
+    let mut tags = filesystem::build_file_tags(path);
+    let mut metadata = BTreeMap::new();
+    if let Some(author) = part.author {
+        metadata.insert(META_AUTHOR.to_string(), author.clone());
+        tags.push((TagType::Author, author));
+    }
+    for recipient in part.recipients {
+        tags.push((TagType::SharedWith, recipient));
+    }
+
+    CrawlResult {
+        content_hash: Some(_content_hash(&part.content)),
+        description: Some(_default_description(&part.content)),
+        title: part.title,
+        url: part_url.to_string(),
+        open_url: Some(url.to_string()),
+        tags,
+        published_at: part
+            .published_at
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
+        last_modified: Some(filesystem::utils::last_modified_time(path)),
+        metadata,
+        content: Some(part.content),
+        ..Default::default()
+    }
+}
+
+/// Builds the crawl result for a file inside an archive. The file is addressed
+/// by its path in the archive after the archive's URL, e.g.
+/// `file:///a.zip!/docs/readme.md`.
+fn _archive_file_to_result(url: &Url, path: &Path, file: ParsedDocument) -> CrawlResult {
+    let file_path = file.part_id.unwrap_or_default();
+    let mut file_url = url.clone();
+    file_url.set_path(&format!("{}{}{}", url.path(), ARCHIVE_SEPARATOR, file_path));
+
+    // Tag w/ the type of the file in the archive rather than the archive's
+    let mut tags = filesystem::build_file_tags(path)
+        .into_iter()
+        .filter(|(tag, _)| !matches!(tag, TagType::FileExt | TagType::MimeType))
+        .collect::<Vec<TagPair>>();
+    if let Some(ext) = Path::new(&file_path).extension() {
+        tags.push((TagType::FileExt, ext.to_string_lossy().to_string()));
+    }
+    let mut metadata = BTreeMap::new();
+    let mime_guess = new_mime_guess::from_path(&file_path);
+    if let Some(mime) = mime_guess.first_raw() {
+        metadata.insert(META_MIME_TYPE.to_string(), mime.to_string());
+    }
+    for mime in mime_guess.iter() {
+        tags.push((TagType::MimeType, mime.to_string()));
+    }
+    if let Some(author) = file.author {
+        metadata.insert(META_AUTHOR.to_string(), author.clone());
+        tags.push((TagType::Author, author));
+    }
+    if let Some(page_count) = file.page_count {
+        metadata.insert(META_PAGE_COUNT.to_string(), page_count.to_string());
+    }
+
+    CrawlResult {
+        content_hash: Some(_content_hash(&file.content)),
+        description: Some(_default_description(&file.content)),
+        title: file.title,
+        url: file_url.to_string(),
+        open_url: Some(file_url.to_string()),
+        tags,
+        last_modified: Some(filesystem::utils::last_modified_time(path)),
+        metadata,
+        page_offsets: file.page_offsets,
+        content: Some(file.content),
+        ..Default::default()
+    }
+}
+
 async fn _process_path(
     state: &AppState,
     path: &Path,
@@ -676,15 +999,49 @@ fn is_html_content(content_type: &str) -> bool {
     content_type.contains("text/html") || content_type.contains("application/xhtml+xml")
 }
 
+/// Headers used to make a conditional request using the validators from a
+/// previous response.
+fn conditional_headers(validators: &CacheValidators) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = validators
+        .etag
+        .as_ref()
+        .and_then(|etag| HeaderValue::from_str(etag).ok())
+    {
+        headers.insert(IF_NONE_MATCH, value);
+    }
+
+    if let Some(value) = validators
+        .last_modified
+        .as_ref()
+        .and_then(|date| HeaderValue::from_str(date).ok())
+    {
+        headers.insert(IF_MODIFIED_SINCE, value);
+    }
+
+    headers
+}
+
+/// Parses an HTTP date header (e.g. `Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT`)
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod test {
     use entities::models::crawl_queue::CrawlType;
+    use entities::models::fetch_history::CacheValidators;
     use entities::models::{crawl_queue, resource_rule};
     use entities::sea_orm::{ActiveModelTrait, Set};
     use entities::test::setup_test_db;
+    use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
     use spyglass_plugin::utils::path_to_uri;
 
-    use crate::crawler::{determine_canonical, normalize_href, Crawler};
+    use crate::crawler::{
+        conditional_headers, determine_canonical, normalize_href, parse_http_date, Crawler,
+    };
     use crate::state::AppState;
     use std::path::Path;
     use url::Url;
@@ -694,7 +1051,7 @@ mod test {
     async fn test_crawl() {
         let crawler = Crawler::default();
         let url = Url::parse("https://oldschool.runescape.wiki").unwrap();
-        let result = crawler.crawl(&url, true).await.expect("success");
+        let result = crawler.crawl(&url, true, None).await.expect("success");
 
         assert_eq!(result.title, Some("Old School RuneScape Wiki".to_string()));
         assert_eq!(result.url, "https://oldschool.runescape.wiki/".to_string());
@@ -876,6 +1233,29 @@ mod test {
         assert_eq!(res, "https://docs.rs/test/0.0.1/lib.rs.html");
     }
 
+    #[test]
+    fn test_parse_http_date() {
+        let date = parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").expect("valid date");
+        assert_eq!(date.to_rfc3339(), "2015-10-21T07:28:00+00:00");
+        assert!(parse_http_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_conditional_headers() {
+        let validators = CacheValidators {
+            etag: Some("W/\"abc123\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+        };
+
+        let headers = conditional_headers(&validators);
+        assert_eq!(headers.get(IF_NONE_MATCH).unwrap(), "W/\"abc123\"");
+        assert_eq!(
+            headers.get(IF_MODIFIED_SINCE).unwrap(),
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+        assert!(conditional_headers(&CacheValidators::default()).is_empty());
+    }
+
     #[tokio::test]
     async fn test_file_fetch() {
         let crawler = Crawler::default();
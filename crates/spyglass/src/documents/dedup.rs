@@ -0,0 +1,179 @@
+//! Exact and near-duplicate detection for crawl results.
+//!
+//! Exact duplicates share the same SHA-256 content hash. Near duplicates are
+//! found by comparing 64-bit [SimHash](https://en.wikipedia.org/wiki/SimHash)
+//! fingerprints of the content's word shingles, which change very little when
+//! a small part of the page (timestamps, ads, navigation) changes.
+use entities::models::document_alias::MatchType;
+use entities::models::indexed_document;
+use entities::sea_orm::{ConnectionTrait, DbErr};
+
+/// Max number of differing bits for two fingerprints to be considered near
+/// duplicates.
+pub const SIMHASH_THRESHOLD: u32 = 3;
+/// Number of words per shingle.
+const SHINGLE_SIZE: usize = 3;
+/// Content with fewer shingles than this is too short to fingerprint reliably.
+const MIN_SHINGLES: usize = 16;
+/// Max number of near duplicate candidates to check per document.
+const MAX_CANDIDATES: u64 = 50;
+
+/// 64-bit FNV-1a. Fingerprints are persisted, so the hash needs to be stable
+/// across builds, unlike `std`'s `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Computes the SimHash fingerprint of `content`, or `None` if the content is
+/// too short to produce a meaningful fingerprint.
+pub fn simhash(content: &str) -> Option<u64> {
+    let words = content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>();
+
+    if words.len() < SHINGLE_SIZE + MIN_SHINGLES - 1 {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE_SIZE) {
+        let hash = fnv1a(shingle.join(" ").as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    let fingerprint = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |acc, (bit, _)| acc | (1 << bit));
+
+    Some(fingerprint)
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    hamming_distance(a, b) <= SIMHASH_THRESHOLD
+}
+
+/// Finds an already indexed web page, other than `url`, that the content
+/// described by `content_hash` & `fingerprint` duplicates. Exact matches are
+/// preferred over near matches. Local files & connection documents are never
+/// canonical for a web page, so they're left out of the lookups.
+pub async fn find_duplicate<C: ConnectionTrait>(
+    db: &C,
+    url: &str,
+    content_hash: Option<&str>,
+    fingerprint: Option<u64>,
+) -> Result<Option<(indexed_document::Model, MatchType)>, DbErr> {
+    if let Some(content_hash) = content_hash {
+        if let Some(doc) = indexed_document::find_by_content_hash(db, content_hash, url).await? {
+            return Ok(Some((doc, MatchType::Exact)));
+        }
+    }
+
+    if let Some(fingerprint) = fingerprint {
+        let candidates =
+            indexed_document::find_simhash_candidates(db, fingerprint as i64, url, MAX_CANDIDATES)
+                .await?;
+
+        let closest = candidates
+            .into_iter()
+            .filter_map(|doc| {
+                let distance = hamming_distance(fingerprint, doc.simhash? as u64);
+                (distance <= SIMHASH_THRESHOLD).then_some((distance, doc))
+            })
+            .min_by_key(|(distance, _)| *distance);
+
+        if let Some((_, doc)) = closest {
+            return Ok(Some((doc, MatchType::Near)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use entities::sea_orm::{ActiveModelTrait, Set};
+    use entities::test::setup_test_db;
+
+    const PAGE: &str =
+        "Spyglass is a personal search engine that indexes your files, bookmarks and \
+        the websites you care about. Lenses describe which sites to crawl and how to rank the \
+        pages that are found. Everything is processed locally on your own machine, nothing is \
+        sent to a remote server, and the index can be searched instantly from anywhere with a \
+        single keyboard shortcut. Results are ranked using the content, the title and the links \
+        between pages.";
+
+    const OTHER_PAGE: &str = "The quick brown fox jumps over the lazy dog while the farmer \
+        watches from the porch of the old red barn. Later that evening the storm rolled in over \
+        the hills and the rain kept everyone inside until the next morning when the sun finally \
+        came out again and the fields were green and bright under the clear blue sky above.";
+
+    #[test]
+    fn test_simhash() {
+        assert_eq!(fnv1a(b"hello"), 0xa430_d846_80aa_bd0b);
+        assert_eq!(simhash("too short to fingerprint"), None);
+
+        let page = simhash(PAGE).expect("fingerprint");
+        // Case & punctuation are ignored
+        assert_eq!(Some(page), simhash(&PAGE.to_uppercase().replace(',', "")));
+
+        let updated = simhash(&format!("{PAGE} Last updated yesterday.")).expect("fingerprint");
+        assert!(is_near_duplicate(page, updated));
+
+        let other = simhash(OTHER_PAGE).expect("fingerprint");
+        assert!(!is_near_duplicate(page, other));
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicate() {
+        let db = setup_test_db().await;
+
+        let fingerprint = simhash(PAGE);
+        let mut doc = indexed_document::ActiveModel {
+            domain: Set("example.com".into()),
+            url: Set("https://example.com/about".into()),
+            doc_id: Set("about".into()),
+            content_hash: Set(Some("hash".into())),
+            ..Default::default()
+        };
+        doc.set_simhash(fingerprint.map(|hash| hash as i64));
+        doc.insert(&db).await.expect("Unable to insert doc");
+
+        let url = "https://mirror.example.com/about";
+        let (doc, match_type) = find_duplicate(&db, url, Some("hash"), None)
+            .await
+            .unwrap()
+            .expect("exact duplicate");
+        assert_eq!(doc.doc_id, "about");
+        assert_eq!(match_type, MatchType::Exact);
+
+        let updated = simhash(&format!("{PAGE} Last updated yesterday."));
+        let (doc, match_type) = find_duplicate(&db, url, Some("other"), updated)
+            .await
+            .unwrap()
+            .expect("near duplicate");
+        assert_eq!(doc.doc_id, "about");
+        assert_eq!(match_type, MatchType::Near);
+
+        let res = find_duplicate(&db, url, Some("other"), simhash(OTHER_PAGE))
+            .await
+            .unwrap();
+        assert!(res.is_none());
+    }
+}
@@ -2,13 +2,14 @@ use chrono::Utc;
 use entities::{
     models::{
         crawl_queue,
+        document_alias::{self, MatchType},
         indexed_document::{self, find_by_doc_ids},
         tag::{self, TagPair},
     },
     sea_orm::{ActiveModelTrait, DatabaseConnection},
     BATCH_SIZE,
 };
-use shared::config::LensConfig;
+use shared::{config::LensConfig, ARCHIVE_SEPARATOR};
 use spyglass_plugin::TagModification;
 use std::{collections::HashMap, str::FromStr, time::Instant};
 
@@ -17,12 +18,16 @@ use url::Url;
 
 use crate::{crawler::CrawlResult, state::AppState};
 use entities::models::tag::TagType;
-use entities::sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use entities::sea_orm::sea_query::{Expr, LikeExpr, SimpleExpr};
+use entities::sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, Set, TransactionTrait};
 use spyglass_searcher::{
     schema::{DocumentUpdate, ToDocument},
-    RetrievedDocument, WriteTrait,
+    RetrievedDocument,
 };
 
+pub mod dedup;
+pub mod pagerank;
+
 /// Helper method to delete indexed documents, crawl queue items and search
 /// documents by url
 pub async fn delete_documents_by_uri(state: &AppState, uri: Vec<String>) {
@@ -68,19 +73,105 @@ pub async fn delete_documents_by_uri(state: &AppState, uri: Vec<String>) {
             existing.len()
         );
     }
+
+    // Remove any documents contained in the deleted ones, e.g. mailbox messages
+    for uri in &uri {
+        let contained: Vec<indexed_document::Model> = indexed_document::Entity::find()
+            .filter(contained_in(uri))
+            .all(&state.db)
+            .await
+            .unwrap_or_default();
+        if contained.is_empty() {
+            continue;
+        }
+
+        let doc_ids = contained
+            .iter()
+            .map(|doc| doc.doc_id.clone())
+            .collect::<Vec<String>>();
+        if let Err(err) = state.index.delete_many_by_id(&doc_ids).await {
+            log::warn!("Unable to delete_many_by_id: {err}")
+        }
+        if let Err(err) = indexed_document::delete_many_by_doc_id(&state.db, &doc_ids).await {
+            log::warn!("Unable to delete parts of {uri}: {err}");
+        }
+    }
+}
+
+/// Matches the documents contained in the one at `url`, i.e. the messages in a
+/// mailbox (`{url}#{id}`) & the files in an archive (`{url}!/{path}`).
+fn contained_in(url: &str) -> Condition {
+    Condition::any()
+        .add(url_starts_with(&format!("{url}#")))
+        .add(url_starts_with(&format!("{url}{ARCHIVE_SEPARATOR}")))
+}
+
+/// Matches the urls starting w/ `prefix`, taking any wildcards in it literally.
+fn url_starts_with(prefix: &str) -> SimpleExpr {
+    let pattern = format!("{}%", escape_like(prefix));
+    Expr::col(indexed_document::Column::Url).like(LikeExpr::str(&pattern).escape('\\'))
+}
+
+/// Escapes the LIKE wildcards in `value` w/ `\`, so it only matches itself.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+
+    escaped
 }
 
 #[derive(Default)]
 pub struct AddUpdateResult {
     pub num_added: usize,
     pub num_updated: usize,
+    /// Results that were not indexed because they duplicate another document,
+    /// mapped from the duplicate url to the canonical document url.
+    pub duplicates: HashMap<String, String>,
+}
+
+/// Replaces the documents contained in a crawled document, e.g. the messages in
+/// a mailbox or the files in an archive, w/ the parts found in the latest
+/// crawl.
+pub async fn process_crawl_parts(
+    state: &AppState,
+    crawl_result: &CrawlResult,
+    global_tags: &[TagPair],
+) -> anyhow::Result<AddUpdateResult> {
+    let part_urls = crawl_result
+        .parts
+        .iter()
+        .map(|part| part.url.clone())
+        .collect::<Vec<String>>();
+
+    // Remove parts that are no longer there
+    let removed: Vec<indexed_document::Model> = indexed_document::Entity::find()
+        .filter(contained_in(&crawl_result.url))
+        .filter(indexed_document::Column::Url.is_not_in(part_urls))
+        .all(&state.db)
+        .await?;
+    if !removed.is_empty() {
+        let doc_ids = removed
+            .iter()
+            .map(|doc| doc.doc_id.clone())
+            .collect::<Vec<String>>();
+        state.index.delete_many_by_id(&doc_ids).await?;
+        indexed_document::delete_many_by_doc_id(&state.db, &doc_ids).await?;
+    }
+
+    process_crawl_results(state, &crawl_result.parts, global_tags).await
 }
 
 /// Process a list of crawl results. The following steps will be taken:
 /// 1. Find all urls that already have been processed in the database
 /// 2. Remove any documents that already exist from the index
-/// 3. Add all new results to the index
-/// 4. Insert all new documents to the indexed document database
+/// 3. Record new results that duplicate an existing document as aliases
+/// 4. Add all other new results to the index
+/// 5. Insert all new documents to the indexed document database
 pub async fn process_crawl_results(
     state: &AppState,
     results: &[CrawlResult],
@@ -129,48 +220,112 @@ pub async fn process_crawl_results(
     let mut inserts = Vec::new();
     let mut updates = Vec::new();
     let mut added_docs = Vec::new();
+    // Fingerprints of the documents added in this batch, used to catch
+    // duplicates within the batch itself.
+    let mut batch_fingerprints: Vec<(String, Option<&str>, Option<u64>)> = Vec::new();
+    // Pairs of (alias url, canonical url, match type)
+    let mut aliases = Vec::new();
+    // Documents to add to the index & the crawl results they were built from
+    let mut documents = Vec::new();
+    let mut to_index = Vec::new();
 
     let tx = state.db.begin().await?;
     for crawl_result in results {
+        let content_hash = crawl_result.content_hash.as_deref();
+        let fingerprint = crawl_result.content.as_deref().and_then(dedup::simhash);
+
+        // Only new web pages are checked, an indexed document stays canonical.
+        // Local files & connection documents with the same content are still
+        // distinct documents to the user.
+        if !id_map.contains_key(&crawl_result.url) && is_web_url(&crawl_result.url) {
+            let in_batch = batch_fingerprints.iter().find_map(|(url, hash, simhash)| {
+                let is_exact = content_hash.is_some() && *hash == content_hash;
+                let is_near = match (simhash, fingerprint) {
+                    (Some(a), Some(b)) => dedup::is_near_duplicate(*a, b),
+                    _ => false,
+                };
+
+                if is_exact {
+                    Some((url.clone(), MatchType::Exact))
+                } else if is_near {
+                    Some((url.clone(), MatchType::Near))
+                } else {
+                    None
+                }
+            });
+
+            let duplicate = match in_batch {
+                Some(found) => Some(found),
+                None => dedup::find_duplicate(&tx, &crawl_result.url, content_hash, fingerprint)
+                    .await?
+                    .map(|(doc, match_type)| (doc.url, match_type)),
+            };
+
+            if let Some((canonical, match_type)) = duplicate {
+                log::debug!(
+                    "{} duplicates {} ({:?}), skipping",
+                    crawl_result.url,
+                    canonical,
+                    match_type
+                );
+                aliases.push((crawl_result.url.clone(), canonical, match_type));
+                continue;
+            }
+
+            batch_fingerprints.push((crawl_result.url.clone(), content_hash, fingerprint));
+        }
+
         // Fetch the tag ids to apply to this crawl.
         let mut tags_for_crawl = _get_tag_ids(&state.db, &crawl_result.tags, &mut tag_cache).await;
         tags_for_crawl.extend(global_tids.clone());
         tag_map.insert(crawl_result.url.clone(), tags_for_crawl.clone());
 
-        // Add document to index
+        // Build the document to add to the index
         let url = Url::parse(&crawl_result.url)?;
         let url_host = url.host_str().unwrap_or("");
-        // Add document to index
-        let doc_id = state
-            .index
-            .upsert(
-                &DocumentUpdate {
-                    doc_id: id_map.get(&crawl_result.url).cloned(),
-                    title: &crawl_result.title.clone().unwrap_or_default(),
-                    domain: url_host,
-                    url: url.as_str(),
-                    content: &crawl_result.content.clone().unwrap_or_default(),
-                    tags: &tags_for_crawl.clone(),
-                    published_at: None,
-                    last_modified: None,
-                }
-                .to_document(),
-            )
-            .await?;
+        documents.push(
+            DocumentUpdate {
+                doc_id: id_map.get(&crawl_result.url).cloned(),
+                title: &crawl_result.title.clone().unwrap_or_default(),
+                domain: url_host,
+                url: url.as_str(),
+                content: &crawl_result.content.clone().unwrap_or_default(),
+                description: crawl_result.description.as_deref(),
+                metadata: &crawl_result.metadata,
+                symbols: &crawl_result.symbols,
+                segments: &crawl_result.segments,
+                page_offsets: &crawl_result.page_offsets,
+                tags: &tags_for_crawl.clone(),
+                published_at: crawl_result.published_at,
+                last_modified: crawl_result.last_modified,
+            }
+            .to_document(),
+        );
+        to_index.push((crawl_result, url, fingerprint));
+    }
 
+    // Add all the documents to the index in a single batch
+    let doc_ids = state.index.upsert_many(&documents).await?;
+    for (doc_id, (crawl_result, url, fingerprint)) in doc_ids.into_iter().zip(to_index) {
+        let url_host = url.host_str().unwrap_or("");
         if !id_map.contains_key(&doc_id) {
             added_docs.push(url.to_string());
-            inserts.push(indexed_document::ActiveModel {
+            let mut insert = indexed_document::ActiveModel {
                 domain: Set(url_host.to_string()),
                 url: Set(url.to_string()),
                 open_url: Set(crawl_result.open_url.clone()),
                 doc_id: Set(doc_id),
+                content_hash: Set(crawl_result.content_hash.clone()),
                 updated_at: Set(Utc::now()),
                 ..Default::default()
-            });
+            };
+            insert.set_simhash(fingerprint.map(|hash| hash as i64));
+            inserts.push(insert);
         } else if let Some(model) = model_map.get(&doc_id) {
             // Touch the existing model so we know it's been checked recently.
             let mut update: indexed_document::ActiveModel = model.to_owned().into();
+            update.content_hash = Set(crawl_result.content_hash.clone());
+            update.set_simhash(fingerprint.map(|hash| hash as i64));
             update.updated_at = Set(Utc::now());
             updates.push(update);
         }
@@ -181,9 +336,31 @@ pub async fn process_crawl_results(
     for update in updates {
         let _ = update.save(&tx).await;
     }
+    // Newly indexed urls are no longer aliases of another document.
+    document_alias::delete_many_by_url(&tx, &added_docs).await?;
+
+    // Point aliases at their canonical documents
+    let canonical_urls = aliases
+        .iter()
+        .map(|(_, canonical, _)| canonical.clone())
+        .collect::<Vec<_>>();
+    let canonical_ids = indexed_document::Entity::find()
+        .filter(indexed_document::Column::Url.is_in(canonical_urls))
+        .all(&tx)
+        .await?
+        .into_iter()
+        .map(|doc| (doc.url, doc.id))
+        .collect::<HashMap<_, _>>();
+
+    let mut duplicates = HashMap::new();
+    for (alias, canonical, match_type) in aliases {
+        if let Some(id) = canonical_ids.get(&canonical) {
+            document_alias::upsert(&tx, *id, &alias, match_type).await?;
+            duplicates.insert(alias, canonical);
+        }
+    }
 
     tx.commit().await?;
-    let _ = state.index.save().await;
 
     // Find the recently added docs & apply the tags to them.
     let added_entries: Vec<indexed_document::Model> = indexed_document::Entity::find()
@@ -212,6 +389,7 @@ pub async fn process_crawl_results(
     Ok(AddUpdateResult {
         num_added: num_entries - num_updates,
         num_updated: num_updates,
+        duplicates,
     })
 }
 
@@ -292,6 +470,11 @@ pub async fn process_records(
                                     domain: url_host,
                                     url: url.as_str(),
                                     content: &crawl_result.content,
+                                    description: Some(&crawl_result.description),
+                                    metadata: &Default::default(),
+                                    symbols: &[],
+                                    segments: &[],
+                                    page_offsets: &[],
                                     tags: &tag_list,
                                     published_at: None,
                                     last_modified: None,
@@ -334,8 +517,8 @@ pub async fn process_records(
     // Save the data
     indexed_document::insert_many(&transaction, &updates).await?;
     transaction.commit().await?;
-    if let Ok(mut writer) = state.index.lock_writer() {
-        let _ = writer.commit();
+    if let Err(err) = state.index.save().await {
+        log::error!("Unable to commit index: {err}");
     }
 
     let added_entries: Vec<indexed_document::Model> = indexed_document::Entity::find()
@@ -452,9 +635,14 @@ pub async fn update_tags(
                         domain: &doc.domain,
                         url: &doc.url,
                         content: &doc.content,
+                        description: Some(&doc.description),
+                        metadata: &doc.metadata,
+                        symbols: &doc.symbols,
+                        segments: &doc.segments,
+                        page_offsets: &doc.page_offsets,
                         tags: ids,
-                        published_at: None,
-                        last_modified: None,
+                        published_at: doc.published,
+                        last_modified: doc.last_modified,
                     }
                     .to_document(),
                 )
@@ -465,6 +653,10 @@ pub async fn update_tags(
     Ok(())
 }
 
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
 /// Helper method used to get the tag ids for a specific crawl result. The tag map and the tag cache
 /// will be modified as results are processed. The tag map contains the url to tag it mapping used
 /// for insertion to the database. The tag_cache is used to avoid additional loops for common tags
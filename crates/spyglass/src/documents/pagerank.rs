@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use entities::models::{indexed_document, link};
+use entities::sea_orm::EntityTrait;
+
+use crate::state::AppState;
+
+/// Probability of following a link vs jumping to a random page
+const DAMPING_FACTOR: f64 = 0.85;
+const MAX_ITERATIONS: usize = 50;
+/// Stop iterating once the total change between iterations drops below this.
+const CONVERGENCE_THRESHOLD: f64 = 1e-6;
+
+/// Computes the pagerank of each node in the graph. Edges to nodes outside of
+/// `nodes` are ignored. Scores are normalized so that the best linked node has
+/// a score of 1.0.
+pub fn compute_pagerank(
+    nodes: &HashSet<String>,
+    edges: &[(String, String)],
+) -> HashMap<String, f64> {
+    if nodes.is_empty() {
+        return HashMap::new();
+    }
+
+    let node_list = nodes.iter().collect::<Vec<_>>();
+    let index = node_list
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.as_str(), idx))
+        .collect::<HashMap<&str, usize>>();
+
+    let mut outbound: Vec<HashSet<usize>> = vec![HashSet::new(); node_list.len()];
+    for (src, dst) in edges {
+        if let (Some(src), Some(dst)) = (index.get(src.as_str()), index.get(dst.as_str())) {
+            if src != dst {
+                outbound[*src].insert(*dst);
+            }
+        }
+    }
+
+    let num_nodes = node_list.len() as f64;
+    let mut ranks = vec![1.0 / num_nodes; node_list.len()];
+    for _ in 0..MAX_ITERATIONS {
+        // Rank from pages w/o any outbound links is spread across every page.
+        let dangling = ranks
+            .iter()
+            .zip(&outbound)
+            .filter(|(_, links)| links.is_empty())
+            .map(|(rank, _)| rank)
+            .sum::<f64>();
+
+        let base = (1.0 - DAMPING_FACTOR) / num_nodes + DAMPING_FACTOR * dangling / num_nodes;
+        let mut next = vec![base; node_list.len()];
+        for (src, links) in outbound.iter().enumerate() {
+            let share = DAMPING_FACTOR * ranks[src] / links.len().max(1) as f64;
+            for dst in links {
+                next[*dst] += share;
+            }
+        }
+
+        let delta = next
+            .iter()
+            .zip(&ranks)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>();
+        ranks = next;
+        if delta < CONVERGENCE_THRESHOLD {
+            break;
+        }
+    }
+
+    let max_rank = ranks.iter().cloned().fold(0.0, f64::max);
+    node_list
+        .into_iter()
+        .zip(ranks)
+        .map(|(node, rank)| {
+            let score = if max_rank > 0.0 { rank / max_rank } else { 0.0 };
+            (node.clone(), score)
+        })
+        .collect()
+}
+
+/// Recomputes the pagerank of every indexed document from the saved link graph
+/// and updates the scores stored in the index.
+pub async fn update_pagerank(state: &AppState) -> anyhow::Result<usize> {
+    let start = Instant::now();
+    // Scores are stored in a fast field of the local index
+    let index = match state.index.as_local() {
+        Some(index) => index,
+        None => return Ok(0),
+    };
+
+    let nodes = indexed_document::Entity::find()
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(|doc| doc.url)
+        .collect::<HashSet<String>>();
+    let edges = link::all_edges(&state.db).await?;
+
+    let scores = compute_pagerank(&nodes, &edges);
+    let updated = index.update_pagerank(scores).await?;
+    if updated > 0 {
+        state.index.save().await?;
+    }
+
+    log::debug!(
+        "updated pagerank for {} of {} docs ({} links) in {}ms",
+        updated,
+        nodes.len(),
+        edges.len(),
+        start.elapsed().as_millis()
+    );
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::compute_pagerank;
+
+    fn edge(src: &str, dst: &str) -> (String, String) {
+        (src.to_string(), dst.to_string())
+    }
+
+    #[test]
+    fn test_compute_pagerank() {
+        let nodes = ["a", "b", "c", "d"]
+            .iter()
+            .map(|node| node.to_string())
+            .collect::<HashSet<_>>();
+        let edges = vec![
+            edge("a", "c"),
+            edge("b", "c"),
+            edge("d", "c"),
+            edge("c", "a"),
+            // Ignored, not an indexed page
+            edge("a", "external"),
+            // Self links are ignored
+            edge("b", "b"),
+        ];
+
+        let scores = compute_pagerank(&nodes, &edges);
+        assert_eq!(scores.len(), 4);
+        assert_eq!(scores.get("c"), Some(&1.0));
+        assert!(scores["a"] > scores["b"]);
+        assert!((scores["b"] - scores["d"]).abs() < 1e-9);
+        assert!(!scores.contains_key("external"));
+    }
+
+    #[test]
+    fn test_compute_pagerank_empty() {
+        assert!(compute_pagerank(&HashSet::new(), &[]).is_empty());
+    }
+}
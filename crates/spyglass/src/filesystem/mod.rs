@@ -788,15 +788,9 @@ async fn _process_file_and_dir(
         };
 
         if let Some(path) = path {
-            let ext = &path
-                .extension()
-                .and_then(|x| x.to_str())
-                .map(|x| x.to_string())
-                .unwrap_or_default();
-
             // If the shortcut points to a file we can process then
             // process the file instead of the shortcut
-            if SupportedExt::from_ext(ext) != SupportedExt::NotSupported {
+            if SupportedExt::from_path(&path) != SupportedExt::NotSupported {
                 let file_uri = utils::path_to_uri(&path);
                 enqueue_list.push(file_uri);
             } else {
@@ -919,6 +913,9 @@ fn _path_to_result(url: &Url, path: &Path) -> Option<CrawlResult> {
             open_url: Some(url.to_string()),
             links: Default::default(),
             tags,
+            published_at: None,
+            last_modified: Some(utils::last_modified_time(path)),
+            ..Default::default()
         })
     } else {
         None
@@ -1,6 +1,7 @@
 extern crate glob;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use ignore::{gitignore::Gitignore, Error};
+use shared::split_archive_path;
 use std::{
     ffi::OsStr,
     path::{Path, PathBuf},
@@ -37,13 +38,21 @@ pub fn path_string_to_uri(path_str: String) -> String {
     new_url.to_string()
 }
 
-/// Converts a uri to a valid path buf
+/// Converts a uri to a valid path buf. Files inside an archive, e.g.
+/// `file:///a.zip!/docs/readme.md`, are converted to the path of the archive.
 pub fn uri_to_path(uri: &str) -> anyhow::Result<PathBuf> {
     match Url::parse(uri) {
-        Ok(url) => match url.to_file_path() {
-            Ok(path) => Ok(path),
-            Err(_) => Err(anyhow::format_err!("Unable to access file path")),
-        },
+        Ok(mut url) => {
+            if let (archive, Some(_)) = split_archive_path(url.path()) {
+                let archive = archive.to_string();
+                url.set_path(&archive);
+            }
+
+            match url.to_file_path() {
+                Ok(path) => Ok(path),
+                Err(_) => Err(anyhow::format_err!("Unable to access file path")),
+            }
+        }
         Err(error) => Err(anyhow::Error::from(error)),
     }
 }
@@ -195,7 +204,7 @@ pub fn is_hidden(path: &Path) -> bool {
 mod test {
     use std::path::Path;
 
-    use super::path_to_uri;
+    use super::{path_to_uri, uri_to_path};
     use url::Url;
 
     #[test]
@@ -224,4 +233,17 @@ mod test {
             std::fs::remove_dir_all(test_folder).expect("Unable to clean up test folder");
         }
     }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///tmp/a.zip!/docs/read%20me.md").unwrap(),
+            Path::new("/tmp/a.zip")
+        );
+        assert_eq!(
+            uri_to_path("file:///tmp/wow!/a.md").unwrap(),
+            Path::new("/tmp/wow!/a.md")
+        );
+    }
 }
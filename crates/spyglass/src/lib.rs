@@ -5,5 +5,7 @@ pub mod filesystem;
 pub mod pipeline;
 pub mod platform;
 pub mod plugin;
+pub mod snapshot;
 pub mod state;
+pub mod suggest;
 pub mod task;
@@ -266,6 +266,9 @@ async fn start_backend(state: AppState, config: Config) {
     // Config change detection
     let config_handle = tokio::spawn(task::config_task(state.clone()));
 
+    // Bring the index up to the current schema w/o blocking searches
+    let _schema_migration_handle = tokio::spawn(task::schema_migration_task(state.clone()));
+
     // Crawlers
     let worker_handle = tokio::spawn(task::worker_task(
         state.clone(),
@@ -7,7 +7,6 @@ use entities::sea_orm::prelude::*;
 use entities::sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
 use shared::config::{Config, LensConfig, PipelineConfiguration};
 use spyglass_searcher::schema::{DocumentUpdate, ToDocument};
-use spyglass_searcher::WriteTrait;
 use tokio::sync::mpsc;
 use url::Url;
 
@@ -141,6 +140,11 @@ async fn start_crawl(
                                         domain: url_host,
                                         url: url.as_str(),
                                         content: &content,
+                                        description: crawl_result.description.as_deref(),
+                                        metadata: &crawl_result.metadata,
+                                        symbols: &crawl_result.symbols,
+                                        segments: &crawl_result.segments,
+                                        page_offsets: &crawl_result.page_offsets,
                                         tags: &[],
                                         published_at: None,
                                         last_modified: None,
@@ -579,7 +579,7 @@ mod test {
             .with_db(db)
             .with_lenses(&vec![test_lens])
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         let filters = lens_to_filters(state, "test").await;
@@ -0,0 +1,438 @@
+//! Snapshots of a user's library, i.e. the search index plus the database,
+//! used to back up & move a library between installations.
+//!
+//! A snapshot is a directory laid out as:
+//! ```text
+//! manifest.json   # SnapshotInfo
+//! db.sqlite       # copy of the database
+//! index/          # copy of the committed index segments & vectors
+//! ```
+//! Exported snapshots are the same directory as a `.tar.gz` archive.
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use entities::models::{create_connection_by_uri, indexed_document, tag};
+use entities::sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, ModelTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Statement,
+};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use migration::{Migrator, MigratorTrait};
+use shared::config::Config;
+use shared::response::{RestoreSnapshotResult, SnapshotInfo};
+use spyglass_searcher::schema::{DocFields, SearchDocument, SCHEMA_VERSION};
+use spyglass_searcher::schema_migration::convert_document;
+use spyglass_searcher::{document_to_struct, RetrievedDocument};
+use tantivy::collector::TopDocs;
+use tantivy::query::TermQuery;
+use tantivy::schema::{Field, IndexRecordOption, Schema};
+use tantivy::{Index, ReloadPolicy, Term};
+
+use crate::crawler::CrawlResult;
+use crate::documents::process_crawl_results;
+use crate::state::AppState;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+const DB_FILE: &str = "db.sqlite";
+const INDEX_DIR: &str = "index";
+/// Number of documents merged into the library at a time when restoring.
+const RESTORE_BATCH_SIZE: u64 = 100;
+
+/// Writes a consistent snapshot of the library into `dest`, which must not
+/// exist or be empty.
+///
+/// The database is copied first, then the index is committed & copied while
+/// index writes are blocked. Documents are written to the index before the
+/// database, so every document in the copied database is in the copied index.
+pub async fn create_snapshot(state: &AppState, dest: &Path) -> anyhow::Result<SnapshotInfo> {
+    if dest.exists() && dest.read_dir()?.next().is_some() {
+        return Err(anyhow!("{} is not empty", dest.display()));
+    }
+    std::fs::create_dir_all(dest)?;
+
+    let backend = state.db.get_database_backend();
+    if backend != DatabaseBackend::Sqlite {
+        return Err(anyhow!("Snapshots are only supported for SQLite databases"));
+    }
+
+    let index = state
+        .index
+        .as_local()
+        .ok_or_else(|| anyhow!("Snapshots are only supported for local indices"))?;
+
+    let db_path = dest.join(DB_FILE);
+    // VACUUM INTO writes a transactionally consistent copy of the database.
+    let db_path_str = db_path
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid snapshot path"))?;
+    state
+        .db
+        .execute(Statement::from_string(
+            backend,
+            format!("VACUUM INTO '{}'", db_path_str.replace('\'', "''")),
+        ))
+        .await?;
+
+    index.snapshot(&dest.join(INDEX_DIR)).await?;
+
+    let db = open_snapshot_db(dest).await?;
+    let num_documents = indexed_document::Entity::find().count(&db).await?;
+    db.close().await?;
+
+    let info = SnapshotInfo {
+        path: dest.to_path_buf(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version: SCHEMA_VERSION.to_string(),
+        num_documents,
+    };
+    std::fs::write(
+        dest.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&info)?,
+    )?;
+
+    log::info!(
+        "Created snapshot w/ {} documents @ {}",
+        num_documents,
+        dest.display()
+    );
+    Ok(info)
+}
+
+/// Snapshots the library and exports it as a single `.tar.gz` archive.
+pub async fn export_snapshot(state: &AppState, archive: &Path) -> anyhow::Result<SnapshotInfo> {
+    let snapshot_dir = tmp_snapshot_dir(&state.config);
+    let res = match create_snapshot(state, &snapshot_dir).await {
+        Ok(info) => archive_snapshot(&snapshot_dir, archive).map(|_| SnapshotInfo {
+            path: archive.to_path_buf(),
+            ..info
+        }),
+        Err(err) => Err(err),
+    };
+
+    let _ = std::fs::remove_dir_all(&snapshot_dir);
+    res
+}
+
+/// Packs the snapshot directory into a `.tar.gz` archive.
+pub fn archive_snapshot(snapshot_dir: &Path, archive: &Path) -> anyhow::Result<()> {
+    // Make sure this is actually a snapshot before archiving it
+    read_manifest(snapshot_dir)?;
+
+    let encoder = GzEncoder::new(File::create(archive)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all(".", snapshot_dir)?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpacks an exported snapshot archive into `dest`.
+pub fn unpack_snapshot(archive: &Path, dest: &Path) -> anyhow::Result<SnapshotInfo> {
+    std::fs::create_dir_all(dest)?;
+    tar::Archive::new(GzDecoder::new(File::open(archive)?)).unpack(dest)?;
+    read_manifest(dest)
+}
+
+pub fn read_manifest(snapshot_dir: &Path) -> anyhow::Result<SnapshotInfo> {
+    let path = snapshot_dir.join(MANIFEST_FILE);
+    let manifest = std::fs::read_to_string(&path)
+        .map_err(|err| anyhow!("Unable to read {}: {err}", path.display()))?;
+    Ok(serde_json::from_str(&manifest)?)
+}
+
+/// Merges a snapshot directory or exported archive into the running library.
+/// Documents already in the library are left as is.
+pub async fn restore_snapshot(
+    state: &AppState,
+    path: &Path,
+) -> anyhow::Result<RestoreSnapshotResult> {
+    if path.is_dir() {
+        return merge_snapshot(state, path).await;
+    }
+
+    let snapshot_dir = tmp_snapshot_dir(&state.config);
+    let res = match unpack_snapshot(path, &snapshot_dir) {
+        Ok(_) => merge_snapshot(state, &snapshot_dir).await,
+        Err(err) => Err(err),
+    };
+
+    let _ = std::fs::remove_dir_all(&snapshot_dir);
+    res
+}
+
+/// Replaces the library in `config`'s data directory with the snapshot. The
+/// previous index & database are kept alongside w/ a `.bak` extension.
+///
+/// Spyglass must not be running while the library is replaced.
+pub fn replace_library(config: &Config, snapshot_dir: &Path) -> anyhow::Result<SnapshotInfo> {
+    let info = read_manifest(snapshot_dir)?;
+    let data_dir = config.data_dir();
+    std::fs::create_dir_all(&data_dir)?;
+
+    let index_dir = config.index_dir();
+    if index_dir.exists() {
+        let backup = index_dir.with_extension("bak");
+        let _ = std::fs::remove_dir_all(&backup);
+        std::fs::rename(&index_dir, &backup)?;
+    }
+    std::fs::create_dir_all(&index_dir)?;
+    for entry in std::fs::read_dir(snapshot_dir.join(INDEX_DIR))? {
+        let path = entry?.path();
+        if let Some(file_name) = path.file_name() {
+            std::fs::copy(&path, index_dir.join(file_name))?;
+        }
+    }
+
+    let db_path = data_dir.join(DB_FILE);
+    if db_path.exists() {
+        std::fs::rename(&db_path, db_path.with_extension("sqlite.bak"))?;
+    }
+    // Leftover write-ahead logs belong to the old database
+    for ext in ["sqlite-wal", "sqlite-shm"] {
+        let _ = std::fs::remove_file(db_path.with_extension(ext));
+    }
+    std::fs::copy(snapshot_dir.join(DB_FILE), &db_path)?;
+
+    Ok(info)
+}
+
+async fn merge_snapshot(
+    state: &AppState,
+    snapshot_dir: &Path,
+) -> anyhow::Result<RestoreSnapshotResult> {
+    let info = read_manifest(snapshot_dir)?;
+    log::info!(
+        "Restoring snapshot from {} (v{}, {} documents)",
+        info.created_at,
+        info.app_version,
+        info.num_documents
+    );
+
+    let db = open_snapshot_db(snapshot_dir).await?;
+    // Snapshots from older versions are brought up to date before reading.
+    if let Err(err) = Migrator::up(&db, None).await {
+        log::warn!("Unable to migrate snapshot db: {err}");
+    }
+
+    // Documents are re-added through the usual pipeline rather than copying
+    // segments, so snapshots taken w/ an older index schema can be restored.
+    let index = SnapshotIndex::open(&snapshot_dir.join(INDEX_DIR))?;
+
+    let mut result = RestoreSnapshotResult::default();
+    let mut pages = indexed_document::Entity::find()
+        .order_by_asc(indexed_document::Column::Id)
+        .paginate(&db, RESTORE_BATCH_SIZE);
+    while let Some(docs) = pages.fetch_and_next().await? {
+        let urls = docs.iter().map(|doc| doc.url.clone()).collect::<Vec<_>>();
+        let existing = indexed_document::Entity::find()
+            .filter(indexed_document::Column::Url.is_in(urls))
+            .all(&state.db)
+            .await?
+            .into_iter()
+            .map(|doc| doc.url)
+            .collect::<HashSet<_>>();
+
+        let mut crawl_results = Vec::new();
+        for doc in docs {
+            if existing.contains(&doc.url) {
+                result.num_skipped += 1;
+                continue;
+            }
+
+            let indexed = match index.get(&doc.doc_id) {
+                Some(indexed) => indexed,
+                None => {
+                    result.num_missing += 1;
+                    continue;
+                }
+            };
+
+            let tags = doc
+                .find_related(tag::Entity)
+                .all(&db)
+                .await?
+                .iter()
+                .map(|tag| tag.tag_pair())
+                .collect();
+
+            crawl_results.push(CrawlResult {
+                content_hash: doc.content_hash,
+                content: Some(indexed.content),
+                description: Some(indexed.description).filter(|desc| !desc.is_empty()),
+                title: Some(indexed.title),
+                url: doc.url,
+                open_url: doc.open_url,
+                tags,
+                published_at: indexed.published,
+                last_modified: indexed.last_modified,
+                metadata: indexed.metadata,
+                symbols: indexed.symbols,
+                segments: indexed.segments,
+                page_offsets: indexed.page_offsets,
+                ..Default::default()
+            });
+        }
+
+        let res = process_crawl_results(state, &crawl_results, &[]).await?;
+        result.num_restored += (res.num_added + res.num_updated) as u64;
+    }
+
+    state.index.save().await?;
+    db.close().await?;
+
+    log::info!(
+        "Restored {} documents, skipped {} already in the library",
+        result.num_restored,
+        result.num_skipped
+    );
+    Ok(result)
+}
+
+/// Index copied into a snapshot, read w/ the schema it was written with.
+struct SnapshotIndex {
+    schema: Schema,
+    id_field: Field,
+    searcher: tantivy::Searcher,
+}
+
+impl SnapshotIndex {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let index = Index::open_in_dir(path)?;
+        let schema = index.schema();
+        let id_field = schema
+            .get_field("id")
+            .ok_or_else(|| anyhow!("No id in snapshot index"))?;
+        let searcher = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?
+            .searcher();
+
+        Ok(Self {
+            schema,
+            id_field,
+            searcher,
+        })
+    }
+
+    /// Reads a document, mapping the fields of the snapshot's schema to the
+    /// current one by name.
+    fn get(&self, doc_id: &str) -> Option<RetrievedDocument> {
+        let query = TermQuery::new(
+            Term::from_field_text(self.id_field, doc_id),
+            IndexRecordOption::Basic,
+        );
+        let (_, addr) = self
+            .searcher
+            .search(&query, &TopDocs::with_limit(1))
+            .ok()?
+            .into_iter()
+            .next()?;
+        let stored = self.searcher.doc(addr).ok()?;
+        document_to_struct(&convert_document(
+            &self.schema,
+            &DocFields::as_schema(),
+            &stored,
+        ))
+    }
+}
+
+async fn open_snapshot_db(snapshot_dir: &Path) -> anyhow::Result<DatabaseConnection> {
+    let path = snapshot_dir.join(DB_FILE);
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid snapshot path"))?;
+    create_connection_by_uri(&format!("sqlite://{path}?mode=rwc")).await
+}
+
+fn tmp_snapshot_dir(config: &Config) -> PathBuf {
+    config
+        .cache_dir()
+        .join(format!("snapshot-{}", uuid::Uuid::new_v4()))
+}
+
+#[cfg(test)]
+mod test {
+    use entities::models::indexed_document;
+    use entities::models::tag::TagType;
+    use entities::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+    use entities::test::setup_test_db;
+    use spyglass_searcher::schema::{DocFields, SearchDocument};
+    use spyglass_searcher::IndexBackend;
+
+    use super::{archive_snapshot, create_snapshot, restore_snapshot, unpack_snapshot};
+    use crate::crawler::CrawlResult;
+    use crate::documents::process_crawl_results;
+    use crate::state::AppState;
+
+    async fn test_state() -> AppState {
+        AppState::builder()
+            .with_db(setup_test_db().await)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("spyglass-library-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let state = test_state().await;
+        let url = "https://example.com/snapshot";
+        let crawl = CrawlResult {
+            title: Some("Snapshot".into()),
+            content: Some("Backing up the library".into()),
+            url: url.into(),
+            tags: vec![(TagType::Source, "web".into())],
+            page_offsets: vec![0, 10],
+            ..Default::default()
+        };
+        process_crawl_results(&state, &[crawl], &[])
+            .await
+            .expect("Unable to add doc");
+
+        let info = create_snapshot(&state, &dir.join("snapshot"))
+            .await
+            .expect("Unable to snapshot");
+        assert_eq!(info.num_documents, 1);
+        // Snapshots aren't written over existing files
+        assert!(create_snapshot(&state, &dir.join("snapshot"))
+            .await
+            .is_err());
+
+        let archive = dir.join("library.tar.gz");
+        archive_snapshot(&dir.join("snapshot"), &archive).expect("Unable to archive");
+        let unpacked = unpack_snapshot(&archive, &dir.join("unpacked")).expect("Unable to unpack");
+        assert_eq!(unpacked.created_at, info.created_at);
+
+        let other = test_state().await;
+        let res = restore_snapshot(&other, &dir.join("unpacked"))
+            .await
+            .expect("Unable to restore");
+        assert_eq!(res.num_restored, 1);
+        assert_eq!(res.num_missing, 0);
+
+        let doc = indexed_document::Entity::find()
+            .filter(indexed_document::Column::Url.eq(url))
+            .one(&other.db)
+            .await
+            .unwrap()
+            .expect("doc restored to db");
+        let indexed = other.index.get(&doc.doc_id).await.expect("doc in index");
+        assert_eq!(indexed.title, "Snapshot");
+        assert_eq!(indexed.page_offsets, vec![0, 10]);
+
+        // Restoring again skips documents already in the library
+        let res = restore_snapshot(&other, &dir.join("unpacked"))
+            .await
+            .expect("Unable to restore");
+        assert_eq!(res.num_restored, 0);
+        assert_eq!(res.num_skipped, 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -20,7 +20,9 @@ use crate::{
 };
 use shared::config::{Config, LensConfig, PipelineConfiguration, UserSettings};
 use shared::metrics::Metrics;
-use spyglass_searcher::{client::Searcher, IndexBackend};
+use spyglass_searcher::similarity::Embedder;
+use spyglass_searcher::{client::open_index, IndexBackend, IndexClient};
+use url::Url;
 
 /// Used to track inflight requests and limit things
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -61,7 +63,7 @@ pub struct AppState {
     pub lenses: Arc<DashMap<String, LensConfig>>,
     pub pipelines: Arc<DashMap<String, PipelineConfiguration>>,
     pub user_settings: Arc<ArcSwap<UserSettings>>,
-    pub index: Searcher,
+    pub index: Arc<dyn IndexClient>,
     pub metrics: Metrics,
     pub config: Config,
     // Task scheduler command/control
@@ -94,9 +96,12 @@ impl AppState {
 
         AppStateBuilder::new()
             .with_db(db)
+            // No embedding model ships w/ the app yet, hybrid ranking falls
+            // back to keyword search until one is provided.
             .with_index(
-                &IndexBackend::LocalPath(config.index_dir()),
+                &Self::index_backend(config),
                 DocFields::as_schema(),
+                None,
                 readonly_mode,
             )
             .with_lenses(&config.lenses.values().cloned().collect())
@@ -111,6 +116,20 @@ impl AppState {
             .build()
     }
 
+    /// Uses the remote index from the user settings when one is set, otherwise
+    /// the local index in the data directory.
+    fn index_backend(config: &Config) -> IndexBackend {
+        let remote_url = config.user_settings.remote_index_url.as_deref();
+        match remote_url.map(Url::parse) {
+            Some(Ok(url)) => IndexBackend::Http(url),
+            Some(Err(err)) => {
+                log::error!("Invalid remote index url, using the local index: {err}");
+                IndexBackend::LocalPath(config.index_dir())
+            }
+            None => IndexBackend::LocalPath(config.index_dir()),
+        }
+    }
+
     pub fn reload_config(&mut self) {
         log::debug!("reloading config...");
         let config = Config::new();
@@ -150,7 +169,7 @@ impl AppState {
 #[derive(Default)]
 pub struct AppStateBuilder {
     db: Option<DatabaseConnection>,
-    index: Option<Searcher>,
+    index: Option<Arc<dyn IndexClient>>,
     lenses: Option<Vec<LensConfig>>,
     pipelines: Option<Vec<PipelineConfiguration>>,
     user_settings: Option<UserSettings>,
@@ -176,7 +195,7 @@ impl AppStateBuilder {
         let index = if let Some(index) = &self.index {
             index.to_owned()
         } else {
-            Searcher::with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            open_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
                 .expect("Unable to open search index")
         };
 
@@ -244,6 +263,7 @@ impl AppStateBuilder {
         &mut self,
         index: &IndexBackend,
         schema: Schema,
+        embedder: Option<Arc<dyn Embedder>>,
         readonly: bool,
     ) -> &mut Self {
         if let IndexBackend::LocalPath(path) = &index {
@@ -252,7 +272,7 @@ impl AppStateBuilder {
             }
         }
 
-        let searcher = Searcher::with_index(index, schema, readonly);
+        let searcher = open_index(index, schema, embedder, readonly);
         if let Err(error) = &searcher {
             log::error!("Error connecting to index {index:?}. Error: {error:?}");
         }
@@ -0,0 +1,158 @@
+//! Query autocomplete & "did you mean" suggestions for the search bar.
+use std::collections::HashSet;
+
+use entities::models::search_history;
+use shared::response::{SuggestResults, Suggestion, SuggestionKind};
+use spyglass_searcher::suggest::MIN_PREFIX_LEN;
+
+use crate::state::AppState;
+
+/// Completions for a partially typed query, from past queries, words in the
+/// index & document titles, in that order. Also suggests a correction when
+/// words in the query have no exact match in the index.
+pub async fn suggest(
+    state: &AppState,
+    query: &str,
+    limit: usize,
+) -> anyhow::Result<SuggestResults> {
+    let mut completions = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add = |completions: &mut Vec<Suggestion>, text: String, kind: SuggestionKind| {
+        if completions.len() < limit && seen.insert(text.to_lowercase()) {
+            completions.push(Suggestion { text, kind });
+        }
+    };
+
+    for past in search_history::past_queries(&state.db, query, limit).await? {
+        add(&mut completions, past, SuggestionKind::PastQuery);
+    }
+
+    // Words & titles are looked up in the local index, remote indices only
+    // get suggestions from past queries.
+    let index = match state.index.as_local() {
+        Some(index) => index,
+        None => {
+            return Ok(SuggestResults {
+                completions,
+                correction: None,
+            })
+        }
+    };
+
+    // Complete the last word, unless it's already been finished.
+    let (head, last_word) = match query.trim_start().rsplit_once(char::is_whitespace) {
+        Some((head, last)) => (format!("{} ", head.trim_end()), last),
+        None => (String::new(), query.trim_start()),
+    };
+    let mut completed_last_word = false;
+    if !query.ends_with(char::is_whitespace) && last_word.chars().count() >= MIN_PREFIX_LEN {
+        for suggestion in index.complete_term(last_word, limit)? {
+            completed_last_word = true;
+            add(
+                &mut completions,
+                format!("{head}{}", suggestion.term),
+                SuggestionKind::Term,
+            );
+        }
+
+        for title in index.complete_title(query, limit)? {
+            add(&mut completions, title, SuggestionKind::Title);
+        }
+    }
+
+    // A partially typed word isn't a typo, unless nothing starts w/ it.
+    let correction = if completed_last_word {
+        index
+            .correct_query(&head)?
+            .map(|corrected| format!("{corrected} {last_word}"))
+    } else {
+        index.correct_query(query)?
+    };
+
+    Ok(SuggestResults {
+        completions,
+        correction,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::suggest;
+    use crate::state::AppState;
+    use entities::models::search_history;
+    use entities::test::setup_test_db;
+    use shared::response::SuggestionKind;
+    use spyglass_searcher::schema::{DocFields, DocumentUpdate, SearchDocument, ToDocument};
+    use spyglass_searcher::IndexBackend;
+
+    #[tokio::test]
+    async fn test_suggest() {
+        let db = setup_test_db().await;
+        let state = AppState::builder()
+            .with_db(db)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
+            .build();
+
+        state
+            .index
+            .upsert(
+                &DocumentUpdate {
+                    doc_id: None,
+                    title: "Salinas Valley",
+                    domain: "example.com",
+                    url: "https://example.com/salinas",
+                    content: "The Salinas valley lies between two ranges of mountains",
+                    description: None,
+                    metadata: &Default::default(),
+                    symbols: &[],
+                    segments: &[],
+                    page_offsets: &[],
+                    tags: &[],
+                    published_at: None,
+                    last_modified: None,
+                }
+                .to_document(),
+            )
+            .await
+            .expect("Unable to add doc");
+        state.index.save().await.expect("Unable to save");
+        state
+            .index
+            .as_local()
+            .expect("Expected a local index")
+            .reader()
+            .reload()
+            .expect("Unable to reload");
+        search_history::record_search(&state.db, "salinas river", Vec::new())
+            .await
+            .expect("Unable to record search");
+
+        let results = suggest(&state, "sali", 5).await.expect("Unable to suggest");
+        let completions = results
+            .completions
+            .iter()
+            .map(|suggestion| (suggestion.text.as_str(), suggestion.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            completions,
+            vec![
+                ("salinas river", SuggestionKind::PastQuery),
+                ("salinas", SuggestionKind::Term),
+                ("Salinas Valley", SuggestionKind::Title),
+            ]
+        );
+        assert_eq!(results.correction, None);
+
+        let results = suggest(&state, "salnas vall", 5)
+            .await
+            .expect("Unable to suggest");
+        assert_eq!(results.completions[0].text, "salnas valley");
+        assert_eq!(results.correction, Some("salinas vall".into()));
+
+        let results = suggest(&state, "mountans ", 5)
+            .await
+            .expect("Unable to suggest");
+        assert!(results.completions.is_empty());
+        assert_eq!(results.correction, Some("mountains".into()));
+    }
+}
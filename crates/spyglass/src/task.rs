@@ -1,12 +1,14 @@
 use anyhow::anyhow;
 use entities::models::crawl_queue::CrawlStatus;
-use entities::models::{bootstrap_queue, connection, crawl_queue};
+use entities::models::{bootstrap_queue, connection, crawl_queue, search_history};
 use entities::sea_orm::{sea_query::Expr, ColumnTrait, Condition, EntityTrait, QueryFilter};
 use futures::StreamExt;
 use notify::event::ModifyKind;
 use notify::{EventKind, RecursiveMode, Watcher};
 use shared::config::{Config, LensConfig, UserSettings, UserSettingsDiff};
-use spyglass_rpc::{ModelDownloadStatusPayload, RpcEvent, RpcEventType};
+use spyglass_rpc::{
+    ModelDownloadStatusPayload, RpcEvent, RpcEventType, SchemaMigrationStatusPayload,
+};
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
@@ -18,6 +20,7 @@ use tokio::sync::{broadcast, mpsc};
 
 use crate::connection::{api_id_to_label, load_connection};
 use crate::crawler::bootstrap;
+use crate::documents;
 use crate::filesystem;
 use crate::state::AppState;
 use crate::task::worker::FetchResult;
@@ -62,6 +65,9 @@ pub enum UserSettingsChange {
     SettingsChanged(UserSettings),
 }
 
+/// How often document pagerank is recomputed from the link graph
+const PAGERANK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Tell the manager to schedule some tasks
 #[derive(Clone, Debug)]
 pub enum ManagerCommand {
@@ -87,6 +93,8 @@ pub enum WorkerCommand {
     Recrawl { id: i64 },
     /// Applies tag information to an URI
     Tag,
+    /// Recomputes document pagerank from the link graph
+    UpdatePageRank,
     /// Updates the document store for indexed document database table to
     /// cleanup inconsistencies
     CleanupDatabase(CleanupTask),
@@ -115,6 +123,11 @@ pub async fn manager_task(
 
     let mut queue_check_interval = tokio::time::interval(Duration::from_millis(100));
     let mut commit_check_interval = tokio::time::interval(Duration::from_secs(10));
+    // Skip the first tick, no need to recompute pagerank as soon as we start up.
+    let mut pagerank_interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + PAGERANK_INTERVAL,
+        PAGERANK_INTERVAL,
+    );
     let mut shutdown_rx = state.shutdown_cmd_tx.lock().await.subscribe();
     // Startup filesystem watcher
     filesystem::configure_watcher(state.clone()).await;
@@ -158,6 +171,9 @@ pub async fn manager_task(
             _ = commit_check_interval.tick() => {
                 let _ = queue.send(WorkerCommand::CommitIndex).await;
             }
+            _ = pagerank_interval.tick() => {
+                let _ = queue.send(WorkerCommand::UpdatePageRank).await;
+            }
             // If we're not handling anything, continually poll for jobs.
             _ = queue_check_interval.tick() => {
                 if let Err(err) = manager_cmd_tx.send(ManagerCommand::CheckForJobs) {
@@ -193,6 +209,13 @@ pub async fn config_task(mut state: AppState) {
                         let diff = new_settings.diff(&old_config);
                         // Process any new added paths
                         process_filesystem_changes(&state, &diff).await;
+                        // Search history disabled? Clear what's been recorded so far
+                        if new_settings.disable_search_history && !old_config.disable_search_history {
+                            match search_history::clear(&state.db).await {
+                                Ok(num_cleared) => log::info!("Cleared {num_cleared} search history entries"),
+                                Err(err) => log::warn!("Unable to clear search history: {err}"),
+                            }
+                        }
                         // Audio transcriptions enabled?
                         if new_settings.audio_settings.enable_audio_transcription {
                             // Do we already have this model?
@@ -304,6 +327,61 @@ async fn download_model(
     }
 }
 
+/// Migrates the search index to the current schema if it was created by an
+/// older version. The old index keeps serving searches until the migrated one
+/// is swapped in.
+pub async fn schema_migration_task(state: AppState) {
+    // Remote indices manage their own mappings
+    let index = match state.index.as_local() {
+        Some(index) if !index.is_readonly() && index.needs_schema_migration() => index.clone(),
+        _ => return,
+    };
+
+    log::info!("Migrating search index to the current schema");
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let handle = tokio::task::spawn_blocking(move || {
+        index.migrate_schema(|progress| {
+            let _ = progress_tx.send(progress);
+        })
+    });
+
+    // Closed once the migration is done & the sender dropped
+    while let Some(progress) = progress_rx.recv().await {
+        state
+            .publish_event(&RpcEvent {
+                event_type: RpcEventType::SchemaMigrationStatus,
+                payload: serde_json::to_string(&SchemaMigrationStatusPayload::InProgress {
+                    num_migrated: progress.num_migrated,
+                    total: progress.total,
+                    percent: progress.percent(),
+                })
+                .unwrap_or_default(),
+            })
+            .await;
+    }
+
+    let status = match handle.await {
+        Ok(Ok(num_docs)) => SchemaMigrationStatusPayload::Finished { num_docs },
+        Ok(Err(err)) => SchemaMigrationStatusPayload::Error {
+            msg: err.to_string(),
+        },
+        Err(err) => SchemaMigrationStatusPayload::Error {
+            msg: err.to_string(),
+        },
+    };
+
+    if let SchemaMigrationStatusPayload::Error { msg } = &status {
+        log::error!("Unable to migrate search index: {msg}");
+    }
+
+    state
+        .publish_event(&RpcEvent {
+            event_type: RpcEventType::SchemaMigrationStatus,
+            payload: serde_json::to_string(&status).unwrap_or_default(),
+        })
+        .await;
+}
+
 // Processes any needed filesystem configuration changes
 async fn process_filesystem_changes(state: &AppState, diff: &UserSettingsDiff) {
     let fs_diff = &diff.filesystem_settings;
@@ -417,11 +495,15 @@ pub async fn worker_task(
                         WorkerCommand::CommitIndex => {
                             let state = state.clone();
                             let num_updated = updated_docs.load(Ordering::Relaxed);
-                            if num_updated > 0 {
+                            // Also commit writes buffered by the index itself,
+                            // e.g. deletions & connection syncs.
+                            if num_updated > 0 || state.index.num_pending_writes() > 0 {
                                 log::debug!("committing {} new/updated docs in index", num_updated);
                                 updated_docs.store(0, Ordering::Relaxed);
                                 tokio::spawn(async move {
-                                    let _ = state.index.save().await;
+                                    if let Err(err) = state.index.save().await {
+                                        log::error!("Unable to commit index: {err}");
+                                    }
                                 });
                             }
                         }
@@ -460,6 +542,14 @@ pub async fn worker_task(
                             });
                         }
                         WorkerCommand::Tag => {}
+                        WorkerCommand::UpdatePageRank => {
+                            let state = state.clone();
+                            tokio::spawn(async move {
+                                if let Err(err) = documents::pagerank::update_pagerank(&state).await {
+                                    log::error!("Unable to update pagerank: {}", err);
+                                }
+                            });
+                        }
                     }
                 }
             },
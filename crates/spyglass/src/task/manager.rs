@@ -1,10 +1,24 @@
-use entities::models::{connection, crawl_queue};
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use entities::models::crawl_queue::CrawlStatus;
+use entities::models::{connection, crawl_queue, fetch_history};
+use entities::sea_orm::prelude::*;
+use entities::sea_orm::sea_query;
+use shared::config::RecrawlPolicy;
 use tokio::sync::mpsc;
+use url::Url;
 
 use super::{CollectTask, CrawlTask, ManagerCommand, WorkerCommand};
 use crate::pipeline::PipelineCommand;
 use crate::state::AppState;
 
+/// Max number of recrawls scheduled each time we check for jobs.
+const RECRAWL_BUDGET: usize = 25;
+/// Number of candidates looked at per lens when scheduling recrawls. Adaptive
+/// policies may skip some of them if they're not due yet.
+const RECRAWL_CANDIDATE_LIMIT: u64 = 100;
+
 // Check for new jobs in the crawl queue and add them to the worker queue.
 #[tracing::instrument(skip(state, queue))]
 pub async fn check_for_jobs(state: &AppState, queue: &mpsc::Sender<WorkerCommand>) -> bool {
@@ -89,6 +103,11 @@ pub async fn check_for_jobs(state: &AppState, queue: &mpsc::Sender<WorkerCommand
         started_task = Some(true);
     }
 
+    // Nothing else going on, refresh any documents that are due for a recrawl.
+    if started_task != Some(true) && schedule_recrawls(state, queue, RECRAWL_BUDGET).await > 0 {
+        started_task = Some(true);
+    }
+
     if let Some(ret) = started_task {
         ret
     } else {
@@ -96,14 +115,153 @@ pub async fn check_for_jobs(state: &AppState, queue: &mpsc::Sender<WorkerCommand
     }
 }
 
+/// Sends up to `budget` documents that are due to be recrawled, based on the
+/// recrawl policy of the lens(es) they belong to, to the workers.
+async fn schedule_recrawls(
+    state: &AppState,
+    queue: &mpsc::Sender<WorkerCommand>,
+    budget: usize,
+) -> usize {
+    // Wait til in-flight crawls are finished.
+    match crawl_queue::num_tasks_in_progress(&state.db).await {
+        Ok(0) => {}
+        Ok(_) => return 0,
+        Err(err) => {
+            log::warn!("Unable to check in-flight tasks: {}", err);
+            return 0;
+        }
+    }
+
+    let policies = state
+        .lenses
+        .iter()
+        .filter(|lens| lens.is_enabled && lens.recrawl != RecrawlPolicy::Never)
+        .map(|lens| (lens.name.clone(), lens.recrawl.clone()))
+        .collect::<Vec<_>>();
+
+    let now = Utc::now();
+    let mut due = HashSet::new();
+    for (lens, policy) in policies {
+        if due.len() >= budget {
+            break;
+        }
+
+        let min_interval = match policy.min_interval_hours() {
+            Some(hours) => Duration::hours(hours.into()),
+            None => continue,
+        };
+
+        let candidates = match crawl_queue::find_recrawl_candidates(
+            &state.db,
+            &lens,
+            now - min_interval,
+            RECRAWL_CANDIDATE_LIMIT,
+        )
+        .await
+        {
+            Ok(candidates) => candidates,
+            Err(err) => {
+                log::warn!("Unable to find recrawls for {}: {}", lens, err);
+                continue;
+            }
+        };
+
+        for task in candidates {
+            if due.len() >= budget {
+                break;
+            }
+
+            let history = match Url::parse(&task.url) {
+                Ok(url) => fetch_history::find_by_url(&state.db, &url)
+                    .await
+                    .ok()
+                    .flatten(),
+                Err(_) => None,
+            };
+
+            if let Some(interval) = recrawl_interval(&policy, history.as_ref()) {
+                if task.updated_at + interval <= now {
+                    due.insert(task.id);
+                }
+            }
+        }
+    }
+
+    if due.is_empty() {
+        return 0;
+    }
+
+    let ids = due.into_iter().collect::<Vec<_>>();
+    if let Err(err) = crawl_queue::Entity::update_many()
+        .col_expr(
+            crawl_queue::Column::Status,
+            sea_query::Expr::value(CrawlStatus::Processing),
+        )
+        .filter(crawl_queue::Column::Id.is_in(ids.clone()))
+        .exec(&state.db)
+        .await
+    {
+        log::warn!("Unable to schedule recrawls: {}", err);
+        return 0;
+    }
+
+    log::debug!("scheduling {} recrawls", ids.len());
+    for id in &ids {
+        if queue
+            .send(WorkerCommand::Recrawl { id: *id })
+            .await
+            .is_err()
+        {
+            log::error!("unable to send command to worker");
+        }
+    }
+
+    ids.len()
+}
+
+/// How long to wait between crawls of a document. Adaptive policies estimate
+/// how often the document changes from its fetch history, clamped to the
+/// policy's bounds.
+fn recrawl_interval(
+    policy: &RecrawlPolicy,
+    history: Option<&fetch_history::Model>,
+) -> Option<Duration> {
+    match policy {
+        RecrawlPolicy::Never => None,
+        RecrawlPolicy::Fixed { interval_hours } => Some(Duration::hours((*interval_hours).into())),
+        RecrawlPolicy::Adaptive {
+            min_interval_hours,
+            max_interval_hours,
+        } => {
+            let min_interval = Duration::hours((*min_interval_hours).into());
+            let max_interval = Duration::hours((*max_interval_hours).into()).max(min_interval);
+
+            let history = match history {
+                Some(history) => history,
+                None => return Some(min_interval),
+            };
+
+            // Average time between content changes since we first fetched this.
+            let tracked = history.updated_at - history.created_at;
+            let changes = i32::try_from(history.change_count.max(0) + 1).unwrap_or(i32::MAX);
+            Some((tracked / changes).clamp(min_interval, max_interval))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use tokio::sync::mpsc;
 
-    use super::check_for_jobs;
+    use chrono::{Duration, Utc};
+    use shared::config::{LensConfig, RecrawlPolicy};
+
+    use super::{check_for_jobs, recrawl_interval};
     use crate::{state::AppState, task::WorkerCommand};
     use entities::models::crawl_queue::{self, CrawlStatus, CrawlType};
-    use entities::sea_orm::{ActiveModelTrait, Set};
+    use entities::models::fetch_history;
+    use entities::models::tag::TagType;
+    use entities::sea_orm::{ActiveModelTrait, EntityTrait, Set};
     use entities::test::setup_test_db;
 
     #[tokio::test]
@@ -133,4 +291,99 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_recrawl_interval() {
+        assert_eq!(recrawl_interval(&RecrawlPolicy::Never, None), None);
+        assert_eq!(
+            recrawl_interval(&RecrawlPolicy::Fixed { interval_hours: 48 }, None),
+            Some(Duration::hours(48))
+        );
+
+        let policy = RecrawlPolicy::Adaptive {
+            min_interval_hours: 24,
+            max_interval_hours: 24 * 30,
+        };
+        // Never fetched, use the shortest interval
+        assert_eq!(recrawl_interval(&policy, None), Some(Duration::hours(24)));
+
+        let now = Utc::now();
+        let mut history = fetch_history::Model {
+            id: 1,
+            protocol: fetch_history::FetchProtocol::Http,
+            domain: "example.com".into(),
+            path: "/".into(),
+            hash: None,
+            status: 200,
+            etag: None,
+            last_modified: None,
+            change_count: 9,
+            no_index: false,
+            created_at: now - Duration::days(40),
+            updated_at: now,
+        };
+        // Changed every ~4 days
+        assert_eq!(
+            recrawl_interval(&policy, Some(&history)),
+            Some(Duration::days(4))
+        );
+
+        // Changes constantly, clamped to the min interval.
+        history.change_count = 1000;
+        assert_eq!(
+            recrawl_interval(&policy, Some(&history)),
+            Some(Duration::hours(24))
+        );
+
+        // Never changed, clamped to the max interval.
+        history.change_count = 0;
+        assert_eq!(
+            recrawl_interval(&policy, Some(&history)),
+            Some(Duration::days(30))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_for_jobs_recrawl() {
+        let db = setup_test_db().await;
+        let lens = LensConfig {
+            name: "example".into(),
+            recrawl: RecrawlPolicy::Fixed { interval_hours: 24 },
+            is_enabled: true,
+            ..Default::default()
+        };
+        let state = AppState::builder()
+            .with_db(db.clone())
+            .with_lenses(&vec![lens])
+            .build();
+
+        // Last crawled a week ago, due for a recrawl
+        let task = crawl_queue::ActiveModel {
+            url: Set("https://example.com".to_owned()),
+            domain: Set("example.com".to_owned()),
+            crawl_type: Set(CrawlType::Normal),
+            status: Set(CrawlStatus::Completed),
+            updated_at: Set(Utc::now() - Duration::days(7)),
+            ..Default::default()
+        };
+        let task = task.insert(&db).await.expect("Unable to save dummy task");
+        task.insert_tags(&db, &[(TagType::Lens, "example".to_owned())])
+            .await
+            .expect("Unable to tag task");
+
+        let (sender, mut recv) = mpsc::channel(10);
+        assert!(check_for_jobs(&state, &sender).await);
+        let message = recv.recv().await.expect("no WorkerCommand in channel");
+        assert_eq!(message, WorkerCommand::Recrawl { id: task.id });
+
+        let updated = crawl_queue::Entity::find_by_id(task.id)
+            .one(&db)
+            .await
+            .expect("Unable to find task")
+            .expect("task should exist");
+        assert_eq!(updated.status, CrawlStatus::Processing);
+
+        // Already in-flight, nothing else to schedule
+        assert!(!check_for_jobs(&state, &sender).await);
+    }
 }
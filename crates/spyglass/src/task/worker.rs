@@ -1,13 +1,12 @@
 use entities::models::crawl_queue::EnqueueSettings;
 
 use entities::models::{
-    bootstrap_queue, crawl_queue, crawl_tag, indexed_document,
+    bootstrap_queue, crawl_queue, crawl_tag, indexed_document, link,
     tag::{self, TagPair},
 };
 use entities::sea_orm::prelude::*;
 use entities::sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
 use shared::config::{Config, LensConfig, LensSource};
-use spyglass_searcher::{SearchTrait, WriteTrait};
 
 use super::{bootstrap, CollectTask, ManagerCommand};
 use super::{CleanupTask, CrawlTask};
@@ -15,7 +14,7 @@ use super::{CleanupTask, CrawlTask};
 use crate::state::AppState;
 use crate::{
     crawler::{CrawlError, CrawlResult, Crawler},
-    documents::process_crawl_results,
+    documents::{process_crawl_parts, process_crawl_results},
 };
 
 /// Handles bootstrapping a lens. If the lens is remote we attempt to process the cache.
@@ -112,7 +111,9 @@ pub async fn cleanup_database(state: &AppState, cleanup_task: CleanupTask) -> an
     }
 
     if changed {
-        let _ = state.index.save().await;
+        if let Err(err) = state.index.save().await {
+            log::error!("Unable to commit index: {err}");
+        }
     }
 
     Ok(())
@@ -211,14 +212,31 @@ pub async fn process_crawl(
         log::error!("error enqueuing all: {}", err);
     }
 
+    // Save outbound links for the link graph
+    if let Err(err) = link::replace_links(&state.db, &crawl_result.url, &to_enqueue).await {
+        log::error!("Unable to save links for {}: {}", crawl_result.url, err);
+    }
+
     // Add / update search index w/ crawl result.
     if crawl_result.content.is_none() {
         return Err(CrawlError::ParseError("No content found".to_string()));
     }
 
-    match process_crawl_results(state, &[crawl_result.clone()], &task_tags).await {
+    let res = process_crawl_results(state, &[crawl_result.clone()], &task_tags).await;
+    // Documents contained in this one, e.g. the messages in a mailbox. Always
+    // processed so parts that are gone since the last crawl are removed.
+    if let Err(err) = process_crawl_parts(state, crawl_result, &task_tags).await {
+        log::error!("Unable to index parts of {}: {}", crawl_result.url, err);
+    }
+
+    match res {
         Ok(res) => {
-            if res.num_updated > 0 {
+            if let Some(canonical) = res.duplicates.get(&crawl_result.url) {
+                if let Err(err) = crawl_queue::mark_duplicate(&state.db, task.id, canonical).await {
+                    log::error!("Unable to mark {} as duplicate: {}", task.id, err);
+                }
+                Ok(FetchResult::Ignore)
+            } else if res.num_updated > 0 {
                 Ok(FetchResult::Updated)
             } else {
                 Ok(FetchResult::New)
@@ -337,7 +355,7 @@ mod test {
         let state = AppState::builder()
             .with_db(db)
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         // Should skip this lens since it's been bootstrapped already.
@@ -355,7 +373,7 @@ mod test {
         let state = AppState::builder()
             .with_db(db.clone())
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         let model = crawl_queue::ActiveModel {
@@ -404,7 +422,7 @@ mod test {
         let state = AppState::builder()
             .with_db(db.clone())
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         let task = crawl_queue::ActiveModel {
@@ -455,7 +473,7 @@ mod test {
         let state = AppState::builder()
             .with_db(db.clone())
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         let model = crawl_queue::ActiveModel {
@@ -520,7 +538,7 @@ mod test {
         let state = AppState::builder()
             .with_db(db.clone())
             .with_user_settings(&UserSettings::default())
-            .with_index(&IndexBackend::Memory, DocFields::as_schema(), false)
+            .with_index(&IndexBackend::Memory, DocFields::as_schema(), None, false)
             .build();
 
         let task = crawl_queue::ActiveModel {
@@ -4,6 +4,7 @@ use std::str::FromStr;
 use std::sync::{atomic::Ordering, Arc};
 
 use shared::response::{DefaultIndices, SearchResults};
+use shared::split_archive_path;
 use tauri::api::dialog::FileDialogBuilder;
 use tauri::State;
 use tauri::{ClipboardManager, Manager};
@@ -84,6 +85,7 @@ pub async fn open_result(
     win: tauri::Window,
     url: &str,
     application: Option<String>,
+    search: Option<request::ResultOpenedParam>,
 ) -> Result<(), String> {
     let mut schema = String::from("unknown");
     let mut is_default_action = false;
@@ -99,6 +101,13 @@ pub async fn open_result(
             schema = String::from(url.scheme());
             if url.scheme() == "file" {
                 let _ = url.set_host(None);
+                // Files inside an archive can't be opened directly, open the
+                // archive they're in instead.
+                if let (archive, Some(_)) = split_archive_path(url.path()) {
+                    let archive = archive.to_string();
+                    url.set_path(&archive);
+                    url.set_fragment(None);
+                }
             }
 
             if let Err(err) = os_open(&url, application) {
@@ -110,6 +119,16 @@ pub async fn open_result(
         Err(err) => Err(err.to_string()),
     };
 
+    // Remember which result was picked for the search
+    if let (Ok(()), Some(search)) = (&result, search) {
+        if let Some(rpc) = win.app_handle().try_state::<rpc::RpcMutex>() {
+            let rpc = rpc.lock().await;
+            if let Err(err) = rpc.client.record_opened(search).await {
+                log::warn!("Unable to record opened result: {err}");
+            }
+        }
+    }
+
     if let Some(metrics) = win.try_state::<Metrics>() {
         metrics
             .track(Event::ResultActionTriggered {
@@ -156,11 +175,16 @@ pub async fn search_docs<'r>(
     win: tauri::Window,
     lenses: Vec<String>,
     query: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
 ) -> Result<SearchResults, String> {
     if let Some(rpc) = win.app_handle().try_state::<rpc::RpcMutex>() {
         let data = request::SearchParam {
             lenses,
             query: query.to_string(),
+            offset,
+            limit,
+            ..Default::default()
         };
 
         let rpc = rpc.lock().await;
@@ -58,6 +58,10 @@ pub async fn save_user_settings(
                                         current_settings.disable_telemetry =
                                             serde_json::from_str(value).unwrap_or_default();
                                     }
+                                    "disable_search_history" => {
+                                        current_settings.disable_search_history =
+                                            serde_json::from_str(value).unwrap_or_default();
+                                    }
                                     "inflight_crawl_limit" => {
                                         let limit: u32 = serde_json::from_str(value).unwrap_or(10);
                                         current_settings.inflight_crawl_limit =
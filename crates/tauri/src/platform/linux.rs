@@ -29,7 +29,9 @@ pub fn os_open(url: &Url, application: Option<String>) -> anyhow::Result<()> {
         binary_path
     };
 
-    let open_url = if url.scheme() == "file" {
+    // Files w/ a fragment, e.g. `#page=3` or `#t=65` pointing at a search match,
+    // are opened as a file URL so viewers that support fragments open at it.
+    let open_url = if url.scheme() == "file" && url.fragment().is_none() {
         use shared::url_to_file_path;
         url_to_file_path(url.path(), false)
     } else {
@@ -32,7 +32,9 @@ pub fn hide_search_bar(window: &Window) {
 }
 
 pub fn os_open(url: &Url, application: Option<String>) -> anyhow::Result<()> {
-    let open_url = if url.scheme() == "file" {
+    // Files w/ a fragment, e.g. `#page=3` or `#t=65` pointing at a search match,
+    // are opened as a file URL so viewers that support fragments open at it.
+    let open_url = if url.scheme() == "file" && url.fragment().is_none() {
         use shared::url_to_file_path;
         url_to_file_path(url.path(), true)
     } else {
@@ -2,7 +2,9 @@ use crate::window::notify;
 use crate::{rpc, AppEvent};
 use anyhow::anyhow;
 use jsonrpsee::core::client::Subscription;
-use spyglass_rpc::{ModelDownloadStatusPayload, RpcClient, RpcEvent, RpcEventType};
+use spyglass_rpc::{
+    ModelDownloadStatusPayload, RpcClient, RpcEvent, RpcEventType, SchemaMigrationStatusPayload,
+};
 use tauri::{
     async_runtime::JoinHandle,
     plugin::{Builder, TauriPlugin},
@@ -46,6 +48,7 @@ async fn _subscribe(app: &AppHandle) -> anyhow::Result<Subscription<RpcEvent>> {
             RpcEventType::LensInstalled,
             RpcEventType::LensUninstalled,
             RpcEventType::ModelDownloadStatus,
+            RpcEventType::SchemaMigrationStatus,
         ])
         .await?;
 
@@ -116,6 +119,23 @@ async fn setup_notification_handler(app: AppHandle) {
                                     None
                                 }
                             }
+                            RpcEventType::SchemaMigrationStatus => {
+                                match serde_json::de::from_str::<SchemaMigrationStatusPayload>(&event.payload) {
+                                    Ok(SchemaMigrationStatusPayload::Finished { num_docs }) => Some((
+                                        "Index Updated".into(),
+                                        format!("Finished updating {} documents", num_docs)
+                                    )),
+                                    Ok(SchemaMigrationStatusPayload::Error { msg }) => Some((
+                                        "Index Update Failed".into(),
+                                        format!("Unable to update the search index: {}", msg)
+                                    )),
+                                    Ok(SchemaMigrationStatusPayload::InProgress { percent, .. }) => {
+                                        log::info!("updating index: {}%", percent);
+                                        None
+                                    }
+                                    Err(_) => None,
+                                }
+                            }
                         };
 
                         if let Some((title, blurb)) = notif {